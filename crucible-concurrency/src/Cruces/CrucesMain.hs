@@ -112,7 +112,7 @@ run :: Crux.Logs Crux.CruxLogMessage
 run (cruxOpts, opts) =
   Crux.withCruxLogMessage $
   do let ?dpor = not (noDpor opts)
-     let ?bound = maxPreemptions opts
+     let ?bound = if maxPreemptions opts > 0 then Just (maxPreemptions opts) else Nothing
      let fn = case Crux.inputFiles cruxOpts of
                 [fn'] -> fn'
                 _     -> error "Expected exactly one input file"
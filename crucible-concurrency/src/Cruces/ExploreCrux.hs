@@ -20,6 +20,7 @@ import           Control.Lens
 import           Data.Generics.Product.Fields (field, setField)
 import qualified Data.Vector as V
 import qualified Data.Map.Strict as Map
+import           Data.Maybe (isJust)
 import           System.IO (Handle)
 
 import           What4.Interface
@@ -48,7 +49,7 @@ import           Crux.Types (totalProcessedGoals, provedGoals)
 
 -- | Callback for crucible-syntax exploration
 exploreCallback :: forall alg.
-  (?bound::Int, SchedulingAlgorithm alg) =>
+  (?bound::Maybe Int, SchedulingAlgorithm alg) =>
   Crux.Logs Crux.CruxLogMessage =>
   Crux.CruxOptions ->
   HandleAllocator ->
@@ -96,6 +97,14 @@ emptyExploration = Exploration { _exec      = initialExecutions
                                , _schedAlg  = initialAlgState
                                , _num       = 0
                                , _gVars     = mempty
+                               , _threadIDVar = Nothing
+                               , _lockHolders = mempty
+                               , _libraryFunction = const False
+                               , _stats = emptyExploreStats
+                               , _currentSchedule = []
+                               , _maxExecutions = Nothing
+                               , _replaySchedule = Nothing
+                               , _reportExploration = const (return ())
                                }
   where
     s0 = Scheduler { _threads      = V.fromList [EmptyThread]
@@ -110,7 +119,7 @@ emptyExploration = Exploration { _exec      = initialExecutions
 exploreOvr :: forall sym bak ext alg ret rtp msgs.
   Crux.Logs msgs =>
   Crux.SupportsCruxLogMessage msgs =>
-  (?bound::Int, IsSymBackend sym bak, IsSyntaxExtension ext, SchedulingAlgorithm alg, RegValue sym ret ~ ()) =>
+  (?bound::Maybe Int, IsSymBackend sym bak, IsSyntaxExtension ext, SchedulingAlgorithm alg, RegValue sym ret ~ ()) =>
   bak ->
   Maybe (Crux.SomeOnlineSolver sym bak) ->
   Crux.CruxOptions ->
@@ -156,17 +165,36 @@ exploreOvr bak symOnline cruxOpts mainAct =
         exc          <- use stateExec
         stateExplAlg %= processExecution exc
         alg          <- use stateExplAlg
+        sched        <- use (stateExpl.currentSchedule)
+        switches     <- use (stateExpl.scheduler.numSwitches)
+        stateExpl.stats %= \st -> st
+          & statSchedulingPoints +~ length sched
+          & statPreemptions +~ switches
+          & statComplete +~ (if maximalExecution exc then 1 else 0)
 
         when (verb > 2) $
            do liftIO $ putStrLn " == Begin Exploration =="
               liftIO $ putStrLn $ ppExecutions exc alg
               liftIO $ putStrLn " == End Exploration   ==\n"
 
-        let amDone = fullyExplored (exc { _currentEventID = 0 }) alg
+        n         <- use (stateExpl.num)
+        limit     <- use (stateExpl.maxExecutions)
+        replaying <- use (stateExpl.replaySchedule.to isJust)
+        let amDone   = fullyExplored (exc { _currentEventID = 0 }) alg
+            hitLimit = maybe False (n >=) limit
         provedAllGoals <- checkGoals
 
-        if amDone || not provedAllGoals then
-          return ()
+        if amDone || not provedAllGoals || hitLimit || replaying then
+          do st     <- use (stateExpl.stats)
+             report <- use (stateExpl.reportExploration)
+             liftIO $ report ExploreResult
+               { exploreExecutions = n
+               , exploreStats = st
+               , exploreAlgStats = explorationStats alg
+               , exploreStoppedEarly = provedAllGoals && not amDone && not replaying
+               , exploreFailingSchedule =
+                   if provedAllGoals then Nothing else Just (reverse sched)
+               }
         else
           loop verb assmSt
 
@@ -181,6 +209,8 @@ exploreOvr bak symOnline cruxOpts mainAct =
          stateExpl.scheduler.threads      .= V.fromList [EmptyThread]
          stateExpl.scheduler %= \s -> s { mainCont = retH verb assmSt }
          stateExpl.scheduler.numSwitches  .= 0
+         stateExpl.lockHolders            .= mempty
+         stateExpl.currentSchedule        .= []
          -- Per-run exploration bookkeeping
          runUpdateSchedAlg prepareNewExecution
          stateExec.birthdays              .= Map.fromList [(ThreadID 0, 0)]
@@ -40,6 +40,10 @@ data ResumePred =
     ProgramVar !Text   -- ^ A predicate the user has defined
   | SchedulerVar !Text -- ^ A name denoting a predicate that we have defined (usually a lock)
                        --   that gets set *by the scheduler*
+  | RwLockVar !Bool !Text -- ^ A reader-writer lock maintained by the scheduler:
+                          --   available for writing (True) once it has no
+                          --   writer and no readers, and for reading (False)
+                          --   once it has no writer
   deriving (Show, Eq)
 
 data ThreadStateP p sym ext ret where
@@ -99,6 +103,7 @@ resumePredResource :: ResumePred -> Set.Set (Resource Text)
 resumePredResource = \case
   ProgramVar r -> Set.singleton $ Resource r
   SchedulerVar r -> Set.singleton $ Resource r
+  RwLockVar _ r -> Set.singleton $ Resource r
 
 -- | Build eventinfos corresponding to resuming the thread denoted by the given
 -- state. Errors if the Direction is not NoDirection with any state other than
@@ -129,6 +134,7 @@ tsEventInfo ts dir =
                   | otherwise          -> error "Direction given with Join"
         OnPred rs (ProgramVar m)       -> [Write (rs <> Set.singleton m)]
         OnPred rs (SchedulerVar m)     -> [Write (rs <> Set.singleton m)]
+        OnPred rs (RwLockVar _ m)      -> [Write (rs <> Set.singleton m)]
         OnCond gv m _                  -> [Write (Set.fromList [gv, m])]
         Resumable (Just (ro, r))       -> [if ro then Read r else Write r]
         Resumable Nothing              -> []
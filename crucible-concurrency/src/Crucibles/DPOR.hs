@@ -57,6 +57,9 @@ data DPOR = DPOR
     -- ^ Clockvectors for the execution: Using a resource here lets us track
     -- resources as well as threads as processes (refactor as this is very
     -- specific to their usage in Flanagan/Godefroid 05)
+  , _enabled        :: !(EventIDMap Int)
+    -- ^ The number of choices (threads, or directions of a branching thread)
+    -- that were available at each scheduling point. Only used for statistics.
   }
 makeLenses ''DPOR
 
@@ -78,6 +81,8 @@ instance SchedulingAlgorithm DPOR where
 
   ppExecutions = ppExecutionsDPOR
 
+  explorationStats = statsDPOR
+
 
 initialDPOR :: DPOR
 initialDPOR = DPOR
@@ -86,6 +91,7 @@ initialDPOR = DPOR
   , _aborting = mempty
   , _lastAccess = mempty
   , _clockVectors = mempty
+  , _enabled = mempty
   }
 
 -- | Convenience lens for looking up the current pending events. Fails if the current
@@ -244,6 +250,18 @@ ppExecutionsDPOR' exe dpor
     this = exe ^. currentEventID
     ev   = exe ^. currentEvent
 
+-- | Compare the choices available at each scheduling point with the ones DPOR
+-- decided to explore: the rest lead to executions equivalent to explored ones.
+statsDPOR :: DPOR -> [(String, Int)]
+statsDPOR dpor =
+  [ ("scheduling choices", sum (fst <$> counts))
+  , ("choices explored", sum (uncurry min <$> counts))
+  , ("choices pruned as equivalent", sum ((\(n, p) -> max 0 (n - p)) <$> counts))
+  ]
+  where
+    counts = [ (n, maybe 0 Set.size (dpor ^. pending.at e))
+             | (e, n) <- IntMap.toList (dpor ^. enabled) ]
+
 -- | Reset the clock vectors
 prepareNewExecutionDPOR :: SchedAlgM DPOR ()
 prepareNewExecutionDPOR =
@@ -298,6 +316,7 @@ markBacktracking me ts =
      let nonAborting  = Set.filter ((`Set.notMember` abortingTids) . pendingThread) ps
          abortingTids = Set.map pendingThread (fromMaybe mempty aborting0)
          ps           = mconcat (uncurry makePending <$> ts)
+     enabled.at curr .= Just (Set.size ps)
      mp <- pickArbitraryThread me nonAborting ts
      case mp of
        Just p -> initializeBacktracking curr (Set.singleton p)
@@ -50,6 +50,8 @@ import qualified Data.IntMap.Strict as IntMap
 import           Data.IntSet (IntSet)
 import qualified Data.IntSet as IntSet
 import           Data.Text (Text)
+import           Data.Char (isSpace)
+import           Data.List (dropWhileEnd, isPrefixOf)
 import           Data.Maybe (fromMaybe)
 import           GHC.Stack
 
@@ -196,6 +198,29 @@ currentTrace = go []
           go (exe ^. currentEvent : tr) (exe & currentEventID .~ exe ^. prevEventID)
 
 
+-- | A @Schedule@ lists the scheduling decisions made in one execution: the
+-- thread that was resumed at each step, and the branch it took if it was
+-- stopped at a branch.
+type Schedule = [(ThreadID, Direction)]
+
+-- | Render a schedule, one decision per line, e.g. @1@ or @0\@T@.
+ppSchedule :: Schedule -> String
+ppSchedule sched = unlines [ show t ++ ppDirection d | (ThreadID t, d) <- sched ]
+
+-- | Parse a schedule rendered by 'ppSchedule'. Blank lines and lines starting
+-- with @#@ are ignored.
+parseSchedule :: String -> Either String Schedule
+parseSchedule = traverse parseStep . filter (not . ignored) . fmap strip . lines
+  where
+    strip = dropWhileEnd isSpace . dropWhile isSpace
+    ignored l = null l || "#" `isPrefixOf` l
+    parseStep l =
+      case break (== '@') l of
+        (t, d) | [(tid, "")] <- reads t
+               , Just dir <- lookup d [("", NoDirection), ("@T", TBranch), ("@F", FBranch)] ->
+                 Right (ThreadID tid, dir)
+        _ -> Left ("bad schedule step: " ++ l)
+
 -- | Pretty print (for debugging)
 ppDirection :: Direction -> String
 ppDirection NoDirection = ""
@@ -50,7 +50,10 @@ import           Data.Parameterized.NatRepr
 import           Data.Text (Text)
 import qualified Data.Vector as V
 
-import Data.Foldable (foldlM)
+import           Control.Applicative ((<|>))
+import Data.Foldable (asum, find, foldlM)
+import           Data.List (intercalate)
+import           Data.Maybe (fromMaybe, isJust, listToMaybe, mapMaybe)
 import           GHC.Stack
 
 import           Lang.Crucible.Backend
@@ -64,6 +67,8 @@ import           Lang.Crucible.Simulator.EvalStmt
 import           Lang.Crucible.Simulator.ExecutionTree
 import           What4.Interface
 import           What4.Config
+import           What4.FunctionName (FunctionName)
+import           What4.ProgramLoc (ProgramLoc(..))
 
 import           Crucibles.Common
 import           Crucibles.Execution
@@ -75,7 +80,7 @@ import Lang.Crucible.Simulator.GlobalState (insertGlobal, lookupGlobal)
 import Data.Parameterized.Nonce (freshNonce)
 
 type SchedulerConstraints sym ext alg =
-  (?bound::Int, IsSymInterface sym, IsSyntaxExtension ext, SchedulingAlgorithm alg)
+  (?bound::Maybe Int, IsSymInterface sym, IsSyntaxExtension ext, SchedulingAlgorithm alg)
 
 -- | Toplevel feature
 scheduleFeature ::
@@ -256,6 +261,21 @@ scheduleYield globs rh call spec mods ro =
 
     Release l ->
       do setInternalGlobal l C.BoolRepr (return . truePred)
+         releaseLockHolder l
+         es <- gets (CallState rh call)
+         return $ ExecutionFeatureModifiedState es
+
+    AcquireRead l ->
+      do initializeRwLock l
+         runYield $ OnPred modSet (RwLockVar False l)
+
+    AcquireWrite l ->
+      do initializeRwLock l
+         runYield $ OnPred modSet (RwLockVar True l)
+
+    ReleaseRead l ->
+      do modifyRwLockReaders l (subtract 1)
+         releaseLockHolder l
          es <- gets (CallState rh call)
          return $ ExecutionFeatureModifiedState es
   where
@@ -314,11 +334,16 @@ yieldThread globs ts0 =
        then do -- res <- use (stateExpl.scheduler.to mainCont)
                curr <- use (stateExec.currentEventID)
                stateExec.maximalEvents %= IntSet.insert curr
-               return Nothing
+               case ts0 of
+                 -- Unless the current thread is itself blocked, in which case
+                 -- nothing can ever wake it up.
+                 RunningThread {} -> Just <$> reportDeadlock
+                 _ -> return Nothing
 
        -- Otherwise mark backtracking points and pick a new thread
        else do me     <- use (stateExpl.scheduler.activeThread)
-               mnext  <- runUpdateSchedAlg $ pickNextThread (ThreadID me) ts
+               mpick  <- runUpdateSchedAlg $ pickNextThread (ThreadID me) ts
+               mnext  <- maybe mpick Just <$> replayNextThread ts
                case mnext of
                  Nothing ->
                    -- Why is this infeasible? The scheduling algorithm might
@@ -328,10 +353,36 @@ yieldThread globs ts0 =
                    Just <$> abortInfeasible
 
                  Just next ->
-                   do let preempted = threadID (fst next) `elem` (fst <$> ts)
+                   -- Switching away from a thread that could have kept
+                   -- running is a preemption.
+                   do let preempted = me `elem` (fst <$> ts)
                                    && me /= threadID (fst next)
                       switchToPendingThread globs preempted next
 
+-- | When replaying a schedule, take its next decision instead of the
+-- scheduling algorithm's, provided that the thread can run. If it can't, the
+-- program no longer matches the schedule, so the rest of it is dropped.
+replayNextThread ::
+  [(Int, ThreadState alg sym ext ret)] ->
+  ThreadExecM alg sym ext ret rtp f a (Maybe (ThreadID, Direction))
+replayNextThread ts =
+  use (stateExpl.replaySchedule) >>= \case
+    Just ((tid, dir) : rest)
+      | Just th <- lookup (threadID tid) ts
+      , isBranching th == (dir /= NoDirection) ->
+        do stateExpl.replaySchedule .= Just rest
+           return (Just (tid, dir))
+      | otherwise ->
+        do liftIO $ putStrLn $
+             "Replayed schedule diverged: thread " ++ show (threadID tid)
+             ++ " can't run here; the scheduler picks the remaining threads."
+           stateExpl.replaySchedule .= Just []
+           return Nothing
+    _ -> return Nothing
+  where
+    isBranching BranchingThread{} = True
+    isBranching _ = False
+
 
 -- | Run an action in the event that there are no threads to yield to. This is
 -- used to wrap the pattern of attempting to yield to a new thread, finding none
@@ -373,10 +424,13 @@ switchToPendingThread globals preempted (tid, dir) =
      runUpdateSchedAlg $ notifyNewEvents tid dir es
      let e = last es
      stateExec.currentEventID .= e ^. eventID
+     stateExpl.currentSchedule %= ((tid, dir) :)
      when preempted $ stateExpl.scheduler.numSwitches %= (+1)
      n <- use (stateExpl.scheduler.numSwitches)
      blocked <- not <$> checkRunnable globals tstate
-     if (?bound > 0 && n > ?bound) || blocked
+     let overBound = maybe False (n >) ?bound
+     when overBound $ stateExpl.stats.statBounded %= (+1)
+     if overBound || blocked
        -- TODO: Re-evaluate if returning a Maybe value here is ever useful.
         then Just <$> abortInfeasible
         else do assertRunnable globals tid tstate e
@@ -396,6 +450,7 @@ resumeThreadState ::
   ThreadExecM alg sym ext ret rtp f a (ExecState (ThreadExec alg sym ext ret) sym ext rtp)
 resumeThreadState globalVars tID ts dir =
   do stateExpl.scheduler.activeThread .= threadID tID
+     setThreadIDGlobal tID
      case ts of
        CompletedThread {} ->
          error "resumeThreadState: trying to resume a completed thread!"
@@ -410,9 +465,20 @@ resumeThreadState globalVars tID ts dir =
          | dir == NoDirection ->
            do isRunnable <- checkRunnable globalVars ts
               if isRunnable then
-                do case resumeCond of
+                do isLib <- use (stateExpl.libraryFunction)
+                   let acquire gvName excl =
+                         stateExpl.lockHolders.at gvName.non mempty.at (threadID tID)
+                           .= fmap (\loc -> (excl, loc)) (stackLoc isLib stack)
+                   case resumeCond of
                      OnPred _ (SchedulerVar gvName) ->
-                       setInternalGlobal gvName C.BoolRepr (return . falsePred)
+                       do setInternalGlobal gvName C.BoolRepr (return . falsePred)
+                          acquire gvName True
+                     OnPred _ (RwLockVar True gvName) ->
+                       do setInternalGlobal gvName C.BoolRepr (return . falsePred)
+                          acquire gvName True
+                     OnPred _ (RwLockVar False gvName) ->
+                       do modifyRwLockReaders gvName (+ 1)
+                          acquire gvName False
                      _ -> return ()
                    restoreRunningThread retHandler stack
               else error "Restoring a thread that is not runnable"
@@ -422,6 +488,20 @@ resumeThreadState globalVars tID ts dir =
        BranchingThread p stk tframe fframe ->
          restoreBranchingThread tID dir p stk tframe fframe
 
+-- | Record the ID of the thread that is about to run in the exploration's
+-- 'threadIDVar' global, if it has one.
+setThreadIDGlobal ::
+  IsSymInterface sym =>
+  ThreadID ->
+  ThreadExecM alg sym ext ret rtp f a ()
+setThreadIDGlobal tID =
+  use (stateExpl.threadIDVar) >>= \case
+    Nothing -> return ()
+    Just gv ->
+      do sym <- use stateSymInterface
+         n <- liftIO $ natLit sym (fromIntegral (threadID tID))
+         stateGlobals %= insertGlobal gv n
+
 -- | Starts a new thread, passing the given RegValue as its argument.
 startNewThread ::
   ( SchedulerConstraints sym ext alg
@@ -558,6 +638,105 @@ returnFinishedResult mres =
               do putStrLn "<deadlock>"
                  runReaderT (abortExec (AssertionFailure simerr)) s
 
+-- | Fail the current execution, because every thread is blocked.
+reportDeadlock ::
+  ( SchedulerConstraints sym ext alg
+  , rtp ~ RegEntry sym ret
+  ) =>
+  ThreadExecM alg sym ext ret rtp f a (ExecState (ThreadExec alg sym ext ret) sym ext rtp)
+reportDeadlock =
+  use stateContext >>= \ctx -> withBackend ctx $ \bak ->
+  do ths     <- use (stateExpl.scheduler.threads)
+     holders <- use (stateExpl.lockHolders)
+     isLib   <- use (stateExpl.libraryFunction)
+     let sym = backendGetSym bak
+     loc <- liftIO $ getCurrentProgramLoc sym
+     let simerr = SimError loc $
+           AssertFailureSimError "deadlock: no thread can make progress"
+                                 (ppDeadlock isLib holders ths)
+     liftIO $ addProofObligation bak (LabeledPred (falsePred sym) simerr)
+     s <- get
+     liftIO $ runReaderT (abortExec (AssertionFailure simerr)) s
+
+-- | Describe a deadlock: the cycle of threads waiting for each other, if there
+-- is one, or else every blocked thread. Each line says where the thread is
+-- stuck and what it is waiting for.
+ppDeadlock ::
+  (FunctionName -> Bool) ->
+  Map.Map Text (Map.Map Int (Bool, ProgramLoc)) ->
+  V.Vector (ThreadState alg sym ext ret) ->
+  String
+ppDeadlock isLib holders ths =
+  unlines (describe <$> fromMaybe blocked (asum (cycleFrom [] <$> blocked)))
+  where
+    waiting i =
+      case ths V.! i of
+        RunningThread cond _ stk -> Just (cond, stackLoc isLib stk)
+        _ -> Nothing
+
+    blocked = [ i | i <- [0 .. V.length ths - 1], isJust (waiting i) ]
+
+    -- A read lock only waits for the writer, while everything else waits for
+    -- every holder.
+    owners excl l =
+      [ (t, loc)
+      | (t, (w, loc)) <- Map.toList (Map.findWithDefault mempty l holders)
+      , excl || w
+      ]
+
+    waitsFor i =
+      case fst <$> waiting i of
+        Just (OnPred _ (SchedulerVar l)) -> fst <$> owners True l
+        Just (OnPred _ (RwLockVar excl l)) -> fst <$> owners excl l
+        Just (OnJoin tid) -> [threadID tid]
+        _ -> []
+
+    cycleFrom path i
+      | i `elem` path = Just (i : reverse (takeWhile (/= i) path))
+      | otherwise = asum (cycleFrom (i : path) <$> waitsFor i)
+
+    describe i =
+      case waiting i of
+        Just (cond, mloc) ->
+          "thread " ++ show i ++ maybe "" ((", at " ++) . ppLoc) mloc
+            ++ ", waits for " ++ ppCond cond
+        Nothing -> "thread " ++ show i
+
+    ppCond cond =
+      case cond of
+        OnPred _ (SchedulerVar l) -> "a lock" ++ heldBy (owners True l)
+        OnPred _ (RwLockVar True l) ->
+          "write access to a reader-writer lock" ++ heldBy (owners True l)
+        OnPred _ (RwLockVar False l) ->
+          "read access to a reader-writer lock" ++ heldBy (owners False l)
+        OnPred _ (ProgramVar v) -> show v ++ " to become true"
+        OnJoin tid -> "thread " ++ show (threadID tid) ++ " to finish"
+        OnCond {} -> "a notification on a condition variable"
+        Resumable {} -> "nothing"
+
+    heldBy [] = ""
+    heldBy os =
+      " held by " ++ intercalate " and "
+        [ "thread " ++ show t ++ " (acquired at " ++ ppLoc loc ++ ")" | (t, loc) <- os ]
+
+    ppLoc = show . plSourceLoc
+
+-- | Where a thread is: the innermost frame outside of library code, if any.
+stackLoc ::
+  (FunctionName -> Bool) ->
+  ActiveTree p sym ext rtp f args ->
+  Maybe ProgramLoc
+stackLoc isLib stk =
+  find (not . isLib . plFunction) locs <|> listToMaybe locs
+  where
+    locs = mapMaybe filterCrucibleFrames (activeFrames stk)
+
+-- | Forget that the current thread holds the lock @l@.
+releaseLockHolder :: Text -> ThreadExecM alg sym ext ret r f a ()
+releaseLockHolder l =
+  do me <- use (stateExpl.scheduler.activeThread)
+     stateExpl.lockHolders.at l %= fmap (Map.delete me)
+
 -- | ThreadState helpers
 
 -- | The ThreadState corresponding to a thread executing @join@
@@ -720,6 +899,15 @@ runnable st ourglobals globals allThreads ts =
       evalGlobalPred gv st
       | otherwise ->
         error $ "Unknown scheduler var: " ++ show predVar
+    RunningThread (OnPred _ (RwLockVar write l)) _ _
+      | Just (C.Some gv) <- Map.lookup l ourglobals
+      , C.BoolRepr <- globalType gv
+      , Just (C.Some rv) <- Map.lookup (rwLockReaders l) ourglobals
+      , C.IntegerRepr <- globalType rv
+      , Just readers <- asInteger =<< lookupGlobal rv st ->
+      evalGlobalPred gv st && (not write || readers == 0)
+      | otherwise ->
+        error $ "Unknown reader-writer lock: " ++ show l
     RunningThread (OnCond _ _ notified) _ _ -> notified
     RunningThread (OnJoin tid) _ _
       | threadID tid < V.length allThreads ->
@@ -787,6 +975,34 @@ initializeInternalGlobal l tpr mkVal =
 
        _ -> return ()
 
+-- | The scheduler variable counting the readers of the reader-writer lock @l@.
+-- The lock's writer is tracked in the boolean variable @l@ itself, just like a
+-- plain lock.
+rwLockReaders :: Text -> Text
+rwLockReaders l = l <> "#readers"
+
+-- | Add the internal globals for the reader-writer lock @l@: initially
+-- available, with no readers.
+initializeRwLock ::
+  IsSymInterface sym =>
+  Text ->
+  ThreadExecM alg sym ext ret r f a ()
+initializeRwLock l =
+  do initializeInternalGlobal l C.BoolRepr (return . truePred)
+     initializeInternalGlobal (rwLockReaders l) C.IntegerRepr (`intLit` 0)
+
+-- | Update the number of readers holding the reader-writer lock @l@.
+modifyRwLockReaders ::
+  IsSymInterface sym =>
+  Text ->
+  (Integer -> Integer) ->
+  ThreadExecM alg sym ext ret r f a ()
+modifyRwLockReaders l f =
+  do mreaders <- getInternalGlobal (rwLockReaders l) C.IntegerRepr
+     case asInteger =<< mreaders of
+       Just n -> setInternalGlobal (rwLockReaders l) C.IntegerRepr (`intLit` f n)
+       Nothing -> error $ "Unknown reader-writer lock: " ++ show l
+
 -- | Debugging
 ppScheduler :: Scheduler p sym1 ext (ThreadState alg sym2 ext ret1) ret2 -> [Char]
 ppScheduler sched =
@@ -15,6 +15,9 @@ import Data.Map.Strict
 import Data.Parameterized (Some(..))
 
 import Lang.Crucible.Simulator
+import Lang.Crucible.Types (NatType)
+import What4.FunctionName (FunctionName)
+import What4.ProgramLoc (ProgramLoc)
 
 import Crucibles.Scheduler
 import Crucibles.Execution
@@ -36,6 +39,36 @@ evalTEWithState ::
   IO b
 evalTEWithState s exec = evalStateT exec s
 
+-- | Counters kept across the executions of an exploration
+data ExploreStats = ExploreStats
+  { _statComplete         :: !Int
+    -- ^ Executions that ran to completion
+  , _statBounded          :: !Int
+    -- ^ Executions abandoned for exceeding the preemption bound
+  , _statSchedulingPoints :: !Int
+    -- ^ Scheduling decisions made, summed over all executions
+  , _statPreemptions      :: !Int
+    -- ^ Preemptions, summed over all executions
+  }
+makeLenses ''ExploreStats
+
+emptyExploreStats :: ExploreStats
+emptyExploreStats = ExploreStats 0 0 0 0
+
+-- | What the client learns once an exploration has finished
+data ExploreResult = ExploreResult
+  { exploreExecutions      :: !Int
+    -- ^ The number of executions explored
+  , exploreStats           :: !ExploreStats
+  , exploreAlgStats        :: ![(String, Int)]
+    -- ^ Statistics from the scheduling algorithm
+  , exploreStoppedEarly    :: !Bool
+    -- ^ Whether the exploration hit its execution limit before covering every
+    -- schedule
+  , exploreFailingSchedule :: !(Maybe Schedule)
+    -- ^ The schedule of the execution that failed, if any
+  }
+
 -- | The state managed across multiple executions
 data Exploration alg ext ret sym = Exploration
   { _exec      :: !(Executions ThreadEvent)
@@ -48,6 +81,25 @@ data Exploration alg ext ret sym = Exploration
     -- ^ Number of executions explored
   , _gVars     :: !(Map Text (Some GlobalVar))
     -- ^ Map from name to GlobalVars that the exploration has invented. Typically these are locks.
+  , _threadIDVar :: !(Maybe (GlobalVar NatType))
+    -- ^ A global that, if present, is set to the ID of the running thread whenever a thread is resumed
+  , _lockHolders :: !(Map Text (Map Int (Bool, ProgramLoc)))
+    -- ^ The threads holding each lock in the current execution, whether they
+    -- hold it exclusively, and where they acquired it. Used for deadlock reports.
+  , _libraryFunction :: !(FunctionName -> Bool)
+    -- ^ Functions that deadlock reports should look past when saying where a
+    -- thread is, so that they point at the client's code
+  , _stats :: !ExploreStats
+  , _currentSchedule :: !Schedule
+    -- ^ The scheduling decisions made so far in the current execution, most
+    -- recent first
+  , _maxExecutions :: !(Maybe Int)
+    -- ^ Stop exploring after this many executions
+  , _replaySchedule :: !(Maybe Schedule)
+    -- ^ If present, follow these (remaining) decisions instead of consulting
+    -- the scheduling algorithm, and stop after a single execution
+  , _reportExploration :: !(ExploreResult -> IO ())
+    -- ^ Called once the exploration has finished
   }
 makeLenses ''Exploration
 
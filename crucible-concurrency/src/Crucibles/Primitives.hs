@@ -133,7 +133,10 @@ data YieldSpec =
     SimpleYield
   | GlobalPred !Text -- ^ Wait for some global (boolean) variable to become True
   | Acquire   !Text -- ^ Acquire a lock
-  | Release   !Text -- ^ Release a lock
+  | Release   !Text -- ^ Release a lock (or the write side of a reader-writer lock)
+  | AcquireRead  !Text -- ^ Acquire the read side of a reader-writer lock
+  | AcquireWrite !Text -- ^ Acquire the write side of a reader-writer lock
+  | ReleaseRead  !Text -- ^ Release the read side of a reader-writer lock
 
 -- | Run a list of matches in order, stopping with the first success.
 matchPrimitive ::
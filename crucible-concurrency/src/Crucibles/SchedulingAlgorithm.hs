@@ -55,6 +55,9 @@ class SchedulingAlgorithm alg where
   -- | Debugging: print out the current execution graph/algorithm state.
   ppExecutions :: Executions (ScheduleEvent EventInfo) -> alg -> String
 
+  -- | Statistics about the exploration so far, for the user
+  explorationStats :: alg -> [(String, Int)]
+
 -- | Run the scheduler action
 runSchedAlg :: Executions (ScheduleEvent EventInfo) -> alg -> SchedAlgM alg a -> (a, alg)
 runSchedAlg ex dpor alg =
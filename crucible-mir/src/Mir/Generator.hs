@@ -107,6 +107,7 @@ import qualified Lang.Crucible.Syntax as S
 import           Mir.DefId
 import           Mir.Mir
 import           Mir.Intrinsics
+import           Mir.Loops (LoopCut, UnwindBound)
 import           Mir.PP
 
 import           Unsafe.Coerce(unsafeCoerce)
@@ -137,20 +138,15 @@ data MirExp s where
 -- result of lvalue evaluation.
 data MirPlace s where
     MirPlace :: C.TypeRepr ty -> R.Expr MIR s (MirReferenceType ty) -> PtrMetadata s -> MirPlace s
-    -- | This is a hack used to support @&dyn Trait@ references. Unlike other
-    -- reference types, trait objects do not use 'MirReferenceType', instead
-    -- using a custom 'AnyType' representation that wraps a
-    -- @'MirReferenceType' t@ (for some unknown @t@). The only 'MirPlaceDynRef'
-    -- operation that is currently supported is @addrOfPlace@, which supports
-    -- code that looks like @&*x@ (where @x: &dyn Trait@). This sort of
-    -- operation does arise in @rustc@-compiled MIR, even in the standard
-    -- libraries, so we must have /some/ level of support for it.
-    --
-    -- All other 'MirPlaceDynRef' operations (e.g., @readPlace@ and
-    -- @evalPlaceProj@) are unsupported and will throw an exception if
-    -- encountered. To implement these operations, we will need to change the
-    -- encoding of trait objects to use 'MirReferenceType' in a more standard
-    -- way. See <https://github.com/GaloisInc/crucible/issues/1092>.
+    -- | The place behind a @&dyn Trait@ reference.  Unlike other reference
+    -- types, trait objects do not use 'MirReferenceType', instead using a
+    -- custom 'AnyType' representation that wraps a @'MirReferenceType' t@
+    -- (for some unknown @t@), paired with the vtable.  Since @t@ is unknown,
+    -- there are no projections into a 'MirPlaceDynRef'.  @addrOfPlace@ gives
+    -- back the original trait object (for code like @&*x@), and @readPlace@
+    -- also produces the trait object, which is how unsized @dyn@ values are
+    -- represented (see @tyToRepr@).  Dropping the value goes through the
+    -- vtable (see @dropDynPlace@).
     MirPlaceDynRef :: R.Expr MIR s DynRefType -> MirPlace s
 
 -- | MIR supports a notion of "unsized places" - for example, it generates code
@@ -183,6 +179,8 @@ type MirGenerator h s ret = G.Generator MIR s FnState ret (ST h)
 data FnState (s :: Type)
   = FnState { _varMap     :: !(VarMap s),
               _labelMap   :: !(LabelMap s),
+              _loopCuts   :: !(LoopCutMap s),
+              _unwindChecks :: !(UnwindCheckMap s),
               _debugLevel :: !Int,
               _transContext :: !FnTransContext,
               _cs         :: !CollectionState,
@@ -216,6 +214,21 @@ data CollectionState
       -- but with different hashes. Most of the time, however, this list will
       -- contain exactly one disambiguator per crate name.
       _crateHashesMap :: !(Map Text (NonEmpty Text)),
+      -- | The global holding the `BorrowState`, if aliasing checks are
+      -- enabled.
+      _borrowStateVar :: !(Maybe (G.GlobalVar BorrowStateType)),
+      -- | The global holding the `HeapAllocs`, if leak checks are enabled.
+      _heapAllocsVar  :: !(Maybe (G.GlobalVar HeapAllocsType)),
+      -- | The globals holding the `WeakMemory` state and the ID of the
+      -- running thread, if weak memory is enabled.
+      _weakMemoryVars :: !(Maybe (G.GlobalVar WeakMemoryType, G.GlobalVar C.NatType)),
+      -- | The globals holding the `RaceDetector` state and the ID of the
+      -- running thread, if data race detection is enabled.
+      _raceDetectorVars :: !(Maybe (G.GlobalVar RaceDetectorType, G.GlobalVar C.NatType)),
+      -- | The globals holding the running test's unwinding bound and whether
+      -- to assume it, if any function has an unwinding bound.  A negative
+      -- bound means the test has none.
+      _unwindBoundVars :: !(Maybe (G.GlobalVar C.IntegerType, G.GlobalVar C.BoolType)),
       _collection     :: !Collection
       }
 
@@ -294,6 +307,33 @@ instance ShowF (VarInfo s)
 -- | The LabelMap maps identifiers to labels of their corresponding basicblock
 type LabelMap s = Map BasicBlockInfo (R.Label s)
 
+---------------------------------------------------------------------------
+-- *** LoopCutMap
+
+-- | The LoopCutMap maps each block that calls `crucible::loop_invariant_cut`
+-- to the loop it cuts, and to a flag recording whether the current execution
+-- of that loop has already passed the cut.
+type LoopCutMap s = Map BasicBlockInfo (LoopCut, R.Reg s C.BoolType)
+
+---------------------------------------------------------------------------
+-- *** UnwindCheckMap
+
+-- | The unwinding check for a loop, which counts the iterations of the
+-- current execution of the loop.
+data UnwindCheck s = UnwindCheck
+    { ucEntries :: [BasicBlockInfo]
+    -- ^ The blocks outside the loop that jump to its header.
+    , ucBound :: Maybe UnwindBound
+    -- ^ The bound from the function itself, or `Nothing` to use the bound
+    -- of the running test.
+    , ucCounter :: R.Reg s C.IntegerType
+    , ucPos :: Text
+    }
+
+-- | The UnwindCheckMap maps the header of each loop that has an unwinding
+-- bound to its check.
+type UnwindCheckMap s = Map BasicBlockInfo (UnwindCheck s)
+
 ---------------------------------------------------------------------------
 -- *** HandleMap
 
@@ -358,6 +398,13 @@ instance Aeson.ToJSON BranchTransInfo where
 data FnTransInfo = FnTransInfo
     { _ftiBranches :: Seq BranchTransInfo
     , _ftiUnreachable :: Set Text
+    -- | The source span where each block of the Core CFG starts, keyed by the
+    -- printed form of its Core `BlockID` (which, unlike the other keys here,
+    -- is what profiling reports use).  Blocks that end in an error, such as
+    -- translations of MIR `unreachable`, are left out.
+    , _ftiBlockSpans :: Map Text Text
+    -- | The printed Core `BlockID` of the entry block.
+    , _ftiEntryBlock :: Maybe Text
     }
   deriving (Generic)
 
@@ -365,11 +412,11 @@ instance Aeson.ToJSON FnTransInfo where
     toEncoding = Aeson.genericToEncoding Aeson.defaultOptions
 
 instance Semigroup FnTransInfo where
-    (FnTransInfo b1 u1) <> (FnTransInfo b2 u2) =
-        FnTransInfo (b1 <> b2) (u1 <> u2)
+    (FnTransInfo b1 u1 s1 e1) <> (FnTransInfo b2 u2 s2 e2) =
+        FnTransInfo (b1 <> b2) (u1 <> u2) (s1 <> s2) (e1 <|> e2)
 
 instance Monoid FnTransInfo where
-    mempty = FnTransInfo mempty mempty
+    mempty = FnTransInfo mempty mempty mempty Nothing
 
 -- | Translation info for the entire crate.  Keys are printed function DefIds,
 -- since that's what's convenient in transCollection (and because the only
@@ -404,10 +451,10 @@ instance Monoid RustModule where
   mempty  = RustModule mempty mempty mempty
 
 instance Semigroup CollectionState  where
-  (CollectionState hm1 vm1 sm1 dm1 chm1 col1) <> (CollectionState hm2 vm2 sm2 dm2 chm2 col2) =
-      (CollectionState (hm1 <> hm2) (vm1 <> vm2) (sm1 <> sm2) (dm1 <> dm2) (Map.unionWith (<>) chm1 chm2) (col1 <> col2))
+  (CollectionState hm1 vm1 sm1 dm1 chm1 bsv1 hav1 wmv1 rdv1 ubv1 col1) <> (CollectionState hm2 vm2 sm2 dm2 chm2 bsv2 hav2 wmv2 rdv2 ubv2 col2) =
+      (CollectionState (hm1 <> hm2) (vm1 <> vm2) (sm1 <> sm2) (dm1 <> dm2) (Map.unionWith (<>) chm1 chm2) (bsv2 <|> bsv1) (hav2 <|> hav1) (wmv2 <|> wmv1) (rdv2 <|> rdv1) (ubv2 <|> ubv1) (col1 <> col2))
 instance Monoid CollectionState where
-  mempty  = CollectionState mempty mempty mempty mempty mempty mempty
+  mempty  = CollectionState mempty mempty mempty mempty mempty Nothing Nothing Nothing Nothing Nothing mempty
 
 
 instance Show (MirExp s) where
@@ -653,17 +700,194 @@ dropMirRef ::
   MirGenerator h s ret ()
 dropMirRef refExp = void $ G.extensionStmt (MirDropRef refExp)
 
+deallocMirRef ::
+  R.Expr MIR s C.AnyType ->
+  MirGenerator h s ret ()
+deallocMirRef ptr = void $ G.extensionStmt (MirDeallocRef ptr)
+
 readMirRef ::
   C.TypeRepr tp ->
   R.Expr MIR s (MirReferenceType tp) ->
   MirGenerator h s ret (R.Expr MIR s tp)
-readMirRef tp refExp = G.extensionStmt (MirReadRef tp refExp)
+readMirRef tp refExp = do
+  borrowAccess BorrowRead refExp
+  raceAccess False refExp
+  G.extensionStmt (MirReadRef tp refExp)
 
 writeMirRef ::
   R.Expr MIR s (MirReferenceType tp) ->
   R.Expr MIR s tp ->
   MirGenerator h s ret ()
-writeMirRef ref x = void $ G.extensionStmt (MirWriteRef ref x)
+writeMirRef ref x = do
+  borrowAccess BorrowWrite ref
+  raceAccess True ref
+  void $ G.extensionStmt (MirWriteRef ref x)
+
+-- | Check an access through `ref` against its borrow tag, if aliasing checks
+-- are enabled.
+borrowAccess ::
+  BorrowAccess ->
+  R.Expr MIR s (MirReferenceType tp) ->
+  MirGenerator h s ret ()
+borrowAccess acc ref = do
+  optGv <- use $ cs . borrowStateVar
+  case optGv of
+    Just gv -> void $ G.extensionStmt (MirBorrowAccess gv acc ref)
+    Nothing -> return ()
+
+-- | Check a read (`False`) or write (`True`) through `ref` for data races, if
+-- race detection is enabled.  Only accesses made by the crates under test are
+-- checked: the libraries synchronize through primitives that the detector
+-- models directly, such as atomics and locks.
+raceAccess ::
+  Bool ->
+  R.Expr MIR s (MirReferenceType tp) ->
+  MirGenerator h s ret ()
+raceAccess isWrite ref = do
+  optVars <- use $ cs . raceDetectorVars
+  ctx <- use transContext
+  rootFns <- use $ cs . collection . roots
+  case (optVars, ctx) of
+    (Just (gv, tidVar), FnContext fn)
+      | fn ^. fname . didCrate `elem` map (^. didCrate) rootFns ->
+        void $ G.extensionStmt (MirRaceAccess gv tidVar isWrite ref)
+    _ -> return ()
+
+-- | Give `ref` a fresh borrow tag, if aliasing checks are enabled.
+retagMirRef ::
+  BorrowPerm ->
+  C.TypeRepr tp ->
+  R.Expr MIR s (MirReferenceType tp) ->
+  MirGenerator h s ret (R.Expr MIR s (MirReferenceType tp))
+retagMirRef perm tpr ref = do
+  optGv <- use $ cs . borrowStateVar
+  case optGv of
+    Just gv -> G.extensionStmt (MirRetagRef gv perm tpr ref)
+    Nothing -> return ref
+
+-- | Record `ref` as a new heap allocation, if leak checks are enabled.
+trackAlloc ::
+  R.Expr MIR s (MirReferenceType tp) ->
+  MirGenerator h s ret ()
+trackAlloc ref = do
+  optGv <- use $ cs . heapAllocsVar
+  case optGv of
+    Just gv -> void $ G.extensionStmt (MirTrackAlloc gv ref)
+    Nothing -> return ()
+
+-- | Read `ref` with an atomic load.  With weak memory enabled, this may
+-- return a value older than the one in memory.
+atomicLoad ::
+  AtomicOrdering ->
+  C.TypeRepr tp ->
+  R.Expr MIR s (MirReferenceType tp) ->
+  MirGenerator h s ret (R.Expr MIR s tp)
+atomicLoad ord tpr ref = do
+  raceAtomic (Just ord) Nothing ref
+  optVars <- use $ cs . weakMemoryVars
+  case optVars of
+    Just (gv, tidVar) -> do
+      borrowAccess BorrowRead ref
+      G.extensionStmt (MirWeakLoad gv tidVar ord tpr ref)
+    Nothing -> readMirRef tpr ref
+
+atomicStore ::
+  AtomicOrdering ->
+  C.TypeRepr tp ->
+  R.Expr MIR s (MirReferenceType tp) ->
+  R.Expr MIR s tp ->
+  MirGenerator h s ret ()
+atomicStore ord tpr ref x = do
+  raceAtomic Nothing (Just ord) ref
+  optVars <- use $ cs . weakMemoryVars
+  case optVars of
+    Just (gv, tidVar) -> void $ G.extensionStmt (MirWeakStore gv tidVar ord tpr ref x)
+    Nothing -> return ()
+  writeMirRef ref x
+
+-- | Finish an atomic read-modify-write by writing `x` to `ref`.  The old value
+-- must have been read with `readMirRef`, since these operations always see
+-- the latest value.  `ok` says whether the operation succeeded, which selects
+-- between the success and failure orderings.
+atomicRMW ::
+  AtomicOrdering ->
+  AtomicOrdering ->
+  C.TypeRepr tp ->
+  R.Expr MIR s (MirReferenceType tp) ->
+  R.Expr MIR s C.BoolType ->
+  R.Expr MIR s tp ->
+  MirGenerator h s ret ()
+atomicRMW succOrd failOrd tpr ref ok x = do
+  -- The race detector treats every operation as successful.
+  raceAtomic (Just succOrd) (Just succOrd) ref
+  optVars <- use $ cs . weakMemoryVars
+  case optVars of
+    Just (gv, tidVar) ->
+      void $ G.extensionStmt (MirWeakRMW gv tidVar succOrd failOrd tpr ref ok x)
+    Nothing -> return ()
+  writeMirRef ref x
+
+atomicFence :: AtomicOrdering -> MirGenerator h s ret ()
+atomicFence ord = do
+  optVars <- use $ cs . weakMemoryVars
+  case optVars of
+    Just (gv, tidVar) -> void $ G.extensionStmt (MirWeakFence gv tidVar ord)
+    Nothing -> return ()
+  optRaceVars <- use $ cs . raceDetectorVars
+  case optRaceVars of
+    Just (gv, tidVar) -> void $ G.extensionStmt (MirRaceFence gv tidVar ord)
+    Nothing -> return ()
+
+-- | Record the synchronization done by an atomic access, if race detection is
+-- enabled.
+raceAtomic ::
+  Maybe AtomicOrdering ->
+  Maybe AtomicOrdering ->
+  R.Expr MIR s (MirReferenceType tp) ->
+  MirGenerator h s ret ()
+raceAtomic readOrd writeOrd ref = do
+  optVars <- use $ cs . raceDetectorVars
+  case optVars of
+    Just (gv, tidVar) -> void $ G.extensionStmt (MirRaceAtomic gv tidVar readOrd writeOrd ref)
+    Nothing -> return ()
+
+-- | Acquire (`False`) or release (`True`) the lock at `ref`, if race
+-- detection is enabled.
+syncLock ::
+  Bool ->
+  R.Expr MIR s (MirReferenceType tp) ->
+  MirGenerator h s ret ()
+syncLock rel ref = do
+  optVars <- use $ cs . raceDetectorVars
+  case optVars of
+    Just (gv, tidVar) -> void $ G.extensionStmt (MirRaceSync gv tidVar rel ref)
+    Nothing -> return ()
+
+-- | Let the thread with the given ID, which was just spawned, see everything
+-- the current thread has seen.
+syncSpawn :: R.Expr MIR s (C.BVType 32) -> MirGenerator h s ret ()
+syncSpawn thid = do
+  optVars <- use $ cs . weakMemoryVars
+  case optVars of
+    Just (gv, tidVar) -> void $ G.extensionStmt (MirWeakSpawn gv tidVar thid)
+    Nothing -> return ()
+  optRaceVars <- use $ cs . raceDetectorVars
+  case optRaceVars of
+    Just (gv, tidVar) -> void $ G.extensionStmt (MirRaceSpawn gv tidVar thid)
+    Nothing -> return ()
+
+-- | Let the current thread see everything the thread with the given ID, which
+-- was just joined, has seen.
+syncJoin :: R.Expr MIR s (C.BVType 32) -> MirGenerator h s ret ()
+syncJoin thid = do
+  optVars <- use $ cs . weakMemoryVars
+  case optVars of
+    Just (gv, tidVar) -> void $ G.extensionStmt (MirWeakJoin gv tidVar thid)
+    Nothing -> return ()
+  optRaceVars <- use $ cs . raceDetectorVars
+  case optRaceVars of
+    Just (gv, tidVar) -> void $ G.extensionStmt (MirRaceJoin gv tidVar thid)
+    Nothing -> return ()
 
 subanyRef ::
   C.TypeRepr tp ->
@@ -671,6 +895,12 @@ subanyRef ::
   MirGenerator h s ret (R.Expr MIR s (MirReferenceType tp))
 subanyRef tpr ref = G.extensionStmt (MirSubanyRef tpr ref)
 
+subunionRef ::
+  C.TypeRepr tp ->
+  R.Expr MIR s (MirReferenceType C.AnyType) ->
+  MirGenerator h s ret (R.Expr MIR s (MirReferenceType tp))
+subunionRef tpr ref = G.extensionStmt (MirSubunionRef tpr ref)
+
 subfieldRef ::
   C.CtxRepr ctx ->
   R.Expr MIR s (MirReferenceType (C.StructType ctx)) ->
@@ -62,13 +62,18 @@ import           Control.Monad.State.Strict
 import           Control.Monad.Trans.Class
 import           Control.Monad.Trans.Maybe
 import qualified Data.BitVector.Sized as BV
+import qualified Data.ByteString as BS
 import           Data.Kind(Type)
 import qualified Data.List as List
 import qualified Data.Maybe as Maybe
+import           Data.Sequence (Seq)
+import qualified Data.Sequence as Seq
+import qualified Data.Set as Set
 import           Data.Map.Strict(Map)
 import qualified Data.Map.Strict as Map
 import           Data.Text (Text)
 import qualified Data.Text as Text
+import qualified Data.Text.Encoding as Text
 import           Data.String
 import qualified Data.Vector as V
 import           Data.Word
@@ -79,6 +84,7 @@ import qualified Text.Regex as Regex
 import           Data.Parameterized.Some
 import           Data.Parameterized.Classes
 import           Data.Parameterized.Context
+import           Data.Parameterized.Nonce (freshNonce, globalNonceGenerator, indexValue)
 import           Data.Parameterized.TraversableFC
 import qualified Data.Parameterized.TH.GADT as U
 import qualified Data.Parameterized.Map as MapF
@@ -90,6 +96,7 @@ import           Lang.Crucible.CFG.Generator hiding (dropRef)
 import           Lang.Crucible.FunctionHandle
 import           Lang.Crucible.Syntax
 import           Lang.Crucible.Types
+import           Lang.Crucible.Simulator.CallFrame (SimFrame)
 import           Lang.Crucible.Simulator.ExecutionTree hiding (FnState)
 import           Lang.Crucible.Simulator.Evaluation
 import           Lang.Crucible.Simulator.GlobalState
@@ -102,6 +109,7 @@ import           Lang.Crucible.Simulator.SimError
 import           What4.Concrete (ConcreteVal(..), concreteType)
 import           What4.Interface
 import           What4.Partial
+import           What4.ProgramLoc (ProgramLoc(..))
     (PartExpr, pattern Unassigned, maybePartExpr, justPartExpr, joinMaybePE, mergePartial, mkPE)
 import           What4.Utils.MonadST
 
@@ -380,6 +388,13 @@ data MirReferencePath sym :: CrucibleType -> CrucibleType -> Type where
     !(TypeRepr tp) ->
     !(MirReferencePath sym tp_base AnyType) ->
     MirReferencePath sym  tp_base tp
+  -- | A field of a union, which is stored as an `Any` holding the field that
+  -- was written last.  Unlike `Any_RefPath`, writing through this path may
+  -- change the type of the value in the `Any`.
+  Union_RefPath ::
+    !(TypeRepr tp) ->
+    !(MirReferencePath sym tp_base AnyType) ->
+    MirReferencePath sym  tp_base tp
   Field_RefPath ::
     !(CtxRepr ctx) ->
     !(MirReferencePath sym tp_base (StructType ctx)) ->
@@ -411,10 +426,22 @@ data MirReferencePath sym :: CrucibleType -> CrucibleType -> Type where
     !(MirReferencePath sym tp_base (UsizeArrayType btp)) ->
     MirReferencePath sym tp_base (MirVectorType (BaseToType btp))
 
+-- | Identifies the reference that a pointer was derived from, for the aliasing
+-- checks described in the "Aliasing checks" section below.  Pointers that
+-- were not derived from a reference, such as pointers to locals and to heap
+-- allocations, carry `rootBorrowTag`, as do all pointers when the checks are
+-- disabled.
+newtype BorrowTag = BorrowTag Word64
+  deriving (Eq, Ord, Show)
+
+rootBorrowTag :: BorrowTag
+rootBorrowTag = BorrowTag 0
+
 data MirReference sym (tp :: CrucibleType) where
   MirReference ::
     !(MirReferenceRoot sym tpr) ->
     !(MirReferencePath sym tpr tp) ->
+    !BorrowTag ->
     MirReference sym tp
   -- The result of an integer-to-pointer cast.  Guaranteed not to be
   -- dereferenceable.
@@ -434,6 +461,7 @@ instance IsSymInterface sym => Show (MirReferenceRoot sym tp) where
 instance IsSymInterface sym => Show (MirReferencePath sym tp tp') where
     show Empty_RefPath = "Empty_RefPath"
     show (Any_RefPath tpr p) = "(Any_RefPath " ++ show tpr ++ " " ++ show p ++ ")"
+    show (Union_RefPath tpr p) = "(Union_RefPath " ++ show tpr ++ " " ++ show p ++ ")"
     show (Field_RefPath ctx p idx) = "(Field_RefPath " ++ show ctx ++ " " ++ show p ++ " " ++ show idx ++ ")"
     show (Variant_RefPath tp ctx p idx) = "(Variant_RefPath " ++ show tp ++ " " ++ show ctx ++ " " ++ show p ++ " " ++ show idx ++ ")"
     show (Index_RefPath tpr p idx) = "(Index_RefPath " ++ show tpr ++ " " ++ show p ++ " " ++ show (printSymExpr idx) ++ ")"
@@ -442,17 +470,17 @@ instance IsSymInterface sym => Show (MirReferencePath sym tp tp') where
     show (ArrayAsMirVector_RefPath btpr p) = "(ArrayAsMirVector_RefPath " ++ show btpr ++ " " ++ show p ++ ")"
 
 instance IsSymInterface sym => Show (MirReference sym tp) where
-    show (MirReference root path) = "(MirReference " ++ show root ++ " " ++ show path ++ ")"
+    show (MirReference root path tag) = "(MirReference " ++ show root ++ " " ++ show path ++ " " ++ show tag ++ ")"
     show (MirReference_Integer tpr _) = "(MirReference_Integer " ++ show tpr ++ " _)"
 
 instance OrdSkel (MirReference sym tp) where
     compareSkel = cmpRef
       where
         cmpRef :: MirReference sym tp1 -> MirReference sym tp2 -> Ordering
-        cmpRef (MirReference r1 p1) (MirReference r2 p2) =
-            cmpRoot r1 r2 <> cmpPath p1 p2
-        cmpRef (MirReference _ _) _ = LT
-        cmpRef _ (MirReference _ _) = GT
+        cmpRef (MirReference r1 p1 t1) (MirReference r2 p2 t2) =
+            cmpRoot r1 r2 <> cmpPath p1 p2 <> compare t1 t2
+        cmpRef (MirReference _ _ _) _ = LT
+        cmpRef _ (MirReference _ _ _) = GT
         cmpRef (MirReference_Integer tpr1 _) (MirReference_Integer tpr2 _) =
             compareSkelF tpr1 tpr2
 
@@ -473,6 +501,10 @@ instance OrdSkel (MirReference sym tp) where
             compareSkelF tpr1 tpr2 <> cmpPath p1 p2
         cmpPath (Any_RefPath _ _) _ = LT
         cmpPath _ (Any_RefPath _ _) = GT
+        cmpPath (Union_RefPath tpr1 p1) (Union_RefPath tpr2 p2) =
+            compareSkelF tpr1 tpr2 <> cmpPath p1 p2
+        cmpPath (Union_RefPath _ _) _ = LT
+        cmpPath _ (Union_RefPath _ _) = GT
         cmpPath (Field_RefPath ctx1 p1 idx1) (Field_RefPath ctx2 p2 idx2) =
             compareSkelF2 ctx1 idx1 ctx2 idx2 <> cmpPath p1 p2
         cmpPath (Field_RefPath _ _ _) _ = LT
@@ -532,6 +564,10 @@ muxRefPath sym c path1 path2 = case (path1,path2) of
     | Just Refl <- testEquality ctx1 ctx2 ->
          do p' <- muxRefPath sym c p1 p2
             return (Any_RefPath ctx1 p')
+  (Union_RefPath tpr1 p1, Union_RefPath tpr2 p2)
+    | Just Refl <- testEquality tpr1 tpr2 ->
+         do p' <- muxRefPath sym c p1 p2
+            return (Union_RefPath tpr1 p')
   (Field_RefPath ctx1 p1 f1, Field_RefPath ctx2 p2 f2)
     | Just Refl <- testEquality ctx1 ctx2
     , Just Refl <- testEquality f1 f2 ->
@@ -566,7 +602,7 @@ muxRef' :: forall sym tp.
   MirReference sym tp ->
   MirReference sym tp ->
   IO (MirReference sym tp)
-muxRef' sym iTypes c (MirReference r1 p1) (MirReference r2 p2) =
+muxRef' sym iTypes c (MirReference r1 p1 t1) (MirReference r2 p2 t2) =
    runMaybeT action >>= \case
      Nothing -> fail "Incompatible MIR reference merge"
      Just x  -> return x
@@ -575,9 +611,10 @@ muxRef' sym iTypes c (MirReference r1 p1) (MirReference r2 p2) =
   action :: MaybeT IO (MirReference sym tp)
   action =
     do Refl <- MaybeT (return $ testEquality (refRootType r1) (refRootType r2))
+       guard (t1 == t2)
        r' <- muxRefRoot sym iTypes c r1 r2
        p' <- muxRefPath sym c p1 p2
-       return (MirReference r' p')
+       return (MirReference r' p' t1)
 muxRef' sym _iTypes c (MirReference_Integer tpr i1) (MirReference_Integer _ i2) = do
     i' <- bvIte sym c i1 i2
     return $ MirReference_Integer tpr i'
@@ -842,6 +879,253 @@ dynRefVtableIndex :: Index (EmptyCtx ::> AnyType ::> AnyType) AnyType
 dynRefVtableIndex = lastIndex (incSize $ incSize zeroSize)
 
 
+--------------------------------------------------------------
+-- * Aliasing checks
+--
+-- When enabled, each reference created by a borrow expression gets a fresh
+-- `BorrowTag`, and a global `BorrowState` records, for each tag, the tag it
+-- was derived from, the memory it points to, and what it may still be used
+-- for.  This follows the Tree Borrows model rather than Stacked Borrows, as
+-- it accepts two-phase borrows such as `v.push(v.len())`:
+--
+-- * An access through a pointer is a /local/ access for the pointer's tag and
+--   all of its ancestors, and a /foreign/ access for every other tag whose
+--   memory overlaps the accessed location.
+-- * A mutable reference may be read and written by local accesses.  A
+--   foreign write disables it, and a foreign read after it has been written
+--   through freezes it, so it may no longer be written.
+-- * A shared reference may not be written through, and is disabled by a
+--   foreign write.  Shared references to types with interior mutability are
+--   not restricted.
+-- * Any access through a disabled tag is an error.
+--
+-- Raw pointers keep the tag of the reference they were cast from.  Function
+-- arguments are not protected, so a reference passed to a function may be
+-- invalidated while the function is running.
+
+-- | The permission granted by a reference.
+data BorrowPerm
+  = UniqueBorrow
+  -- ^ `&mut T`
+  | SharedReadOnlyBorrow
+  -- ^ `&T`, where `T` has no interior mutability
+  | SharedReadWriteBorrow
+  -- ^ `&T`, where `T` contains an `UnsafeCell`
+  deriving (Eq, Ord, Show)
+
+data BorrowAccess = BorrowRead | BorrowWrite
+  deriving (Eq, Ord, Show)
+
+data BorrowItem sym = BorrowItem
+  { biPerm :: !BorrowPerm
+  -- | The tags this one was derived from, nearest first.  This never includes
+  -- `rootBorrowTag`.
+  , biParents :: ![BorrowTag]
+  -- | The pointer that was tagged, which identifies the memory the borrow
+  -- covers.
+  , biLocation :: !(Some (MirReference sym))
+  , biDisabled :: !(Pred sym)
+  , biFrozen :: !(Pred sym)
+  -- | Whether the reference has been written through.
+  , biActive :: !(Pred sym)
+  }
+
+-- | Identifies the root of the memory a borrow covers.  Pointers into
+-- different roots never overlap, except that `Const_RefRoot`s can't be told
+-- apart, so they all share one key.
+data BorrowRootKey
+  = RefCellRootKey !(Some RefCell)
+  | GlobalVarRootKey !(Some GlobalVar)
+  | ConstRootKey
+  deriving (Eq, Ord)
+
+borrowRootKey :: MirReferenceRoot sym tp -> BorrowRootKey
+borrowRootKey (RefCell_RefRoot rc) = RefCellRootKey (Some rc)
+borrowRootKey (GlobalVar_RefRoot gv) = GlobalVarRootKey (Some gv)
+borrowRootKey (Const_RefRoot _ _) = ConstRootKey
+
+-- | The state of every tag, grouped by the root of the memory it covers, so
+-- that an access only visits the tags it might conflict with.  A tag's
+-- parents always have the same root, since retagging keeps the pointer's
+-- root.
+newtype BorrowState sym =
+    BorrowState (Map BorrowRootKey (Map BorrowTag (BorrowItem sym)))
+
+emptyBorrowState :: BorrowState sym
+emptyBorrowState = BorrowState Map.empty
+
+type BorrowStateSymbol = "MirBorrowState"
+type BorrowStateType = IntrinsicType BorrowStateSymbol EmptyCtx
+
+pattern BorrowStateRepr :: () => tp' ~ BorrowStateType => TypeRepr tp'
+pattern BorrowStateRepr <-
+     IntrinsicRepr (testEquality (knownSymbol @BorrowStateSymbol) -> Just Refl) Empty
+ where BorrowStateRepr = IntrinsicRepr (knownSymbol @BorrowStateSymbol) Empty
+
+
+--------------------------------------------------------------
+-- * Leak checks
+--
+-- When enabled, each heap allocation made by `crucible::alloc::allocate` is
+-- recorded in a global `HeapAllocs` map, along with the call stack at the
+-- point of allocation.  Deallocating clears the allocation's `RefCell`, so
+-- any recorded `RefCell` that is still initialized at the end of a test was
+-- leaked.
+
+data HeapAlloc = HeapAlloc
+  { haRefCell :: !(Some RefCell)
+  -- | The locations of the active Crucible frames when the allocation was
+  -- made, innermost first.
+  , haStack :: ![ProgramLoc]
+  }
+
+newtype HeapAllocs = HeapAllocs (Map Word64 HeapAlloc)
+
+emptyHeapAllocs :: HeapAllocs
+emptyHeapAllocs = HeapAllocs Map.empty
+
+type HeapAllocsSymbol = "MirHeapAllocs"
+type HeapAllocsType = IntrinsicType HeapAllocsSymbol EmptyCtx
+
+pattern HeapAllocsRepr :: () => tp' ~ HeapAllocsType => TypeRepr tp'
+pattern HeapAllocsRepr <-
+     IntrinsicRepr (testEquality (knownSymbol @HeapAllocsSymbol) -> Just Refl) Empty
+ where HeapAllocsRepr = IntrinsicRepr (knownSymbol @HeapAllocsSymbol) Empty
+
+
+--------------------------------------------------------------
+-- * Weak memory
+--
+-- When enabled, atomic accesses follow a view-based model of the C11
+-- release/acquire/relaxed orderings, instead of sequential consistency.  Each
+-- atomic location has a history of the messages (values) written to it, in
+-- modification order.  Each thread has a view, mapping locations to the index
+-- of the latest message it is known to have observed, and a load may read any
+-- message at or after that point.  Release stores attach the writer's view to
+-- the message, and acquire loads of the message merge it into the reader's
+-- view.  Relaxed accesses and fences use the pending acquire and release views
+-- to implement fence synchronization.  `SeqCst` accesses and fences also
+-- synchronize through a single global view.
+--
+-- `wmWeak` records whether the current execution contains a load that did not
+-- read the latest message, and so is only possible under weak orderings.
+
+data AtomicOrdering
+  = AtomicRelaxed
+  | AtomicRelease
+  | AtomicAcquire
+  | AtomicAcqRel
+  | AtomicSeqCst
+  deriving (Eq, Ord, Show)
+
+atomicAcquires :: AtomicOrdering -> Bool
+atomicAcquires o = o `elem` [AtomicAcquire, AtomicAcqRel, AtomicSeqCst]
+
+atomicReleases :: AtomicOrdering -> Bool
+atomicReleases o = o `elem` [AtomicRelease, AtomicAcqRel, AtomicSeqCst]
+
+-- | For each location, the index of a message in its history.  Missing
+-- locations map to 0, the first message.
+type WeakView sym = Map Text (SymNat sym)
+
+data WeakMessage sym = WeakMessage
+  { wmsgValue :: !(AnyValue sym)
+  -- | The view released by the write, which is acquired by anyone who reads
+  -- this message with an acquire ordering.
+  , wmsgView :: !(WeakView sym)
+  }
+
+data WeakThread sym = WeakThread
+  { wtView :: !(WeakView sym)
+  -- | Views of messages read by relaxed loads, which are acquired by the next
+  -- acquire fence.
+  , wtAcquireView :: !(WeakView sym)
+  -- | The view as of the last release fence, which relaxed stores release.
+  , wtReleaseView :: !(WeakView sym)
+  }
+
+data WeakMemory sym = WeakMemory
+  { wmHistories :: !(Map Text (Seq (WeakMessage sym)))
+  , wmThreads :: !(Map Natural (WeakThread sym))
+  , wmSCView :: !(WeakView sym)
+  , wmWeak :: !(Pred sym)
+  }
+
+emptyWeakThread :: WeakThread sym
+emptyWeakThread = WeakThread Map.empty Map.empty Map.empty
+
+emptyWeakMemory :: IsSymInterface sym => sym -> WeakMemory sym
+emptyWeakMemory sym = WeakMemory Map.empty Map.empty Map.empty (falsePred sym)
+
+type WeakMemorySymbol = "MirWeakMemory"
+type WeakMemoryType = IntrinsicType WeakMemorySymbol EmptyCtx
+
+pattern WeakMemoryRepr :: () => tp' ~ WeakMemoryType => TypeRepr tp'
+pattern WeakMemoryRepr <-
+     IntrinsicRepr (testEquality (knownSymbol @WeakMemorySymbol) -> Just Refl) Empty
+ where WeakMemoryRepr = IntrinsicRepr (knownSymbol @WeakMemorySymbol) Empty
+
+
+--------------------------------------------------------------
+-- * Data race detection
+--
+-- When enabled, each thread has a vector clock, and each non-atomic access
+-- that user code makes through a pointer is recorded along with the clock of
+-- the thread that made it.  Two accesses to the same location race if they
+-- come from different threads, at least one of them is a write, and neither
+-- happens before the other.  Threads synchronize when they are spawned and
+-- joined, through locks, which call `sync_acquire` and `sync_release`, and
+-- through atomic accesses and fences with release and acquire orderings.  As
+-- in the weak memory model, relaxed accesses only synchronize through fences.
+--
+-- The scheduler runs one interleaving at a time, and locations and thread IDs
+-- must be concrete, so the state is concrete as well.
+
+-- | For each thread, the latest point in its execution known to have
+-- happened.  Missing threads map to 0.
+type VectorClock = Map Natural Natural
+
+data RaceAccess = RaceAccess
+  { raThread :: !Natural
+  -- | The accessing thread's own entry in its clock at the time.
+  , raTime :: !Natural
+  , raWrite :: !Bool
+  , raLoc :: !ProgramLoc
+  }
+  deriving Eq
+
+data RaceThread = RaceThread
+  { rtClock :: !VectorClock
+  -- | Clocks released to relaxed loads, which are acquired by the next
+  -- acquire fence.
+  , rtAcquireClock :: !VectorClock
+  -- | The clock as of the last release fence, which relaxed stores release.
+  , rtReleaseClock :: !VectorClock
+  }
+
+data RaceDetector = RaceDetector
+  { rdThreads :: !(Map Natural RaceThread)
+  -- | The clocks released through each lock and atomic location.
+  , rdSyncClocks :: !(Map Text VectorClock)
+  -- | For each location, the last write and the reads that might not happen
+  -- after it.
+  , rdAccesses :: !(Map Text [RaceAccess])
+  -- | Locations with a race already reported, which aren't checked again.
+  , rdRaced :: !(Set.Set Text)
+  }
+
+emptyRaceDetector :: RaceDetector
+emptyRaceDetector = RaceDetector Map.empty Map.empty Map.empty Set.empty
+
+type RaceDetectorSymbol = "MirRaceDetector"
+type RaceDetectorType = IntrinsicType RaceDetectorSymbol EmptyCtx
+
+pattern RaceDetectorRepr :: () => tp' ~ RaceDetectorType => TypeRepr tp'
+pattern RaceDetectorRepr <-
+     IntrinsicRepr (testEquality (knownSymbol @RaceDetectorSymbol) -> Just Refl) Empty
+ where RaceDetectorRepr = IntrinsicRepr (knownSymbol @RaceDetectorSymbol) Empty
+
+
 data MirStmt :: (CrucibleType -> Type) -> CrucibleType -> Type where
   MirNewRef ::
      !(TypeRepr tp) ->
@@ -868,10 +1152,133 @@ data MirStmt :: (CrucibleType -> Type) -> CrucibleType -> Type where
   MirDropRef ::
      !(f (MirReferenceType tp)) ->
      MirStmt f UnitType
+  -- | Free a heap allocation.  The pointer is packed in `AnyType` so that the
+  -- data pointer of a trait object, whose pointee type is unknown, can be
+  -- freed too.
+  MirDeallocRef ::
+     !(f AnyType) ->
+     MirStmt f UnitType
+  -- | Record a new heap allocation in `HeapAllocs`, for the leak checks.
+  MirTrackAlloc ::
+     !(GlobalVar HeapAllocsType) ->
+     !(f (MirReferenceType tp)) ->
+     MirStmt f UnitType
+  -- | Perform an atomic load under the weak memory model.  The `NatType`
+  -- global holds the ID of the current thread.
+  MirWeakLoad ::
+     !(GlobalVar WeakMemoryType) ->
+     !(GlobalVar NatType) ->
+     !AtomicOrdering ->
+     !(TypeRepr tp) ->
+     !(f (MirReferenceType tp)) ->
+     MirStmt f tp
+  -- | Record an atomic store in the weak memory model.  This doesn't update
+  -- the memory itself, which is done separately with `MirWriteRef`.
+  MirWeakStore ::
+     !(GlobalVar WeakMemoryType) ->
+     !(GlobalVar NatType) ->
+     !AtomicOrdering ->
+     !(TypeRepr tp) ->
+     !(f (MirReferenceType tp)) ->
+     !(f tp) ->
+     MirStmt f UnitType
+  -- | Record an atomic read-modify-write, which reads the latest value and
+  -- writes the given one.  The condition selects between the success and
+  -- failure orderings.
+  MirWeakRMW ::
+     !(GlobalVar WeakMemoryType) ->
+     !(GlobalVar NatType) ->
+     !AtomicOrdering ->
+     !AtomicOrdering ->
+     !(TypeRepr tp) ->
+     !(f (MirReferenceType tp)) ->
+     !(f BoolType) ->
+     !(f tp) ->
+     MirStmt f UnitType
+  MirWeakFence ::
+     !(GlobalVar WeakMemoryType) ->
+     !(GlobalVar NatType) ->
+     !AtomicOrdering ->
+     MirStmt f UnitType
+  -- | Start the view of a newly spawned thread from that of the current
+  -- thread.
+  MirWeakSpawn ::
+     !(GlobalVar WeakMemoryType) ->
+     !(GlobalVar NatType) ->
+     !(f (BVType 32)) ->
+     MirStmt f UnitType
+  -- | Merge the view of a finished thread into that of the current thread.
+  MirWeakJoin ::
+     !(GlobalVar WeakMemoryType) ->
+     !(GlobalVar NatType) ->
+     !(f (BVType 32)) ->
+     MirStmt f UnitType
+  -- | Check a non-atomic read (`False`) or write (`True`) through a pointer
+  -- for data races.
+  MirRaceAccess ::
+     !(GlobalVar RaceDetectorType) ->
+     !(GlobalVar NatType) ->
+     !Bool ->
+     !(f (MirReferenceType tp)) ->
+     MirStmt f UnitType
+  -- | Acquire (`False`) or release (`True`) the lock at the given address, for
+  -- the data race detector.
+  MirRaceSync ::
+     !(GlobalVar RaceDetectorType) ->
+     !(GlobalVar NatType) ->
+     !Bool ->
+     !(f (MirReferenceType tp)) ->
+     MirStmt f UnitType
+  -- | Synchronize through an atomic access.  The first ordering applies to
+  -- the read, if any, and the second to the write, if any.
+  MirRaceAtomic ::
+     !(GlobalVar RaceDetectorType) ->
+     !(GlobalVar NatType) ->
+     !(Maybe AtomicOrdering) ->
+     !(Maybe AtomicOrdering) ->
+     !(f (MirReferenceType tp)) ->
+     MirStmt f UnitType
+  MirRaceFence ::
+     !(GlobalVar RaceDetectorType) ->
+     !(GlobalVar NatType) ->
+     !AtomicOrdering ->
+     MirStmt f UnitType
+  -- | Start the clock of a newly spawned thread from that of the current
+  -- thread.
+  MirRaceSpawn ::
+     !(GlobalVar RaceDetectorType) ->
+     !(GlobalVar NatType) ->
+     !(f (BVType 32)) ->
+     MirStmt f UnitType
+  -- | Merge the clock of a finished thread into that of the current thread.
+  MirRaceJoin ::
+     !(GlobalVar RaceDetectorType) ->
+     !(GlobalVar NatType) ->
+     !(f (BVType 32)) ->
+     MirStmt f UnitType
+  -- | Give a reference a fresh `BorrowTag` derived from its current one.
+  -- This counts as a read through the original pointer.
+  MirRetagRef ::
+     !(GlobalVar BorrowStateType) ->
+     !BorrowPerm ->
+     !(TypeRepr tp) ->
+     !(f (MirReferenceType tp)) ->
+     MirStmt f (MirReferenceType tp)
+  -- | Check that an access through a pointer is permitted by its
+  -- `BorrowTag`, and update the state of conflicting borrows.
+  MirBorrowAccess ::
+     !(GlobalVar BorrowStateType) ->
+     !BorrowAccess ->
+     !(f (MirReferenceType tp)) ->
+     MirStmt f UnitType
   MirSubanyRef ::
      !(TypeRepr tp) ->
      !(f (MirReferenceType AnyType)) ->
      MirStmt f (MirReferenceType tp)
+  MirSubunionRef ::
+     !(TypeRepr tp) ->
+     !(f (MirReferenceType AnyType)) ->
+     MirStmt f (MirReferenceType tp)
   MirSubfieldRef ::
      !(CtxRepr ctx) ->
      !(f (MirReferenceType (StructType ctx))) ->
@@ -1039,7 +1446,24 @@ instance TypeApp MirStmt where
     MirReadRef tp _ -> tp
     MirWriteRef _ _ -> UnitRepr
     MirDropRef _    -> UnitRepr
+    MirDeallocRef _ -> UnitRepr
+    MirRetagRef _ _ tp _ -> MirReferenceRepr tp
+    MirBorrowAccess _ _ _ -> UnitRepr
+    MirTrackAlloc _ _ -> UnitRepr
+    MirWeakLoad _ _ _ tp _ -> tp
+    MirWeakStore _ _ _ _ _ _ -> UnitRepr
+    MirWeakRMW _ _ _ _ _ _ _ _ -> UnitRepr
+    MirWeakFence _ _ _ -> UnitRepr
+    MirWeakSpawn _ _ _ -> UnitRepr
+    MirWeakJoin _ _ _ -> UnitRepr
+    MirRaceAccess _ _ _ _ -> UnitRepr
+    MirRaceSync _ _ _ _ -> UnitRepr
+    MirRaceAtomic _ _ _ _ _ -> UnitRepr
+    MirRaceFence _ _ _ -> UnitRepr
+    MirRaceSpawn _ _ _ -> UnitRepr
+    MirRaceJoin _ _ _ -> UnitRepr
     MirSubanyRef tp _ -> MirReferenceRepr tp
+    MirSubunionRef tp _ -> MirReferenceRepr tp
     MirSubfieldRef ctx _ idx -> MirReferenceRepr (ctx ! idx)
     MirSubvariantRef _ ctx _ idx -> MirReferenceRepr (ctx ! idx)
     MirSubindexRef tp _ _ -> MirReferenceRepr tp
@@ -1074,7 +1498,25 @@ instance PrettyApp MirStmt where
     MirReadRef _ x  -> "readMirRef" <+> pp x
     MirWriteRef x y -> "writeMirRef" <+> pp x <+> "<-" <+> pp y
     MirDropRef x    -> "dropMirRef" <+> pp x
+    MirDeallocRef x -> "deallocMirRef" <+> pp x
+    MirRetagRef _ perm _ x -> "retagMirRef" <+> viaShow perm <+> pp x
+    MirBorrowAccess _ acc x -> "borrowAccess" <+> viaShow acc <+> pp x
+    MirTrackAlloc _ x -> "trackAlloc" <+> pp x
+    MirWeakLoad _ _ ord _ x -> "weakLoad" <+> viaShow ord <+> pp x
+    MirWeakStore _ _ ord _ x y -> "weakStore" <+> viaShow ord <+> pp x <+> "<-" <+> pp y
+    MirWeakRMW _ _ ord1 ord2 _ x c y ->
+      "weakRMW" <+> viaShow ord1 <+> viaShow ord2 <+> pp x <+> pp c <+> pp y
+    MirWeakFence _ _ ord -> "weakFence" <+> viaShow ord
+    MirWeakSpawn _ _ t -> "weakSpawn" <+> pp t
+    MirWeakJoin _ _ t -> "weakJoin" <+> pp t
+    MirRaceAccess _ _ w x -> (if w then "raceWrite" else "raceRead") <+> pp x
+    MirRaceSync _ _ rel x -> (if rel then "raceRelease" else "raceAcquire") <+> pp x
+    MirRaceAtomic _ _ rd wr x -> "raceAtomic" <+> viaShow rd <+> viaShow wr <+> pp x
+    MirRaceFence _ _ ord -> "raceFence" <+> viaShow ord
+    MirRaceSpawn _ _ t -> "raceSpawn" <+> pp t
+    MirRaceJoin _ _ t -> "raceJoin" <+> pp t
     MirSubanyRef tpr x -> "subanyRef" <+> pretty tpr <+> pp x
+    MirSubunionRef tpr x -> "subunionRef" <+> pretty tpr <+> pp x
     MirSubfieldRef _ x idx -> "subfieldRef" <+> pp x <+> viaShow idx
     MirSubvariantRef _ _ x idx -> "subvariantRef" <+> pp x <+> viaShow idx
     MirSubindexRef _ x idx -> "subindexRef" <+> pp x <+> pp idx
@@ -1113,7 +1555,7 @@ instance IsSyntaxExtension MIR
 
 readBeforeWriteMsg :: SimErrorReason
 readBeforeWriteMsg = ReadBeforeWriteSimError
-    "Attempted to read uninitialized reference cell"
+    "Attempted to read uninitialized or deallocated reference cell"
 
 newConstMirRef :: IsSymInterface sym =>
     sym ->
@@ -1121,7 +1563,7 @@ newConstMirRef :: IsSymInterface sym =>
     RegValue sym tp ->
     MirReferenceMux sym tp
 newConstMirRef sym tpr v = MirReferenceMux $ toFancyMuxTree sym $
-    MirReference (Const_RefRoot tpr v) Empty_RefPath
+    MirReference (Const_RefRoot tpr v) Empty_RefPath rootBorrowTag
 
 readRefRoot :: (IsSymBackend sym bak) =>
     bak ->
@@ -1190,7 +1632,7 @@ readMirRefLeaf ::
     SymGlobalState sym ->
     IntrinsicTypes sym ->
     MirReference sym tp -> MuxLeafT sym IO (RegValue sym tp)
-readMirRefLeaf bak gs iTypes (MirReference r path) = do
+readMirRefLeaf bak gs iTypes (MirReference r path _) = do
     v <- readRefRoot bak gs r
     v' <- readRefPath bak iTypes v path
     return v'
@@ -1206,9 +1648,9 @@ writeMirRefLeaf ::
     MirReference sym tp ->
     RegValue sym tp ->
     MuxLeafT sym IO (SymGlobalState sym)
-writeMirRefLeaf bak gs iTypes (MirReference root Empty_RefPath) val =
+writeMirRefLeaf bak gs iTypes (MirReference root Empty_RefPath _) val =
     writeRefRoot bak gs iTypes root val
-writeMirRefLeaf bak gs iTypes (MirReference root path) val = do
+writeMirRefLeaf bak gs iTypes (MirReference root path _) val = do
     x <- readRefRoot bak gs root
     x' <- writeRefPath bak iTypes x path val
     writeRefRoot bak gs iTypes root x'
@@ -1222,8 +1664,8 @@ dropMirRefLeaf ::
     SymGlobalState sym ->
     MirReference sym tp ->
     MuxLeafT sym IO (SymGlobalState sym)
-dropMirRefLeaf bak gs (MirReference root Empty_RefPath) = dropRefRoot bak gs root
-dropMirRefLeaf _bak _gs (MirReference _ _) =
+dropMirRefLeaf bak gs (MirReference root Empty_RefPath _) = dropRefRoot bak gs root
+dropMirRefLeaf _bak _gs (MirReference _ _ _) =
     leafAbort $ GenericSimError $
       "attempted to drop an interior reference (non-empty ref path)"
 dropMirRefLeaf _bak _gs (MirReference_Integer _ _) =
@@ -1239,12 +1681,51 @@ dropMirRefIO ::
 dropMirRefIO bak gs (MirReferenceMux ref) =
     foldFancyMuxTree bak (dropMirRefLeaf bak) gs ref
 
+-- | Free the allocation that `ref` points to the start of.  Allocations made
+-- by `crucible::alloc::allocate` are vectors stored in a `RefCell`, and the
+-- pointer returned is to element 0.  Freeing clears the `RefCell`, so any
+-- later access through a pointer into the allocation fails, and freeing it
+-- again is reported as a double free.
+deallocMirRefLeaf ::
+    (IsSymBackend sym bak) =>
+    bak ->
+    SymGlobalState sym ->
+    MirReference sym tp ->
+    MuxLeafT sym IO (SymGlobalState sym)
+deallocMirRefLeaf bak gs
+        (MirReference root@(RefCell_RefRoot rc) (Index_RefPath _ Empty_RefPath idx) _) = do
+    let sym = backendGetSym bak
+    isStart <- liftIO $ bvEq sym idx =<< bvLit sym knownNat (BV.zero knownNat)
+    leafAssert bak isStart $ GenericSimError $
+        "attempted to deallocate a pointer that is not the start of an allocation"
+    _ <- leafReadPartExpr bak (lookupRef rc gs) $ GenericSimError $
+        "attempted to deallocate memory that was already deallocated (double free)"
+    dropRefRoot bak gs root
+deallocMirRefLeaf _bak _gs (MirReference _ _ _) =
+    leafAbort $ GenericSimError $
+      "attempted to deallocate memory that was not allocated by crucible::alloc::allocate"
+deallocMirRefLeaf _bak _gs (MirReference_Integer _ _) =
+    leafAbort $ GenericSimError $
+      "attempted to deallocate the result of an integer-to-pointer cast"
+
+deallocMirRefIO ::
+    IsSymBackend sym bak =>
+    bak ->
+    SymGlobalState sym ->
+    RegValue sym AnyType ->
+    IO (SymGlobalState sym)
+deallocMirRefIO bak gs (AnyValue (MirReferenceRepr _) (MirReferenceMux ref)) =
+    foldFancyMuxTree bak (deallocMirRefLeaf bak) gs ref
+deallocMirRefIO bak _gs (AnyValue tpr _) =
+    addFailedAssertion bak $ GenericSimError $
+      "attempted to deallocate a non-pointer value of type " ++ show tpr
+
 subanyMirRefLeaf ::
     TypeRepr tp ->
     MirReference sym AnyType ->
     MuxLeafT sym IO (MirReference sym tp)
-subanyMirRefLeaf tpr (MirReference root path) =
-    return $ MirReference root (Any_RefPath tpr path)
+subanyMirRefLeaf tpr (MirReference root path tag) =
+    return $ MirReference root (Any_RefPath tpr path) tag
 subanyMirRefLeaf _ (MirReference_Integer _ _) =
     leafAbort $ GenericSimError $
       "attempted subany on the result of an integer-to-pointer cast"
@@ -1259,13 +1740,33 @@ subanyMirRefIO ::
 subanyMirRefIO bak iTypes tpr ref =
     modifyRefMuxIO bak iTypes (subanyMirRefLeaf tpr) ref
 
+subunionMirRefLeaf ::
+    TypeRepr tp ->
+    MirReference sym AnyType ->
+    MuxLeafT sym IO (MirReference sym tp)
+subunionMirRefLeaf tpr (MirReference root path tag) =
+    return $ MirReference root (Union_RefPath tpr path) tag
+subunionMirRefLeaf _ (MirReference_Integer _ _) =
+    leafAbort $ GenericSimError $
+      "attempted subunion on the result of an integer-to-pointer cast"
+
+subunionMirRefIO ::
+    IsSymBackend sym bak =>
+    bak ->
+    IntrinsicTypes sym ->
+    TypeRepr tp ->
+    MirReferenceMux sym AnyType ->
+    IO (MirReferenceMux sym tp)
+subunionMirRefIO bak iTypes tpr ref =
+    modifyRefMuxIO bak iTypes (subunionMirRefLeaf tpr) ref
+
 subfieldMirRefLeaf ::
     CtxRepr ctx ->
     MirReference sym (StructType ctx) ->
     Index ctx tp ->
     MuxLeafT sym IO (MirReference sym tp)
-subfieldMirRefLeaf ctx (MirReference root path) idx =
-    return $ MirReference root (Field_RefPath ctx path idx)
+subfieldMirRefLeaf ctx (MirReference root path tag) idx =
+    return $ MirReference root (Field_RefPath ctx path idx) tag
 subfieldMirRefLeaf _ (MirReference_Integer _ _) _ =
     leafAbort $ GenericSimError $
       "attempted subfield on the result of an integer-to-pointer cast"
@@ -1287,8 +1788,8 @@ subvariantMirRefLeaf ::
     MirReference sym (RustEnumType discrTp variantsCtx) ->
     Index variantsCtx tp ->
     MuxLeafT sym IO (MirReference sym tp)
-subvariantMirRefLeaf tp ctx (MirReference root path) idx =
-    return $ MirReference root (Variant_RefPath tp ctx path idx)
+subvariantMirRefLeaf tp ctx (MirReference root path tag) idx =
+    return $ MirReference root (Variant_RefPath tp ctx path idx) tag
 subvariantMirRefLeaf _ _ (MirReference_Integer _ _) _ =
     leafAbort $ GenericSimError $
       "attempted subvariant on the result of an integer-to-pointer cast"
@@ -1310,8 +1811,8 @@ subindexMirRefLeaf ::
     MirReference sym (MirVectorType tp) ->
     RegValue sym UsizeType ->
     MuxLeafT sym IO (MirReference sym tp)
-subindexMirRefLeaf tpr (MirReference root path) idx =
-    return $ MirReference root (Index_RefPath tpr path idx)
+subindexMirRefLeaf tpr (MirReference root path tag) idx =
+    return $ MirReference root (Index_RefPath tpr path idx) tag
 subindexMirRefLeaf _ (MirReference_Integer _ _) _ =
     leafAbort $ GenericSimError $
       "attempted subindex on the result of an integer-to-pointer cast"
@@ -1320,8 +1821,8 @@ subjustMirRefLeaf ::
     TypeRepr tp ->
     MirReference sym (MaybeType tp) ->
     MuxLeafT sym IO (MirReference sym tp)
-subjustMirRefLeaf tpr (MirReference root path) =
-    return $ MirReference root (Just_RefPath tpr path)
+subjustMirRefLeaf tpr (MirReference root path tag) =
+    return $ MirReference root (Just_RefPath tpr path) tag
 subjustMirRefLeaf _ (MirReference_Integer _ _) =
     leafAbort $ GenericSimError $
       "attempted subjust on the result of an integer-to-pointer cast"
@@ -1340,8 +1841,8 @@ mirRef_vectorAsMirVectorLeaf ::
     TypeRepr tp ->
     MirReference sym (VectorType tp) ->
     MuxLeafT sym IO (MirReference sym (MirVectorType tp))
-mirRef_vectorAsMirVectorLeaf tpr (MirReference root path) =
-    return $ MirReference root (VectorAsMirVector_RefPath tpr path)
+mirRef_vectorAsMirVectorLeaf tpr (MirReference root path tag) =
+    return $ MirReference root (VectorAsMirVector_RefPath tpr path) tag
 mirRef_vectorAsMirVectorLeaf _ (MirReference_Integer _ _) =
     leafAbort $ GenericSimError $
         "attempted Vector->MirVector conversion on the result of an integer-to-pointer cast"
@@ -1360,8 +1861,8 @@ mirRef_arrayAsMirVectorLeaf ::
     BaseTypeRepr btp ->
     MirReference sym (UsizeArrayType btp) ->
     MuxLeafT sym IO (MirReference sym (MirVectorType (BaseToType btp)))
-mirRef_arrayAsMirVectorLeaf btpr (MirReference root path) =
-    return $ MirReference root (ArrayAsMirVector_RefPath btpr path)
+mirRef_arrayAsMirVectorLeaf btpr (MirReference root path tag) =
+    return $ MirReference root (ArrayAsMirVector_RefPath btpr path) tag
 mirRef_arrayAsMirVectorLeaf _ (MirReference_Integer _ _) =
     leafAbort $ GenericSimError $
       "attempted Array->MirVector conversion on the result of an integer-to-pointer cast"
@@ -1400,6 +1901,8 @@ refPathEq ::
 refPathEq sym Empty_RefPath Empty_RefPath = return $ truePred sym
 refPathEq sym (Any_RefPath tpr1 p1) (Any_RefPath tpr2 p2)
   | Just Refl <- testEquality tpr1 tpr2 = refPathEq sym p1 p2
+refPathEq sym (Union_RefPath tpr1 p1) (Union_RefPath tpr2 p2)
+  | Just Refl <- testEquality tpr1 tpr2 = refPathEq sym p1 p2
 refPathEq sym (Field_RefPath ctx1 p1 idx1) (Field_RefPath ctx2 p2 idx2)
   | Just Refl <- testEquality ctx1 ctx2
   , Just Refl <- testEquality idx1 idx2 = refPathEq sym p1 p2
@@ -1425,7 +1928,7 @@ mirRef_eqLeaf ::
     MirReference sym tp ->
     MirReference sym tp ->
     MuxLeafT sym IO (RegValue sym BoolType)
-mirRef_eqLeaf sym (MirReference root1 path1) (MirReference root2 path2) = do
+mirRef_eqLeaf sym (MirReference root1 path1 _) (MirReference root2 path2 _) = do
     rootEq <- refRootEq sym root1 root2
     pathEq <- refPathEq sym path1 path2
     liftIO $ andPred sym rootEq pathEq
@@ -1543,6 +2046,8 @@ reverseRefPath rp = go RrpNil rp
         go (Variant_RefPath tp ctx Empty_RefPath idx `RrpCons` acc) rp
     go acc (Index_RefPath tpr rp idx) =
         go (Index_RefPath tpr Empty_RefPath idx `RrpCons` acc) rp
+    go acc (Union_RefPath tpr rp) =
+        go (Union_RefPath tpr Empty_RefPath `RrpCons` acc) rp
     go acc (Just_RefPath tpr rp) =
         go (Just_RefPath tpr Empty_RefPath `RrpCons` acc) rp
     go acc (VectorAsMirVector_RefPath tpr rp) =
@@ -1600,6 +2105,11 @@ refPathOverlaps sym path1 path2 = do
     -- give a type mismatch error.
     go (Any_RefPath tpr1 _ `RrpCons` rrp1) (Any_RefPath tpr2 _ `RrpCons` rrp2)
       | Just Refl <- testEquality tpr1 tpr2 = go rrp1 rrp2
+    -- Fields of a union all share the union's storage, so fields of different
+    -- types overlap.
+    go (Union_RefPath tpr1 _ `RrpCons` rrp1) (Union_RefPath tpr2 _ `RrpCons` rrp2)
+      | Just Refl <- testEquality tpr1 tpr2 = go rrp1 rrp2
+      | otherwise = return $ truePred sym
     go (Field_RefPath ctx1 _ idx1 `RrpCons` rrp1) (Field_RefPath ctx2 _ idx2 `RrpCons` rrp2)
       | Just Refl <- testEquality ctx1 ctx2
       , Just Refl <- testEquality idx1 idx2 = go rrp1 rrp2
@@ -1629,7 +2139,7 @@ mirRef_overlapsLeaf ::
     MirReference sym tp ->
     MirReference sym tp' ->
     MuxLeafT sym IO (RegValue sym BoolType)
-mirRef_overlapsLeaf sym (MirReference root1 path1) (MirReference root2 path2) = do
+mirRef_overlapsLeaf sym (MirReference root1 path1 _) (MirReference root2 path2 _) = do
     rootOverlaps <- refRootOverlaps sym root1 root2
     case asConstantPred rootOverlaps of
         Just False -> return $ falsePred sym
@@ -1652,6 +2162,708 @@ mirRef_overlapsIO bak (MirReferenceMux r1) (MirReferenceMux r2) =
     zipFancyMuxTrees' bak (mirRef_overlapsLeaf sym) (itePred sym) r1 r2
 
 
+-- | Check an access through `ref`, which is the pointer in use when `c`
+-- holds, and update the state of every other borrow it affects.  The rules
+-- are described in the "Aliasing checks" section above.
+borrowAccessLeaf ::
+    IsSymBackend sym bak =>
+    bak ->
+    BorrowAccess ->
+    Pred sym ->
+    MirReference sym tp ->
+    BorrowState sym ->
+    IO (BorrowState sym)
+borrowAccessLeaf _bak _acc _c (MirReference_Integer _ _) st = return st
+borrowAccessLeaf bak acc c ref@(MirReference root _ tag) st@(BorrowState roots) =
+    case Map.lookup key roots of
+        -- No borrow of this root has been made, so there is nothing to check.
+        Nothing -> return st
+        Just items -> do
+            let localTags = tag : maybe [] biParents (Map.lookup tag items)
+            items' <- foldM (\m t -> case Map.lookup t m of
+                Just item -> do
+                    item' <- localAccess item
+                    return $ Map.insert t item' m
+                Nothing -> return m) items localTags
+            items'' <- Map.traverseWithKey (\t item ->
+                if t `elem` localTags then return item else foreignAccess item) items'
+            return $ BorrowState $ Map.insert key items'' roots
+  where
+    key = borrowRootKey root
+    sym = backendGetSym bak
+
+    localAccess item = do
+        disabled <- andPred sym c (biDisabled item)
+        assert bak (notPred sym disabled) $ GenericSimError $
+            "aliasing violation: attempted to access memory through a reference " ++
+            "that was invalidated by a conflicting access"
+        case (acc, biPerm item) of
+            (BorrowWrite, SharedReadOnlyBorrow) -> do
+                assert bak (notPred sym c) $ GenericSimError $
+                    "aliasing violation: attempted to write through a pointer " ++
+                    "derived from a shared reference"
+                return item
+            (BorrowWrite, UniqueBorrow) -> do
+                frozen <- andPred sym c (biFrozen item)
+                assert bak (notPred sym frozen) $ GenericSimError $
+                    "aliasing violation: attempted to write through a mutable " ++
+                    "reference after its memory was read through a conflicting pointer"
+                active <- orPred sym c (biActive item)
+                return item { biActive = active }
+            _ -> return item
+
+    foreignAccess item
+      | Just True <- asConstantPred (biDisabled item) = return item
+      | otherwise = case (acc, biPerm item) of
+            (_, SharedReadWriteBorrow) -> return item
+            (BorrowRead, SharedReadOnlyBorrow) -> return item
+            (BorrowRead, UniqueBorrow) -> do
+                conflict <- conflicts item
+                frozen <- orPred sym (biFrozen item) =<< andPred sym conflict (biActive item)
+                return item { biFrozen = frozen }
+            (BorrowWrite, _) -> do
+                conflict <- conflicts item
+                disabled <- orPred sym (biDisabled item) conflict
+                return item { biDisabled = disabled }
+
+    conflicts item = case biLocation item of
+        Some loc -> do
+            overlaps <- mirRef_overlapsIO bak
+                (MirReferenceMux $ toFancyMuxTree sym loc)
+                (MirReferenceMux $ toFancyMuxTree sym ref)
+            andPred sym c overlaps
+
+borrowAccessIO ::
+    IsSymBackend sym bak =>
+    bak ->
+    SymGlobalState sym ->
+    GlobalVar BorrowStateType ->
+    BorrowAccess ->
+    MirReferenceMux sym tp ->
+    IO (SymGlobalState sym)
+borrowAccessIO bak gs gv acc (MirReferenceMux ref) = case lookupGlobal gv gs of
+    -- The checks are enabled by initializing the global.
+    Nothing -> return gs
+    Just st -> do
+        st' <- foldM (\st0 (leaf, c) -> borrowAccessLeaf bak acc c leaf st0)
+            st (viewFancyMuxTree ref)
+        return $ insertGlobal gv st' gs
+
+retagMirRefIO ::
+    IsSymBackend sym bak =>
+    bak ->
+    SymGlobalState sym ->
+    IntrinsicTypes sym ->
+    GlobalVar BorrowStateType ->
+    BorrowPerm ->
+    MirReferenceMux sym tp ->
+    IO (MirReferenceMux sym tp, SymGlobalState sym)
+retagMirRefIO bak gs iTypes gv perm ref@(MirReferenceMux tree) = case lookupGlobal gv gs of
+    Nothing -> return (ref, gs)
+    Just st -> do
+        (leaves, st') <- foldM retag ([], st) (viewFancyMuxTree tree)
+        tree' <- buildFancyMuxTree sym (muxRef' sym iTypes) (reverse leaves)
+        return (MirReferenceMux tree', insertGlobal gv st' gs)
+  where
+    sym = backendGetSym bak
+
+    retag (leaves, st0) (leaf, c) = do
+        st1 <- borrowAccessLeaf bak BorrowRead c leaf st0
+        (leaf', st2) <- retagLeaf leaf st1
+        return ((leaf', c) : leaves, st2)
+
+    retagLeaf leaf@(MirReference_Integer _ _) st0 = return (leaf, st0)
+    retagLeaf (MirReference root path parent) (BorrowState roots) = do
+        let key = borrowRootKey root
+        let items = Map.findWithDefault Map.empty key roots
+        nonce <- freshNonce globalNonceGenerator
+        -- Skip 0, which is `rootBorrowTag`.
+        let tag = BorrowTag (indexValue nonce + 1)
+        let leaf' = MirReference root path tag
+        let parents
+              | parent == rootBorrowTag = []
+              | otherwise = parent : maybe [] biParents (Map.lookup parent items)
+        let item = BorrowItem
+              { biPerm = perm
+              , biParents = parents
+              , biLocation = Some leaf'
+              , biDisabled = falsePred sym
+              , biFrozen = backendPred sym (perm == SharedReadOnlyBorrow)
+              , biActive = falsePred sym
+              }
+        return (leaf', BorrowState $ Map.insert key (Map.insert tag item items) roots)
+
+
+trackAllocIO ::
+    SymGlobalState sym ->
+    GlobalVar HeapAllocsType ->
+    [SomeFrame (SimFrame sym MIR)] ->
+    MirReferenceMux sym tp ->
+    IO (SymGlobalState sym)
+trackAllocIO gs gv frames (MirReferenceMux ref) = case lookupGlobal gv gs of
+    -- The checks are enabled by initializing the global.
+    Nothing -> return gs
+    Just (HeapAllocs allocs) -> do
+        allocs' <- foldM track allocs (viewFancyMuxTree ref)
+        return $ insertGlobal gv (HeapAllocs allocs') gs
+  where
+    stack = Maybe.mapMaybe filterCrucibleFrames frames
+
+    track allocs (MirReference (RefCell_RefRoot rc) _ _, _) = do
+        nonce <- freshNonce globalNonceGenerator
+        return $ Map.insert (indexValue nonce) (HeapAlloc (Some rc) stack) allocs
+    track allocs _ = return allocs
+
+-- | Assert that every allocation recorded in `HeapAllocs` has been freed,
+-- other than those reachable from `res` (the test's return value), which
+-- still owns them.  A leak is reported at the innermost frame of the
+-- allocation's call stack that satisfies `isUserLoc`, or at the allocation
+-- itself if there is no such frame.
+checkLeaksSim ::
+    IsSymInterface sym =>
+    (ProgramLoc -> Bool) ->
+    GlobalVar HeapAllocsType ->
+    RegEntry sym tp ->
+    OverrideSim p sym MIR rtp args ret ()
+checkLeaksSim isUserLoc gv (RegEntry resTpr res) = do
+    HeapAllocs allocs <- readGlobal gv
+    gs <- readGlobals
+    let owned = reachableRefCells gs resTpr res
+    ovrWithBackend $ \bak -> liftIO $ do
+        let sym = backendGetSym bak
+        forM_ (Map.elems allocs) $ \(HeapAlloc (Some rc) stack) ->
+          case lookupRef rc gs of
+            _ | Set.member (Some rc) owned -> return ()
+            Unassigned -> return ()
+            PE live _ -> do
+                loc <- case filter isUserLoc stack ++ stack of
+                    l : _ -> return l
+                    [] -> getCurrentProgramLoc sym
+                freed <- notPred sym live
+                addAssertion bak $ LabeledPred freed $ SimError loc $
+                    GenericSimError "memory leak: this allocation is never freed"
+
+-- | The `RefCell`s that a value of type `tpr` can reach by following
+-- references, including through other `RefCell`s.  This over-approximates:
+-- references and fields that are only conditionally present are followed
+-- too.
+reachableRefCells ::
+    forall sym tp.
+    SymGlobalState sym ->
+    TypeRepr tp ->
+    RegValue sym tp ->
+    Set.Set (Some RefCell)
+reachableRefCells gs tpr0 v0 = execState (go tpr0 v0) Set.empty
+  where
+    go :: forall tp'. TypeRepr tp' -> RegValue sym tp' -> State (Set.Set (Some RefCell)) ()
+    go tpr v = case tpr of
+        StructRepr ctx -> forIndexM (size ctx) $ \i -> go (ctx ! i) (unRV $ v ! i)
+        VariantRepr ctx -> forIndexM (size ctx) $ \i -> case unVB $ v ! i of
+            PE _ x -> go (ctx ! i) x
+            Unassigned -> return ()
+        MaybeRepr tpr' -> case v of
+            PE _ x -> go tpr' x
+            Unassigned -> return ()
+        VectorRepr tpr' -> mapM_ (go tpr') v
+        AnyRepr | AnyValue tpr' x <- v -> go tpr' x
+        MirVectorRepr tpr' -> case v of
+            MirVector_Vector xs -> mapM_ (go tpr') xs
+            MirVector_PartialVector xs -> mapM_ (go (MaybeRepr tpr')) xs
+            MirVector_Array _ -> return ()
+        MirReferenceRepr _ | MirReferenceMux tree <- v ->
+            mapM_ (goRef . fst) (viewFancyMuxTree tree)
+        _ -> return ()
+
+    goRef :: forall tp'. MirReference sym tp' -> State (Set.Set (Some RefCell)) ()
+    goRef (MirReference (RefCell_RefRoot rc) _ _) = do
+        seen <- get
+        unless (Set.member (Some rc) seen) $ do
+            put $ Set.insert (Some rc) seen
+            case lookupRef rc gs of
+                PE _ x -> go (refType rc) x
+                Unassigned -> return ()
+    goRef (MirReference (Const_RefRoot tpr x) _ _) = go tpr x
+    goRef _ = return ()
+
+
+-- | A name identifying the memory location that a pointer refers to, for the
+-- weak memory model.  Unlike the resource names used by the scheduler, this
+-- distinguishes array elements, so it fails on symbolic indices.
+weakLocationName ::
+    forall sym tp. IsSymInterface sym => MirReference sym tp -> Maybe Text
+weakLocationName (MirReference root path _) =
+    (\p -> Text.pack (show root ++ p)) <$> go path
+  where
+    go :: forall tp_base tp'. MirReferencePath sym tp_base tp' -> Maybe String
+    go Empty_RefPath = Just ""
+    go (Any_RefPath _ p) = go p
+    go (Union_RefPath _ p) = go p
+    go (Field_RefPath _ p idx) = (++ "." ++ show (indexVal idx)) <$> go p
+    go (Variant_RefPath _ _ p idx) = (++ "." ++ show (indexVal idx)) <$> go p
+    go (Index_RefPath _ p idx) = do
+        i <- asBV idx
+        (++ "[" ++ show (BV.asUnsigned i) ++ "]") <$> go p
+    go (Just_RefPath _ p) = go p
+    go (VectorAsMirVector_RefPath _ p) = go p
+    go (ArrayAsMirVector_RefPath _ p) = go p
+weakLocationName (MirReference_Integer _ _) = Nothing
+
+weakViewAt :: IsSymInterface sym => sym -> WeakView sym -> Text -> IO (SymNat sym)
+weakViewAt sym view loc = maybe (natLit sym 0) return (Map.lookup loc view)
+
+joinWeakViews ::
+    IsSymInterface sym => sym -> WeakView sym -> WeakView sym -> IO (WeakView sym)
+joinWeakViews sym v1 v2 = sequence $
+    Map.mergeWithKey (\_ a b -> Just $ natMax a b) (fmap return) (fmap return) v1 v2
+  where
+    natMax a b = do
+        le <- natLe sym a b
+        natIte sym le b a
+
+iteWeakViews ::
+    IsSymInterface sym =>
+    sym -> Pred sym -> WeakView sym -> WeakView sym -> IO (WeakView sym)
+iteWeakViews sym c v1 v2 = sequence $ Map.fromSet pick (Map.keysSet v1 <> Map.keysSet v2)
+  where
+    pick loc = do
+        a <- weakViewAt sym v1 loc
+        b <- weakViewAt sym v2 loc
+        natIte sym c a b
+
+putWeakThread :: Natural -> WeakThread sym -> WeakMemory sym -> WeakMemory sym
+putWeakThread tid th wm = wm { wmThreads = Map.insert tid th (wmThreads wm) }
+
+-- | Merge the global `SeqCst` view into a thread's view.
+joinSCView :: IsSymInterface sym => sym -> WeakMemory sym -> WeakThread sym -> IO (WeakThread sym)
+joinSCView sym wm th = do
+    view <- joinWeakViews sym (wtView th) (wmSCView wm)
+    return th { wtView = view }
+
+-- | Get the weak memory state, the ID of the current thread, and that
+-- thread's state.
+weakThreadState ::
+    IsSymInterface sym =>
+    SymGlobalState sym ->
+    GlobalVar WeakMemoryType ->
+    GlobalVar NatType ->
+    IO (WeakMemory sym, Natural, WeakThread sym)
+weakThreadState gs gv tidVar = do
+    wm <- case lookupGlobal gv gs of
+        Just wm -> return wm
+        Nothing -> fail "weak memory state is not initialized"
+    tid <- case lookupGlobal tidVar gs >>= asNat of
+        Just tid -> return tid
+        Nothing -> fail "weak memory: the ID of the current thread is unknown"
+    return (wm, tid, Map.findWithDefault emptyWeakThread tid (wmThreads wm))
+
+-- | Like `weakThreadState`, but also get the location accessed through `ref`.
+-- The first atomic access to a location starts its history with the value
+-- currently in memory.
+weakAccessState ::
+    IsSymBackend sym bak =>
+    bak ->
+    SymGlobalState sym ->
+    IntrinsicTypes sym ->
+    GlobalVar WeakMemoryType ->
+    GlobalVar NatType ->
+    TypeRepr tp ->
+    MirReferenceMux sym tp ->
+    IO (WeakMemory sym, Natural, WeakThread sym, Text, Seq (WeakMessage sym))
+weakAccessState bak gs iTypes gv tidVar tpr ref@(MirReferenceMux tree) = do
+    (wm, tid, th) <- weakThreadState gs gv tidVar
+    loc <- case viewFancyMuxTree tree of
+        [(leaf, _)] | Just loc <- weakLocationName leaf -> return loc
+        _ -> addFailedAssertion bak $ Unsupported callStack $
+            "atomic access through a symbolic pointer with weak memory enabled"
+    case Map.lookup loc (wmHistories wm) of
+        Just hist -> return (wm, tid, th, loc, hist)
+        Nothing -> do
+            v <- readMirRefIO bak gs iTypes tpr ref
+            let hist = Seq.singleton $ WeakMessage (AnyValue tpr v) Map.empty
+            return (wm, tid, th, loc, hist)
+
+-- | Get the value and view of the message at index `i` of a history.
+weakReadMessage ::
+    forall sym tp.
+    IsSymInterface sym =>
+    sym ->
+    IntrinsicTypes sym ->
+    TypeRepr tp ->
+    SymNat sym ->
+    Seq (WeakMessage sym) ->
+    IO (RegValue sym tp, WeakView sym)
+weakReadMessage sym iTypes tpr i hist = do
+    msgs <- traverse unpack hist
+    case Seq.viewr msgs of
+        Seq.EmptyR -> fail "weak memory: empty message history"
+        older Seq.:> latest -> foldM pick latest (Seq.mapWithIndex (,) older)
+  where
+    unpack :: WeakMessage sym -> IO (RegValue sym tp, WeakView sym)
+    unpack (WeakMessage (AnyValue tpr' v) view)
+      | Just Refl <- testEquality tpr tpr' = return (v, view)
+      | otherwise = fail $ "weak memory: location written at type " ++ show tpr'
+            ++ " was read at type " ++ show tpr
+    pick (v, view) (j, (v', view')) = do
+        c <- natEq sym i =<< natLit sym (fromIntegral j)
+        (,) <$> muxRegForType sym iTypes tpr c v' v <*> iteWeakViews sym c view' view
+
+weakLoadIO ::
+    IsSymBackend sym bak =>
+    bak ->
+    SymGlobalState sym ->
+    IntrinsicTypes sym ->
+    GlobalVar WeakMemoryType ->
+    GlobalVar NatType ->
+    AtomicOrdering ->
+    TypeRepr tp ->
+    MirReferenceMux sym tp ->
+    IO (RegValue sym tp, SymGlobalState sym)
+weakLoadIO bak gs iTypes gv tidVar ord tpr ref = do
+    let sym = backendGetSym bak
+    (wm, tid, th0, loc, hist) <- weakAccessState bak gs iTypes gv tidVar tpr ref
+    th1 <- if ord == AtomicSeqCst then joinSCView sym wm th0 else return th0
+    -- Read any message no older than the last one this thread has observed.
+    latest <- natLit sym (fromIntegral (Seq.length hist - 1))
+    earliest <- weakViewAt sym (wtView th1) loc
+    i <- freshNat sym (safeSymbol "weak_read")
+    inRange <- join $ andPred sym <$> natLe sym earliest i <*> natLe sym i latest
+    pl <- getCurrentProgramLoc sym
+    addAssumption bak $ GenericAssumption pl "weak memory read" inRange
+    (v, msgView) <- weakReadMessage sym iTypes tpr i hist
+    let th2 = th1 { wtView = Map.insert loc i (wtView th1) }
+    th3 <- if atomicAcquires ord
+        then (\view -> th2 { wtView = view }) <$> joinWeakViews sym (wtView th2) msgView
+        else (\view -> th2 { wtAcquireView = view }) <$>
+            joinWeakViews sym (wtAcquireView th2) msgView
+    stale <- natLt sym i latest
+    weak <- orPred sym (wmWeak wm) stale
+    let wm' = putWeakThread tid th3 $ wm
+          { wmHistories = Map.insert loc hist (wmHistories wm)
+          , wmSCView = if ord == AtomicSeqCst then wtView th3 else wmSCView wm
+          , wmWeak = weak
+          }
+    return (v, insertGlobal gv wm' gs)
+
+weakStoreIO ::
+    IsSymBackend sym bak =>
+    bak ->
+    SymGlobalState sym ->
+    IntrinsicTypes sym ->
+    GlobalVar WeakMemoryType ->
+    GlobalVar NatType ->
+    AtomicOrdering ->
+    TypeRepr tp ->
+    MirReferenceMux sym tp ->
+    RegValue sym tp ->
+    IO (SymGlobalState sym)
+weakStoreIO bak gs iTypes gv tidVar ord tpr ref v = do
+    let sym = backendGetSym bak
+    (wm, tid, th0, loc, hist) <- weakAccessState bak gs iTypes gv tidVar tpr ref
+    th1 <- if ord == AtomicSeqCst then joinSCView sym wm th0 else return th0
+    t <- natLit sym (fromIntegral (Seq.length hist))
+    let th2 = th1 { wtView = Map.insert loc t (wtView th1) }
+    let msgView
+          | atomicReleases ord = wtView th2
+          | otherwise = Map.insert loc t (wtReleaseView th2)
+    let msg = WeakMessage (AnyValue tpr v) msgView
+    let wm' = putWeakThread tid th2 $ wm
+          { wmHistories = Map.insert loc (hist Seq.|> msg) (wmHistories wm)
+          , wmSCView = if ord == AtomicSeqCst then wtView th2 else wmSCView wm
+          }
+    return $ insertGlobal gv wm' gs
+
+-- | A read-modify-write always reads the latest message.  The ordering that
+-- applies is chosen by `ok`: `succOrd` if it holds and `failOrd` otherwise.
+weakRMWIO ::
+    IsSymBackend sym bak =>
+    bak ->
+    SymGlobalState sym ->
+    IntrinsicTypes sym ->
+    GlobalVar WeakMemoryType ->
+    GlobalVar NatType ->
+    AtomicOrdering ->
+    AtomicOrdering ->
+    TypeRepr tp ->
+    MirReferenceMux sym tp ->
+    RegValue sym BoolType ->
+    RegValue sym tp ->
+    IO (SymGlobalState sym)
+weakRMWIO bak gs iTypes gv tidVar succOrd failOrd tpr ref ok v = do
+    let sym = backendGetSym bak
+    let isSC = succOrd == AtomicSeqCst || failOrd == AtomicSeqCst
+    (wm, tid, th0, loc, hist) <- weakAccessState bak gs iTypes gv tidVar tpr ref
+    th1 <- if isSC then joinSCView sym wm th0 else return th0
+    let readView = wmsgView $ Seq.index hist (Seq.length hist - 1)
+    t <- natLit sym (fromIntegral (Seq.length hist))
+    acq <- case (atomicAcquires succOrd, atomicAcquires failOrd) of
+        (True, True) -> return $ truePred sym
+        (False, False) -> return $ falsePred sym
+        (True, False) -> return ok
+        (False, True) -> notPred sym ok
+    -- Only a successful operation writes, so only it can release.
+    let rel = if atomicReleases succOrd then ok else falsePred sym
+    acquired <- joinWeakViews sym (wtView th1) readView
+    view <- Map.insert loc t <$> iteWeakViews sym acq acquired (wtView th1)
+    acqView <- joinWeakViews sym (wtAcquireView th1) readView
+    relView <- iteWeakViews sym rel view (Map.insert loc t (wtReleaseView th1))
+    -- The new message continues the release sequence of the one it replaces,
+    -- so it carries that message's view as well.
+    msgView <- joinWeakViews sym readView relView
+    let th2 = th1 { wtView = view, wtAcquireView = acqView }
+    let msg = WeakMessage (AnyValue tpr v) msgView
+    let wm' = putWeakThread tid th2 $ wm
+          { wmHistories = Map.insert loc (hist Seq.|> msg) (wmHistories wm)
+          , wmSCView = if isSC then view else wmSCView wm
+          }
+    return $ insertGlobal gv wm' gs
+
+weakFenceIO ::
+    IsSymInterface sym =>
+    sym ->
+    SymGlobalState sym ->
+    GlobalVar WeakMemoryType ->
+    GlobalVar NatType ->
+    AtomicOrdering ->
+    IO (SymGlobalState sym)
+weakFenceIO sym gs gv tidVar ord = do
+    (wm, tid, th0) <- weakThreadState gs gv tidVar
+    view1 <- if atomicAcquires ord
+        then joinWeakViews sym (wtView th0) (wtAcquireView th0)
+        else return (wtView th0)
+    view2 <- if ord == AtomicSeqCst
+        then joinWeakViews sym view1 (wmSCView wm)
+        else return view1
+    let th1 = th0
+          { wtView = view2
+          , wtReleaseView = if atomicReleases ord then view2 else wtReleaseView th0
+          }
+    let wm' = putWeakThread tid th1 $ wm
+          { wmSCView = if ord == AtomicSeqCst then view2 else wmSCView wm }
+    return $ insertGlobal gv wm' gs
+
+weakThreadID :: IsSymInterface sym => RegValue sym (BVType 32) -> IO Natural
+weakThreadID t = case asBV t of
+    Just bv -> return $ fromInteger $ BV.asUnsigned bv
+    Nothing -> fail "weak memory: thread ID is symbolic"
+
+weakSpawnIO ::
+    IsSymInterface sym =>
+    sym ->
+    SymGlobalState sym ->
+    GlobalVar WeakMemoryType ->
+    GlobalVar NatType ->
+    RegValue sym (BVType 32) ->
+    IO (SymGlobalState sym)
+weakSpawnIO _sym gs gv tidVar child = do
+    (wm, _, th) <- weakThreadState gs gv tidVar
+    childTid <- weakThreadID child
+    let wm' = putWeakThread childTid (emptyWeakThread { wtView = wtView th }) wm
+    return $ insertGlobal gv wm' gs
+
+weakJoinIO ::
+    IsSymInterface sym =>
+    sym ->
+    SymGlobalState sym ->
+    GlobalVar WeakMemoryType ->
+    GlobalVar NatType ->
+    RegValue sym (BVType 32) ->
+    IO (SymGlobalState sym)
+weakJoinIO sym gs gv tidVar child = do
+    (wm, tid, th) <- weakThreadState gs gv tidVar
+    childTid <- weakThreadID child
+    let childTh = Map.findWithDefault emptyWeakThread childTid (wmThreads wm)
+    view <- joinWeakViews sym (wtView th) (wtView childTh)
+    return $ insertGlobal gv (putWeakThread tid th { wtView = view } wm) gs
+
+
+newRaceThread :: Natural -> RaceThread
+newRaceThread tid = RaceThread (Map.singleton tid 1) Map.empty Map.empty
+
+clockAt :: Natural -> VectorClock -> Natural
+clockAt = Map.findWithDefault 0
+
+joinClocks :: VectorClock -> VectorClock -> VectorClock
+joinClocks = Map.unionWith max
+
+-- | Advance the thread's own entry in its clock, so that its later accesses
+-- don't happen before anything it has just released.
+tickRaceThread :: Natural -> RaceThread -> RaceThread
+tickRaceThread tid th = th { rtClock = Map.insertWith (+) tid 1 (rtClock th) }
+
+putRaceThread :: Natural -> RaceThread -> RaceDetector -> RaceDetector
+putRaceThread tid th rd = rd { rdThreads = Map.insert tid th (rdThreads rd) }
+
+-- | Get the race detector state, the ID of the current thread, and that
+-- thread's state.
+raceThreadState ::
+    IsSymInterface sym =>
+    SymGlobalState sym ->
+    GlobalVar RaceDetectorType ->
+    GlobalVar NatType ->
+    IO (RaceDetector, Natural, RaceThread)
+raceThreadState gs gv tidVar = do
+    rd <- case lookupGlobal gv gs of
+        Just rd -> return rd
+        Nothing -> fail "data race detector state is not initialized"
+    tid <- case lookupGlobal tidVar gs >>= asNat of
+        Just tid -> return tid
+        Nothing -> fail "data race detector: the ID of the current thread is unknown"
+    return (rd, tid, Map.findWithDefault (newRaceThread tid) tid (rdThreads rd))
+
+-- | The location accessed through `ref`, if it is concrete.  Accesses through
+-- symbolic pointers are not checked.
+raceLocationName :: IsSymInterface sym => MirReferenceMux sym tp -> Maybe Text
+raceLocationName (MirReferenceMux tree) = case viewFancyMuxTree tree of
+    [(leaf, _)] -> weakLocationName leaf
+    _ -> Nothing
+
+raceAccessIO ::
+    IsSymBackend sym bak =>
+    bak ->
+    SymGlobalState sym ->
+    GlobalVar RaceDetectorType ->
+    GlobalVar NatType ->
+    Bool ->
+    MirReferenceMux sym tp ->
+    IO (SymGlobalState sym)
+raceAccessIO bak gs gv tidVar isWrite ref
+  | Just loc <- raceLocationName ref = do
+    let sym = backendGetSym bak
+    (rd, tid, th) <- raceThreadState gs gv tidVar
+    pl <- getCurrentProgramLoc sym
+    let clock = rtClock th
+        happensBefore a = raThread a == tid || raTime a <= clockAt (raThread a) clock
+        racy a = (isWrite || raWrite a) && not (happensBefore a)
+        old = Map.findWithDefault [] loc (rdAccesses rd)
+        access = RaceAccess tid (clockAt tid clock) isWrite pl
+    case filter racy old of
+        _ | loc `Set.member` rdRaced rd -> return gs
+        a : _ -> do
+            let verb w = if w then "writes" else "reads"
+                pastVerb w = if w then "wrote" else "read"
+                msg = "data race: thread " ++ show tid ++ " " ++ verb isWrite
+                    ++ " memory that thread " ++ show (raThread a) ++ " "
+                    ++ pastVerb (raWrite a) ++ " at " ++ show (plSourceLoc (raLoc a))
+                    ++ ", and neither access happens before the other"
+            addAssertion bak $ LabeledPred (falsePred sym) $ SimError pl $
+                GenericSimError msg
+            let rd' = rd
+                  { rdAccesses = Map.delete loc (rdAccesses rd)
+                  , rdRaced = Set.insert loc (rdRaced rd)
+                  }
+            return $ insertGlobal gv rd' gs
+        [] -> do
+            -- A write happens after every access it was checked against, so
+            -- later accesses only need to be checked against the write.  A
+            -- read likewise replaces the reads that happen before it.
+            let accesses
+                  | isWrite = [access]
+                  | otherwise = filter (\a -> raWrite a || not (happensBefore a)) old
+                      ++ [access]
+            let rd' = rd { rdAccesses = Map.insert loc accesses (rdAccesses rd) }
+            return $ insertGlobal gv rd' gs
+  | otherwise = return gs
+
+raceSyncIO ::
+    IsSymInterface sym =>
+    SymGlobalState sym ->
+    GlobalVar RaceDetectorType ->
+    GlobalVar NatType ->
+    Bool ->
+    MirReferenceMux sym tp ->
+    IO (SymGlobalState sym)
+raceSyncIO gs gv tidVar rel ref
+  | Just obj <- raceLocationName ref = do
+    (rd, tid, th) <- raceThreadState gs gv tidVar
+    let objClock = Map.findWithDefault Map.empty obj (rdSyncClocks rd)
+    let rd'
+          | rel = putRaceThread tid (tickRaceThread tid th) rd
+              { rdSyncClocks = Map.insert obj (joinClocks objClock (rtClock th)) (rdSyncClocks rd) }
+          | otherwise = putRaceThread tid th { rtClock = joinClocks (rtClock th) objClock } rd
+    return $ insertGlobal gv rd' gs
+  | otherwise = return gs
+
+raceAtomicIO ::
+    IsSymInterface sym =>
+    SymGlobalState sym ->
+    GlobalVar RaceDetectorType ->
+    GlobalVar NatType ->
+    Maybe AtomicOrdering ->
+    Maybe AtomicOrdering ->
+    MirReferenceMux sym tp ->
+    IO (SymGlobalState sym)
+raceAtomicIO gs gv tidVar readOrd writeOrd ref
+  | Just loc <- raceLocationName ref = do
+    (rd, tid, th0) <- raceThreadState gs gv tidVar
+    let locClock = Map.findWithDefault Map.empty loc (rdSyncClocks rd)
+    let th1 = case readOrd of
+          Just ord
+            | atomicAcquires ord -> th0 { rtClock = joinClocks (rtClock th0) locClock }
+            | otherwise -> th0 { rtAcquireClock = joinClocks (rtAcquireClock th0) locClock }
+          Nothing -> th0
+    let (locClock', th2) = case writeOrd of
+          Just ord ->
+            let released
+                  | atomicReleases ord = rtClock th1
+                  | otherwise = rtReleaseClock th1
+                -- A read-modify-write continues the release sequence of the
+                -- value it replaces, but a plain store ends it.
+                prev = if Maybe.isJust readOrd then locClock else Map.empty
+                th' = if atomicReleases ord then tickRaceThread tid th1 else th1
+            in (joinClocks prev released, th')
+          Nothing -> (locClock, th1)
+    let rd' = putRaceThread tid th2 rd
+          { rdSyncClocks = Map.insert loc locClock' (rdSyncClocks rd) }
+    return $ insertGlobal gv rd' gs
+  | otherwise = return gs
+
+raceFenceIO ::
+    IsSymInterface sym =>
+    SymGlobalState sym ->
+    GlobalVar RaceDetectorType ->
+    GlobalVar NatType ->
+    AtomicOrdering ->
+    IO (SymGlobalState sym)
+raceFenceIO gs gv tidVar ord = do
+    (rd, tid, th0) <- raceThreadState gs gv tidVar
+    let th1
+          | atomicAcquires ord = th0 { rtClock = joinClocks (rtClock th0) (rtAcquireClock th0) }
+          | otherwise = th0
+    let th2
+          | atomicReleases ord = tickRaceThread tid th1 { rtReleaseClock = rtClock th1 }
+          | otherwise = th1
+    return $ insertGlobal gv (putRaceThread tid th2 rd) gs
+
+raceSpawnIO ::
+    IsSymInterface sym =>
+    SymGlobalState sym ->
+    GlobalVar RaceDetectorType ->
+    GlobalVar NatType ->
+    RegValue sym (BVType 32) ->
+    IO (SymGlobalState sym)
+raceSpawnIO gs gv tidVar child = do
+    (rd, tid, th) <- raceThreadState gs gv tidVar
+    childTid <- weakThreadID child
+    let childTh = newRaceThread childTid
+        childTh' = childTh { rtClock = joinClocks (rtClock th) (rtClock childTh) }
+    let rd' = putRaceThread tid (tickRaceThread tid th) $ putRaceThread childTid childTh' rd
+    return $ insertGlobal gv rd' gs
+
+raceJoinIO ::
+    IsSymInterface sym =>
+    SymGlobalState sym ->
+    GlobalVar RaceDetectorType ->
+    GlobalVar NatType ->
+    RegValue sym (BVType 32) ->
+    IO (SymGlobalState sym)
+raceJoinIO gs gv tidVar child = do
+    (rd, tid, th) <- raceThreadState gs gv tidVar
+    childTid <- weakThreadID child
+    let childTh = Map.findWithDefault (newRaceThread childTid) childTid (rdThreads rd)
+    let th' = th { rtClock = joinClocks (rtClock th) (rtClock childTh) }
+    return $ insertGlobal gv (putRaceThread tid th' rd) gs
+
+
 mirRef_offsetLeaf ::
     (IsSymBackend sym bak) =>
     bak ->
@@ -1672,12 +2884,12 @@ mirRef_offsetWrapLeaf ::
     MirReference sym tp ->
     RegValue sym IsizeType ->
     MuxLeafT sym IO (MirReference sym tp)
-mirRef_offsetWrapLeaf bak _tpr (MirReference root (Index_RefPath tpr path idx)) offset = do
+mirRef_offsetWrapLeaf bak _tpr (MirReference root (Index_RefPath tpr path idx) tag) offset = do
     let sym = backendGetSym bak
     -- `wrapping_offset` puts no restrictions on the arithmetic performed.
     idx' <- liftIO $ bvAdd sym idx offset
-    return $ MirReference root $ Index_RefPath tpr path idx'
-mirRef_offsetWrapLeaf bak _ ref@(MirReference _ _) offset = do
+    return $ MirReference root (Index_RefPath tpr path idx') tag
+mirRef_offsetWrapLeaf bak _ ref@(MirReference _ _ _) offset = do
     let sym = backendGetSym bak
     isZero <- liftIO $ bvEq sym offset =<< bvLit sym knownNat (BV.zero knownNat)
     leafAssert bak isZero $ Unsupported callStack $
@@ -1698,7 +2910,7 @@ mirRef_tryOffsetFromLeaf ::
     MirReference sym tp ->
     MirReference sym tp ->
     MuxLeafT sym IO (RegValue sym (MaybeType IsizeType))
-mirRef_tryOffsetFromLeaf sym (MirReference root1 path1) (MirReference root2 path2) = do
+mirRef_tryOffsetFromLeaf sym (MirReference root1 path1 _) (MirReference root2 path2 _) = do
     rootEq <- refRootEq sym root1 root2
     case (path1, path2) of
         (Index_RefPath _ path1' idx1, Index_RefPath _ path2' idx2) -> do
@@ -1739,10 +2951,10 @@ mirRef_peelIndexLeaf ::
     MirReference sym tp ->
     MuxLeafT sym IO
         (RegValue sym (StructType (EmptyCtx ::> MirReferenceType (MirVectorType tp) ::> UsizeType)))
-mirRef_peelIndexLeaf sym _tpr (MirReference root (Index_RefPath _tpr' path idx)) = do
-    let ref = MirReferenceMux $ toFancyMuxTree sym $ MirReference root path
+mirRef_peelIndexLeaf sym _tpr (MirReference root (Index_RefPath _tpr' path idx) tag) = do
+    let ref = MirReferenceMux $ toFancyMuxTree sym $ MirReference root path tag
     return $ Empty :> RV ref :> RV idx
-mirRef_peelIndexLeaf _sym _ (MirReference _ _) =
+mirRef_peelIndexLeaf _sym _ (MirReference _ _ _) =
     leafAbort $ Unsupported callStack $
         "peelIndex is not yet implemented for this RefPath kind"
 mirRef_peelIndexLeaf _sym _ _ = do
@@ -1776,9 +2988,9 @@ mirRef_indexAndLenLeaf ::
     IntrinsicTypes sym ->
     MirReference sym tp ->
     MuxLeafT sym IO (RegValue sym UsizeType, RegValue sym UsizeType)
-mirRef_indexAndLenLeaf bak gs iTypes (MirReference root (Index_RefPath _tpr' path idx)) = do
+mirRef_indexAndLenLeaf bak gs iTypes (MirReference root (Index_RefPath _tpr' path idx) tag) = do
     let sym = backendGetSym bak
-    let parent = MirReference root path
+    let parent = MirReference root path tag
     parentVec <- readMirRefLeaf bak gs iTypes parent
     lenInt <- case parentVec of
         MirVector_Vector v -> return $ V.length v
@@ -1787,7 +2999,7 @@ mirRef_indexAndLenLeaf bak gs iTypes (MirReference root (Index_RefPath _tpr' pat
             "can't compute allocation length for MirVector_Array, which is unbounded"
     len <- liftIO $ bvLit sym knownNat $ BV.mkBV knownNat $ fromIntegral lenInt
     return (idx, len)
-mirRef_indexAndLenLeaf bak _ _ (MirReference _ _) = do
+mirRef_indexAndLenLeaf bak _ _ (MirReference _ _ _) = do
     let sym = backendGetSym bak
     idx <- liftIO $ bvLit sym knownNat $ BV.mkBV knownNat 0
     len <- liftIO $ bvLit sym knownNat $ BV.mkBV knownNat 1
@@ -1840,11 +3052,11 @@ execMirStmt stmt s = withBackend ctx $ \bak ->
             return (mkRef r', s)
 
        MirGlobalRef gv ->
-         do let r = MirReference (GlobalVar_RefRoot gv) Empty_RefPath
+         do let r = MirReference (GlobalVar_RefRoot gv) Empty_RefPath rootBorrowTag
             return (mkRef r, s)
 
        MirConstRef tpr (regValue -> v) ->
-         do let r = MirReference (Const_RefRoot tpr v) Empty_RefPath
+         do let r = MirReference (Const_RefRoot tpr v) Empty_RefPath rootBorrowTag
             return (mkRef r, s)
 
        MirReadRef tpr (regValue -> ref) ->
@@ -1853,8 +3065,44 @@ execMirStmt stmt s = withBackend ctx $ \bak ->
          writeOnly s $ writeMirRefIO bak gs iTypes ref x
        MirDropRef (regValue -> ref) ->
          writeOnly s $ dropMirRefIO bak gs ref
+       MirDeallocRef (regValue -> ptr) ->
+         writeOnly s $ deallocMirRefIO bak gs ptr
+       MirRetagRef gv perm _tp (regValue -> ref) -> do
+         (ref', gs') <- retagMirRefIO bak gs iTypes gv perm ref
+         return (ref', s & stateTree.actFrame.gpGlobals .~ gs')
+       MirBorrowAccess gv acc (regValue -> ref) ->
+         writeOnly s $ borrowAccessIO bak gs gv acc ref
+       MirTrackAlloc gv (regValue -> ref) ->
+         writeOnly s $ trackAllocIO gs gv (activeFrames (s ^. stateTree)) ref
+       MirWeakLoad gv tidVar ord tpr (regValue -> ref) -> do
+         (v, gs') <- weakLoadIO bak gs iTypes gv tidVar ord tpr ref
+         return (v, s & stateTree.actFrame.gpGlobals .~ gs')
+       MirWeakStore gv tidVar ord tpr (regValue -> ref) (regValue -> x) ->
+         writeOnly s $ weakStoreIO bak gs iTypes gv tidVar ord tpr ref x
+       MirWeakRMW gv tidVar succOrd failOrd tpr (regValue -> ref) (regValue -> ok) (regValue -> x) ->
+         writeOnly s $ weakRMWIO bak gs iTypes gv tidVar succOrd failOrd tpr ref ok x
+       MirWeakFence gv tidVar ord ->
+         writeOnly s $ weakFenceIO sym gs gv tidVar ord
+       MirWeakSpawn gv tidVar (regValue -> child) ->
+         writeOnly s $ weakSpawnIO sym gs gv tidVar child
+       MirWeakJoin gv tidVar (regValue -> child) ->
+         writeOnly s $ weakJoinIO sym gs gv tidVar child
+       MirRaceAccess gv tidVar isWrite (regValue -> ref) ->
+         writeOnly s $ raceAccessIO bak gs gv tidVar isWrite ref
+       MirRaceSync gv tidVar rel (regValue -> ref) ->
+         writeOnly s $ raceSyncIO gs gv tidVar rel ref
+       MirRaceAtomic gv tidVar readOrd writeOrd (regValue -> ref) ->
+         writeOnly s $ raceAtomicIO gs gv tidVar readOrd writeOrd ref
+       MirRaceFence gv tidVar ord ->
+         writeOnly s $ raceFenceIO gs gv tidVar ord
+       MirRaceSpawn gv tidVar (regValue -> child) ->
+         writeOnly s $ raceSpawnIO gs gv tidVar child
+       MirRaceJoin gv tidVar (regValue -> child) ->
+         writeOnly s $ raceJoinIO gs gv tidVar child
        MirSubanyRef tp (regValue -> ref) ->
          readOnly s $ subanyMirRefIO bak iTypes tp ref
+       MirSubunionRef tp (regValue -> ref) ->
+         readOnly s $ subunionMirRefIO bak iTypes tp ref
        MirSubfieldRef ctx0 (regValue -> ref) idx ->
          readOnly s $ subfieldMirRefIO bak iTypes ctx0 ref idx
        MirSubvariantRef tp0 ctx0 (regValue -> ref) idx ->
@@ -1953,7 +3201,7 @@ newMirRefIO ::
     IO (MirReferenceMux sym tp)
 newMirRefIO sym halloc tpr = do
     rc <- freshRefCell halloc tpr
-    let ref = MirReference (RefCell_RefRoot rc) Empty_RefPath
+    let ref = MirReference (RefCell_RefRoot rc) Empty_RefPath rootBorrowTag
     return $ MirReferenceMux $ toFancyMuxTree sym ref
 
 readRefMuxSim :: IsSymInterface sym =>
@@ -2120,6 +3368,12 @@ writeRefPath ::
 -- which allows writing to an uninitialized MirReferenceRoot.
 writeRefPath bak iTypes v (Just_RefPath _tp path) x =
   adjustRefPath bak iTypes v path (\_ -> return $ justPartExpr (backendGetSym bak) x)
+-- Similarly, a write through a final `Union_RefPath` replaces the whole `Any`
+-- rather than adjusting the value inside it, so the new value may have a
+-- different type than the old one.  This is how writes to union fields change
+-- the active field.
+writeRefPath bak iTypes v (Union_RefPath tpr path) x =
+  adjustRefPath bak iTypes v path (\_ -> return $ AnyValue tpr x)
 -- Similar case for writing to MirVectors.  Uninitialized entries of a
 -- MirVector_PartialVector can be initialized by a write.
 writeRefPath bak iTypes v (Index_RefPath tp path idx) x = do
@@ -2145,6 +3399,12 @@ adjustRefPath bak iTypes v path0 adj = case path0 of
                             "\nbut got: " ++ show vtp)
            Just Refl -> AnyValue vtp <$> adj x
          )
+  Union_RefPath tpr path ->
+      adjustRefPath bak iTypes v path (\(AnyValue vtp x) ->
+         case testEquality vtp tpr of
+           Nothing -> fail (unionMismatchMsg tpr vtp)
+           Just Refl -> AnyValue vtp <$> adj x
+         )
   Field_RefPath _ctx path fld ->
       adjustRefPath bak iTypes v path
         (\x -> adjustM (\x' -> RV <$> adj (unRV x')) fld x)
@@ -2177,6 +3437,14 @@ adjustRefPath bak iTypes v path0 adj = case path0 of
             _ -> leafAbort $ Unsupported callStack $
                 "tried to change underlying type of MirVector ref"
 
+-- | The error for accessing a union field whose type differs from the field
+-- that was written last.  Reinterpreting the bytes of one field as another is
+-- not supported.
+unionMismatchMsg :: TypeRepr tp -> TypeRepr tp' -> String
+unionMismatchMsg tpr vtp =
+  "union field type mismatch: accessed a field of type " ++ show tpr ++
+  ", but the field written last has type " ++ show vtp
+
 readRefPath ::
   (IsSymBackend sym bak) =>
   bak ->
@@ -2192,6 +3460,11 @@ readRefPath bak iTypes v = \case
          Nothing -> leafAbort $ GenericSimError $
             "Any type mismatch! Expected: " ++ show tpr ++ "\nbut got: " ++ show vtp
          Just Refl -> return x
+  Union_RefPath tpr path ->
+    do AnyValue vtp x <- readRefPath bak iTypes v path
+       case testEquality vtp tpr of
+         Nothing -> leafAbort $ GenericSimError $ unionMismatchMsg tpr vtp
+         Just Refl -> return x
   Field_RefPath _ctx path fld ->
     do flds <- readRefPath bak iTypes v path
        return $ unRV $ flds ! fld
@@ -2256,15 +3529,31 @@ getSlicePtr e = getStruct i1of2 e
 getSliceLen :: Expr MIR s (MirSlice tp) -> Expr MIR s UsizeType
 getSliceLen e = getStruct i2of2 e
 
+-- | Build a Rust @&str@ from a Haskell string.
+strSlice ::
+  IsSymInterface sym =>
+  Text ->
+  OverrideSim (p sym) sym MIR rtp args ret (RegValue sym (MirSlice (BVType 8)))
+strSlice s = do
+  sym <- getSymInterface
+  let bytes = BS.unpack $ Text.encodeUtf8 s
+  vals <- liftIO $ mapM (bvLit sym knownNat . BV.word8) bytes
+  let vecRef = newConstMirRef sym (MirVectorRepr (BVRepr knownNat)) (MirVector_Vector $ V.fromList vals)
+  ptr <- subindexMirRefSim (BVRepr knownNat) vecRef =<<
+    liftIO (bvLit sym knownRepr (BV.zero knownRepr))
+  len <- liftIO $ bvLit sym knownRepr (BV.mkBV knownRepr (toInteger $ length bytes))
+  return $ Empty :> RV ptr :> RV len
+
 
 --------------------------------------------------------------------------------
 -- ** MethodSpec and MethodSpecBuilder
 --
 -- We define the intrinsics here so they can be used in `TransTy.tyToRepr`, and
--- also define their interfaces (as typeclasses), but we don't provide any
--- concrete implementations in `crux-mir`.  Instead, implementations of these
--- types are in `saw-script/crux-mir-comp`, since they depend on some SAW
--- components, such as `saw-script`'s `MethodSpec`.
+-- also define their interfaces (as typeclasses).  `crux-mir` provides an
+-- implementation in `Mir.MethodSpec`, built from What4 assumptions and
+-- assertions.  `saw-script/crux-mir-comp` provides another, based on
+-- `saw-script`'s `MethodSpec`, which it installs in place of `crux-mir`'s
+-- overrides.
 
 class MethodSpecImpl sym ms where
     -- | Pretty-print the MethodSpec, returning the result as a Rust string
@@ -2346,6 +3635,107 @@ instance IsSymInterface sym => IntrinsicClass sym MethodSpecBuilderSymbol where
   muxIntrinsic _sym _tys nm ctx _ _ _ = typeError nm ctx
 
 
+type family BorrowStateFam (sym :: Type) (ctx :: Ctx CrucibleType) :: Type where
+  BorrowStateFam sym EmptyCtx = BorrowState sym
+  BorrowStateFam sym ctx = TypeError
+    ('Text "BorrowStateType expects no arguments, but was given" ':<>: 'ShowType ctx)
+instance IsSymInterface sym => IntrinsicClass sym BorrowStateSymbol where
+  type Intrinsic sym BorrowStateSymbol ctx = BorrowStateFam sym ctx
+
+  muxIntrinsic sym _iTypes _nm Empty c (BorrowState m1) (BorrowState m2) =
+    -- A tag created on only one branch is carried by no pointer on the other,
+    -- so its state there doesn't matter.
+    BorrowState <$> mergeMaps (mergeMaps muxItem) m1 m2
+    where
+      mergeMaps :: Ord k => (a -> a -> IO a) -> Map k a -> Map k a -> IO (Map k a)
+      mergeMaps f x y = sequence $ Map.mergeWithKey (\_ a b -> Just $ f a b)
+        (fmap return) (fmap return) x y
+      muxItem i1 i2 = do
+        disabled <- itePred sym c (biDisabled i1) (biDisabled i2)
+        frozen <- itePred sym c (biFrozen i1) (biFrozen i2)
+        active <- itePred sym c (biActive i1) (biActive i2)
+        return i1 { biDisabled = disabled, biFrozen = frozen, biActive = active }
+  muxIntrinsic _sym _tys nm ctx _ _ _ = typeError nm ctx
+
+
+type family HeapAllocsFam (sym :: Type) (ctx :: Ctx CrucibleType) :: Type where
+  HeapAllocsFam sym EmptyCtx = HeapAllocs
+  HeapAllocsFam sym ctx = TypeError
+    ('Text "HeapAllocsType expects no arguments, but was given" ':<>: 'ShowType ctx)
+instance IsSymInterface sym => IntrinsicClass sym HeapAllocsSymbol where
+  type Intrinsic sym HeapAllocsSymbol ctx = HeapAllocsFam sym ctx
+
+  -- An allocation made on only one branch has an uninitialized `RefCell` on
+  -- the other, so it's safe to keep every allocation from both sides.
+  muxIntrinsic _sym _iTypes _nm Empty _c (HeapAllocs m1) (HeapAllocs m2) =
+    return $ HeapAllocs $ Map.union m1 m2
+  muxIntrinsic _sym _tys nm ctx _ _ _ = typeError nm ctx
+
+
+type family WeakMemoryFam (sym :: Type) (ctx :: Ctx CrucibleType) :: Type where
+  WeakMemoryFam sym EmptyCtx = WeakMemory sym
+  WeakMemoryFam sym ctx = TypeError
+    ('Text "WeakMemoryType expects no arguments, but was given" ':<>: 'ShowType ctx)
+instance IsSymInterface sym => IntrinsicClass sym WeakMemorySymbol where
+  type Intrinsic sym WeakMemorySymbol ctx = WeakMemoryFam sym ctx
+
+  -- The scheduler explores each interleaving separately, so the branches
+  -- being merged always come from the same thread and agree on the order of
+  -- the messages they have in common.  A location or thread that appears on
+  -- only one branch is kept as is.
+  muxIntrinsic sym iTypes _nm Empty c wm1 wm2 = do
+    hists <- sequence $ Map.mergeWithKey (\_ h1 h2 -> Just $ muxHistory h1 h2)
+        (fmap return) (fmap return) (wmHistories wm1) (wmHistories wm2)
+    threads <- sequence $ Map.mergeWithKey (\_ t1 t2 -> Just $ muxThread t1 t2)
+        (fmap return) (fmap return) (wmThreads wm1) (wmThreads wm2)
+    sc <- iteWeakViews sym c (wmSCView wm1) (wmSCView wm2)
+    weak <- itePred sym c (wmWeak wm1) (wmWeak wm2)
+    return $ WeakMemory hists threads sc weak
+    where
+      muxHistory h1 h2
+        | Seq.length h1 == Seq.length h2 = sequence $ Seq.zipWith muxMessage h1 h2
+        | otherwise = fail "weak memory: can't merge histories of different lengths"
+      muxMessage (WeakMessage v1 view1) (WeakMessage v2 view2) =
+        WeakMessage
+          <$> muxRegForType sym iTypes AnyRepr c v1 v2
+          <*> iteWeakViews sym c view1 view2
+      muxThread t1 t2 =
+        WeakThread
+          <$> iteWeakViews sym c (wtView t1) (wtView t2)
+          <*> iteWeakViews sym c (wtAcquireView t1) (wtAcquireView t2)
+          <*> iteWeakViews sym c (wtReleaseView t1) (wtReleaseView t2)
+  muxIntrinsic _sym _tys nm ctx _ _ _ = typeError nm ctx
+
+
+type family RaceDetectorFam (sym :: Type) (ctx :: Ctx CrucibleType) :: Type where
+  RaceDetectorFam sym EmptyCtx = RaceDetector
+  RaceDetectorFam sym ctx = TypeError
+    ('Text "RaceDetectorType expects no arguments, but was given" ':<>: 'ShowType ctx)
+instance IsSymInterface sym => IntrinsicClass sym RaceDetectorSymbol where
+  type Intrinsic sym RaceDetectorSymbol ctx = RaceDetectorFam sym ctx
+
+  -- When both branches are feasible, keep the accesses from both, but only
+  -- the synchronization they agree on, so that a race on either branch is
+  -- still reported.
+  muxIntrinsic _sym _iTypes _nm Empty c rd1 rd2 = case asConstantPred c of
+    Just True -> return rd1
+    Just False -> return rd2
+    Nothing -> return RaceDetector
+      { rdThreads = Map.unionWith muxThread (rdThreads rd1) (rdThreads rd2)
+      , rdSyncClocks = Map.unionWith meetClocks (rdSyncClocks rd1) (rdSyncClocks rd2)
+      , rdAccesses = Map.unionWith List.union (rdAccesses rd1) (rdAccesses rd2)
+      , rdRaced = Set.union (rdRaced rd1) (rdRaced rd2)
+      }
+    where
+      meetClocks = Map.intersectionWith min
+      muxThread t1 t2 = RaceThread
+        { rtClock = meetClocks (rtClock t1) (rtClock t2)
+        , rtAcquireClock = meetClocks (rtAcquireClock t1) (rtAcquireClock t2)
+        , rtReleaseClock = meetClocks (rtReleaseClock t1) (rtReleaseClock t2)
+        }
+  muxIntrinsic _sym _tys nm ctx _ _ _ = typeError nm ctx
+
+
 -- Table of all MIR-specific intrinsic types.  Must be at the end so it can see
 -- past all previous TH calls.
 
@@ -2355,4 +3745,8 @@ mirIntrinsicTypes =
    MapF.insert (knownSymbol @MirVectorSymbol) IntrinsicMuxFn $
    MapF.insert (knownSymbol @MethodSpecSymbol) IntrinsicMuxFn $
    MapF.insert (knownSymbol @MethodSpecBuilderSymbol) IntrinsicMuxFn $
+   MapF.insert (knownSymbol @BorrowStateSymbol) IntrinsicMuxFn $
+   MapF.insert (knownSymbol @HeapAllocsSymbol) IntrinsicMuxFn $
+   MapF.insert (knownSymbol @WeakMemorySymbol) IntrinsicMuxFn $
+   MapF.insert (knownSymbol @RaceDetectorSymbol) IntrinsicMuxFn $
    MapF.empty
@@ -0,0 +1,574 @@
+{-# LANGUAGE OverloadedStrings #-}
+
+-----------------------------------------------------------------------
+-- |
+-- Module           : Mir.Loops
+-- Description      : Loop structure of MIR function bodies
+-- Copyright        : (c) Galois, Inc 2026
+-- License          : BSD3
+-- Stability        : provisional
+--
+-- This module finds the natural loops of a MIR function body, and the places
+-- that a loop may modify.  The translation uses it to cut a loop at a
+-- `crucible::loop_invariant!`: every place that the loop may modify, and
+-- that is still needed after the cut, is replaced with a fresh symbolic
+-- value.  It also finds the unwinding bounds that `#[crucible::unwind]` and
+-- `crucible::loop_unwind!` put on a function's loops.
+-----------------------------------------------------------------------
+module Mir.Loops
+( Loop(..)
+, LoopCut(..)
+, UnwindBound(..)
+, blockSuccessors
+, naturalLoops
+, loopEntries
+, innermostLoop
+, loopPos
+, loopCut
+, unwindBounds
+) where
+
+import Control.Lens ((^.), (^?), ix)
+import Control.Monad (when)
+import qualified Data.List as List
+import Data.Map.Strict (Map)
+import qualified Data.Map.Strict as Map
+import Data.Set (Set)
+import qualified Data.Set as Set
+import Data.Text (Text)
+import qualified Data.Text as Text
+
+import Mir.DefId (idKey)
+import Mir.Mir
+import Mir.PP (fmt)
+
+
+-- | A natural loop: its header, and the blocks of its body (including the
+-- header).  Loops that share a header are merged into one.
+data Loop = Loop
+    { loopHeader :: BasicBlockInfo
+    , loopBody :: Set BasicBlockInfo
+    }
+    deriving (Show, Eq)
+
+-- | How to cut a loop at a call to `crucible::loop_invariant_cut`.
+data LoopCut = LoopCut
+    { cutLoop :: Loop
+    -- ^ The innermost loop containing the call.
+    , cutEntries :: [BasicBlockInfo]
+    -- ^ The blocks outside the loop that jump to its header.  After passing
+    -- through one of these, the next visit to the cut belongs to a new
+    -- execution of the loop.
+    , cutHavoc :: [Lvalue]
+    -- ^ The places that the loop may modify, and whose values may still be
+    -- used after the cut.
+    , cutPos :: Text
+    -- ^ The position of the loop (see 'loopPos').
+    }
+    deriving (Show, Eq)
+
+
+-- | The successors of a block.  Unwinding edges are ignored, since the
+-- translation never takes them.
+blockSuccessors :: Terminator -> [BasicBlockInfo]
+blockSuccessors term = case term of
+    Goto bb -> [bb]
+    SwitchInt { _stargets = bbs } -> bbs
+    Drop { _dtarget = bb } -> [bb]
+    DropAndReplace { _drtarget = bb } -> [bb]
+    Call { _cdest = Just (_, bb) } -> [bb]
+    Assert { _atarget = bb } -> [bb]
+    _ -> []
+
+successorMap :: MirBody -> Map BasicBlockInfo [BasicBlockInfo]
+successorMap body = Map.fromList
+    [ (bb, blockSuccessors (bbd ^. bbterminator)) | BasicBlock bb bbd <- body ^. mblocks ]
+
+predecessorMap :: Map BasicBlockInfo [BasicBlockInfo] -> Map BasicBlockInfo [BasicBlockInfo]
+predecessorMap succs = Map.fromListWith (++) [ (s, [bb]) | (bb, ss) <- Map.toList succs, s <- ss ]
+
+reachableFrom :: Map BasicBlockInfo [BasicBlockInfo] -> BasicBlockInfo -> Set BasicBlockInfo
+reachableFrom succs entry = go Set.empty [entry]
+  where
+    go seen [] = seen
+    go seen (bb : bbs)
+      | Set.member bb seen = go seen bbs
+      | otherwise = go (Set.insert bb seen) (Map.findWithDefault [] bb succs ++ bbs)
+
+-- | The dominators of each block that is reachable from `entry`.
+dominators :: Map BasicBlockInfo [BasicBlockInfo] -> BasicBlockInfo ->
+    Map BasicBlockInfo (Set BasicBlockInfo)
+dominators succs entry = fixpoint initial
+  where
+    reachable = reachableFrom succs entry
+    preds = predecessorMap succs
+    initial = Map.fromSet
+        (\bb -> if bb == entry then Set.singleton entry else reachable) reachable
+
+    fixpoint doms =
+        let doms' = Map.mapWithKey (update doms) doms
+        in if doms' == doms then doms else fixpoint doms'
+
+    update doms bb ds
+      | bb == entry = ds
+      | otherwise = case [ d | p <- Map.findWithDefault [] bb preds, Just d <- [Map.lookup p doms] ] of
+          [] -> ds
+          d : ds' -> Set.insert bb $ List.foldl' Set.intersection d ds'
+
+-- | Find the natural loops of a function body.  The first block of the body
+-- is its entry point.
+naturalLoops :: MirBody -> [Loop]
+naturalLoops body = case body ^. mblocks of
+    [] -> []
+    BasicBlock entry _ : _ ->
+        let succs = successorMap body
+            doms = dominators succs entry
+            -- Only reachable blocks can be part of a loop.
+            preds = predecessorMap (Map.restrictKeys succs (Map.keysSet doms))
+            backEdges =
+                [ (src, hd) | (src, ds) <- Map.toList doms
+                , hd <- Map.findWithDefault [] src succs
+                , Set.member hd ds ]
+            loops = Map.fromListWith Set.union
+                [ (hd, grow preds hd (Set.fromList [hd, src]) [src]) | (src, hd) <- backEdges ]
+        in [ Loop hd bbs | (hd, bbs) <- Map.toList loops ]
+  where
+    -- Add every block that reaches the back edge without passing through the
+    -- header.
+    grow _ _ acc [] = acc
+    grow preds hd acc (bb : bbs)
+      | bb == hd = grow preds hd acc bbs
+      | otherwise =
+        let new = [ p | p <- Map.findWithDefault [] bb preds, not (Set.member p acc) ]
+        in grow preds hd (foldr Set.insert acc new) (new ++ bbs)
+
+
+-- | The innermost loop containing a block.
+innermostLoop :: [Loop] -> BasicBlockInfo -> Maybe Loop
+innermostLoop loops bb =
+    case List.sortOn (Set.size . loopBody) (filter (Set.member bb . loopBody) loops) of
+        l : _ -> Just l
+        [] -> Nothing
+
+-- | The blocks outside a loop that jump to its header.  Control passes
+-- through one of these each time the loop starts a new execution.
+loopEntries :: MirBody -> Loop -> [BasicBlockInfo]
+loopEntries body lp =
+    [ p | (p, ss) <- Map.toList (successorMap body)
+    , not (Set.member p (loopBody lp))
+    , loopHeader lp `elem` ss ]
+
+-- | The source position of a loop: the condition tested by its header, or
+-- failing that, the first statement of the loop that has a position.
+loopPos :: MirBody -> Loop -> Text
+loopPos body lp = case [ pos | Just bbd <- [header], SwitchInt { _spos = pos } <- [bbd ^. bbterminator] ] ++
+        [ pos | BasicBlock bb bbd <- body ^. mblocks, Set.member bb (loopBody lp)
+        , Assign _ _ pos <- bbd ^. bbstmts ] of
+    pos : _ -> pos
+    [] -> "<unknown>"
+  where
+    header = Map.lookup (loopHeader lp) (body ^. mblockmap)
+
+-- | Work out how to cut the loop around `bb`, whose terminator calls
+-- `crucible::loop_invariant_cut` and then continues at `target`.
+loopCut :: Collection -> MirBody -> BasicBlockInfo -> BasicBlockInfo -> Either String LoopCut
+loopCut col body bb target = do
+    lp <- case innermostLoop (naturalLoops body) bb of
+        Just l -> Right l
+        Nothing -> Left "it must be used inside a loop"
+    let entries = loopEntries body lp
+
+    let blockData = Map.fromList [ (b, bbd) | BasicBlock b bbd <- body ^. mblocks ]
+    let loopBlocks = Map.elems $ Map.restrictKeys blockData (loopBody lp)
+    (writes, assigns) <- loopWrites col loopBlocks
+    staticWrites col loopBlocks
+    places <- concat <$> mapM (resolveWrite writes assigns Set.empty . exactPrefix) writes
+
+    -- A place behind a reference may be visible to the caller, so it's always
+    -- havocked.  A local is havocked only if it's live after the cut, or if
+    -- it's been borrowed (and so might be read through a pointer).
+    let live = Map.findWithDefault mempty target (liveIn body) <> addrTaken body
+    let needed p = hasDeref p || Set.member (rootName p) live
+    let places' = List.nub (filter needed places)
+    let minimal = [ p | p <- places', not (any (\q -> q /= p && isPrefixPlace q p) places') ]
+    return $ LoopCut lp entries minimal (loopPos body lp)
+
+
+-- | A bound on the number of iterations of a loop, from a call to
+-- `crucible::unwind_bound`.
+data UnwindBound = UnwindBound
+    { unwindLimit :: Integer
+    , unwindAssume :: Bool
+    -- ^ Whether to assume that the loop stays within the bound, rather than
+    -- reporting an unwinding assertion failure when it doesn't.
+    }
+    deriving (Show, Eq)
+
+-- | Find the unwinding bounds in a function body.  A call to
+-- `crucible::unwind_bound` inside a loop bounds the innermost loop containing
+-- it; a call outside of any loop, as inserted by `#[crucible::unwind]`,
+-- bounds every other loop of the function.  This returns the function's
+-- bound, if any, and the bounds of individual loops, by loop header.
+unwindBounds :: MirBody -> Either String (Maybe UnwindBound, Map BasicBlockInfo UnwindBound)
+unwindBounds body = do
+    bounds <- sequence
+        [ (,) (innermostLoop loops bb) <$> parseBound args
+        | BasicBlock bb bbd <- body ^. mblocks
+        , Call (OpConstant (Constant (TyFnDef did) _)) args _ _ <- [bbd ^. bbterminator]
+        , idKey did == ["crucible", "unwind_bound"] ]
+    fnBound <- case [ b | (Nothing, b) <- bounds ] of
+        [] -> Right Nothing
+        [b] -> Right (Just b)
+        _ -> Left "a function can only have one #[crucible::unwind] bound"
+    let loopBounds = Map.fromListWith min' [ (loopHeader lp, b) | (Just lp, b) <- bounds ]
+    return (fnBound, loopBounds)
+  where
+    loops = naturalLoops body
+    min' a b = if unwindLimit a <= unwindLimit b then a else b
+
+    parseBound [OpConstant (Constant _ (ConstInt n)), OpConstant (Constant _ (ConstBool a))] =
+        Right $ UnwindBound (fromIntegerLit n) a
+    parseBound _ = Left "the arguments of unwind_bound must be constants"
+
+-- | Where a local that's assigned inside the loop gets its value from.
+data Source = FromRvalue Rvalue | FromCall [Operand]
+
+-- | The places written by the blocks of a loop, and the assignments made to
+-- each local.  Mutably borrowing a place counts as writing it, and so does
+-- passing a mutable reference to a function.  A call that gets any other way
+-- to write through its arguments is rejected, since the places it modifies
+-- can't be named.
+loopWrites :: Collection -> [BasicBlockData] -> Either String ([Lvalue], Map Text [Source])
+loopWrites col bbds = do
+    (writes, assigns) <- unzip <$> mapM go
+        [ s | bbd <- bbds, s <- map Left (bbd ^. bbstmts) ++ [Right (bbd ^. bbterminator)] ]
+    return (concat writes, Map.fromListWith (flip (++)) (concat assigns))
+  where
+    go (Left (Assign lv rv _)) = Right (lv : borrowed rv, assignTo lv (FromRvalue rv))
+    go (Left (SetDiscriminant lv _)) = Right ([lv], [])
+    go (Left (StmtIntrinsic (NDICopyNonOverlapping _ _ _))) =
+        Left "can't tell which places copy_nonoverlapping modifies"
+    go (Right (Call _ args dest _)) = do
+        argWrites <- concat <$> mapM argWrite args
+        return $ case dest of
+            Just (lv, _) -> (lv : argWrites, assignTo lv (FromCall args))
+            Nothing -> (argWrites, [])
+    go (Right (DropAndReplace lv _ _ _ _)) = Right ([lv], [])
+    go _ = Right ([], [])
+
+    -- A mutable reference to a local lets the function modify what it points
+    -- to, and nothing else, as long as the pointee doesn't lead further.
+    argWrite op
+      | Just v <- mutRefArg op, not (writesThrough col False (pointee (typeOf v))) =
+          Right [LProj (LBase v) Deref]
+      | writesThrough col False (typeOf op) = Left $
+          "can't tell which places a call in the loop modifies through an argument " ++
+          "that holds a mutable reference, a raw pointer, or an UnsafeCell"
+      | otherwise = Right []
+
+    pointee (TyRef ty _) = ty
+    pointee (TyRawPtr ty _) = ty
+    pointee ty = ty
+
+    borrowed (Ref Mutable lv _) = [lv]
+    borrowed (Ref Unique lv _) = [lv]
+    borrowed (AddressOf Mut lv) = [lv]
+    borrowed _ = []
+
+    assignTo (LBase v) src = [(v ^. varname, [src])]
+    assignTo _ _ = []
+
+-- | The local holding a mutable reference or pointer that's passed to a
+-- function.
+mutRefArg :: Operand -> Maybe Var
+mutRefArg op = case op of
+    Copy (LBase v) -> check v
+    Move (LBase v) -> check v
+    _ -> Nothing
+  where
+    check v = case typeOf v of
+        TyRef _ Mut -> Just v
+        TyRawPtr _ Mut -> Just v
+        _ -> Nothing
+
+-- | Whether a value of type `ty` lets the code that has it modify memory
+-- outside of the value itself: through a mutable reference, a raw pointer,
+-- or an `UnsafeCell`.  Behind a shared reference (`shared`), a mutable
+-- reference can't be written through, but the others still can.  Trait
+-- objects and types missing from the collection may hold anything.
+writesThrough :: Collection -> Bool -> Ty -> Bool
+writesThrough col = go Set.empty
+  where
+    go seen shared ty = case ty of
+        TyRef ty' Mut -> not shared || go seen shared ty'
+        TyRef ty' Immut -> go seen True ty'
+        TyRawPtr _ _ -> True
+        TyTuple tys -> any (go seen shared) tys
+        TyClosure tys -> any (go seen shared) tys
+        TyArray ty' _ -> go seen shared ty'
+        TySlice ty' -> go seen shared ty'
+        TyDynamic _ -> True
+        TyForeign -> True
+        TyAdt nm orig _
+          | idKey orig == ["core", "cell", "UnsafeCell"] -> True
+          | Set.member nm seen -> False
+          | otherwise -> case Map.lookup nm (col ^. adts) of
+              Just adt -> any (go (Set.insert nm seen) shared . (^. fty))
+                  [ f | v <- adt ^. adtvariants, f <- v ^. vfields ]
+              Nothing -> True
+        _ -> False
+
+-- | Reject the loop if a call made inside it may modify a `static mut`.  This
+-- follows direct calls through the bodies of the functions in the crate, and
+-- counts any function that mentions a mutable static as modifying it.  A call
+-- through a function pointer or a trait object may reach any function, so
+-- it's rejected whenever the crate has a mutable static.
+staticWrites :: Collection -> [BasicBlockData] -> Either String ()
+staticWrites col bbds
+  | Set.null mutStatics = Right ()
+  | otherwise = mapM (callees . (^. bbterminator)) bbds >>= reach Set.empty . concat
+  where
+    mutStatics = Map.keysSet (Map.filter (^. sMutable) (col ^. statics))
+
+    reach _ [] = Right ()
+    reach seen (f : fs)
+      | Set.member f seen = reach seen fs
+      | Just fn <- Map.lookup f (col ^. functions) = do
+          let bbds' = [ bbd | BasicBlock _ bbd <- fn ^. fbody . mblocks ]
+          when (any (any mutStatic . blockConstants) bbds') $
+              Left "can't tell whether a call in the loop modifies a static mut"
+          fs' <- concat <$> mapM (callees . (^. bbterminator)) bbds'
+          reach (Set.insert f seen) (fs' ++ fs)
+      | otherwise = case col ^? intrinsics . ix f . intrInst . inKind of
+          Just (IkVirtual _ _) -> Left indirect
+          Just (IkFnPtrShim _) -> Left indirect
+          -- A function without a body is built in, and doesn't touch statics.
+          _ -> reach (Set.insert f seen) fs
+
+    callees term = case term of
+        Call (OpConstant (Constant (TyFnDef f) _)) _ _ _ -> Right [f]
+        Call _ _ _ _ -> Left indirect
+        Drop { _ddrop_fn = Just f } -> Right [f]
+        DropAndReplace { _drdrop_fn = Just f } -> Right [f]
+        _ -> Right []
+
+    indirect = "can't tell whether an indirect call in the loop modifies a static mut"
+
+    mutStatic cv = case cv of
+        ConstStaticRef did -> Set.member did mutStatics
+        ConstSlice cvs -> any mutStatic cvs
+        ConstTuple cvs -> any mutStatic cvs
+        ConstClosure cvs -> any mutStatic cvs
+        ConstArray cvs -> any mutStatic cvs
+        ConstRepeat cv' _ -> mutStatic cv'
+        ConstStruct cvs -> any mutStatic cvs
+        ConstEnum _ cvs -> any mutStatic cvs
+        _ -> False
+
+-- | Find the places that writing to `lv` inside the loop may modify, in terms
+-- of the values that locals have at the cut.
+resolveWrite :: [Lvalue] -> Map Text [Source] -> Set Text -> Lvalue -> Either String [Lvalue]
+resolveWrite writes assigns seen lv = case lv of
+    LBase _ -> Right [lv]
+    LProj (LBase v) Deref | Just srcs <- Map.lookup (v ^. varname) assigns ->
+        -- `v` points to different places in different iterations.
+        concat <$> mapM (pointees v) srcs
+    LProj base Deref
+      | stable base -> Right [lv]
+      | otherwise -> Left $ "can't tell which place " ++ fmt lv ++ " refers to"
+    LProj base elm -> map (`LProj` elm) <$> resolveWrite writes assigns seen base
+  where
+    -- The pointer stored in `base` doesn't change during the loop.
+    stable base = not (Map.member (rootName base) assigns)
+        && not (any (\w -> isPrefixPlace w base || storesInto base w) writes)
+
+    -- Writing `w` modifies the value stored in `base` itself, rather than
+    -- something that it points to.
+    storesInto base w
+      | w == base = True
+      | LProj w' elm <- w = elm /= Deref && storesInto base w'
+      | otherwise = False
+
+    pointees v src
+      | Set.member (v ^. varname) seen = Left $
+          "can't tell which place " ++ Text.unpack (v ^. varname) ++ " refers to"
+      | otherwise = case src of
+          FromRvalue (Ref _ pl _) -> again (exactPrefix pl)
+          FromRvalue (AddressOf _ pl) -> again (exactPrefix pl)
+          FromRvalue (Use op) | Just w <- operandLocal op -> again (LProj (LBase w) Deref)
+          FromRvalue (Cast _ op _) | Just w <- operandLocal op -> again (LProj (LBase w) Deref)
+          FromRvalue (CopyForDeref pl) | exactPrefix pl == pl -> again (LProj pl Deref)
+          -- A reference returned by a function must borrow from one of its
+          -- arguments, which the function may also modify.
+          FromCall args | ws@(_ : _) <- [ w | Just w <- map mutRefArg args ] ->
+              concat <$> mapM (\w -> again (LProj (LBase w) Deref)) ws
+          _ -> Left $ "can't tell which place " ++ Text.unpack (v ^. varname) ++ " refers to"
+      where
+        again = resolveWrite writes assigns (Set.insert (v ^. varname) seen)
+
+    operandLocal (Copy (LBase w)) = Just w
+    operandLocal (Move (LBase w)) = Just w
+    operandLocal _ = Nothing
+
+-- | The longest prefix of a place that always denotes the same memory,
+-- regardless of the values of index variables or enum discriminants.
+exactPrefix :: Lvalue -> Lvalue
+exactPrefix lv@(LBase _) = lv
+exactPrefix lv@(LProj base elm)
+  | base' /= base = base'
+  | exact elm = lv
+  | otherwise = base
+  where
+    base' = exactPrefix base
+    exact Deref = True
+    exact (PField _ _) = True
+    exact (ConstantIndex _ _ fromEnd) = not fromEnd
+    exact _ = False
+
+isPrefixPlace :: Lvalue -> Lvalue -> Bool
+isPrefixPlace p q
+  | p == q = True
+  | LProj q' _ <- q = isPrefixPlace p q'
+  | otherwise = False
+
+rootName :: Lvalue -> Text
+rootName (LBase v) = v ^. varname
+rootName (LProj lv _) = rootName lv
+
+hasDeref :: Lvalue -> Bool
+hasDeref (LBase _) = False
+hasDeref (LProj _ Deref) = True
+hasDeref (LProj lv _) = hasDeref lv
+
+
+-- | The locals whose addresses are taken anywhere in the function.
+addrTaken :: MirBody -> Set Text
+addrTaken body = Set.fromList
+    [ rootName lv
+    | BasicBlock _ bbd <- body ^. mblocks
+    , Assign _ rv _ <- bbd ^. bbstmts
+    , lv <- case rv of
+        Ref _ lv _ -> [lv]
+        AddressOf _ lv -> [lv]
+        _ -> [] ]
+
+-- | The locals that are live on entry to each block.
+liveIn :: MirBody -> Map BasicBlockInfo (Set Text)
+liveIn body = go (Map.map (const Set.empty) blocks)
+  where
+    blocks = Map.fromList [ (bb, bbd) | BasicBlock bb bbd <- body ^. mblocks ]
+
+    go live =
+        let live' = Map.map (transfer live) blocks
+        in if live' == live then live else go live'
+
+    transfer live bbd =
+        let term = bbd ^. bbterminator
+            out = foldMap (\s -> Map.findWithDefault Set.empty s live) (blockSuccessors term)
+        in foldr (step . statementEffect) (step (terminatorEffect term) out) (bbd ^. bbstmts)
+
+    step (defs, uses) live = uses <> (live `Set.difference` defs)
+
+-- | The locals that a statement defines and uses.
+statementEffect :: Statement -> (Set Text, Set Text)
+statementEffect stmt = case stmt of
+    Assign lv rv _ -> (defined lv, lhsUses lv <> rvalueVars rv)
+    SetDiscriminant lv _ -> (mempty, lvalueVars lv)
+    StorageLive v -> (Set.singleton (v ^. varname), mempty)
+    StorageDead v -> (Set.singleton (v ^. varname), mempty)
+    StmtIntrinsic (NDIAssume op) -> (mempty, operandVars op)
+    StmtIntrinsic (NDICopyNonOverlapping a b c) ->
+        (mempty, operandVars a <> operandVars b <> operandVars c)
+    _ -> (mempty, mempty)
+
+-- | The locals that a terminator defines and uses.
+terminatorEffect :: Terminator -> (Set Text, Set Text)
+terminatorEffect term = case term of
+    Return -> (mempty, Set.singleton "_0")
+    SwitchInt { _sdiscr = op } -> (mempty, operandVars op)
+    Drop { _dloc = lv } -> (mempty, lvalueVars lv)
+    DropAndReplace { _drloc = lv, _drval = op } -> (mempty, lvalueVars lv <> operandVars op)
+    Call f args dest _ ->
+        let uses = foldMap operandVars (f : args)
+        in case dest of
+            Just (lv, _) -> (defined lv, lhsUses lv <> uses)
+            Nothing -> (mempty, uses)
+    Assert { _acond = op } -> (mempty, operandVars op)
+    _ -> (mempty, mempty)
+
+defined :: Lvalue -> Set Text
+defined (LBase v) = Set.singleton (v ^. varname)
+defined _ = mempty
+
+-- | Writing to a projection of a local reads the local.
+lhsUses :: Lvalue -> Set Text
+lhsUses (LBase _) = mempty
+lhsUses lv = lvalueVars lv
+
+lvalueVars :: Lvalue -> Set Text
+lvalueVars (LBase v) = Set.singleton (v ^. varname)
+lvalueVars (LProj lv elm) = lvalueVars lv <> case elm of
+    Index v -> Set.singleton (v ^. varname)
+    _ -> mempty
+
+operandVars :: Operand -> Set Text
+operandVars op = case op of
+    Copy lv -> lvalueVars lv
+    Move lv -> lvalueVars lv
+    OpConstant _ -> mempty
+    Temp rv -> rvalueVars rv
+
+rvalueVars :: Rvalue -> Set Text
+rvalueVars rv = case rv of
+    Use op -> operandVars op
+    Repeat op _ -> operandVars op
+    Ref _ lv _ -> lvalueVars lv
+    AddressOf _ lv -> lvalueVars lv
+    Len lv -> lvalueVars lv
+    Cast _ op _ -> operandVars op
+    BinaryOp _ a b -> operandVars a <> operandVars b
+    CheckedBinaryOp _ a b -> operandVars a <> operandVars b
+    NullaryOp _ _ -> mempty
+    UnaryOp _ op -> operandVars op
+    Discriminant lv _ -> lvalueVars lv
+    Aggregate _ ops -> foldMap operandVars ops
+    RAdtAg ag -> foldMap operandVars (ag ^. aops)
+    ShallowInitBox op _ -> operandVars op
+    CopyForDeref lv -> lvalueVars lv
+    ThreadLocalRef _ _ -> mempty
+
+-- | The constant values used by a block.
+blockConstants :: BasicBlockData -> [ConstVal]
+blockConstants bbd = concatMap stmtConsts (bbd ^. bbstmts) ++ termConsts (bbd ^. bbterminator)
+  where
+    stmtConsts stmt = case stmt of
+        Assign _ rv _ -> rvalueConstants rv
+        StmtIntrinsic (NDIAssume op) -> operandConstants op
+        StmtIntrinsic (NDICopyNonOverlapping a b c) -> concatMap operandConstants [a, b, c]
+        _ -> []
+
+    termConsts term = case term of
+        SwitchInt { _sdiscr = op } -> operandConstants op
+        DropAndReplace { _drval = op } -> operandConstants op
+        Call f args _ _ -> concatMap operandConstants (f : args)
+        Assert { _acond = op } -> operandConstants op
+        _ -> []
+
+operandConstants :: Operand -> [ConstVal]
+operandConstants op = case op of
+    OpConstant (Constant _ cv) -> [cv]
+    Temp rv -> rvalueConstants rv
+    _ -> []
+
+rvalueConstants :: Rvalue -> [ConstVal]
+rvalueConstants rv = case rv of
+    Use op -> operandConstants op
+    Repeat op _ -> operandConstants op
+    Cast _ op _ -> operandConstants op
+    BinaryOp _ a b -> operandConstants a ++ operandConstants b
+    CheckedBinaryOp _ a b -> operandConstants a ++ operandConstants b
+    UnaryOp _ op -> operandConstants op
+    Aggregate _ ops -> concatMap operandConstants ops
+    RAdtAg ag -> concatMap operandConstants (ag ^. aops)
+    ShallowInitBox op _ -> operandConstants op
+    _ -> []
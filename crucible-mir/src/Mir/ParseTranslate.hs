@@ -68,7 +68,9 @@ uninternMir col = uninternTys unintern (col { _namedTys = mempty })
 
 
 -- | Translate a MIR collection to Crucible
-translateMIR :: (HasCallStack, ?debug::Int, ?assertFalseOnError::Bool, ?printCrucible::Bool)
+translateMIR :: (HasCallStack, ?debug::Int, ?assertFalseOnError::Bool, ?printCrucible::Bool,
+                 ?checkAliasing::Bool, ?checkLeaks::Bool, ?weakMemory::Bool,
+                 ?detectRaces::Bool)
    => CollectionState -> Collection -> C.HandleAllocator -> IO RustModule
 translateMIR lib col halloc =
   let ?customOps = Mir.customOps in
@@ -37,8 +37,10 @@ module Mir.Trans(transCollection,transStatics,RustModule(..)
                 , evalRval
                 , callExp
                 , derefExp, readPlace, addrOfPlace
+                , dynVtableData
                 ) where
 
+import Control.Applicative ((<|>))
 import Control.Monad
 import Control.Monad.ST
 import Control.Monad.Trans.Class
@@ -65,6 +67,7 @@ import qualified Data.Text as Text
 import qualified Data.Traversable as Trav
 import qualified Data.Vector as V
 import Data.String (fromString)
+import GHC.Float (double2Float)
 import Numeric
 import Numeric.Natural()
 
@@ -97,6 +100,7 @@ import qualified Mir.DefId as M
 
 import Mir.Intrinsics
 import Mir.Generator
+import Mir.Loops
 import Mir.GenericOps
 import Mir.TransTy
 
@@ -197,11 +201,14 @@ transConstVal (M.TyTuple tys) (Some (C.StructRepr tprs)) (M.ConstTuple vals) =
 transConstVal (M.TyClosure upvar_tys) (Some (C.StructRepr upvar_tprs)) (M.ConstClosure upvar_vals) =
     transConstTuple upvar_tys upvar_tprs upvar_vals
 
-transConstVal _ty (Some (C.RealValRepr)) (M.ConstFloat (M.FloatLit _ str)) =
-    case reads str of
-      (d , _):_ -> let rat = toRational (d :: Double) in
-                   return (MirExp C.RealValRepr (S.app $ E.RationalLit rat))
-      []        -> mirFail $ "cannot parse float constant: " ++ show str
+transConstVal _ty (Some (C.FloatRepr C.SingleFloatRepr)) (M.ConstFloat (M.FloatLit _ str)) =
+    case parseFloatLit str of
+      Just d  -> return $ MirExp (C.FloatRepr C.SingleFloatRepr) (S.app $ E.FloatLit (double2Float d))
+      Nothing -> mirFail $ "cannot parse float constant: " ++ show str
+transConstVal _ty (Some (C.FloatRepr C.DoubleFloatRepr)) (M.ConstFloat (M.FloatLit _ str)) =
+    case parseFloatLit str of
+      Just d  -> return $ MirExp (C.FloatRepr C.DoubleFloatRepr) (S.app $ E.DoubleLit d)
+      Nothing -> mirFail $ "cannot parse float constant: " ++ show str
 
 transConstVal _ty _ (ConstInitializer funid) =
     callExp funid []
@@ -263,6 +270,18 @@ transConstVal _ty (Some tpr@(C.FunctionHandleRepr argTys retTy)) (ConstFnPtr ins
 transConstVal ty tp cv = mirFail $
     "fail or unimp constant: " ++ show ty ++ " (" ++ show tp ++ ") " ++ show cv
 
+-- | Parse a float literal as printed by `mir-json`.  Rust prints non-finite
+-- values as `inf`, `-inf`, and `NaN`, which `reads` doesn't accept.
+-- Single-precision literals are also parsed as `Double`, then narrowed with
+-- `double2Float`.
+parseFloatLit :: String -> Maybe Double
+parseFloatLit "inf" = Just (1 / 0)
+parseFloatLit "-inf" = Just (-1 / 0)
+parseFloatLit "NaN" = Just (0 / 0)
+parseFloatLit str = case reads str of
+    (d, _) : _ -> Just d
+    [] -> Nothing
+
 -- Translate a constant (non-empty) tuple or constant closure value.
 transConstTuple :: [M.Ty] -> C.CtxRepr ctx -> [ConstVal] -> MirGenerator h s ret (MirExp s)
 transConstTuple tys tprs vals = do
@@ -391,9 +410,11 @@ readPlace (MirPlace tpr r NoMeta) = MirExp tpr <$> readMirRef tpr r
 readPlace (MirPlace tpr _ meta) =
     mirFail $ "don't know how to read from place with metadata " ++ show meta
         ++ " (type " ++ show tpr ++ ")"
-readPlace MirPlaceDynRef{} =
-    -- See https://github.com/GaloisInc/crucible/issues/1092
-    mirFail "readPlace not supported for dyn references"
+-- Reading an unsized `dyn` value produces its fat pointer, which is how
+-- `tyToRepr` represents `dyn Tr`.  MIR only does this to pass the value to a
+-- by-value method like `FnOnce::call_once`, and the vtable shim for such a
+-- method reads the concrete value out of the data pointer.
+readPlace (MirPlaceDynRef dynRef) = return $ MirExp DynRefRepr dynRef
 
 addrOfPlace :: HasCallStack => MirPlace s -> MirGenerator h s ret (MirExp s)
 addrOfPlace (MirPlace tpr r NoMeta) = return $ MirExp (MirReferenceRepr tpr) r
@@ -402,6 +423,41 @@ addrOfPlace (MirPlace tpr r (SliceMeta len)) =
 addrOfPlace (MirPlaceDynRef dynRef) =
     return $ MirExp DynRefRepr dynRef
 
+-- | Give the reference produced by a borrow expression its own borrow tag, if
+-- aliasing checks are enabled.  References to slices and trait objects keep
+-- the tag of the pointer they were borrowed from, so accesses through them are
+-- checked as accesses through that pointer.
+retagBorrow :: M.BorrowKind -> M.Ty -> MirExp s -> MirGenerator h s ret (MirExp s)
+retagBorrow bk ty (MirExp (MirReferenceRepr tpr) ref) = do
+    perm <- case bk of
+        M.Shared -> do
+            interiorMut <- containsUnsafeCell ty
+            return $ if interiorMut then SharedReadWriteBorrow else SharedReadOnlyBorrow
+        _ -> return UniqueBorrow
+    MirExp (MirReferenceRepr tpr) <$> retagMirRef perm tpr ref
+retagBorrow _ _ e = return e
+
+-- | Check whether a value of type `ty` contains an `UnsafeCell`, in which case
+-- shared references to it may be used for mutation.
+containsUnsafeCell :: M.Ty -> MirGenerator h s ret Bool
+containsUnsafeCell = go Set.empty
+  where
+    go _ (CTyUnsafeCell _) = return True
+    go seen (M.TyAdt name _ _)
+      | name `Set.member` seen = return False
+      | otherwise = do
+        optAdt <- use $ cs . collection . M.adts . at name
+        case optAdt of
+            Just adt -> anyM (go (Set.insert name seen))
+                [f ^. M.fty | v <- adt ^. M.adtvariants, f <- v ^. M.vfields]
+            Nothing -> return False
+    go seen (M.TyTuple tys) = anyM (go seen) tys
+    go seen (M.TyArray ty' _) = go seen ty'
+    go seen (M.TyClosure tys) = anyM (go seen) tys
+    go _ _ = return False
+
+    anyM f = foldM (\b x -> if b then return True else f x) False
+
 
 
 -- Given two bitvectors, extend the length of the shorter one so that they
@@ -542,23 +598,27 @@ evalBinOp bop mat me1 me2 =
             M.Beq -> return (MirExp C.BoolRepr (S.app $ E.Not $ S.app $ E.BoolXor e1 e2), noOverflow)
             M.Ne  -> return (MirExp C.BoolRepr (S.app $ E.BoolXor e1 e2), noOverflow)
             _ -> mirFail $ "No translation for bool binop: " ++ fmt bop
-      (MirExp C.RealValRepr e1, MirExp C.RealValRepr e2) ->
+      (MirExp (C.FloatRepr fi1) e1, MirExp (C.FloatRepr fi2) e2)
+        | Just Refl <- testEquality fi1 fi2 ->
+          let fi = fi1 in
           case bop of
-            M.Beq -> return (MirExp C.BoolRepr (S.app $ E.RealEq e1 e2), noOverflow)
-            M.Lt -> return (MirExp C.BoolRepr (S.app $ E.RealLt e1 e2), noOverflow)
-            M.Le -> return (MirExp C.BoolRepr (S.app $ E.RealLe e1 e2), noOverflow)
-            M.Gt -> return (MirExp C.BoolRepr (S.app $ E.RealLt e2 e1), noOverflow)
-            M.Ge -> return (MirExp C.BoolRepr (S.app $ E.RealLe e2 e1), noOverflow)
-            M.Ne -> return (MirExp C.BoolRepr (S.app $ E.Not $ S.app $ E.RealEq e1 e2), noOverflow)
+            -- Rust's float comparisons follow IEEE-754: comparisons involving
+            -- NaN are false (except `!=`), and `0.0 == -0.0`.
+            M.Beq -> return (MirExp C.BoolRepr (S.app $ E.FloatFpEq e1 e2), noOverflow)
+            M.Lt -> return (MirExp C.BoolRepr (S.app $ E.FloatLt e1 e2), noOverflow)
+            M.Le -> return (MirExp C.BoolRepr (S.app $ E.FloatLe e1 e2), noOverflow)
+            M.Gt -> return (MirExp C.BoolRepr (S.app $ E.FloatGt e1 e2), noOverflow)
+            M.Ge -> return (MirExp C.BoolRepr (S.app $ E.FloatGe e1 e2), noOverflow)
+            M.Ne -> return (MirExp C.BoolRepr (S.app $ E.Not $ S.app $ E.FloatFpEq e1 e2), noOverflow)
 
             -- Binops on floats never set the overflow flag
-            M.Add -> return (MirExp C.RealValRepr (S.app $ E.RealAdd e1 e2), noOverflow)
-            M.Sub -> return (MirExp C.RealValRepr (S.app $ E.RealSub e1 e2), noOverflow)
-            M.Mul -> return (MirExp C.RealValRepr (S.app $ E.RealMul e1 e2), noOverflow)
-            M.Div -> return (MirExp C.RealValRepr (S.app $ E.RealDiv e1 e2), noOverflow)
-            M.Rem -> return (MirExp C.RealValRepr (S.app $ E.RealMod e1 e2), noOverflow)
+            M.Add -> return (MirExp (C.FloatRepr fi) (S.app $ E.FloatAdd fi E.RNE e1 e2), noOverflow)
+            M.Sub -> return (MirExp (C.FloatRepr fi) (S.app $ E.FloatSub fi E.RNE e1 e2), noOverflow)
+            M.Mul -> return (MirExp (C.FloatRepr fi) (S.app $ E.FloatMul fi E.RNE e1 e2), noOverflow)
+            M.Div -> return (MirExp (C.FloatRepr fi) (S.app $ E.FloatDiv fi E.RNE e1 e2), noOverflow)
+            M.Rem -> return (MirExp (C.FloatRepr fi) (floatTruncRem fi e1 e2), noOverflow)
 
-            _ -> mirFail $ "No translation for real number binop: " ++ fmt bop
+            _ -> mirFail $ "No translation for float binop: " ++ fmt bop
 
       (MirExp (MirReferenceRepr tpr1) e1, MirExp (MirReferenceRepr tpr2) e2)
         | Just Refl <- testEquality tpr1 tpr2 ->
@@ -581,6 +641,25 @@ evalBinOp bop mat me1 me2 =
     noOverflow :: R.Expr MIR s C.BoolType
     noOverflow = S.app $ E.BoolLit False
 
+    -- Rust's `%` on floats truncates the quotient, like C's `fmod`, so the
+    -- result has the sign of the dividend.  `FloatRem` is IEEE `remainder`,
+    -- which rounds the quotient to nearest instead.  The two differ by exactly
+    -- `|y|` when the remainder is nonzero and its sign differs from that of
+    -- `x`, and adding or subtracting `|y|` in that case is exact.
+    floatTruncRem :: C.FloatInfoRepr fi ->
+        R.Expr MIR s (C.FloatType fi) -> R.Expr MIR s (C.FloatType fi) ->
+        R.Expr MIR s (C.FloatType fi)
+    floatTruncRem fi x y =
+        let r = S.app $ E.FloatRem fi x y
+            absY = S.app $ E.FloatAbs fi y
+            rNonZero = S.app $ E.Not $ S.app $ E.FloatIsZero r
+            tooLow = S.app $ E.And (S.app $ E.FloatIsPositive x)
+                (S.app $ E.And (S.app $ E.FloatIsNegative r) rNonZero)
+            tooHigh = S.app $ E.And (S.app $ E.FloatIsNegative x)
+                (S.app $ E.And (S.app $ E.FloatIsPositive r) rNonZero)
+        in S.app $ E.FloatIte fi tooLow (S.app $ E.FloatAdd fi E.RNE r absY) $
+            S.app $ E.FloatIte fi tooHigh (S.app $ E.FloatSub fi E.RNE r absY) r
+
     -- Check whether unsigned multiplication of `e1 * e2` overflows `w` bits.
     -- If `zext e1 * zext e2 /= zext (e1 * e2)`, then overflow has occurred.
     mulOverflow :: forall w. (1 <= w, 1 <= w + w) =>
@@ -670,7 +749,7 @@ transUnaryOp uop op = do
       (M.Not, MirExp (C.BVRepr n) e) -> return $ MirExp (C.BVRepr n) $ S.app $ E.BVNot n e
       (M.Neg, MirExp (C.BVRepr n) e) -> return $ MirExp (C.BVRepr n) (S.app $ E.BVSub n (S.app $ eBVLit n 0) e)
       (M.Neg, MirExp C.IntegerRepr e) -> return $ MirExp C.IntegerRepr $ S.app $ E.IntNeg e
-      (M.Neg, MirExp C.RealValRepr e) -> return $ MirExp C.RealValRepr $ S.app $ E.RealNeg e
+      (M.Neg, MirExp (C.FloatRepr fi) e) -> return $ MirExp (C.FloatRepr fi) $ S.app $ E.FloatNeg fi e
       (_ , MirExp ty e) -> mirFail $ "Unimplemented unary op `" ++ fmt uop ++ "' for " ++ show ty
 
 
@@ -711,6 +790,27 @@ extendSignedBV (MirExp tp e) w =
       _ -> mirFail $ "unimplemented signed bvext " ++ show tp ++ " " ++ show w
 
 
+-- | Convert a float to a bitvector with the semantics of a Rust `as` cast:
+-- the value is rounded toward zero, values outside the range of the target
+-- type saturate to its minimum or maximum, and NaN becomes zero.
+floatToBV :: (1 <= w) =>
+    Bool -> C.FloatInfoRepr fi -> NatRepr w ->
+    R.Expr MIR s (C.FloatType fi) -> R.Expr MIR s (C.BVType w)
+floatToBV signed fi w e =
+    R.App $ E.BVIte (R.App $ E.FloatIsNaN e) w (R.App $ eBVLit w 0) $
+    R.App $ E.BVIte (R.App $ E.FloatLe e (floatLit lo)) w (R.App $ eBVLit w lo) $
+    R.App $ E.BVIte (R.App $ E.FloatGe e (floatLit hi)) w (R.App $ eBVLit w hi) $
+    R.App $ if signed then E.FloatToSBV w E.RTZ e else E.FloatToBV w E.RTZ e
+  where
+    (lo, hi)
+      | signed = (minSigned w, maxSigned w)
+      | otherwise = (0, maxUnsigned w)
+    -- `hi` may not be representable as a float, in which case it rounds up to
+    -- the next power of two.  Any float that compares `>=` to the rounded
+    -- value is still out of range, so the comparison above remains correct.
+    floatLit :: Integer -> R.Expr MIR s (C.FloatType fi)
+    floatLit x = R.App $ E.FloatFromReal fi E.RNE $ R.App $ E.RationalLit (fromInteger x)
+
 evalCast' :: HasCallStack => M.CastKind -> M.Ty -> MirExp s -> M.Ty -> MirGenerator h s ret (MirExp s)
 evalCast' ck ty1 e ty2  = do
     col <- use $ cs . collection
@@ -798,29 +898,67 @@ evalCast' ck ty1 e ty2  = do
 
 
 
-{-      -- BV to Float
-      (M.Misc, M.TyInt bsz, TyFloat fsz)
-       | MirExp (C.BVRepr sz) e0 <- e
-       -> return $ MirExp C.FloatRepr -}
+      -- int to float.  Values that aren't exactly representable are rounded
+      -- to the nearest float, as in Rust.
+      (M.Misc, M.TyInt _, M.TyFloat fk)
+       | MirExp (C.BVRepr _) e0 <- e
+       -> floatKindToInfoCont fk $ \fi -> return $
+         MirExp (C.FloatRepr fi) (R.App $ E.FloatFromSBV fi E.RNE e0)
+      (M.Misc, M.TyUint _, M.TyFloat fk)
+       | MirExp (C.BVRepr _) e0 <- e
+       -> floatKindToInfoCont fk $ \fi -> return $
+         MirExp (C.FloatRepr fi) (R.App $ E.FloatFromBV fi E.RNE e0)
+
+      -- float to int.  These casts saturate; see `floatToBV` for details.
+      (M.Misc, M.TyFloat _, M.TyInt bsz)
+       | MirExp (C.FloatRepr fi) e0 <- e
+       -> baseSizeToNatCont bsz $ \w -> return $
+         MirExp (C.BVRepr w) (floatToBV True fi w e0)
+      (M.Misc, M.TyFloat _, M.TyUint bsz)
+       | MirExp (C.FloatRepr fi) e0 <- e
+       -> baseSizeToNatCont bsz $ \w -> return $
+         MirExp (C.BVRepr w) (floatToBV False fi w e0)
+
+      -- float to float
+      (M.Misc, M.TyFloat _, M.TyFloat fk)
+       | MirExp (C.FloatRepr _) e0 <- e
+       -> floatKindToInfoCont fk $ \fi -> return $
+         MirExp (C.FloatRepr fi) (R.App $ E.FloatCast fi E.RNE e0)
 
       -- Not sure why this appears in generated MIR, but libcore has some no-op
       -- unsizes from `*const dyn Any` to `*const dyn Any`
       (M.Unsize,a,b) | a == b -> return e
 
+      -- Fields that `coerceUnsized` leaves alone, like the allocator in
+      -- `Box<T, A>`
+      (M.UnsizeVtable _, a, b) | a == b -> return e
+
       -- ADT -> ADT unsizing is done via `CoerceUnsized`.
       (M.Unsize, M.TyAdt aname1 _ _, M.TyAdt aname2 _ _) ->
-        coerceUnsized aname1 aname2 e
+        coerceUnsized ck aname1 aname2 e
+      (M.UnsizeVtable _, M.TyAdt aname1 _ _, M.TyAdt aname2 _ _) ->
+        coerceUnsized ck aname1 aname2 e
 
       (M.Unsize, M.TyRef (M.TyArray tp sz) _, M.TyRef (M.TySlice tp') _) ->
         unsizeArray tp sz tp'
       (M.Unsize, M.TyRawPtr (M.TyArray tp sz) _, M.TyRawPtr (M.TySlice tp') _) ->
         unsizeArray tp sz tp'
 
-      -- TODO: extend coerceUnsized to handle UnsizeVtable as well
-      -- Trait object creation from a ref
+      -- Trait object creation from a ref or raw pointer.  The raw pointer
+      -- case is used by `coerceUnsized`, e.g. for `Box<T>` to `Box<dyn Tr>`.
       (M.UnsizeVtable vtbl, M.TyRef baseType _,
         M.TyRef (M.TyDynamic traitName) _) ->
-          mkTraitObject traitName vtbl e
+          mkTraitObject traitName vtbl baseType e
+      (M.UnsizeVtable vtbl, M.TyRawPtr baseType _,
+        M.TyRawPtr (M.TyDynamic traitName) _) ->
+          mkTraitObject traitName vtbl baseType e
+
+      -- A struct whose last field is `dyn Tr`, like `RcBox<dyn Tr>` or
+      -- `ArcInner<dyn Tr>`.  We have no representation for pointers to these.
+      (M.UnsizeVtable _, M.TyRef _ _, M.TyRef adt@M.TyAdt{} _) ->
+          mirFail $ "unsizing to a custom DST is not supported: " ++ show adt
+      (M.UnsizeVtable _, M.TyRawPtr _ _, M.TyRawPtr adt@M.TyAdt{} _) ->
+          mirFail $ "unsizing to a custom DST is not supported: " ++ show adt
 
       -- Casting between TyDynamics that vary only in their auto traits
       -- TODO: this should also normalize the TraitProjection predicates, to
@@ -915,22 +1053,23 @@ evalCast' ck ty1 e ty2  = do
     -- CoerceUnsized<Foo<U>>`, then this operation is enabled for converting
     -- `Foo<T>` to `Foo<U>`.  The actual operation consists of disassembling
     -- the struct, coercing any raw pointers inside, and putting it back
-    -- together again.
+    -- together again.  `ck` is either `Unsize` or `UnsizeVtable`, and is
+    -- passed along to the cast of the pointer inside.
     coerceUnsized :: HasCallStack =>
-        M.AdtName -> M.AdtName -> MirExp s -> MirGenerator h s ret (MirExp s)
-    coerceUnsized an1 an2 e = do
+        M.CastKind -> M.AdtName -> M.AdtName -> MirExp s -> MirGenerator h s ret (MirExp s)
+    coerceUnsized ck' an1 an2 e = do
         col <- use $ cs . collection
         adt1 <- findAdt an1
         adt2 <- findAdt an2
         case (reprTransparentFieldTy col adt1, reprTransparentFieldTy col adt2) of
-            (Just ty1, Just ty2) -> evalCast' M.Unsize ty1 e ty2
-            (Nothing, Nothing) -> coerceUnsizedNormal adt1 adt2 e
+            (Just ty1, Just ty2) -> evalCast' ck' ty1 e ty2
+            (Nothing, Nothing) -> coerceUnsizedNormal ck' adt1 adt2 e
             _ -> mirFail $ "impossible: coerceUnsized: one of " ++ show (an1, an2) ++
                 " is repr(transparent) and the other is not?"
 
     coerceUnsizedNormal :: HasCallStack =>
-        M.Adt -> M.Adt -> MirExp s -> MirGenerator h s ret (MirExp s)
-    coerceUnsizedNormal adt1 adt2 e = do
+        M.CastKind -> M.Adt -> M.Adt -> MirExp s -> MirGenerator h s ret (MirExp s)
+    coerceUnsizedNormal ck' adt1 adt2 e = do
         when (adt1 ^. adtkind /= Struct || adt2 ^. adtkind /= Struct) $ mirFail $
             "coerceUnsized not yet implemented for non-struct types: " ++ show (an1, an2)
         let v1 = Maybe.fromJust $ adt1 ^? adtvariants . ix 0
@@ -941,7 +1080,7 @@ evalCast' ck ty1 e ty2  = do
             "coerceUnsized on incompatible types (mismatched fields): " ++ show (an1, an2)
         vals' <- forM (zip3 [0..] (v1 ^. vfields) (v2 ^. vfields)) $ \(i, f1, f2) -> do
             val <- getStructField adt1 i e
-            evalCast' M.Unsize (f1 ^. fty) val (f2 ^. fty)
+            evalCast' ck' (f1 ^. fty) val (f2 ^. fty)
         buildStruct adt2 vals'
       where
         an1 = adt1 ^. adtname
@@ -954,12 +1093,16 @@ evalCast ck op ty = do
     evalCast' ck (M.typeOf op) e ty
 
 -- | Create a new trait object by combining `e` with the named vtable.  This is
--- only valid when `e` is TyRef or TyRawPtr.  Coercions via the `CoerceUnsized`
--- trait require unpacking and repacking structs, which we don't handle here.
+-- only valid when `e` is TyRef or TyRawPtr, pointing to a value of type
+-- `baseType`.  Coercions via the `CoerceUnsized` trait require unpacking and
+-- repacking structs, which we don't handle here.
+--
+-- After the method shims, the vtable has one more entry, which describes the
+-- data behind `e` (see `vtableDataEntry`).
 mkTraitObject :: HasCallStack => M.TraitName ->
-    M.VtableName -> MirExp s ->
+    M.VtableName -> M.Ty -> MirExp s ->
     MirGenerator h s ret (MirExp s)
-mkTraitObject traitName vtableName e = do
+mkTraitObject traitName vtableName baseType e = do
     handles <- Maybe.fromMaybe (error $ "missing vtable handles for " ++ show vtableName) <$>
         use (cs . vtableMap . at vtableName)
 
@@ -967,7 +1110,8 @@ mkTraitObject traitName vtableName e = do
         mkEntry (MirHandle hname _ fh) =
             MirExp (C.FunctionHandleRepr (FH.handleArgTypes fh) (FH.handleReturnType fh))
                 (R.App $ E.HandleLit fh)
-    vtable@(MirExp vtableTy _) <- return $ buildTuple $ map mkEntry handles
+    dataEntry <- MirExp vtableDataRepr <$> vtableDataEntry baseType e
+    vtable@(MirExp vtableTy _) <- return $ buildTuple $ map mkEntry handles ++ [dataEntry]
 
     -- Check that the vtable we constructed has the appropriate type for the
     -- trait.  A mismatch would cause runtime errors at calls to trait methods.
@@ -986,12 +1130,79 @@ mkTraitObject traitName vtableName e = do
         , packAny vtable
         ]
 
+-- | Build the data entry of a vtable for a trait object whose data pointer is
+-- `e`, of type `&ty`.  Since the drop closure captures the data pointer, each
+-- trait object gets its own copy of the vtable.
+vtableDataEntry :: HasCallStack => M.Ty -> MirExp s ->
+    MirGenerator h s ret (R.Expr MIR s (C.StructType VtableDataCtx))
+vtableDataEntry ty e = do
+    dropEntry <- vtableDropEntry ty e
+    col <- use $ cs . collection
+    return $ R.App $ E.MkStruct (Ctx.empty Ctx.:> vtableDropRepr Ctx.:> UsizeRepr Ctx.:> UsizeRepr)
+        (Ctx.empty Ctx.:> dropEntry Ctx.:> tySize col ty Ctx.:> tyAlign col ty)
+
+-- | A closure that calls the drop glue for `ty` on `e`, or `Nothing` if `ty`
+-- has no drop glue.
+vtableDropEntry :: HasCallStack => M.Ty -> MirExp s ->
+    MirGenerator h s ret (R.Expr MIR s VtableDropType)
+vtableDropEntry ty (MirExp tpr e) = do
+    intrs <- use $ cs . collection . M.intrinsics
+    hmap <- use $ cs . handleMap
+    let glues =
+            [ mh
+            | intr <- Map.elems intrs
+            , IkDropGlue (Just ty') <- [intr ^. M.intrInst . M.inKind]
+            , ty' == ty
+            , Just mh <- [Map.lookup (intr ^. M.intrName) hmap] ]
+    case glues of
+        [] -> return $ R.App $ E.NothingValue vtableDropFnRepr
+        MirHandle _ _ fh : _
+          | Ctx.Empty Ctx.:> argTpr <- FH.handleArgTypes fh
+          , Just Refl <- testEquality argTpr tpr
+          , Just Refl <- testEquality (FH.handleReturnType fh) C.UnitRepr ->
+            return $ R.App $ E.JustValue vtableDropFnRepr $
+                R.App $ E.Closure Ctx.empty C.UnitRepr (R.App $ E.HandleLit fh) tpr e
+          | otherwise -> mirFail $ "drop glue for " ++ show ty ++
+                " has unexpected signature " ++ show (FH.handleType fh)
+
+-- | Get the data entry of the vtable of a `dyn traitName` trait object.
+dynVtableData :: HasCallStack => M.TraitName -> R.Expr MIR s DynRefType ->
+    MirGenerator h s ret (R.Expr MIR s (C.StructType VtableDataCtx))
+dynVtableData traitName dynRef = do
+    trait <- Maybe.fromMaybe (error $ "unknown trait " ++ show traitName) <$>
+        use (cs . collection . M.traits . at traitName)
+    col <- use $ cs . collection
+    Some vtableTy <- return $ traitVtableType col traitName trait
+    case vtableTy of
+        C.StructRepr vtableCtx
+          | Ctx.AssignExtend _ dataTpr <- Ctx.viewAssign vtableCtx
+          , Just Refl <- testEquality dataTpr vtableDataRepr -> do
+            let vtableAny = R.App $ E.GetStruct dynRef dynRefVtableIndex C.AnyRepr
+            vtable <- G.fromJustExpr (R.App $ E.UnpackAny vtableTy vtableAny)
+                (R.App $ E.StringLit $ fromString $
+                    "bad vtable downcast for dyn " ++ show traitName)
+            return $ R.App $ E.GetStruct vtable
+                (Ctx.lastIndex (Ctx.size vtableCtx)) vtableDataRepr
+        _ -> mirFail $ "bad vtable type for " ++ show traitName ++ ": " ++ show vtableTy
+
+-- | Drop the value behind a `dyn` place by calling the drop entry of its
+-- vtable.
+dropDynPlace :: HasCallStack => M.TraitName -> MirPlace s -> MirGenerator h s ret ()
+dropDynPlace traitName (MirPlaceDynRef dynRef) = do
+    vtableData <- dynVtableData traitName dynRef
+    let dropFn = R.App $ E.GetStruct vtableData vtableDataDropIndex vtableDropRepr
+    G.caseMaybe_ dropFn $ G.MatchMaybe
+        (\f -> void $ G.call f Ctx.empty)
+        (return ())
+dropDynPlace traitName pl =
+    mirFail $ "expected a dyn place when dropping dyn " ++ show traitName ++ ", but got " ++ show pl
+
 -- Expressions: evaluation of Rvalues and Lvalues
 
 evalRval :: HasCallStack => M.Rvalue -> MirGenerator h s ret (MirExp s)
 evalRval (M.Use op) = evalOperand op
 evalRval (M.Repeat op size) = buildRepeat op size
-evalRval (M.Ref _bk lv _) = evalPlace lv >>= addrOfPlace
+evalRval (M.Ref bk lv _) = evalPlace lv >>= addrOfPlace >>= retagBorrow bk (M.typeOf lv)
 evalRval (M.AddressOf _mutbl lv) = evalPlace lv >>= addrOfPlace
 evalRval (M.Len lv) =
     case M.typeOf lv of
@@ -1002,8 +1213,8 @@ evalRval (M.Len lv) =
             meta <- case place of
                 MirPlace _tpr _ref meta -> pure meta
                 MirPlaceDynRef{} ->
-                    -- See https://github.com/GaloisInc/crucible/issues/1092
-                    mirFail "evalRval (length of slice) not supported for dyn references"
+                    mirFail $ "evalRval: expected a slice place, but got a dyn place for "
+                        ++ show ty
             case meta of
                 SliceMeta len -> return $ MirExp UsizeRepr len
                 _ -> mirFail $ "bad metadata " ++ show meta ++ " for reference to " ++ show ty
@@ -1060,8 +1271,7 @@ evalRval rv@(M.RAdtAg (M.AdtAg adt agv ops ty)) = do
             case adt^.adtkind of
                 M.Struct -> buildStruct adt es
                 M.Enum _ -> buildEnum adt (fromInteger agv) es
-                M.Union -> do
-                    mirFail $ "evalRval: Union types are unsupported, for " ++ show (adt ^. adtname)
+                M.Union -> buildUnion adt es
       _ -> mirFail $ "evalRval: unsupported type for AdtAg: " ++ show ty
 evalRval (M.ThreadLocalRef did _) = staticPlace did >>= addrOfPlace
 
@@ -1134,7 +1344,7 @@ evalPlaceProj ty pl@(MirPlace tpr ref NoMeta) (M.PField idx _mirTy) = do
         case adt^.adtkind of
             Struct -> structFieldRef adt idx tpr ref
             Enum _ -> mirFail $ "tried to access field of non-downcast " ++ show ty
-            Union -> mirFail $ "evalPlace (PField, Union) NYI"
+            Union -> unionFieldRef adt idx tpr ref
 
     M.TyDowncast (M.TyAdt nm _ _) i -> do
         adt <- findAdt nm
@@ -1186,9 +1396,13 @@ evalPlaceProj ty (MirPlace tpr ref meta) (M.ConstantIndex idx _minLen fromEnd) =
 evalPlaceProj _ pl (M.Downcast _idx) = return pl
 evalPlaceProj ty (MirPlace _ _ meta) proj =
     mirFail $ "projection " ++ show proj ++ " not yet implemented for " ++ show (ty, meta)
-evalPlaceProj _ MirPlaceDynRef{} _ =
-    -- See https://github.com/GaloisInc/crucible/issues/1092
-    mirFail "evalPlaceProj not supported for dyn references"
+-- The concrete type behind a `dyn` place is unknown, so there's nothing to
+-- project into.  Rust only accesses the value through the vtable.  The one
+-- exception is a custom DST, like `ArcInner<dyn Tr>`, whose sized fields can be
+-- projected out of a `dyn`-tailed place; `evalCast'` refuses to create
+-- pointers to those.
+evalPlaceProj ty MirPlaceDynRef{} proj =
+    mirFail $ "projection " ++ show proj ++ " is not valid on dyn place of type " ++ show ty
 
 --------------------------------------------------------------------------------------
 -- ** Statements
@@ -1227,9 +1441,12 @@ doAssign :: HasCallStack => M.Lvalue -> MirExp s -> MirGenerator h s ret ()
 doAssign lv (MirExp tpr val) = do
     place <- evalPlace lv
     case place of
+        -- Rust doesn't allow assigning an unsized value through a pointer
+        -- (`*r = x` where `r: &mut dyn Tr`), so this never arises for
+        -- well-typed MIR.  Assignments to fields of a custom DST would, but
+        -- `evalCast'` refuses to create pointers to those.
         MirPlaceDynRef{} ->
-            -- See https://github.com/GaloisInc/crucible/issues/1092
-            mirFail "doAssign not supported for dyn references"
+            mirFail $ "doAssign: can't assign unsized value to dyn place " ++ show lv
         MirPlace tpr' ref _ -> do
             Refl <- testEqualityOrFail tpr tpr' $
                 "ill-typed assignment of " ++ show tpr ++ " to " ++ show tpr'
@@ -1615,9 +1832,14 @@ transTerminator (M.Assert cond expected msg target _cleanup) _ = do
 transTerminator (M.Resume) tr =
     doReturn tr -- resume happens when unwinding
 transTerminator (M.Drop dlv dt _dunwind dropFn) _ = do
-    let ptrOp = M.Temp $ M.Cast M.Misc
-            (M.Temp $ M.AddressOf M.Mut dlv) (M.TyRawPtr (M.typeOf dlv) M.Mut)
-    maybe (return ()) (\f -> void $ callExp f [ptrOp]) dropFn
+    case M.typeOf dlv of
+        -- The drop glue for a `dyn` value depends on its concrete type, so we
+        -- find it in the vtable.
+        M.TyDynamic traitName -> evalPlace dlv >>= dropDynPlace traitName
+        _ -> do
+            let ptrOp = M.Temp $ M.Cast M.Misc
+                    (M.Temp $ M.AddressOf M.Mut dlv) (M.TyRawPtr (M.typeOf dlv) M.Mut)
+            maybe (return ()) (\f -> void $ callExp f [ptrOp]) dropFn
     jumpToBlock dt
 transTerminator M.Abort tr =
     G.reportError (S.litExpr "process abort in unwinding")
@@ -1701,6 +1923,8 @@ initFnState colState transCtxt =
             _debugLevel = ?debug,
             _cs         = colState,
             _labelMap   = Map.empty,
+            _loopCuts   = Map.empty,
+            _unwindChecks = Map.empty,
             _customOps  = ?customOps,
             _assertFalseOnError = ?assertFalseOnError,
             _transInfo  = mempty
@@ -1716,11 +1940,103 @@ translateBlockBody tr (M.BasicBlockData stmts terminator) = (mapM_ transStatemen
 registerBlock :: HasCallStack => C.TypeRepr ret -> M.BasicBlock -> MirGenerator h s ret ()
 registerBlock tr (M.BasicBlock bbinfo bbdata)  = do
     lm <- use labelMap
+    cuts <- use loopCuts
+    checks <- use unwindChecks
     case (Map.lookup bbinfo lm) of
       Just lab -> do
-        G.defineBlock lab (translateBlockBody tr bbdata)
+        G.defineBlock lab $ do
+          -- Once control is outside a loop, the next visit to its cut starts
+          -- a new execution of the loop.
+          forM_ cuts $ \(cut, flag) ->
+              when (bbinfo `elem` cutEntries cut) $
+                  G.assignReg flag (S.app $ E.BoolLit False)
+          forM_ checks $ \uc ->
+              when (bbinfo `elem` ucEntries uc) $
+                  G.assignReg (ucCounter uc) (S.app $ E.IntLit 0)
+          mapM_ checkUnwinding (Map.lookup bbinfo checks)
+          case Map.lookup bbinfo cuts of
+            Just (cut, flag) -> translateCutBlock cut flag bbdata
+            Nothing -> translateBlockBody tr bbdata
       _ -> mirFail "bad label"
 
+-- | Count a visit to the header of a loop, and check that the current
+-- execution of the loop hasn't gone around more times than its unwinding
+-- bound allows.  Entering the loop visits the header once, and so does each
+-- iteration.
+checkUnwinding :: HasCallStack => UnwindCheck s -> MirGenerator h s ret ()
+checkUnwinding uc = do
+    count <- S.app . E.IntAdd (S.app $ E.IntLit 1) <$> G.readReg (ucCounter uc)
+    G.assignReg (ucCounter uc) count
+    (limit, assume, desc) <- case ucBound uc of
+        Just (UnwindBound n a) ->
+            return (S.app $ E.IntLit n, S.app $ E.BoolLit a, "its bound of " <> Text.pack (show n) <> " iterations")
+        Nothing -> use (cs . unwindBoundVars) >>= \case
+            Just (limitVar, assumeVar) -> do
+                limit <- G.readGlobal limitVar
+                assume <- G.readGlobal assumeVar
+                return (limit, assume, "the test's unwinding bound")
+            Nothing -> mirFail "unwinding check without an unwinding bound"
+    let bounded = S.app $ E.IntLe (S.app $ E.IntLit 0) limit
+    let exceeded = S.app $ E.And bounded $
+            S.app $ E.IntLt (S.app $ E.IntAdd limit (S.app $ E.IntLit 1)) count
+    setPosition (ucPos uc)
+    G.assertExpr (S.app $ E.Or assume (S.app $ E.Not exceeded)) $
+        S.app $ E.StringLit $ W4.UnicodeLiteral $ "unwinding assertion: loop exceeded " <> desc
+    G.assumeExpr (S.app $ E.Not exceeded) $
+        S.litExpr "loop stays within its unwinding bound"
+
+-- | Translate a block that ends in a call to `crucible::loop_invariant_cut`.
+-- The first time an execution of the loop reaches the cut, every place that
+-- the loop may modify is replaced with a fresh symbolic value, so the rest of
+-- the path covers an arbitrary iteration.  If that iteration comes back
+-- around to the cut, `loop_invariant!` has already checked that it preserved
+-- the invariant, and the path ends there.
+translateCutBlock :: HasCallStack =>
+    LoopCut -> R.Reg s C.BoolType -> M.BasicBlockData -> MirGenerator h s ret a
+translateCutBlock cut flag (M.BasicBlockData stmts term) = do
+    mapM_ transStatement stmts
+    target <- case term of
+        M.Call _ _ (Just (_, target)) _ -> return target
+        _ -> mirFail $ "expected a call to loop_invariant_cut, but got " ++ show term
+    done <- G.readReg flag
+    G.assumeExpr (S.app $ E.Not done) $
+        S.litExpr "loop invariant is preserved by an arbitrary iteration"
+    -- Places that can't be havocked are reported at the loop.
+    setPosition (cutPos cut)
+    forM_ (cutHavoc cut) $ \lv -> do
+        pl <- evalPlace lv
+        havocPlace lv (M.typeOf lv) pl
+    G.assignReg flag (S.app $ E.BoolLit True)
+    jumpToBlock target
+
+-- | Replace the value stored in a place with a fresh symbolic one.  `lv` is
+-- the place being replaced, for use in error messages.
+havocPlace :: HasCallStack => M.Lvalue -> M.Ty -> MirPlace s -> MirGenerator h s ret ()
+havocPlace _ _ (MirPlace tpr ref NoMeta)
+  | C.AsBaseType btpr <- C.asBaseType tpr = do
+    val <- G.mkFresh btpr Nothing
+    writeMirRef ref (R.AtomExpr val)
+  | C.FloatRepr fi <- tpr = do
+    val <- G.mkFreshFloat fi Nothing
+    writeMirRef ref (R.AtomExpr val)
+havocPlace lv ty pl@(MirPlace _ _ NoMeta) = case ty of
+    M.TyTuple tys -> forM_ (zip [0..] tys) $ \(i, fty) ->
+        evalPlaceProj ty pl (M.PField i fty) >>= havocPlace lv fty
+    M.TyArray elt n -> forM_ [0 .. n - 1] $ \i ->
+        evalPlaceProj ty pl (M.ConstantIndex i n False) >>= havocPlace lv elt
+    M.TyAdt nm _ _ -> do
+        adt <- findAdt nm
+        case (adt ^. adtkind, adt ^. adtvariants) of
+            (M.Struct, [v]) -> forM_ (zip [0..] (v ^. vfields)) $ \(i, f) ->
+                evalPlaceProj ty pl (M.PField i (f ^. fty)) >>= havocPlace lv (f ^. fty)
+            _ -> cantHavoc lv ty
+    _ -> cantHavoc lv ty
+havocPlace lv ty _ = cantHavoc lv ty
+
+cantHavoc :: M.Lvalue -> M.Ty -> MirGenerator h s ret a
+cantHavoc lv ty = mirFail $ "loop_invariant!: can't replace " ++ fmt lv ++
+    " with a fresh value, since it contains a value of type " ++ fmt ty
+
 
 
 -------------------------------------------------------------------------------------------
@@ -1744,6 +2060,39 @@ genFn (M.Fn fname argvars _ body@(MirBody localvars blocks _)) rettype inputs =
   initLocals (argvars ++ localvars) addrTaken
   initArgs inputs (reverse argvars)
 
+  -- Each call to `crucible::loop_invariant_cut` cuts the loop around it.
+  col <- use $ cs . collection
+  cuts <- forM blocks $ \(M.BasicBlock bbi (M.BasicBlockData _ term)) -> case term of
+      M.Call (M.OpConstant (M.Constant (M.TyFnDef funid) _)) _ (Just (_, target)) _
+        | M.idKey funid == ["crucible", "loop_invariant_cut"] -> do
+          cut <- case loopCut col body bbi target of
+              Right x -> return x
+              Left err -> do
+                  -- Report the problem at the loop, like the ones found while
+                  -- havocking.
+                  forM_ (innermostLoop (naturalLoops body) bbi) $ \lp ->
+                      setPosition (loopPos body lp)
+                  mirFail $ "loop_invariant!: " ++ err
+          flag <- G.newReg $ S.app $ E.BoolLit False
+          return [(bbi, (cut, flag))]
+      _ -> return []
+  loopCuts .= Map.fromList (concat cuts)
+
+  -- Each loop with an unwinding bound, either its own or the function's,
+  -- counts its iterations.  If the crate has unwinding bounds, loops without
+  -- one use the bound of the running test, if any.
+  (fnBound, loopBounds) <- case unwindBounds body of
+      Right x -> return x
+      Left err -> mirFail $ "unwinding bound in " ++ show fname ++ ": " ++ err
+  haveTestBound <- Maybe.isJust <$> use (cs . unwindBoundVars)
+  checks <- forM (naturalLoops body) $ \lp ->
+      case Map.lookup (loopHeader lp) loopBounds <|> fnBound of
+          Nothing | not haveTestBound -> return []
+          bound -> do
+              counter <- G.newReg $ S.app $ E.IntLit 0
+              return [(loopHeader lp, UnwindCheck (loopEntries body lp) bound counter (loopPos body lp))]
+  unwindChecks .= Map.fromList (concat checks)
+
   db <- use debugLevel
   when (db > 3) $ do
      vmm <- use varMap
@@ -1815,7 +2164,21 @@ transDefine colState fn@(M.Fn fname _ _ _) =
           traceM $ unwords [" ======= end", show fname, "======="]
       fti <- readSTRef ftiRef
       case SSA.toSSA g of
-        Core.SomeCFG g_ssa -> return (M.idText fname, Core.AnyCFG g_ssa, fti)
+        Core.SomeCFG g_ssa -> do
+          -- Coverage reports list the Core blocks that were visited, so
+          -- record where each of them starts in the source.
+          let blockSpan b
+                | endsInError b = []
+                | otherwise =
+                    [(Text.pack $ show $ Core.blockID b,
+                      Text.pack $ show $ PL.plSourceLoc $ Core.blockLoc b)]
+              endsInError b = Core.withBlockTermStmt b $ \_ t -> case t of
+                Core.ErrorStmt _ -> True
+                _ -> False
+          let fti' = fti
+                & ftiBlockSpans .~ Map.fromList (concat $ toListFC blockSpan $ Core.cfgBlockMap g_ssa)
+                & ftiEntryBlock .~ Just (Text.pack $ show $ Core.cfgEntryBlockID g_ssa)
+          return (M.idText fname, Core.AnyCFG g_ssa, fti')
 
 
 -- | Allocate method handles for each of the functions in the Collection
@@ -1954,8 +2317,22 @@ transVtableShim colState vtableName (VtableItem fnName defName)
         recvDowncast <- G.fromJustExpr (R.App $ E.UnpackAny recvTy recv)
             (R.App $ E.StringLit $ fromString $ "bad receiver type for " ++ show fnName)
         G.tailCall (R.App $ E.HandleLit implFH) (recvDowncast <: args)
+      -- By-value `self`, as in `FnOnce::call_once`.  The data part of the
+      -- trait object still points to the value, so we read it out.  ADT
+      -- receivers are excluded, since `self: Box<Self>` would also land here.
+      | isByValueRecv recvMirTy = \argsA -> (\x -> ([], x)) $ do
+        let (recv, args) = splitMethodArgs @C.AnyType @argTys argsA (Ctx.size argTys)
+        recvRef <- G.fromJustExpr (R.App $ E.UnpackAny (MirReferenceRepr recvTy) recv)
+            (R.App $ E.StringLit $ fromString $ "bad receiver type for " ++ show fnName)
+        recvVal <- G.extensionStmt (MirReadRef recvTy recvRef)
+        G.tailCall (R.App $ E.HandleLit implFH) (recvVal <: args)
       | otherwise = die ["unsupported MIR receiver type", show recvMirTy]
 
+    isByValueRecv M.TyRef{} = False
+    isByValueRecv M.TyRawPtr{} = False
+    isByValueRecv M.TyAdt{} = False
+    isByValueRecv _ = True
+
 splitMethodArgs :: forall recvTy argTys s.
     Ctx.Assignment (R.Atom s) (recvTy :<: argTys) ->
     Ctx.Size argTys ->
@@ -2225,7 +2602,8 @@ mkCrateHashesMap
 transCollection ::
     (HasCallStack, ?debug::Int, ?assertFalseOnError::Bool,
      ?libCS::CollectionState, ?customOps::CustomOpMap,
-     ?printCrucible::Bool)
+     ?printCrucible::Bool, ?checkAliasing::Bool, ?checkLeaks::Bool,
+     ?weakMemory::Bool, ?detectRaces::Bool)
     => M.Collection
     -> FH.HandleAllocator
     -> IO RustModule
@@ -2257,8 +2635,41 @@ transCollection col halloc = do
     let dm = mkDiscrMap col
     let chm = mkCrateHashesMap col
 
+    -- allocate the state for aliasing and leak checks, weak memory and race
+    -- detection, which is initialized by the caller before running any code
+    bsv <- if ?checkAliasing
+        then Just <$> G.freshGlobalVar halloc "borrow_state" BorrowStateRepr
+        else return Nothing
+    hav <- if ?checkLeaks
+        then Just <$> G.freshGlobalVar halloc "heap_allocs" HeapAllocsRepr
+        else return Nothing
+    tid <- G.freshGlobalVar halloc "current_thread" C.NatRepr
+    wmv <- if ?weakMemory
+        then do
+            wm <- G.freshGlobalVar halloc "weak_memory" WeakMemoryRepr
+            return $ Just (wm, tid)
+        else return Nothing
+    rdv <- if ?detectRaces
+        then do
+            rd <- G.freshGlobalVar halloc "race_detector" RaceDetectorRepr
+            return $ Just (rd, tid)
+        else return Nothing
+
+    -- allocate the running test's unwinding bound, which the caller sets
+    -- before running each test, if anything has an unwinding bound
+    let callsUnwindBound fn = or
+            [ M.idKey did == ["crucible", "unwind_bound"]
+            | M.BasicBlock _ bbd <- fn ^. M.fbody . M.mblocks
+            , M.Call (M.OpConstant (M.Constant (M.TyFnDef did) _)) _ _ _ <- [bbd ^. M.bbterminator] ]
+    ubv <- if any callsUnwindBound (col ^. M.functions)
+        then do
+            limit <- G.freshGlobalVar halloc "unwind_bound" C.IntegerRepr
+            assume <- G.freshGlobalVar halloc "unwind_assume" C.BoolRepr
+            return $ Just (limit, assume)
+        else return Nothing
+
     let colState :: CollectionState
-        colState = CollectionState hmap vm sm dm chm col
+        colState = CollectionState hmap vm sm dm chm bsv hav wmv rdv ubv col
 
     -- translate all of the functions
     fnInfo <- mapM (stToIO . transDefine (?libCS <> colState)) (Map.elems (col^.M.functions))
@@ -103,6 +103,8 @@ customOpDefs = Map.fromList $ [
                          , rotate_right
                          , size_of
                          , min_align_of
+                         , size_of_val
+                         , min_align_of_val
                          , intrinsics_assume
                          , assert_inhabited
                          , unlikely
@@ -164,6 +166,12 @@ customOpDefs = Map.fromList $ [
                          , allocate
                          , allocate_zeroed
                          , reallocate
+                         , deallocate
+
+                         , sync_spawn
+                         , sync_join
+                         , sync_acquire
+                         , sync_release
 
                          , maybe_uninit_uninit
 
@@ -183,7 +191,7 @@ customOpDefs = Map.fromList $ [
                          , integer_rem
                          , integer_eq
                          , integer_lt
-                         ] ++ bv_funcs ++ atomic_funcs
+                         ] ++ bv_funcs ++ float_funcs ++ atomic_funcs
 
 
 
@@ -933,20 +941,54 @@ type_id = (["core","intrinsics", "{extern}", "type_id"],
     -- TODO: keep a map from Ty to Word64, assigning IDs on first use of each type
     return $ MirExp knownRepr $ R.App (eBVLit (knownRepr :: NatRepr 64) 0))
 
+-- `size_of` and `min_align_of` report 1 for every type.  Our `Box::new` and
+-- `RawVec` allocate every value, even zero-sized ones, which only works if the
+-- standard library never takes its special paths for zero-sized types.
 size_of :: (ExplodedDefId, CustomRHS)
 size_of = (["core", "intrinsics", "{extern}", "size_of"], \substs -> case substs of
-    Substs [t] -> Just $ CustomOp $ \_ _ ->
-        -- TODO: return the actual size, once mir-json exports size/layout info
+    Substs [_] -> Just $ CustomOp $ \_ _ ->
         return $ MirExp UsizeRepr $ R.App $ usizeLit 1
     )
 
 min_align_of :: (ExplodedDefId, CustomRHS)
 min_align_of = (["core", "intrinsics", "{extern}", "min_align_of"], \substs -> case substs of
-    Substs [t] -> Just $ CustomOp $ \_ _ ->
-        -- TODO: return the actual alignment, once mir-json exports size/layout info
+    Substs [_] -> Just $ CustomOp $ \_ _ ->
         return $ MirExp UsizeRepr $ R.App $ usizeLit 1
     )
 
+-- The `_val` variants report the real layout of the value, and also accept
+-- unsized pointees.  A slice's size is its length times the size of its
+-- elements, and a trait object's size and alignment are those of its concrete
+-- type, which are stored in the vtable.
+size_of_val :: (ExplodedDefId, CustomRHS)
+size_of_val = (["core", "intrinsics", "{extern}", "size_of_val"], \substs -> case substs of
+    Substs [ty] -> Just $ CustomOp $ \_ ops -> do
+      col <- use $ cs . collection
+      case (ty, ops) of
+        (TySlice elemTy, [MirExp (MirSliceRepr _) e]) ->
+            return $ MirExp UsizeRepr $ R.App $ E.BVMul knownNat (getSliceLen e) (tySize col elemTy)
+        (TyStr, [MirExp (MirSliceRepr _) e]) -> return $ MirExp UsizeRepr $ getSliceLen e
+        (TyDynamic traitName, [MirExp DynRefRepr e]) -> do
+            vtableData <- dynVtableData traitName e
+            return $ MirExp UsizeRepr $ R.App $ E.GetStruct vtableData vtableDataSizeIndex UsizeRepr
+        (_, [MirExp (MirReferenceRepr _) _]) -> return $ MirExp UsizeRepr $ tySize col ty
+        _ -> mirFail $ "bad arguments for intrinsics::size_of_val<" ++ show ty ++ ">: " ++ show ops
+    _ -> Nothing)
+
+min_align_of_val :: (ExplodedDefId, CustomRHS)
+min_align_of_val = (["core", "intrinsics", "{extern}", "min_align_of_val"], \substs -> case substs of
+    Substs [ty] -> Just $ CustomOp $ \_ ops -> do
+      col <- use $ cs . collection
+      case (ty, ops) of
+        (TySlice elemTy, [MirExp (MirSliceRepr _) _]) -> return $ MirExp UsizeRepr $ tyAlign col elemTy
+        (TyStr, [MirExp (MirSliceRepr _) _]) -> return $ MirExp UsizeRepr $ tyAlign col (TyUint B8)
+        (TyDynamic traitName, [MirExp DynRefRepr e]) -> do
+            vtableData <- dynVtableData traitName e
+            return $ MirExp UsizeRepr $ R.App $ E.GetStruct vtableData vtableDataAlignIndex UsizeRepr
+        (_, [MirExp (MirReferenceRepr _) _]) -> return $ MirExp UsizeRepr $ tyAlign col ty
+        _ -> mirFail $ "bad arguments for intrinsics::min_align_of_val<" ++ show ty ++ ">: " ++ show ops
+    _ -> Nothing)
+
 -- mem::swap is used pervasively (both directly and via mem::replace), but it
 -- has a nasty unsafe implementation, with lots of raw pointers and
 -- reintepreting casts.  Fortunately, it requires `T: Sized`, so it's almost
@@ -990,12 +1032,33 @@ mem_transmute = (["core", "intrinsics", "{extern}", "transmute"],
             Some retTy <- tyToReprM tyU
             case testEquality argTy retTy of
                 Just Refl -> return e
-                Nothing -> mirFail $
+                Nothing
+                  | Just e' <- transmuteFloatBits e retTy -> return e'
+                  | otherwise -> mirFail $
                     "representation mismatch in transmute: " ++ show argTy ++ " != " ++ show retTy
         _ -> mirFail $ "bad arguments to transmute: "
           ++ show (tyT, tyU, ops)
       _ -> Nothing)
 
+-- | Reinterpret a float as an integer of the same width, or vice versa.
+-- `f32::to_bits`, `f32::from_bits`, and their `f64` equivalents are
+-- implemented using `transmute`.
+transmuteFloatBits :: MirExp s -> C.TypeRepr tp -> Maybe (MirExp s)
+transmuteFloatBits (MirExp argTy e) retTy = case (argTy, retTy) of
+    (C.FloatRepr C.SingleFloatRepr, C.BVRepr w)
+      | Just Refl <- testEquality w (knownNat @32) ->
+        Just $ MirExp retTy $ R.App $ E.FloatToBinary C.SingleFloatRepr e
+    (C.FloatRepr C.DoubleFloatRepr, C.BVRepr w)
+      | Just Refl <- testEquality w (knownNat @64) ->
+        Just $ MirExp retTy $ R.App $ E.FloatToBinary C.DoubleFloatRepr e
+    (C.BVRepr w, C.FloatRepr C.SingleFloatRepr)
+      | Just Refl <- testEquality w (knownNat @32) ->
+        Just $ MirExp retTy $ R.App $ E.FloatFromBinary C.SingleFloatRepr e
+    (C.BVRepr w, C.FloatRepr C.DoubleFloatRepr)
+      | Just Refl <- testEquality w (knownNat @64) ->
+        Just $ MirExp retTy $ R.App $ E.FloatFromBinary C.DoubleFloatRepr e
+    _ -> Nothing
+
 
 intrinsics_assume :: (ExplodedDefId, CustomRHS)
 intrinsics_assume = (["core", "intrinsics", "{extern}", "assume"], \_substs ->
@@ -1385,6 +1448,14 @@ bv_leading_zeros =
 --------------------------------------------------------------------------------------------------------------------------
 -- crucible::alloc implementation
 
+-- | Record an allocation for leak checks, unless its elements are zero-sized.
+-- Rust never allocates zero-sized values, and `box_free` doesn't free them, so
+-- they can't leak.
+trackNonZeroSized :: Ty -> R.Expr MIR s (MirReferenceType tp) -> MirGenerator h s ret ()
+trackNonZeroSized t ref = do
+    col <- use $ cs . collection
+    unless (isZeroSized col t) $ trackAlloc ref
+
 -- fn allocate<T>(len: usize) -> *mut T
 allocate :: (ExplodedDefId, CustomRHS)
 allocate = (["crucible", "alloc", "allocate"], \substs -> case substs of
@@ -1395,6 +1466,7 @@ allocate = (["crucible", "alloc", "allocate"], \substs -> case substs of
             Some tpr <- tyToReprM t
             vec <- mirVector_uninit tpr len
             ref <- newMirRef (MirVectorRepr tpr)
+            trackNonZeroSized t ref
             writeMirRef ref vec
             -- `subindexRef` doesn't do a bounds check (those happen on deref
             -- instead), so this works even when len is 0.
@@ -1414,6 +1486,7 @@ allocate_zeroed = (["crucible", "alloc", "allocate_zeroed"], \substs -> case sub
             vec <- mirVector_fromVector tpr vec
 
             ref <- newMirRef (MirVectorRepr tpr)
+            trackNonZeroSized t ref
             writeMirRef ref vec
             ptr <- subindexRef tpr ref (R.App $ usizeLit 0)
             return $ MirExp (MirReferenceRepr tpr) ptr
@@ -1442,59 +1515,103 @@ reallocate = (["crucible", "alloc", "reallocate"], \substs -> case substs of
         _ -> mirFail $ "BUG: invalid arguments to reallocate: " ++ show ops
     _ -> Nothing)
 
--- No `deallocate` for now - we'd need some extra MirRef ops to implement that
--- (since we need to get from the first-element pointer to the underlying
--- RefCell that we want to drop).
+-- fn deallocate<T: ?Sized>(ptr: *mut T)
+deallocate :: (ExplodedDefId, CustomRHS)
+deallocate = (["crucible", "alloc", "deallocate"], \substs -> case substs of
+    Substs [_] -> Just $ CustomOp $ \_ ops -> do
+        ptr <- case ops of
+            [MirExp (MirReferenceRepr tpr) ptr] ->
+                return $ R.App $ E.PackAny (MirReferenceRepr tpr) ptr
+            [MirExp (MirSliceRepr tpr) slice] ->
+                return $ R.App $ E.PackAny (MirReferenceRepr tpr) (getSlicePtr slice)
+            -- The data part of a trait object is already an `Any` wrapping
+            -- a `MirReference`.
+            [MirExp DynRefRepr dynRef] ->
+                return $ R.App $ E.GetStruct dynRef dynRefDataIndex C.AnyRepr
+            _ -> mirFail $ "BUG: invalid arguments to deallocate: " ++ show ops
+        deallocMirRef ptr
+        return $ MirExp C.UnitRepr $ R.App E.EmptyApp
+    _ -> Nothing)
 
 
 --------------------------------------------------------------------------------------------------------------------------
 -- Atomic operations
 --
 -- These intrinsics come in many varieties that differ only in memory ordering.
--- We don't support multithreading, so there are no visible differences between
--- orderings, and we can use a single implementation for each group.
+-- The orderings only matter when the weak memory model is enabled (see
+-- `atomicLoad` and friends); otherwise all atomic operations are sequentially
+-- consistent.
 
 -- Make a group of atomic intrinsics.  If `name` is "foo", this generates
 -- overrides for `atomic_foo`, `atomic_foo_variant1`, `atomic_foo_variant2`,
--- etc., all with the same `rhs`.
-makeAtomicIntrinsics :: Text -> [Text] -> CustomRHS -> [(ExplodedDefId, CustomRHS)]
+-- etc.  `rhs` is given the variant, which names the memory orderings, or ""
+-- for `atomic_foo` itself.
+makeAtomicIntrinsics :: Text -> [Text] -> (Text -> CustomRHS) -> [(ExplodedDefId, CustomRHS)]
 makeAtomicIntrinsics name variants rhs =
-    [(["core", "intrinsics", "{extern}", "atomic_" <> name <> suffix], rhs)
-        | suffix <- "" : map ("_" <>) variants]
-
-atomic_store_impl :: CustomRHS
-atomic_store_impl = \_substs -> Just $ CustomOp $ \_ ops -> case ops of
+    [(["core", "intrinsics", "{extern}", "atomic_" <> name <> suffix], rhs variant)
+        | variant <- "" : variants
+        , let suffix = if Text.null variant then "" else "_" <> variant]
+
+-- Parse a memory ordering from an intrinsic variant.  Intrinsics without a
+-- variant are `SeqCst`.
+atomicOrdering :: Text -> AtomicOrdering
+atomicOrdering variant = case variant of
+    "relaxed" -> AtomicRelaxed
+    "release" -> AtomicRelease
+    "acquire" -> AtomicAcquire
+    "acqrel" -> AtomicAcqRel
+    _ -> AtomicSeqCst
+
+-- Parse the success and failure orderings of a compare-exchange variant,
+-- such as "acqrel_relaxed".
+atomicOrderingPair :: Text -> (AtomicOrdering, AtomicOrdering)
+atomicOrderingPair variant = case Text.splitOn "_" variant of
+    [success, failure] -> (atomicOrdering success, atomicOrdering failure)
+    _ -> (AtomicSeqCst, AtomicSeqCst)
+
+atomic_store_impl :: Text -> CustomRHS
+atomic_store_impl variant = \_substs -> Just $ CustomOp $ \_ ops -> case ops of
     [MirExp (MirReferenceRepr tpr) ref, MirExp tpr' val]
       | Just Refl <- testEquality tpr tpr' -> do
-        writeMirRef ref val
+        atomicStore (atomicOrdering variant) tpr ref val
         return $ MirExp C.UnitRepr $ R.App E.EmptyApp
     _ -> mirFail $ "BUG: invalid arguments to atomic_store: " ++ show ops
 
-atomic_load_impl :: CustomRHS
-atomic_load_impl = \_substs -> Just $ CustomOp $ \_ ops -> case ops of
+atomic_load_impl :: Text -> CustomRHS
+atomic_load_impl variant = \_substs -> Just $ CustomOp $ \_ ops -> case ops of
     [MirExp (MirReferenceRepr tpr) ref] ->
-        MirExp tpr <$> readMirRef tpr ref
+        MirExp tpr <$> atomicLoad (atomicOrdering variant) tpr ref
     _ -> mirFail $ "BUG: invalid arguments to atomic_load: " ++ show ops
 
-atomic_cxchg_impl :: CustomRHS
-atomic_cxchg_impl = \_substs -> Just $ CustomOp $ \opTys ops -> case (opTys, ops) of
+atomic_cxchg_impl :: Text -> CustomRHS
+atomic_cxchg_impl variant = \_substs -> Just $ CustomOp $ \opTys ops -> case (opTys, ops) of
     ([_, ty, _], [MirExp (MirReferenceRepr tpr) ref, MirExp tpr' expect, MirExp tpr'' val])
       | Just Refl <- testEquality tpr tpr'
       , Just Refl <- testEquality tpr tpr''
       , C.BVRepr w <- tpr -> do
+        let (succOrd, failOrd) = atomicOrderingPair variant
         old <- readMirRef tpr ref
         let eq = R.App $ E.BVEq w old expect
         let new = R.App $ E.BVIte eq w val old
-        writeMirRef ref new
+        atomicRMW succOrd failOrd tpr ref eq new
         buildTupleMaybeM [ty, TyBool] $
             [Just $ MirExp tpr old, Just $ MirExp C.BoolRepr eq]
     _ -> mirFail $ "BUG: invalid arguments to atomic_cxchg: " ++ show ops
 
-atomic_fence_impl :: CustomRHS
-atomic_fence_impl = \_substs -> Just $ CustomOp $ \_ ops -> case ops of
-    [] -> return $ MirExp C.UnitRepr $ R.App E.EmptyApp
+atomic_fence_impl :: Text -> CustomRHS
+atomic_fence_impl variant = \_substs -> Just $ CustomOp $ \_ ops -> case ops of
+    [] -> do
+        atomicFence (atomicOrdering variant)
+        return $ MirExp C.UnitRepr $ R.App E.EmptyApp
     _ -> mirFail $ "BUG: invalid arguments to atomic_fence: " ++ show ops
 
+-- Compiler fences only restrict reordering within a single thread, which we
+-- never do.
+atomic_singlethreadfence_impl :: Text -> CustomRHS
+atomic_singlethreadfence_impl _variant = \_substs -> Just $ CustomOp $ \_ ops -> case ops of
+    [] -> return $ MirExp C.UnitRepr $ R.App E.EmptyApp
+    _ -> mirFail $ "BUG: invalid arguments to atomic_singlethreadfence: " ++ show ops
+
 -- Common implementation for all atomic read-modify-write operations.  These
 -- all read the value, apply some operation, write the result back, and return
 -- the old value.
@@ -1505,14 +1622,16 @@ atomic_rmw_impl ::
         R.Expr MIR s (C.BVType w) ->
         R.Expr MIR s (C.BVType w) ->
         MirGenerator h s ret (R.Expr MIR s (C.BVType w))) ->
+    Text ->
     CustomRHS
-atomic_rmw_impl name rmw = \_substs -> Just $ CustomOp $ \_ ops -> case ops of
+atomic_rmw_impl name rmw variant = \_substs -> Just $ CustomOp $ \_ ops -> case ops of
     [MirExp (MirReferenceRepr tpr) ref, MirExp tpr' val]
       | Just Refl <- testEquality tpr tpr'
       , C.BVRepr w <- tpr -> do
+        let ord = atomicOrdering variant
         old <- readMirRef tpr ref
         new <- rmw w old val
-        writeMirRef ref new
+        atomicRMW ord ord tpr ref (R.App $ E.BoolLit True) new
         return $ MirExp tpr old
     _ -> mirFail $ "BUG: invalid arguments to atomic_" ++ name ++ ": " ++ show ops
 
@@ -1539,7 +1658,7 @@ atomic_funcs =
     makeAtomicIntrinsics "cxchg" compareExchangeVariants atomic_cxchg_impl ++
     makeAtomicIntrinsics "cxchgweak" compareExchangeVariants atomic_cxchg_impl ++
     makeAtomicIntrinsics "fence" fenceVariants atomic_fence_impl ++
-    makeAtomicIntrinsics "singlethreadfence" fenceVariants atomic_fence_impl ++
+    makeAtomicIntrinsics "singlethreadfence" fenceVariants atomic_singlethreadfence_impl ++
     concat [
         makeAtomicRMW "xchg" $ \w old val -> return val,
         makeAtomicRMW "xadd" $ \w old val -> return $ R.App $ E.BVAdd w old val,
@@ -1568,6 +1687,42 @@ atomic_funcs =
     -- See https://github.com/rust-lang/rust/blob/22b4c688956de0925f7a10a79cb0e1ca35f55425/library/core/src/sync/atomic.rs#L3366-L3370
     fenceVariants = ["acquire", "release", "acqrel", "seqcst"]
 
+-- fn sync_spawn(thid: u32)
+sync_spawn :: (ExplodedDefId, CustomRHS)
+sync_spawn = (["core", "crucible", "concurrency", "sync_spawn"], \_substs ->
+    Just $ CustomOp $ \_ ops -> case ops of
+        [MirExp (C.BVRepr w) thid] | Just Refl <- testEquality w (knownNat @32) -> do
+            syncSpawn thid
+            return $ MirExp C.UnitRepr $ R.App E.EmptyApp
+        _ -> mirFail $ "BUG: invalid arguments to sync_spawn: " ++ show ops)
+
+-- fn sync_join(thid: u32)
+sync_join :: (ExplodedDefId, CustomRHS)
+sync_join = (["core", "crucible", "concurrency", "sync_join"], \_substs ->
+    Just $ CustomOp $ \_ ops -> case ops of
+        [MirExp (C.BVRepr w) thid] | Just Refl <- testEquality w (knownNat @32) -> do
+            syncJoin thid
+            return $ MirExp C.UnitRepr $ R.App E.EmptyApp
+        _ -> mirFail $ "BUG: invalid arguments to sync_join: " ++ show ops)
+
+-- fn sync_acquire<T>(x: *const T)
+sync_acquire :: (ExplodedDefId, CustomRHS)
+sync_acquire = (["core", "crucible", "concurrency", "sync_acquire"], \_substs ->
+    Just $ CustomOp $ \_ ops -> case ops of
+        [MirExp (MirReferenceRepr _) ref] -> do
+            syncLock False ref
+            return $ MirExp C.UnitRepr $ R.App E.EmptyApp
+        _ -> mirFail $ "BUG: invalid arguments to sync_acquire: " ++ show ops)
+
+-- fn sync_release<T>(x: *const T)
+sync_release :: (ExplodedDefId, CustomRHS)
+sync_release = (["core", "crucible", "concurrency", "sync_release"], \_substs ->
+    Just $ CustomOp $ \_ ops -> case ops of
+        [MirExp (MirReferenceRepr _) ref] -> do
+            syncLock True ref
+            return $ MirExp C.UnitRepr $ R.App E.EmptyApp
+        _ -> mirFail $ "BUG: invalid arguments to sync_release: " ++ show ops)
+
 --------------------------------------------------------------------------------------------------------------------------
 
 unlikely :: (ExplodedDefId, CustomRHS)
@@ -1604,6 +1759,48 @@ ctpop = (["core", "intrinsics", "{extern}", "ctpop"],
         _ -> Nothing)
 
 
+-----------------------------------------------------------------------------------------------------
+-- ** Custom: float intrinsics
+
+float_funcs :: [(ExplodedDefId, CustomRHS)]
+float_funcs =
+    [ float_unop "fabsf32" E.FloatAbs
+    , float_unop "fabsf64" E.FloatAbs
+    , float_unop "sqrtf32" (\fi -> E.FloatSqrt fi E.RNE)
+    , float_unop "sqrtf64" (\fi -> E.FloatSqrt fi E.RNE)
+    , float_binop "minnumf32" E.FloatMin
+    , float_binop "minnumf64" E.FloatMin
+    , float_binop "maxnumf32" E.FloatMax
+    , float_binop "maxnumf64" E.FloatMax
+    ]
+
+type FloatUnOp = forall ext f fi.
+        C.FloatInfoRepr fi
+        -> f (C.FloatType fi)
+        -> E.App ext f (C.FloatType fi)
+
+type FloatBinOp = forall ext f fi.
+        C.FloatInfoRepr fi
+        -> f (C.FloatType fi)
+        -> f (C.FloatType fi)
+        -> E.App ext f (C.FloatType fi)
+
+float_unop :: Text -> FloatUnOp -> (ExplodedDefId, CustomRHS)
+float_unop name op = (["core", "intrinsics", "{extern}", name], \_substs ->
+    Just $ CustomOp $ \_ ops -> case ops of
+        [MirExp tpr@(C.FloatRepr fi) e] ->
+            return $ MirExp tpr $ R.App $ op fi e
+        _ -> mirFail $ "bad arguments to intrinsics::" ++ Text.unpack name ++ ": " ++ show ops)
+
+float_binop :: Text -> FloatBinOp -> (ExplodedDefId, CustomRHS)
+float_binop name op = (["core", "intrinsics", "{extern}", name], \_substs ->
+    Just $ CustomOp $ \_ ops -> case ops of
+        [MirExp tpr@(C.FloatRepr fi) e1, MirExp (C.FloatRepr fi') e2]
+          | Just Refl <- testEquality fi fi' ->
+            return $ MirExp tpr $ R.App $ op fi e1 e2
+        _ -> mirFail $ "bad arguments to intrinsics::" ++ Text.unpack name ++ ": " ++ show ops)
+
+
 --------------------------------------------------------------------------------------------------------------------------
 -- Implementation for `IkFnPtrShim`.  Function pointer shims are auto-generated
 -- `Fn`/`FnMut`/`FnOnce` methods for `TyFnDef` and `TyFnPtr`, allowing ordinary
@@ -56,13 +56,13 @@ import qualified Debug.Trace as Debug
 
 import           Mir.Generator
     ( MirExp(..), MirPlace(..), PtrMetadata(..), MirGenerator, mirFail
-    , subanyRef, subfieldRef, subvariantRef, subjustRef
+    , subanyRef, subunionRef, subfieldRef, subvariantRef, subjustRef
     , mirVector_fromVector
     , cs, collection, discrMap, findAdt, mirVector_uninit, arrayZeroed )
 import           Mir.Intrinsics
     ( MIR, pattern MirSliceRepr, pattern MirReferenceRepr, MirReferenceType
     , pattern MirVectorRepr
-    , SizeBits, pattern UsizeRepr, pattern IsizeRepr
+    , SizeBits, UsizeType, pattern UsizeRepr, pattern IsizeRepr
     , isizeLit
     , RustEnumType, pattern RustEnumRepr, SomeRustEnumRepr(..)
     , mkRustEnum, rustEnumVariant, rustEnumDiscriminant
@@ -97,6 +97,13 @@ baseSizeToNatCont M.B64  k = k (knownNat :: NatRepr 64)
 baseSizeToNatCont M.B128 k = k (knownNat :: NatRepr 128)
 baseSizeToNatCont M.USize k = k (knownNat :: NatRepr SizeBits)
 
+-- | Convert a float kind to the corresponding Crucible float representation.
+-- Whether the resulting `FloatType` has IEEE-754 or real-number semantics is
+-- decided later, by the what4 float mode chosen for the symbolic backend.
+floatKindToInfoCont :: M.FloatKind -> (forall fi. C.FloatInfoRepr fi -> a) -> a
+floatKindToInfoCont M.F32 k = k C.SingleFloatRepr
+floatKindToInfoCont M.F64 k = k C.DoubleFloatRepr
+
 
 -- Custom type aliases
 pattern CTyInt512 <- M.TyAdt _ $(M.explodedDefIdPat ["int512", "Int512"]) (M.Substs [])
@@ -107,8 +114,9 @@ pattern CTyMaybeUninit t <- M.TyAdt _ $(M.explodedDefIdPat ["core", "mem", "mayb
 maybeUninitExplodedDefId :: M.ExplodedDefId
 maybeUninitExplodedDefId = ["core", "mem", "maybe_uninit", "MaybeUninit"]
 
--- `UnsafeCell` isn't handled specially inside baseline `crucible-mir`, but
--- `crux-mir-comp` looks for it (using this pattern synonym).
+-- `UnsafeCell` is only handled specially by the aliasing checks (see
+-- `containsUnsafeCell`), but `crux-mir-comp` also looks for it (using this
+-- pattern synonym).
 pattern CTyUnsafeCell t <- M.TyAdt _ $(M.explodedDefIdPat ["core", "cell", "UnsafeCell"]) (M.Substs [t])
 
 pattern CTyVector t <- M.TyAdt _ $(M.explodedDefIdPat ["crucible", "vector", "Vector"]) (M.Substs [t])
@@ -214,7 +222,7 @@ tyToRepr col t0 = case t0 of
     | otherwise -> Some C.AnyRepr
   M.TyDowncast _adt _i   -> Some C.AnyRepr
 
-  M.TyFloat _ -> Some C.RealValRepr
+  M.TyFloat fk -> floatKindToInfoCont fk $ \fi -> Some (C.FloatRepr fi)
 
   -- non polymorphic function types go to FunctionHandleRepr
   M.TyFnPtr sig@(M.FnSig args ret _abi _spread) ->
@@ -222,10 +230,10 @@ tyToRepr col t0 = case t0 of
      tyToReprCont col ret $ \retr ->
         Some (C.FunctionHandleRepr argsr retr)
 
-  -- We don't support unsized rvalues.  Currently we error only for standalone
-  -- standalone (i.e., not under `TyRef`/`TyRawPtr`) use of `TyDynamic` - we
-  -- should do the same for TySlice and TyStr as well.
-  M.TyDynamic _trait -> error $ unwords ["standalone use of `dyn` is not supported:", show t0]
+  -- An unsized `dyn Tr` value is represented by its fat pointer.  This only
+  -- arises when a `dyn` value is moved out of its box to call a by-value
+  -- method, as in `Box<dyn FnOnce()>`; see `readPlace`.
+  M.TyDynamic _trait -> Some DynRefRepr
 
   M.TyFnDef _def -> Some C.UnitRepr
   M.TyNever -> Some C.AnyRepr
@@ -1031,6 +1039,32 @@ enumFieldRef adt i j tpr ref = do
     return $ MirPlace (fieldDataType fld) ref NoMeta
 
 
+-- | A union is represented as an `Any` containing the value of whichever field
+-- was written last.  Reading a field at a different type than the one stored
+-- fails at simulation time; reinterpreting the bytes of one field as another
+-- is not supported.
+unionFieldRef ::
+    M.Adt -> Int ->
+    C.TypeRepr tp -> R.Expr MIR s (MirReferenceType tp) ->
+    MirGenerator h s ret (MirPlace s)
+unionFieldRef adt i tpr ref = do
+    fldTy <- case M.onlyVariant adt ^? M.vfields . ix i . M.fty of
+        Just x -> return x
+        Nothing -> mirFail $ "field index " ++ show i ++ " is out of range for union " ++
+            show (adt ^. M.adtname)
+    Some fldTpr <- tyToReprM fldTy
+    Refl <- testEqualityOrFail tpr C.AnyRepr $
+        "unionFieldRef: bad referent type: expected Any, but got " ++ show tpr
+    ref <- subunionRef fldTpr ref
+    return $ MirPlace fldTpr ref NoMeta
+
+-- | Build a union from the value of its initialized field.  Rust union
+-- expressions initialize exactly one field.
+buildUnion :: M.Adt -> [MirExp s] -> MirGenerator h s ret (MirExp s)
+buildUnion _ [MirExp tpr e] = buildAnyE tpr e
+buildUnion adt es = mirFail $ "expected exactly one field for union " ++
+    show (adt ^. M.adtname) ++ ", but got " ++ show (length es)
+
 enumDiscriminant :: M.Adt -> MirExp s ->
     MirGenerator h s ret (MirExp s)
 enumDiscriminant adt e = do
@@ -1083,7 +1117,100 @@ traitVtableType col tname trait = vtableTy
     shimSigs = map convertShimSig methodSigs
 
     vtableTy = tyListToCtx col (map M.TyFnPtr shimSigs) $ \ctx ->
-        Some $ C.StructRepr ctx
+        Some $ C.StructRepr (ctx Ctx.:> vtableDataRepr)
+
+-- | The type of the last entry of every vtable, which describes the trait
+-- object's data rather than one of its methods: how to drop it, and its size
+-- and alignment.  See `mkTraitObject`.
+type VtableDataCtx = Ctx.EmptyCtx Ctx.::> VtableDropType Ctx.::> UsizeType Ctx.::> UsizeType
+
+vtableDataRepr :: C.TypeRepr (C.StructType VtableDataCtx)
+vtableDataRepr = C.StructRepr (Ctx.empty Ctx.:> vtableDropRepr Ctx.:> UsizeRepr Ctx.:> UsizeRepr)
+
+-- | A closure that runs the drop glue on the data, or `Nothing` if its type
+-- has no drop glue.
+vtableDataDropIndex :: Ctx.Index VtableDataCtx VtableDropType
+vtableDataDropIndex = Ctx.i1of3
+
+vtableDataSizeIndex :: Ctx.Index VtableDataCtx UsizeType
+vtableDataSizeIndex = Ctx.i2of3
+
+vtableDataAlignIndex :: Ctx.Index VtableDataCtx UsizeType
+vtableDataAlignIndex = Ctx.i3of3
+
+type VtableDropType = C.MaybeType (C.FunctionHandleType Ctx.EmptyCtx C.UnitType)
+
+vtableDropRepr :: C.TypeRepr VtableDropType
+vtableDropRepr = C.MaybeRepr vtableDropFnRepr
+
+vtableDropFnRepr :: C.TypeRepr (C.FunctionHandleType Ctx.EmptyCtx C.UnitType)
+vtableDropFnRepr = C.FunctionHandleRepr Ctx.empty C.UnitRepr
+
+-- | The size of a sized type, as reported by `mem::size_of_val` and stored in
+-- vtables.
+tySize :: M.Collection -> M.Ty -> R.Expr MIR s UsizeType
+tySize col ty = R.App $ usizeLit $ fst $ tyLayout col ty
+
+-- | The alignment of a sized type, as reported by `mem::align_of_val` and
+-- stored in vtables.
+tyAlign :: M.Collection -> M.Ty -> R.Expr MIR s UsizeType
+tyAlign col ty = R.App $ usizeLit $ snd $ tyLayout col ty
+
+-- | The size and alignment of a sized type, in bytes.  Structs and enums take
+-- their size from mir-json; it doesn't record their alignment, so that's the
+-- largest alignment of their fields (and discriminant), capped at the largest
+-- power of two dividing the size.  Tuples and closures are laid out the way
+-- rustc does, with their fields reordered to avoid padding.  Pointers and
+-- `usize` take 8 bytes, as on the 64-bit targets that mir-json normally
+-- translates for.
+tyLayout :: M.Collection -> M.Ty -> (Integer, Integer)
+tyLayout col = go
+  where
+    go ty = case ty of
+      M.TyBool -> (1, 1)
+      M.TyChar -> (4, 4)
+      M.TyInt sz -> baseSize sz
+      M.TyUint sz -> baseSize sz
+      M.TyFloat M.F32 -> (4, 4)
+      M.TyFloat M.F64 -> (8, 8)
+      M.TyRef ty' _ -> pointer ty'
+      M.TyRawPtr ty' _ -> pointer ty'
+      M.TyFnPtr _ -> (8, 8)
+      M.TyTuple tys -> aggregate (map go tys)
+      M.TyClosure tys -> aggregate (map go tys)
+      M.TyArray ty' n -> let (size, align) = go ty' in (size * toInteger n, align)
+      M.TyAdt name _ _ | Just adt <- col ^? M.adts . ix name -> adtLayout adt
+      -- Function items and `!` are zero-sized, and unsized types have no
+      -- static size.
+      _ -> (0, 1)
+
+    -- 128-bit integers are 8-byte aligned, as on x86-64.
+    baseSize sz = case sz of
+      M.B8 -> (1, 1)
+      M.B16 -> (2, 2)
+      M.B32 -> (4, 4)
+      M.B64 -> (8, 8)
+      M.B128 -> (16, 8)
+      M.USize -> (8, 8)
+
+    pointer ty' = if isUnsized ty' then (16, 8) else (8, 8)
+
+    aggregate layouts =
+      let align = maximum (1 : map snd layouts)
+      in (roundUp align (sum (map fst layouts)), align)
+
+    roundUp align size = (size + align - 1) `div` align * align
+
+    adtLayout adt =
+      let size = toInteger (adt ^. M.adtSize)
+          discr = case adt ^. M.adtkind of
+            M.Enum discrTy -> [go discrTy]
+            _ -> []
+          fields = [go (f ^. M.fty) | v <- adt ^. M.adtvariants, f <- v ^. M.vfields]
+          align = maximum (1 : map snd (discr ++ fields))
+      in (size, if size > 0 then min align (largestPow2Divisor size) else align)
+
+    largestPow2Divisor n = if even n then 2 * largestPow2Divisor (n `div` 2) else 1
 
 eraseSigReceiver :: M.FnSig -> M.FnSig
 eraseSigReceiver sig = sig & M.fsarg_tys %~ \xs -> case xs of
@@ -1226,7 +1353,10 @@ initialValue (M.TyAdt nm _ _) = do
                 Just (discr, var) -> do
                     fldExps <- mapM initField (var^.M.vfields)
                     Just <$> buildEnum' adt discr fldExps
-        M.Union -> return Nothing
+        -- A union starts out with no active field, so any field read before
+        -- the first write will fail.
+        M.Union -> return $ Just $ MirExp C.AnyRepr $
+            R.App $ E.PackAny C.UnitRepr $ R.App E.EmptyApp
 
 
 
@@ -341,7 +341,15 @@ pub(crate) const unsafe fn box_free<T: ?Sized, A: ~const Allocator + ~const Dest
     ptr: Unique<T>,
     alloc: A,
 ) {
-    // Crucible: currently we don't implement `deallocate`, so this is a no-op.
+    unsafe {
+        // Crucible: use the Crucible allocator, as in `Box::new`.  As upstream,
+        // skip zero-sized values.  Some, like the empty slice from
+        // `Vec::new().into_boxed_slice()`, were never allocated; the rest hold
+        // no data, and leak checks ignore them.
+        if size_of_val(ptr.as_ref()) != 0 {
+            crucible::alloc::deallocate(ptr.as_ptr());
+        }
+    }
 }
 
 // # Allocation error handler
@@ -427,7 +427,11 @@ impl<T, A: Allocator> RawVec<T, A> {
 unsafe impl<#[may_dangle] T, A: Allocator> Drop for RawVec<T, A> {
     /// Frees the memory owned by the `RawVec` *without* trying to drop its contents.
     fn drop(&mut self) {
-        // Crucible currently doesn't provide a deallocate operation.
+        // Crucible: `allocate_in` and the `grow` methods use the Crucible allocator, and don't
+        // allocate when `cap` is 0.
+        if !T::IS_ZST && self.cap != 0 {
+            crucible::alloc::deallocate(self.ptr.as_ptr());
+        }
     }
 }
 
@@ -1597,7 +1597,9 @@ unsafe impl<#[may_dangle] T: ?Sized> Drop for Rc<T> {
                 self.inner().dec_weak();
 
                 if self.inner().weak() == 0 {
-                    Global.deallocate(self.ptr.cast(), Layout::for_value(self.ptr.as_ref()));
+                    // Crucible: `Rc::new` allocates with `Box::new`, which uses the Crucible
+                    // allocator.
+                    crucible::alloc::deallocate(self.ptr.as_ptr());
                 }
             }
         }
@@ -2531,9 +2533,8 @@ unsafe impl<#[may_dangle] T: ?Sized> Drop for Weak<T> {
         // the weak count starts at 1, and will only go to zero if all
         // the strong pointers have disappeared.
         if inner.weak() == 0 {
-            unsafe {
-                Global.deallocate(self.ptr.cast(), Layout::for_value_raw(self.ptr.as_ptr()));
-            }
+            // Crucible: see `Rc::drop`.
+            crucible::alloc::deallocate(self.ptr.as_ptr());
         }
     }
 }
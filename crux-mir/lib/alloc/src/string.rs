@@ -3005,3 +3005,19 @@ impl From<char> for String {
         c.to_string()
     }
 }
+
+/// Produces an ASCII string of up to `max_len` bytes.
+impl crucible::SymbolicBounded for String {
+    fn symbolic_bounded(desc: &'static str, max_len: usize) -> String {
+        let len = <usize as crucible::Symbolic>::symbolic_where(desc, |&n| n <= max_len);
+        let mut bytes = Vec::with_capacity(max_len);
+        for i in 0..max_len {
+            let b = <u8 as crucible::Symbolic>::symbolic_where(desc, |&b| b < 0x80);
+            if i < len {
+                bytes.push(b);
+            }
+        }
+        // SAFETY: every byte is ASCII, so the buffer is valid UTF-8.
+        unsafe { String::from_utf8_unchecked(bytes) }
+    }
+}
@@ -2247,7 +2247,8 @@ unsafe impl<#[may_dangle] T: ?Sized> Drop for Weak<T> {
 
         if inner.weak.fetch_sub(1, Release) == 1 {
             acquire!(inner.weak);
-            // Crucible: we should deallocate `self.ptr` here, but we don't support `deallocate` yet.
+            // Crucible: `Arc::new` allocates with `Box::new`, which uses the Crucible allocator.
+            crucible::alloc::deallocate(self.ptr.as_ptr());
         }
     }
 }
@@ -3304,3 +3304,19 @@ impl<T, A: Allocator, const N: usize> TryFrom<Vec<T, A>> for [T; N] {
         Ok(array)
     }
 }
+
+impl<T: crucible::Symbolic> crucible::SymbolicBounded for Vec<T> {
+    fn symbolic_bounded(desc: &'static str, max_len: usize) -> Vec<T> {
+        let len = <usize as crucible::Symbolic>::symbolic_where(desc, |&n| n <= max_len);
+        // Reserve the full capacity up front, so the pushes below never reallocate under a
+        // symbolic branch.
+        let mut v = Vec::with_capacity(max_len);
+        for i in 0..max_len {
+            let x = T::symbolic(desc);
+            if i < len {
+                v.push(x);
+            }
+        }
+        v
+    }
+}
@@ -17,11 +17,71 @@ pub fn mutex_lock<T>(x: *const T) {}
 #[unstable(feature = "crucible_intrinsics", issue = "none")]
 pub fn mutex_unlock<T>(x: *const T) {}
 
+// Signal the acquisition of a shared (read) lock on the reader-writer lock `x`.
+#[unstable(feature = "crucible_intrinsics", issue = "none")]
+pub fn rwlock_read<T>(x: *const T) {}
+
+// Signal the release of a shared (read) lock on the reader-writer lock `x`.
+#[unstable(feature = "crucible_intrinsics", issue = "none")]
+pub fn rwlock_read_unlock<T>(x: *const T) {}
+
+// Signal the acquisition of the exclusive (write) lock on the reader-writer
+// lock `x`.
+#[unstable(feature = "crucible_intrinsics", issue = "none")]
+pub fn rwlock_write<T>(x: *const T) {}
+
+// Signal the release of the exclusive (write) lock on the reader-writer lock
+// `x`.
+#[unstable(feature = "crucible_intrinsics", issue = "none")]
+pub fn rwlock_write_unlock<T>(x: *const T) {}
+
+// Block until the condition variable `cv` is notified. The caller must already
+// have released `mutex` (with `mutex_unlock`), and should reacquire it
+// afterward. With `--concurrency`, the scheduler intercepts this call, so the
+// body only runs in single-threaded mode.
+#[unstable(feature = "crucible_intrinsics", issue = "none")]
+pub fn cond_wait<C, M>(cv: *const C, mutex: *const M) {
+    panic!("deadlock: no thread is left to notify the condvar")
+}
+
+// Wake up every thread waiting on the condition variable `cv`.
+#[unstable(feature = "crucible_intrinsics", issue = "none")]
+pub fn cond_notify<T>(cv: *const T) {}
+
+// With race detection enabled, let the current thread see everything that
+// was released through the lock `x`.  Call this after acquiring the lock.
+// Overridden in crucible-mir.
+#[unstable(feature = "crucible_intrinsics", issue = "none")]
+pub fn sync_acquire<T>(x: *const T) {}
+
+// With race detection enabled, release everything the current thread has
+// seen through the lock `x`.  Call this before releasing the lock.
+// Overridden in crucible-mir.
+#[unstable(feature = "crucible_intrinsics", issue = "none")]
+pub fn sync_release<T>(x: *const T) {}
+
 // Signal to block until thread `thid` has terminated.
+fn join_internal<T>(thid : u64) -> T {
+    panic!("crucible::concurrency::join_internal should never be executed!")
+}
+
+// Block until thread `thid` has terminated, and return its result.
 pub fn join<T>(thid : u64) -> T {
-    panic!("crucible::concurrency::join should never be executed!")
+    let x = join_internal(thid);
+    sync_join(thid as u32);
+    x
 }
 
+// With weak memory or race detection enabled, let the newly spawned thread
+// `thid` see everything the current thread has seen.  Overridden in
+// crucible-mir.
+fn sync_spawn(thid: u32) {}
+
+// With weak memory or race detection enabled, let the current thread see
+// everything the terminated thread `thid` has seen.  Overridden in
+// crucible-mir.
+fn sync_join(thid: u32) {}
+
 fn thread_exit<T>(f:T) {}
 
 /**
@@ -58,5 +118,7 @@ where
     F: Send + 'static,
     T: Send + 'static,
 {
-    spawn_internal(f)
+    let thid = spawn_internal(f);
+    sync_spawn(thid);
+    thid
 }
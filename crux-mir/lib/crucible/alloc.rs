@@ -14,3 +14,11 @@ pub fn allocate_zeroed<T>(len: usize) -> *mut T {
 pub fn reallocate<T>(ptr: *mut T, new_len: usize) {
     unimplemented!("reallocate")
 }
+
+/// Deallocate the array at `*ptr`, which must have been returned by `allocate` or
+/// `allocate_zeroed`.  Any later access through a pointer into the array is reported as an error,
+/// as is deallocating it a second time.  `ptr` may also be a slice or trait object pointer to the
+/// start of an allocation.
+pub const fn deallocate<T: ?Sized>(ptr: *mut T) {
+    unimplemented!("deallocate")
+}
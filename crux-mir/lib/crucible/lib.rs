@@ -18,8 +18,17 @@ pub mod symbolic;
 #[doc(hidden)] pub use core::crucible::ptr;
 #[doc(hidden)] pub mod vector;
 
-// Re-export the `Symbolic` trait, which is used to create symbolic values.
-pub use self::symbolic::Symbolic;
+// Re-export the `Symbolic` traits and derive macro, which are used to create symbolic values.
+pub use self::symbolic::{Symbolic, SymbolicBounded};
+pub use crucible_derive::Symbolic;
+
+// Re-export the function contract macros.  `#[crucible::contract]` reads the `#[crux::requires]`,
+// `#[crux::ensures]`, and `#[crux::modifies]` attributes of a function.
+pub use crucible_derive::{contract, use_contract};
+
+// Re-export the attribute for unwinding bounds.  `#[crucible::unwind(N)]` bounds the loops of a
+// function (or of a test, and everything it calls); `loop_unwind!(N)` bounds a single loop.
+pub use crucible_derive::unwind;
 
 /// Assert that a condition holds.  During symbolic testing, `crux-mir` will search for an
 /// assignment to the symbolic variables that violates an assertion.
@@ -111,6 +120,73 @@ macro_rules! crucible_assume_unreachable {
 }
 
 
+/// State an invariant of the enclosing loop, so that `crux-mir` can verify the loop for any number
+/// of iterations instead of unrolling it.
+///
+/// The first time execution reaches the invariant, `crux-mir` checks that `cond` holds.  It then
+/// gives every variable that the loop may modify an arbitrary value that satisfies `cond`, so the
+/// rest of the path stands for an arbitrary iteration.  If that iteration comes back around to the
+/// invariant, `crux-mir` checks that `cond` still holds and ends the path there; if it leaves the
+/// loop, execution continues after the loop with whatever the invariant says about the state.
+///
+/// ```ignore
+/// let mut i = 0;
+/// let mut sum = 0;
+/// while i < n {
+///     loop_invariant!(i < n && sum == 2 * i);
+///     sum += 2;
+///     i += 1;
+/// }
+/// crucible_assert!(sum == 2 * n);
+/// ```
+///
+/// The invariant should be placed at the start of the loop body, and must say everything about
+/// the modified variables that the rest of the function relies on.  Each modified variable (or
+/// place modified through a reference) must have a type made of integers, `bool`s, `char`s,
+/// floats, and tuples, arrays, and structs of these.  `for` loops keep their position in a hidden
+/// iterator, which the invariant can't mention, so they're better written as `while` loops.
+///
+/// A function called inside the loop may modify what a `&mut` argument points to, but its
+/// arguments can't otherwise hold mutable references, raw pointers, or cells, and it can't use a
+/// `static mut`, since then there's no telling which places the loop modifies.
+#[macro_export]
+macro_rules! loop_invariant {
+    ($cond:expr) => {{
+        $crate::crucible_assert!($cond, concat!("loop invariant does not hold: ", stringify!($cond)));
+        $crate::loop_invariant_cut();
+        $crate::crucible_assume!($cond);
+    }};
+}
+
+/// Internal implementation detail of `loop_invariant!`.  The translation cuts the loop at each call
+/// to this function.
+#[doc(hidden)]
+pub fn loop_invariant_cut() {}
+
+
+/// Bound the number of iterations of the enclosing loop.  If the loop goes around more than `n`
+/// times, `crux-mir` reports an unwinding assertion failure at the loop, rather than unrolling it
+/// further.  With `loop_unwind!(n, assume)`, it instead assumes that the loop runs at most `n`
+/// times, and drops the paths where it runs longer.
+///
+/// This must be the first statement of the loop body, and `n` must be an integer literal.  It
+/// takes precedence over any `#[crucible::unwind]` bound on the function or test.
+#[macro_export]
+macro_rules! loop_unwind {
+    ($n:literal) => {
+        $crate::unwind_bound($n, false)
+    };
+    ($n:literal, assume) => {
+        $crate::unwind_bound($n, true)
+    };
+}
+
+/// Internal implementation detail of `loop_unwind!` and `#[crucible::unwind]`.  The translation
+/// reads the bound from each call to this function.
+#[doc(hidden)]
+pub fn unwind_bound(_n: u64, _assume: bool) {}
+
+
 /// Given a symbolic value, choose an arbitrary instance that satisfies the current path condition.
 /// This function operates recursively: a call to `concretize(&(x, y))` (where `x` and `y` are
 /// symbolic integers) will produce a concrete result like `&(1, 2)`.
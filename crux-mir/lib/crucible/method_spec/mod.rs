@@ -2,8 +2,11 @@
 //! `MethodSpecBuilder`, which is used internally by the compositional reasoning macros to
 //! construct a `MethodSpec` from a symbolic test.
 //!
-//! Note that these types work only when running under `crux-mir-comp`.  Trying to use them under
-//! ordinary `crux-mir` will produce an error.
+//! A spec function creates symbolic arguments and assumes the function's preconditions, then calls
+//! `add_arg` for each argument and `gather_assumes`.  It then calls the function, asserts the
+//! postconditions about its result, and calls `set_return` and `gather_asserts`.  Running the spec
+//! function as a test checks the function against the spec, and enabling the resulting
+//! `MethodSpec` makes later calls in the same test use the spec instead of the function.
 use core::fmt;
 
 mod raw;
@@ -1,11 +1,10 @@
 //! Bindings for low-level MethodSpec APIs.
 //!
 //! Like most functions in the `crucible` crate, these functions are left unimplemented in Rust
-//! and are replaced by a real implementation via the Crucible override mechanism.  However, unlike
-//! most other functions, the necessary overrides are not provided by `crux-mir`; instead, they are
-//! provided by the `crux-mir-comp` package in the `saw-script` repository, which extends ordinary
-//! `crux-mir` with additional overrides using `crux-mir`'s new `mainWithExtraOverrides` entry
-//! point.  Trying to use these APIs under ordinary `crux-mir` will produce an error.
+//! and are replaced by a real implementation via the Crucible override mechanism.  `crux-mir`
+//! provides the overrides in `Mir.MethodSpec`.  The `crux-mir-comp` package in the `saw-script`
+//! repository replaces them with its own, based on SAW's `MethodSpec`, using `crux-mir`'s
+//! `mainWithExtraOverrides` entry point.
 
 /// Crucible `MethodSpecType`, exposed to Rust.
 ///
@@ -34,11 +33,9 @@ pub fn spec_enable(ms: MethodSpec) {
 pub struct MethodSpecBuilder(u8);
 
 pub fn builder_new<F>() -> MethodSpecBuilder {
-    // If the program is running under ordinary `crux-mir` instead of `crux-mir-comp`, then the
-    // override for this function will be missing.  We could provide a dummy implementation for
-    // that case, but it's better to fail early.  Otherwise users will get cryptic errors when
-    // invoking their spec functions, as `builder_finish` won't have its usual effect of discarding
-    // any new goals added by the spec function.
+    // If the override for this function is missing, fail early.  Otherwise users will get
+    // cryptic errors when invoking their spec functions, as the other functions here would do
+    // nothing.
     unimplemented!("MethodSpecBuilder is not supported on this version of crux-mir")
 }
 
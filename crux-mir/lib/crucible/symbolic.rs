@@ -82,28 +82,43 @@ impl Symbolic for bool {
     }
 }
 
-
-macro_rules! array_impls {
-    ($($size:expr)*) => {
+macro_rules! float_impls {
+    ($($ty:ty, $func:ident;)*) => {
         $(
-            impl<T: Symbolic + Copy> Symbolic for [T; $size] {
-                fn symbolic(desc: &'static str) -> [T; $size] {
-                    let mut arr = [T::symbolic(desc); $size];
-                    for i in 1 .. $size {
-                        arr[i] = T::symbolic(desc);
-                    }
-                    arr
-                }
+            /// Hook for a crucible override that creates a symbolic instance of $ty.  The result
+            /// may be any value of the type, including NaN and the infinities.
+            #[allow(unused)]
+            fn $func(desc: &'static str) -> $ty { unimplemented!(stringify!($func)); }
+
+            impl Symbolic for $ty {
+                fn symbolic(desc: &'static str) -> $ty { $func(desc) }
             }
         )*
     };
 }
 
-array_impls! {
-    0 1 2 3 4 5 6 7 8 9
-    10 11 12 13 14 15 16 17 18 19
-    20 21 22 23 24 25 26 27 28 29
-    30 31 32
+float_impls! {
+    f32, symbolic_f32;
+    f64, symbolic_f64;
+}
+
+/// Hook for a crucible override that returns the name `desc_field`, for the part `field` of a
+/// symbolic value named `desc`.  `#[derive(Symbolic)]` uses this to name the fields of a value.
+#[doc(hidden)]
+#[allow(unused)]
+pub fn sub_name(desc: &'static str, field: &'static str) -> &'static str {
+    unimplemented!("sub_name");
+}
+
+
+impl<T: Symbolic + Copy, const N: usize> Symbolic for [T; N] {
+    fn symbolic(desc: &'static str) -> [T; N] {
+        let mut arr = [T::symbolic(desc); N];
+        for i in 1 .. N {
+            arr[i] = T::symbolic(desc);
+        }
+        arr
+    }
 }
 
 macro_rules! tuple_impls {
@@ -137,6 +152,42 @@ tuple_impls! {
     A B C D E F G H I J K L;
 }
 
+impl Symbolic for char {
+    fn symbolic(desc: &'static str) -> char {
+        // Exclude the surrogate range and anything past the last Unicode scalar value.
+        let val = u32::symbolic_where(desc, |&x| x < 0xd800 || (0xe000 <= x && x < 0x110000));
+        unsafe { char::from_u32_unchecked(val) }
+    }
+}
+
+impl<T: Symbolic> Symbolic for Option<T> {
+    fn symbolic(desc: &'static str) -> Option<T> {
+        if bool::symbolic(desc) {
+            Some(T::symbolic(desc))
+        } else {
+            None
+        }
+    }
+}
+
+impl<T: Symbolic, E: Symbolic> Symbolic for Result<T, E> {
+    fn symbolic(desc: &'static str) -> Result<T, E> {
+        if bool::symbolic(desc) {
+            Ok(T::symbolic(desc))
+        } else {
+            Err(E::symbolic(desc))
+        }
+    }
+}
+
+
+/// Symbolic values of variable-length types, such as `Vec<T>` and `String`.  The impls live in
+/// `alloc`, which depends on this crate.
+pub trait SymbolicBounded: Sized {
+    /// Create a new symbolic value whose length can be anywhere in the range `0 ..= max_len`.
+    fn symbolic_bounded(desc: &'static str, max_len: usize) -> Self;
+}
+
 
 /// Take a symbolic-length prefix of `xs`.  The length of the returned slice can be anywhere in the
 /// range `0 ..= xs.len()`.
@@ -0,0 +1,333 @@
+//! `#[contract]`, which turns the `#[crux::requires]`, `#[crux::ensures]` and `#[crux::modifies]`
+//! attributes of a function into a test that checks the function against them, and a replacement
+//! for the function that `use_contract!` installs in its place.
+//!
+//! For a function `f`, the test `f_contract` calls `f` on symbolic arguments, assuming each
+//! `requires` and asserting each `ensures`.  The replacement `__crux_contract_f` does the reverse:
+//! it asserts each `requires` of the actual arguments, gives the return value and each place
+//! listed in `modifies` a fresh symbolic value, and assumes each `ensures`.  `use_contract!(f)`
+//! installs the replacement with `crucible::override_`.
+//!
+//! In an `ensures`, `result` is the return value, and `old(e)` is the value of `e` before the call.
+
+use proc_macro::{Delimiter, Group, Ident, Span, TokenStream, TokenTree};
+
+use crate::{is_group, is_ident, is_punct, skip_visibility, split_commas, tokens_to_string};
+
+struct ContractFn {
+    name: String,
+    vis: String,
+    is_unsafe: bool,
+    params: Vec<Param>,
+    /// `None` if the function returns `()`.
+    ret: Option<String>,
+    /// The function item itself, without the contract attributes.
+    item: Vec<TokenTree>,
+    requires: Vec<TokenStream>,
+    ensures: Vec<TokenStream>,
+    modifies: Vec<String>,
+}
+
+#[derive(PartialEq)]
+enum ParamKind {
+    Value,
+    Ref,
+    RefMut,
+}
+
+struct Param {
+    name: String,
+    kind: ParamKind,
+    /// The parameter's type, or for a reference, the type it points to.
+    ty: String,
+    /// The parameter as written in the function's signature.
+    decl: String,
+}
+
+pub fn contract(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let code = if !attr.is_empty() {
+        format!("compile_error!(\"contract: `#[contract]` takes no arguments\"); {}", item)
+    } else {
+        match parse_fn(item.clone()) {
+            Ok(f) => expand(&f),
+            Err(msg) => format!("compile_error!({:?}); {}", format!("contract: {}", msg), item),
+        }
+    };
+    code.parse().unwrap()
+}
+
+pub fn use_contract(input: TokenStream) -> TokenStream {
+    let tts = input.into_iter().collect::<Vec<_>>();
+    let code = match tts.last() {
+        Some(TokenTree::Ident(name)) => format!(
+            "::crucible::override_({path}, {prefix} __crux_contract_{name})",
+            path = tokens_to_string(&tts),
+            prefix = tokens_to_string(&tts[.. tts.len() - 1]),
+            name = name,
+        ),
+        _ => "compile_error!(\"use_contract: expected the path of a function\")".to_owned(),
+    };
+    code.parse().unwrap()
+}
+
+
+/// If `attr` is the body of a `#[crux::requires(...)]`, `#[crux::ensures(...)]` or
+/// `#[crux::modifies(...)]` attribute, return its kind and arguments.
+fn contract_attr(attr: &TokenTree) -> Option<(String, TokenStream)> {
+    let g = match attr {
+        TokenTree::Group(g) => g,
+        _ => return None,
+    };
+    let inner = g.stream().into_iter().collect::<Vec<_>>();
+    if inner.len() != 5 || !is_ident(&inner[0], "crux") || !is_punct(&inner[1], ':')
+            || !is_punct(&inner[2], ':') {
+        return None;
+    }
+    let kind = inner[3].to_string();
+    match (&kind as &str, &inner[4]) {
+        ("requires" | "ensures" | "modifies", TokenTree::Group(args))
+                if args.delimiter() == Delimiter::Parenthesis => Some((kind, args.stream())),
+        _ => None,
+    }
+}
+
+fn parse_param(tts: &[TokenTree]) -> Result<Param, String> {
+    let mut pos = 0;
+    while pos + 1 < tts.len() && is_punct(&tts[pos], '#') && is_group(&tts[pos + 1], Delimiter::Bracket) {
+        pos += 2;
+    }
+    let decl = tokens_to_string(&tts[pos..]);
+    if tts[pos..].iter().take(3).any(|tt| is_ident(tt, "self")) {
+        return Err("methods are not supported".to_owned());
+    }
+    if pos < tts.len() && is_ident(&tts[pos], "mut") {
+        pos += 1;
+    }
+    let name = match (tts.get(pos), tts.get(pos + 1)) {
+        (Some(TokenTree::Ident(i)), Some(colon)) if is_punct(colon, ':') => i.to_string(),
+        _ => return Err(format!("parameter `{}` must be a plain variable", decl)),
+    };
+    pos += 2;
+
+    let mut kind = ParamKind::Value;
+    if pos < tts.len() && is_punct(&tts[pos], '&') {
+        pos += 1;
+        kind = ParamKind::Ref;
+        // Skip a lifetime.
+        if pos < tts.len() && is_punct(&tts[pos], '\'') {
+            pos += 2;
+        }
+        if pos < tts.len() && is_ident(&tts[pos], "mut") {
+            pos += 1;
+            kind = ParamKind::RefMut;
+        }
+    }
+    let ty = tokens_to_string(&tts[pos..]);
+    Ok(Param { name, kind, ty, decl })
+}
+
+fn parse_fn(input: TokenStream) -> Result<ContractFn, String> {
+    let tts = input.into_iter().collect::<Vec<_>>();
+    let mut item = Vec::new();
+    let mut requires = Vec::new();
+    let mut ensures = Vec::new();
+    let mut modifies_args = Vec::new();
+    let mut pos = 0;
+    while pos + 1 < tts.len() && is_punct(&tts[pos], '#') && is_group(&tts[pos + 1], Delimiter::Bracket) {
+        match contract_attr(&tts[pos + 1]) {
+            Some((kind, args)) => match &kind as &str {
+                "requires" => requires.push(args),
+                "ensures" => ensures.push(args),
+                _ => modifies_args.push(args),
+            },
+            None => item.extend_from_slice(&tts[pos .. pos + 2]),
+        }
+        pos += 2;
+    }
+    item.extend_from_slice(&tts[pos..]);
+
+    let vis_start = pos;
+    skip_visibility(&tts, &mut pos);
+    let vis = tokens_to_string(&tts[vis_start .. pos]);
+    // Of the qualifiers, only `unsafe` matters to callers.
+    let mut is_unsafe = false;
+    while pos < tts.len() && !is_ident(&tts[pos], "fn") {
+        if is_ident(&tts[pos], "unsafe") {
+            is_unsafe = true;
+        } else if is_ident(&tts[pos], "async") {
+            return Err("async functions are not supported".to_owned());
+        }
+        pos += 1;
+    }
+    pos += 1;
+    let name = match tts.get(pos) {
+        Some(TokenTree::Ident(i)) => i.to_string(),
+        _ => return Err("expected a function".to_owned()),
+    };
+    pos += 1;
+    if pos < tts.len() && is_punct(&tts[pos], '<') {
+        return Err("generic functions are not supported".to_owned());
+    }
+    let params = match tts.get(pos) {
+        Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Parenthesis => {
+            let tts = g.stream().into_iter().collect::<Vec<_>>();
+            split_commas(&tts, true).iter().map(|p| parse_param(p)).collect::<Result<Vec<_>, _>>()?
+        },
+        _ => return Err("expected a parameter list".to_owned()),
+    };
+    pos += 1;
+
+    let mut ret = None;
+    if pos + 1 < tts.len() && is_punct(&tts[pos], '-') && is_punct(&tts[pos + 1], '>') {
+        pos += 2;
+        let start = pos;
+        while pos < tts.len() && !is_group(&tts[pos], Delimiter::Brace) && !is_ident(&tts[pos], "where") {
+            pos += 1;
+        }
+        let ty = tokens_to_string(&tts[start .. pos]);
+        if ty != "()" {
+            ret = Some(ty);
+        }
+    }
+    if pos < tts.len() && is_ident(&tts[pos], "where") {
+        return Err("where clauses are not supported".to_owned());
+    }
+
+    let mut modifies = Vec::new();
+    for args in modifies_args {
+        let tts = args.into_iter().collect::<Vec<_>>();
+        for arg in split_commas(&tts, false) {
+            let arg = tokens_to_string(&arg);
+            if !params.iter().any(|p| p.name == arg && p.kind == ParamKind::RefMut) {
+                return Err(format!("`modifies({})` must name a `&mut` parameter", arg));
+            }
+            modifies.push(arg);
+        }
+    }
+
+    Ok(ContractFn { name, vis, is_unsafe, params, ret, item, requires, ensures, modifies })
+}
+
+
+/// Replace each `old(e)` in `ts` with a variable `__crux_old_<n>`, adding `e` to `olds`.
+fn replace_olds(ts: TokenStream, olds: &mut Vec<String>) -> TokenStream {
+    let tts = ts.into_iter().collect::<Vec<_>>();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < tts.len() {
+        // Skip method calls and paths such as `x.old(..)` or `m::old(..)`.
+        let is_call = is_ident(&tts[i], "old")
+            && matches!(tts.get(i + 1), Some(tt) if is_group(tt, Delimiter::Parenthesis))
+            && !(i > 0 && (is_punct(&tts[i - 1], '.') || is_punct(&tts[i - 1], ':')));
+        match &tts[i] {
+            TokenTree::Ident(_) if is_call => {
+                if let TokenTree::Group(g) = &tts[i + 1] {
+                    let e = replace_olds(g.stream(), olds).to_string();
+                    olds.push(e);
+                }
+                let var = format!("__crux_old_{}", olds.len() - 1);
+                out.push(TokenTree::Ident(Ident::new(&var, Span::call_site())));
+                i += 1;
+            },
+            TokenTree::Group(g) => {
+                let mut g2 = Group::new(g.delimiter(), replace_olds(g.stream(), olds));
+                g2.set_span(g.span());
+                out.push(TokenTree::Group(g2));
+            },
+            tt => out.push(tt.clone()),
+        }
+        i += 1;
+    }
+    out.into_iter().collect()
+}
+
+fn symbolic(ty: &str, desc: &str) -> String {
+    format!("<{} as ::crucible::Symbolic>::symbolic({:?})", ty, desc)
+}
+
+fn expand(f: &ContractFn) -> String {
+    let mut olds = Vec::new();
+    let ensures = f.ensures.iter().map(|e| (e.to_string(), replace_olds(e.clone(), &mut olds).to_string()))
+        .collect::<Vec<_>>();
+    let requires = f.requires.iter().map(|r| r.to_string()).collect::<Vec<_>>();
+    let save_olds = olds.iter().enumerate()
+        .map(|(i, e)| format!("let __crux_old_{} = {};", i, e))
+        .collect::<String>();
+    let unchanged = f.params.iter()
+        .filter(|p| p.kind == ParamKind::RefMut && !f.modifies.contains(&p.name))
+        .collect::<Vec<_>>();
+    let unsafe_kw = if f.is_unsafe { "unsafe" } else { "" };
+
+    // The test: call the function on symbolic arguments that satisfy the preconditions.
+    let mut check = String::new();
+    for p in &f.params {
+        check += &match p.kind {
+            ParamKind::Value => format!("let {} = {};", p.name, symbolic(&p.ty, &p.name)),
+            ParamKind::Ref => format!("let __crux_{n} = {}; let {n} = &__crux_{n};",
+                                      symbolic(&p.ty, &p.name), n = p.name),
+            ParamKind::RefMut => format!("let mut __crux_{n} = {}; let {n} = &mut __crux_{n};",
+                                         symbolic(&p.ty, &p.name), n = p.name),
+        };
+    }
+    for r in &requires {
+        check += &format!("::crucible::crucible_assume!({});", r);
+    }
+    check += &save_olds;
+    for p in &unchanged {
+        check += &format!("let __crux_unchanged_{n} = ::core::clone::Clone::clone(&*{n});", n = p.name);
+    }
+    let args = f.params.iter().map(|p| match p.kind {
+        ParamKind::RefMut => format!("&mut *{}", p.name),
+        _ => p.name.clone(),
+    }).collect::<Vec<_>>();
+    check += &format!("let result = {} {{ {}({}) }};", unsafe_kw, f.name, args.join(", "));
+    for (src, e) in &ensures {
+        let msg = format!("`{}` does not satisfy its postcondition: {}", f.name, src);
+        check += &format!("::crucible::crucible_assert!({}, {:?});", e, msg);
+    }
+    for p in &unchanged {
+        let msg = format!("`{}` modified `*{}`, which is not listed in its `modifies`", f.name, p.name);
+        check += &format!("::crucible::crucible_assert!(*{n} == __crux_unchanged_{n}, {:?});", msg,
+                          n = p.name);
+    }
+
+    // The replacement: check the preconditions, then make up a result that satisfies the
+    // postconditions.
+    let mut replace = String::new();
+    for r in &requires {
+        let msg = format!("precondition of `{}` does not hold: {}", f.name, r);
+        replace += &format!("::crucible::crucible_assert!({}, {:?});", r, msg);
+    }
+    replace += &save_olds;
+    for m in &f.modifies {
+        let p = f.params.iter().find(|p| &p.name == m).unwrap();
+        replace += &format!("*{} = {};", m, symbolic(&p.ty, &format!("{}_{}", f.name, m)));
+    }
+    replace += &match &f.ret {
+        Some(ty) => format!("let result = {};", symbolic(ty, &format!("{}_result", f.name))),
+        None => "let result = ();".to_owned(),
+    };
+    for (_, e) in &ensures {
+        replace += &format!("::crucible::crucible_assume!({});", e);
+    }
+
+    let decls = f.params.iter().map(|p| p.decl.clone()).collect::<Vec<_>>();
+    let ret = f.ret.as_ref().map_or(String::new(), |ty| format!("-> {}", ty));
+    format!(
+        "{item} \
+        #[cfg_attr(crux, crux::test)] \
+        #[allow(dead_code, non_snake_case, unused_mut, unused_unsafe, unused_variables)] \
+        fn {name}_contract() {{ {check} }} \
+        #[doc(hidden)] \
+        #[allow(dead_code, non_snake_case, unused_mut, unused_variables)] \
+        {vis} {unsafe_kw} fn __crux_contract_{name}({decls}) {ret} {{ {replace} result }}",
+        item = tokens_to_string(&f.item),
+        name = f.name,
+        check = check,
+        vis = f.vis,
+        unsafe_kw = unsafe_kw,
+        decls = decls.join(", "),
+        ret = ret,
+        replace = replace,
+    )
+}
@@ -0,0 +1,426 @@
+//! `#[derive(Symbolic)]`, which implements `crucible::Symbolic` for a struct or enum by creating
+//! a symbolic value for each field.  This is re-exported from the `crucible` crate, so tests
+//! should use it as `crucible::Symbolic` rather than depending on this crate directly.
+//!
+//! Each field of a value created with description `desc` is named `desc_field` (or `desc_0` for
+//! tuple fields), so it can be identified in counterexamples.  For enums, the variant is chosen
+//! by a symbolic discriminant named `desc`, and fields are named `desc_Variant_field`.
+//!
+//! A `#[symbolic(where = f)]` attribute on a field creates that field with
+//! `Symbolic::symbolic_where` instead of `Symbolic::symbolic`.  On the struct or enum itself, it
+//! adds an assumption that `f(&value)` holds for the whole value.
+//!
+//! It also provides `#[contract]` and `use_contract!`, which are described in `contracts.rs`, and
+//! `#[unwind]`, which is described in `unwind.rs`.
+//!
+//! This crate has no dependencies besides `proc_macro`, so it can be built with a bare `rustc`
+//! invocation in `translate_libs.sh`.
+
+extern crate proc_macro;
+
+use proc_macro::{Delimiter, Spacing, TokenStream, TokenTree};
+
+mod contracts;
+mod unwind;
+
+#[proc_macro_attribute]
+pub fn contract(attr: TokenStream, item: TokenStream) -> TokenStream {
+    contracts::contract(attr, item)
+}
+
+#[proc_macro]
+pub fn use_contract(input: TokenStream) -> TokenStream {
+    contracts::use_contract(input)
+}
+
+#[proc_macro_attribute]
+pub fn unwind(attr: TokenStream, item: TokenStream) -> TokenStream {
+    unwind::unwind(attr, item)
+}
+
+#[proc_macro_derive(Symbolic, attributes(symbolic))]
+pub fn derive_symbolic(input: TokenStream) -> TokenStream {
+    let result = parse_item(input).map(|item| expand(&item));
+    let code = match result {
+        Ok(code) => code,
+        Err(msg) => format!("compile_error!({:?});", format!("derive(Symbolic): {}", msg)),
+    };
+    code.parse().unwrap()
+}
+
+
+struct Item {
+    name: String,
+    generics: Vec<GenericParam>,
+    where_preds: Vec<String>,
+    where_attr: Option<String>,
+    body: Body,
+}
+
+enum Body {
+    Struct(Fields),
+    Enum(Vec<Variant>),
+}
+
+enum Fields {
+    Named(Vec<Field>),
+    Unnamed(Vec<Field>),
+    Unit,
+}
+
+struct Field {
+    /// `None` for tuple fields.
+    name: Option<String>,
+    ty: String,
+    where_attr: Option<String>,
+}
+
+struct Variant {
+    name: String,
+    fields: Fields,
+}
+
+struct GenericParam {
+    /// The parameter as written in the impl's generics, with any default removed.
+    decl: String,
+    /// The parameter as written in the self type's generic arguments.
+    arg: String,
+    /// Type parameters get a `Symbolic` bound.
+    is_type: bool,
+}
+
+
+fn is_punct(tt: &TokenTree, ch: char) -> bool {
+    match tt {
+        TokenTree::Punct(p) => p.as_char() == ch,
+        _ => false,
+    }
+}
+
+fn is_ident(tt: &TokenTree, name: &str) -> bool {
+    match tt {
+        TokenTree::Ident(i) => i.to_string() == name,
+        _ => false,
+    }
+}
+
+fn is_group(tt: &TokenTree, delim: Delimiter) -> bool {
+    match tt {
+        TokenTree::Group(g) => g.delimiter() == delim,
+        _ => false,
+    }
+}
+
+fn tokens_to_string(tts: &[TokenTree]) -> String {
+    tts.iter().cloned().collect::<TokenStream>().to_string()
+}
+
+/// Split `tts` on commas that are not nested inside any group.  If `track_angles` is set, commas
+/// inside `<...>` are also skipped, as needed for generic arguments in types.  Empty pieces (from
+/// a trailing comma) are dropped.
+fn split_commas(tts: &[TokenTree], track_angles: bool) -> Vec<Vec<TokenTree>> {
+    let mut pieces = Vec::new();
+    let mut cur = Vec::new();
+    let mut depth = 0_usize;
+    let mut prev_dash = false;
+    for tt in tts {
+        if track_angles {
+            if is_punct(tt, '<') {
+                depth += 1;
+            } else if is_punct(tt, '>') && !prev_dash {
+                depth = depth.saturating_sub(1);
+            }
+        }
+        prev_dash = matches!(tt, TokenTree::Punct(p) if p.as_char() == '-'
+                             && p.spacing() == Spacing::Joint);
+        if depth == 0 && is_punct(tt, ',') {
+            pieces.push(std::mem::take(&mut cur));
+        } else {
+            cur.push(tt.clone());
+        }
+    }
+    pieces.push(cur);
+    pieces.retain(|p| !p.is_empty());
+    pieces
+}
+
+/// Consume leading outer attributes, returning the expression from a `#[symbolic(where = ...)]`
+/// attribute if one is present.
+fn parse_attrs(tts: &[TokenTree], pos: &mut usize) -> Result<Option<String>, String> {
+    let mut where_attr = None;
+    while *pos + 1 < tts.len() && is_punct(&tts[*pos], '#')
+            && is_group(&tts[*pos + 1], Delimiter::Bracket) {
+        if let TokenTree::Group(g) = &tts[*pos + 1] {
+            let inner = g.stream().into_iter().collect::<Vec<_>>();
+            if inner.len() == 2 && is_ident(&inner[0], "symbolic") {
+                let args = match &inner[1] {
+                    TokenTree::Group(args) if args.delimiter() == Delimiter::Parenthesis =>
+                        args.stream().into_iter().collect::<Vec<_>>(),
+                    _ => return Err("expected `#[symbolic(where = ...)]`".to_owned()),
+                };
+                if args.len() < 3 || !is_ident(&args[0], "where") || !is_punct(&args[1], '=') {
+                    return Err("expected `#[symbolic(where = ...)]`".to_owned());
+                }
+                if where_attr.is_some() {
+                    return Err("duplicate `#[symbolic(where = ...)]` attribute".to_owned());
+                }
+                where_attr = Some(tokens_to_string(&args[2..]));
+            }
+        }
+        *pos += 2;
+    }
+    Ok(where_attr)
+}
+
+/// Consume a visibility qualifier such as `pub` or `pub(crate)`, if present.
+fn skip_visibility(tts: &[TokenTree], pos: &mut usize) {
+    if *pos < tts.len() && is_ident(&tts[*pos], "pub") {
+        *pos += 1;
+        // `pub (u8, u8)` in a tuple struct is a public field of tuple type, not a restricted
+        // visibility, so only skip groups that look like `(crate)`, `(in path)`, etc.
+        if let Some(TokenTree::Group(g)) = tts.get(*pos) {
+            let first = g.stream().into_iter().next();
+            let restricted = g.delimiter() == Delimiter::Parenthesis && matches!(first, Some(tt)
+                if is_ident(&tt, "crate") || is_ident(&tt, "super") || is_ident(&tt, "self")
+                    || is_ident(&tt, "in"));
+            if restricted {
+                *pos += 1;
+            }
+        }
+    }
+}
+
+fn parse_generics(tts: &[TokenTree], pos: &mut usize) -> Result<Vec<GenericParam>, String> {
+    if *pos >= tts.len() || !is_punct(&tts[*pos], '<') {
+        return Ok(Vec::new());
+    }
+    *pos += 1;
+    let start = *pos;
+    let mut depth = 1_usize;
+    let mut prev_dash = false;
+    while *pos < tts.len() {
+        let tt = &tts[*pos];
+        if is_punct(tt, '<') {
+            depth += 1;
+        } else if is_punct(tt, '>') && !prev_dash {
+            depth -= 1;
+            if depth == 0 {
+                break;
+            }
+        }
+        prev_dash = matches!(tt, TokenTree::Punct(p) if p.as_char() == '-'
+                             && p.spacing() == Spacing::Joint);
+        *pos += 1;
+    }
+    if depth != 0 {
+        return Err("unterminated generic parameter list".to_owned());
+    }
+    let params = &tts[start .. *pos];
+    *pos += 1;
+
+    let mut result = Vec::new();
+    for param in split_commas(params, true) {
+        let mut p = 0;
+        parse_attrs(&param, &mut p)?;
+        let param = &param[p..];
+        // Drop any default (`T = u8`, `const N: usize = 4`).
+        let decl_end = param.iter().position(|tt| is_punct(tt, '=')).unwrap_or(param.len());
+        let decl = tokens_to_string(&param[..decl_end]);
+        if is_punct(&param[0], '\'') {
+            let arg = tokens_to_string(&param[..2]);
+            result.push(GenericParam { decl, arg, is_type: false });
+        } else if is_ident(&param[0], "const") {
+            let arg = tokens_to_string(&param[1..2]);
+            result.push(GenericParam { decl, arg, is_type: false });
+        } else {
+            let arg = tokens_to_string(&param[..1]);
+            result.push(GenericParam { decl, arg, is_type: true });
+        }
+    }
+    Ok(result)
+}
+
+/// Parse an optional `where` clause, stopping before the body (a brace group or `;`).
+fn parse_where_clause(tts: &[TokenTree], pos: &mut usize) -> Vec<String> {
+    if *pos >= tts.len() || !is_ident(&tts[*pos], "where") {
+        return Vec::new();
+    }
+    *pos += 1;
+    let start = *pos;
+    while *pos < tts.len() && !is_group(&tts[*pos], Delimiter::Brace) && !is_punct(&tts[*pos], ';') {
+        *pos += 1;
+    }
+    split_commas(&tts[start .. *pos], true).iter().map(|p| tokens_to_string(p)).collect()
+}
+
+fn parse_fields(group: Option<&TokenTree>) -> Result<Fields, String> {
+    let g = match group {
+        Some(TokenTree::Group(g)) => g,
+        _ => return Ok(Fields::Unit),
+    };
+    let tts = g.stream().into_iter().collect::<Vec<_>>();
+    let named = g.delimiter() == Delimiter::Brace;
+    let mut fields = Vec::new();
+    for field in split_commas(&tts, true) {
+        let mut pos = 0;
+        let where_attr = parse_attrs(&field, &mut pos)?;
+        skip_visibility(&field, &mut pos);
+        let name = if named {
+            if pos + 1 >= field.len() || !is_punct(&field[pos + 1], ':') {
+                return Err(format!("can't parse field `{}`", tokens_to_string(&field)));
+            }
+            let name = field[pos].to_string();
+            pos += 2;
+            Some(name)
+        } else {
+            None
+        };
+        let ty = tokens_to_string(&field[pos..]);
+        fields.push(Field { name, ty, where_attr });
+    }
+    Ok(if named { Fields::Named(fields) } else { Fields::Unnamed(fields) })
+}
+
+fn parse_item(input: TokenStream) -> Result<Item, String> {
+    let tts = input.into_iter().collect::<Vec<_>>();
+    let mut pos = 0;
+    let where_attr = parse_attrs(&tts, &mut pos)?;
+    skip_visibility(&tts, &mut pos);
+
+    let kind = tts.get(pos).map(|tt| tt.to_string()).unwrap_or_default();
+    pos += 1;
+    let name = match tts.get(pos) {
+        Some(TokenTree::Ident(i)) => i.to_string(),
+        _ => return Err("expected a type name".to_owned()),
+    };
+    pos += 1;
+    let generics = parse_generics(&tts, &mut pos)?;
+
+    match &kind as &str {
+        "struct" => {
+            // A tuple struct's where clause comes after its fields.
+            let mut where_preds = parse_where_clause(&tts, &mut pos);
+            let fields = match tts.get(pos) {
+                Some(tt) if is_group(tt, Delimiter::Brace) => parse_fields(Some(tt))?,
+                Some(tt) if is_group(tt, Delimiter::Parenthesis) => {
+                    let fields = parse_fields(Some(tt))?;
+                    pos += 1;
+                    where_preds.extend(parse_where_clause(&tts, &mut pos));
+                    fields
+                },
+                _ => Fields::Unit,
+            };
+            Ok(Item { name, generics, where_preds, where_attr, body: Body::Struct(fields) })
+        },
+        "enum" => {
+            let where_preds = parse_where_clause(&tts, &mut pos);
+            let body = match tts.get(pos) {
+                Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Brace =>
+                    g.stream().into_iter().collect::<Vec<_>>(),
+                _ => return Err("expected enum body".to_owned()),
+            };
+            let mut variants = Vec::new();
+            for variant in split_commas(&body, false) {
+                let mut vpos = 0;
+                parse_attrs(&variant, &mut vpos)?;
+                let name = match variant.get(vpos) {
+                    Some(TokenTree::Ident(i)) => i.to_string(),
+                    _ => return Err(format!("can't parse variant `{}`",
+                                            tokens_to_string(&variant))),
+                };
+                // Anything after the fields is an explicit discriminant, which we don't need.
+                let fields = parse_fields(variant.get(vpos + 1).filter(|tt| {
+                    is_group(tt, Delimiter::Brace) || is_group(tt, Delimiter::Parenthesis)
+                }))?;
+                variants.push(Variant { name, fields });
+            }
+            Ok(Item { name, generics, where_preds, where_attr, body: Body::Enum(variants) })
+        },
+        _ => Err("only structs and enums are supported".to_owned()),
+    }
+}
+
+
+/// Build an expression that constructs `path` with a symbolic value for each field.  `prefix` is
+/// an expression giving the description of the enclosing value.
+fn expand_fields(path: &str, fields: &Fields, prefix: &str) -> String {
+    let field_value = |f: &Field, label: &str| {
+        let desc = format!("::crucible::symbolic::sub_name({}, {:?})", prefix, label);
+        match &f.where_attr {
+            Some(pred) => format!("<{} as ::crucible::Symbolic>::symbolic_where({}, {})",
+                                  f.ty, desc, pred),
+            None => format!("<{} as ::crucible::Symbolic>::symbolic({})", f.ty, desc),
+        }
+    };
+    match fields {
+        Fields::Named(fs) => {
+            let inits = fs.iter().map(|f| {
+                let name = f.name.as_ref().unwrap();
+                format!("{}: {},", name, field_value(f, name.trim_start_matches("r#")))
+            }).collect::<String>();
+            format!("{} {{ {} }}", path, inits)
+        },
+        Fields::Unnamed(fs) => {
+            let inits = fs.iter().enumerate().map(|(i, f)| {
+                format!("{},", field_value(f, &i.to_string()))
+            }).collect::<String>();
+            format!("{}({})", path, inits)
+        },
+        Fields::Unit => path.to_owned(),
+    }
+}
+
+fn expand(item: &Item) -> String {
+    let body = match &item.body {
+        Body::Struct(fields) => expand_fields("Self", fields, "desc"),
+        Body::Enum(variants) if variants.is_empty() =>
+            "::crucible::crucible_assume_unreachable!()".to_owned(),
+        Body::Enum(variants) => {
+            let arms = variants.iter().enumerate().map(|(i, v)| {
+                let pat = if i + 1 == variants.len() { "_".to_owned() } else { i.to_string() };
+                let prefix = format!("::crucible::symbolic::sub_name(desc, {:?})", v.name);
+                let path = format!("Self::{}", v.name);
+                format!("{} => {{ let __desc = {}; {} }},", pat, prefix,
+                        expand_fields(&path, &v.fields, "__desc"))
+            }).collect::<String>();
+            format!(
+                "{{ let __variant = <usize as ::crucible::Symbolic>::symbolic_where(\
+                    desc, |&d| d < {}); match __variant {{ {} }} }}",
+                variants.len(), arms)
+        },
+    };
+
+    let assume = match &item.where_attr {
+        Some(pred) => format!("::crucible::crucible_assume!(({})(&__value));", pred),
+        None => String::new(),
+    };
+
+    let impl_generics = item.generics.iter().map(|g| g.decl.clone()).collect::<Vec<_>>();
+    let ty_args = item.generics.iter().map(|g| g.arg.clone()).collect::<Vec<_>>();
+    let mut where_preds = item.where_preds.clone();
+    where_preds.extend(item.generics.iter().filter(|g| g.is_type)
+        .map(|g| format!("{}: ::crucible::Symbolic", g.arg)));
+    let where_clause = if where_preds.is_empty() {
+        String::new()
+    } else {
+        format!("where {}", where_preds.join(", "))
+    };
+
+    format!(
+        "#[automatically_derived] \
+        impl<{impl_generics}> ::crucible::Symbolic for {name}<{ty_args}> {where_clause} {{ \
+            #[allow(unused_variables)] \
+            fn symbolic(desc: &'static str) -> Self {{ \
+                let __value = {body}; \
+                {assume} \
+                __value \
+            }} \
+        }}",
+        impl_generics = impl_generics.join(", "),
+        name = item.name,
+        ty_args = ty_args.join(", "),
+        where_clause = where_clause,
+        body = body,
+        assume = assume,
+    )
+}
@@ -0,0 +1,54 @@
+//! `#[unwind(N)]`, which bounds the number of iterations of each loop in a function.  It inserts
+//! a call to `crucible::unwind_bound` at the start of the function body; the translation applies
+//! the bound to every loop of the function that doesn't have a `loop_unwind!` of its own.  On a
+//! `#[crux::test]`, the bound also applies to loops in the functions the test calls.  (This isn't
+//! a `#[crux::unwind(N)]` tool attribute because mir-json doesn't export function attributes, so
+//! the translation would never see it.)
+//!
+//! `#[unwind(N, assume)]` assumes that no loop goes around more than `N` times, instead of
+//! reporting an unwinding assertion failure when one does.
+
+use proc_macro::{Delimiter, Group, TokenStream, TokenTree};
+
+use crate::{is_ident, split_commas, tokens_to_string};
+
+pub fn unwind(attr: TokenStream, item: TokenStream) -> TokenStream {
+    match parse_bound(attr).and_then(|bound| add_bound(item.clone(), &bound)) {
+        Ok(item) => item,
+        Err(msg) => {
+            let err = format!("compile_error!({:?});", format!("unwind: {}", msg));
+            err.parse::<TokenStream>().unwrap().into_iter().chain(item).collect()
+        }
+    }
+}
+
+/// Parse the arguments of the attribute into the arguments of `crucible::unwind_bound`.
+fn parse_bound(attr: TokenStream) -> Result<String, String> {
+    let tts = attr.into_iter().collect::<Vec<_>>();
+    let args = split_commas(&tts, false);
+    let (limit, assume) = match &args[..] {
+        [limit] => (limit, false),
+        [limit, flag] if flag.len() == 1 && is_ident(&flag[0], "assume") => (limit, true),
+        _ => return Err("expected `#[unwind(N)]` or `#[unwind(N, assume)]`".to_owned()),
+    };
+    match &limit[..] {
+        [TokenTree::Literal(_)] => Ok(format!("{}, {}", tokens_to_string(limit), assume)),
+        _ => Err("the bound must be an integer literal".to_owned()),
+    }
+}
+
+/// Insert a call to `crucible::unwind_bound` at the start of the function's body, which is the
+/// last token of the item.  The rest of the item keeps its spans, so errors in the body are still
+/// reported at the right place.
+fn add_bound(item: TokenStream, bound: &str) -> Result<TokenStream, String> {
+    let mut tts = item.into_iter().collect::<Vec<_>>();
+    let body = match tts.pop() {
+        Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Brace => g,
+        _ => return Err("expected a function with a body".to_owned()),
+    };
+    let call = format!("::crucible::unwind_bound({});", bound).parse::<TokenStream>().unwrap();
+    let mut new_body = Group::new(Delimiter::Brace, call.into_iter().chain(body.stream()).collect());
+    new_body.set_span(body.span());
+    tts.push(TokenTree::Group(new_body));
+    Ok(tts.into_iter().collect())
+}
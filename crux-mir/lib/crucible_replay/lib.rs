@@ -0,0 +1,266 @@
+//! A stand-in for the `crucible` crate that replays a counterexample found by `crux-mir` under
+//! ordinary `rustc`.
+//!
+//! `crux-mir --regression-tests FILE` emits a `#[test]` function for each counterexample.  Each
+//! one calls [`replay::run`] with a table of the values the solver chose for the test's symbolic
+//! variables, in the order they were created, and then calls the original test function.  With
+//! this crate built as `crucible`, every `Symbolic::symbolic` call takes the next value recorded
+//! under its name, and `crucible_assert!` panics like `assert!`, so the failure reproduces
+//! without Crux:
+//!
+//! ```sh
+//! rustc --edition=2021 --crate-type rlib --crate-name crucible \
+//!     --extern crucible_derive=... crux-mir/lib/crucible_replay/lib.rs
+//! ```
+//!
+//! Only the parts of the `crucible` API that create symbolic values through `Symbolic` are
+//! supported.  Other sources of symbolic values, such as `crucible::array` and
+//! `crucible::bitvector`, are not.  The table may also list variables that `crux-mir` created for
+//! its own purposes; values that no `Symbolic::symbolic` call asks for are ignored.
+
+pub use crucible_derive::Symbolic;
+
+pub mod replay {
+    use std::cell::RefCell;
+    use std::collections::{HashMap, VecDeque};
+
+    thread_local! {
+        static VALUES: RefCell<HashMap<&'static str, VecDeque<u128>>> =
+            RefCell::new(HashMap::new());
+    }
+
+    /// Run `f`, with symbolic values taken from `values`.  Each entry is a variable name (the
+    /// `desc` argument of `Symbolic::symbolic`) and its value, as the unsigned integer with the
+    /// same bit pattern.  Entries with the same name are used in order.
+    pub fn run<R>(values: &[(&'static str, u128)], f: impl FnOnce() -> R) -> R {
+        let mut table: HashMap<&'static str, VecDeque<u128>> = HashMap::new();
+        for &(name, value) in values {
+            table.entry(name).or_default().push_back(value);
+        }
+        VALUES.with(|v| *v.borrow_mut() = table);
+        f()
+    }
+
+    /// Take the next value recorded for `desc`.  Panics if there is none left, since that means
+    /// the replay no longer follows the path that `crux-mir` found.
+    pub fn next(desc: &'static str) -> u128 {
+        let value = VALUES.with(|v| v.borrow_mut().get_mut(desc).and_then(|q| q.pop_front()));
+        match value {
+            Some(value) => value,
+            None => panic!(
+                "replay diverged: no value left for symbolic variable `{}`", desc),
+        }
+    }
+}
+
+pub trait Symbolic: Sized {
+    /// Create a new symbolic value of this type.  During replay, this returns the next value from
+    /// the replay table.
+    fn symbolic(desc: &'static str) -> Self;
+
+    /// Create a new symbolic value, subject to constraints.  During replay, the constraint is
+    /// checked rather than assumed.
+    fn symbolic_where<F: FnOnce(&Self) -> bool>(desc: &'static str, f: F) -> Self {
+        let x = Self::symbolic(desc);
+        crucible_assume!(f(&x));
+        x
+    }
+}
+
+pub trait SymbolicBounded: Sized {
+    fn symbolic_bounded(desc: &'static str, max_len: usize) -> Self;
+}
+
+// The impls below must create variables in the same order as the ones in `crucible/symbolic.rs`
+// and `alloc`, or values with the same name won't line up.
+
+macro_rules! uint_impls {
+    ($($ty:ty;)*) => {
+        $(
+            impl Symbolic for $ty {
+                fn symbolic(desc: &'static str) -> $ty { replay::next(desc) as $ty }
+            }
+        )*
+    };
+}
+
+uint_impls! { u8; u16; u32; u64; u128; usize; }
+
+macro_rules! int_impls {
+    ($($ty:ty, $uty:ty;)*) => {
+        $(
+            impl Symbolic for $ty {
+                fn symbolic(desc: &'static str) -> $ty { <$uty>::symbolic(desc) as $ty }
+            }
+        )*
+    };
+}
+
+int_impls! {
+    i8, u8;
+    i16, u16;
+    i32, u32;
+    i64, u64;
+    i128, u128;
+    isize, usize;
+}
+
+impl Symbolic for bool {
+    fn symbolic(desc: &'static str) -> bool {
+        let val = u8::symbolic_where(desc, |&x| x < 2);
+        val == 1
+    }
+}
+
+impl Symbolic for f32 {
+    fn symbolic(desc: &'static str) -> f32 { f32::from_bits(replay::next(desc) as u32) }
+}
+
+impl Symbolic for f64 {
+    fn symbolic(desc: &'static str) -> f64 { f64::from_bits(replay::next(desc) as u64) }
+}
+
+impl<T: Symbolic + Copy, const N: usize> Symbolic for [T; N] {
+    fn symbolic(desc: &'static str) -> [T; N] {
+        let mut arr = [T::symbolic(desc); N];
+        for i in 1 .. N {
+            arr[i] = T::symbolic(desc);
+        }
+        arr
+    }
+}
+
+macro_rules! tuple_impls {
+    ($($($name:ident)*;)*) => {
+        $(
+            #[allow(unused)] #[allow(bad_style)]
+            impl<$($name: Symbolic,)*> Symbolic for ($($name,)*) {
+                fn symbolic(desc: &'static str) -> ($($name,)*) {
+                    (
+                        $($name::symbolic(desc),)*
+                    )
+                }
+            }
+        )*
+    };
+}
+
+tuple_impls! {
+    ;
+    A;
+    A B;
+    A B C;
+    A B C D;
+    A B C D E;
+    A B C D E F;
+    A B C D E F G;
+    A B C D E F G H;
+    A B C D E F G H I;
+    A B C D E F G H I J;
+    A B C D E F G H I J K;
+    A B C D E F G H I J K L;
+}
+
+impl Symbolic for char {
+    fn symbolic(desc: &'static str) -> char {
+        let val = u32::symbolic_where(desc, |&x| x < 0xd800 || (0xe000 <= x && x < 0x110000));
+        char::from_u32(val).unwrap()
+    }
+}
+
+impl<T: Symbolic> Symbolic for Option<T> {
+    fn symbolic(desc: &'static str) -> Option<T> {
+        if bool::symbolic(desc) {
+            Some(T::symbolic(desc))
+        } else {
+            None
+        }
+    }
+}
+
+impl<T: Symbolic, E: Symbolic> Symbolic for Result<T, E> {
+    fn symbolic(desc: &'static str) -> Result<T, E> {
+        if bool::symbolic(desc) {
+            Ok(T::symbolic(desc))
+        } else {
+            Err(E::symbolic(desc))
+        }
+    }
+}
+
+impl<T: Symbolic> SymbolicBounded for Vec<T> {
+    fn symbolic_bounded(desc: &'static str, max_len: usize) -> Vec<T> {
+        let len = usize::symbolic_where(desc, |&n| n <= max_len);
+        let mut v = Vec::with_capacity(max_len);
+        for i in 0..max_len {
+            let x = T::symbolic(desc);
+            if i < len {
+                v.push(x);
+            }
+        }
+        v
+    }
+}
+
+impl SymbolicBounded for String {
+    fn symbolic_bounded(desc: &'static str, max_len: usize) -> String {
+        let len = usize::symbolic_where(desc, |&n| n <= max_len);
+        let mut bytes = Vec::with_capacity(max_len);
+        for i in 0..max_len {
+            let b = u8::symbolic_where(desc, |&b| b < 0x80);
+            if i < len {
+                bytes.push(b);
+            }
+        }
+        String::from_utf8(bytes).unwrap()
+    }
+}
+
+pub mod symbolic {
+    pub use super::{Symbolic, SymbolicBounded};
+
+    /// Take a symbolic-length prefix of `xs`.
+    pub fn prefix<'a, T>(xs: &'a [T]) -> &'a [T] {
+        let len = usize::symbolic_where("prefix_len", |&n| n < xs.len());
+        &xs[..len]
+    }
+
+    /// The name of a field of a `#[derive(Symbolic)]` value.  This must match the override of
+    /// `crucible::symbolic::sub_name` in `crux-mir`.  Leaking the name is harmless here, since
+    /// replay runs under plain `rustc`.
+    #[doc(hidden)]
+    pub fn sub_name(desc: &'static str, field: &'static str) -> &'static str {
+        Box::leak(format!("{}_{}", desc, field).into_boxed_str())
+    }
+}
+
+
+#[macro_export]
+macro_rules! crucible_assert {
+    ($($args:tt)*) => { assert!($($args)*) };
+}
+
+#[macro_export]
+macro_rules! crucible_assume {
+    ($e:expr) => {
+        assert!($e, "assumption failed during replay: {}", stringify!($e))
+    };
+}
+
+#[macro_export]
+macro_rules! crucible_assert_unreachable {
+    () => { unreachable!() };
+}
+
+#[macro_export]
+macro_rules! crucible_assume_unreachable {
+    () => { unreachable!("assumption failed during replay") };
+}
+
+/// Values are already concrete during replay.
+pub fn concretize<T>(x: T) -> T {
+    x
+}
+
+pub fn dump_what4<T>(_desc: &str, _x: T) {
+}
@@ -151,6 +151,7 @@ mod sync_tests;
 use crate::error;
 use crate::fmt;
 use crate::sync::mpmc;
+use crate::sys::crux::channel;
 use crate::time::{Duration, Instant};
 
 /// The receiving half of Rust's [`channel`] (or [`sync_channel`]) type.
@@ -182,7 +183,7 @@ use crate::time::{Duration, Instant};
 #[stable(feature = "rust1", since = "1.0.0")]
 #[cfg_attr(not(test), rustc_diagnostic_item = "Receiver")]
 pub struct Receiver<T> {
-    inner: mpmc::Receiver<T>,
+    inner: channel::Receiver<T>,
 }
 
 // The receiver port can be sent from place to place, so long as it
@@ -339,7 +340,7 @@ pub struct IntoIter<T> {
 /// ```
 #[stable(feature = "rust1", since = "1.0.0")]
 pub struct Sender<T> {
-    inner: mpmc::Sender<T>,
+    inner: channel::Sender<T>,
 }
 
 // The send port can be sent from place to place, so long as it
@@ -398,7 +399,7 @@ impl<T> !Sync for Sender<T> {}
 /// ```
 #[stable(feature = "rust1", since = "1.0.0")]
 pub struct SyncSender<T> {
-    inner: mpmc::Sender<T>,
+    inner: channel::Sender<T>,
 }
 
 #[stable(feature = "rust1", since = "1.0.0")]
@@ -524,7 +525,7 @@ pub enum TrySendError<T> {
 #[must_use]
 #[stable(feature = "rust1", since = "1.0.0")]
 pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
-    let (tx, rx) = mpmc::channel();
+    let (tx, rx) = channel::channel();
     (Sender { inner: tx }, Receiver { inner: rx })
 }
 
@@ -573,7 +574,7 @@ pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
 #[must_use]
 #[stable(feature = "rust1", since = "1.0.0")]
 pub fn sync_channel<T>(bound: usize) -> (SyncSender<T>, Receiver<T>) {
-    let (tx, rx) = mpmc::sync_channel(bound);
+    let (tx, rx) = channel::sync_channel(bound);
     (SyncSender { inner: tx }, Receiver { inner: rx })
 }
 
@@ -745,7 +746,10 @@ impl<T> SyncSender<T> {
     // This method is currently private and only used for tests.
     #[allow(unused)]
     fn send_timeout(&self, t: T, timeout: Duration) -> Result<(), mpmc::SendTimeoutError<T>> {
-        self.inner.send_timeout(t, timeout)
+        self.inner.send_timeout(t, timeout).map_err(|e| match e {
+            TrySendError::Full(t) => mpmc::SendTimeoutError::Timeout(t),
+            TrySendError::Disconnected(t) => mpmc::SendTimeoutError::Disconnected(t),
+        })
     }
 }
 
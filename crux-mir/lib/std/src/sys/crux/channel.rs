@@ -0,0 +1,270 @@
+//! The channel behind `std::sync::mpsc`.  It is built on the Crux `Mutex` and `Condvar`, so with
+//! `--concurrency`, a send on a full channel or a receive from an empty one blocks the thread until
+//! another thread makes progress.
+
+use crate::cell::UnsafeCell;
+use crate::collections::VecDeque;
+use crate::sync::mpsc::{RecvError, RecvTimeoutError, SendError, TryRecvError, TrySendError};
+use crate::sync::Arc;
+use crate::sys::crux::condvar::Condvar;
+use crate::sys::crux::mutex::Mutex;
+use crate::time::{Duration, Instant};
+
+struct State<T> {
+    queue: VecDeque<T>,
+    // The number of messages the channel can hold, or `None` if it is unbounded.  A bound of 0
+    // makes a rendezvous channel, where each send waits until its message is received.
+    bound: Option<usize>,
+    // The number of messages sent and received so far.  A rendezvous send waits for `received` to
+    // catch up with its own message.
+    sent: usize,
+    received: usize,
+    senders: usize,
+    receiver: bool,
+    // Whether the receiver is blocked in `recv`, in which case `try_send` on a rendezvous channel
+    // can succeed.
+    receiving: bool,
+}
+
+impl<T> State<T> {
+    fn has_room(&self) -> bool {
+        match self.bound {
+            None => true,
+            // A rendezvous channel holds the message being handed over.
+            Some(n) => self.queue.len() < n.max(1),
+        }
+    }
+}
+
+struct Channel<T> {
+    lock: Mutex,
+    // Notified on every change to `state`.  The Crux `Condvar` wakes up every waiting thread, so
+    // senders and receivers can share it.
+    changed: Condvar,
+    state: UnsafeCell<State<T>>,
+}
+
+unsafe impl<T: Send> Send for Channel<T> {}
+unsafe impl<T: Send> Sync for Channel<T> {}
+
+impl<T> Channel<T> {
+    fn new(bound: Option<usize>) -> Arc<Channel<T>> {
+        Arc::new(Channel {
+            lock: Mutex::new(),
+            changed: Condvar::new(),
+            state: UnsafeCell::new(State {
+                queue: VecDeque::new(),
+                bound,
+                sent: 0,
+                received: 0,
+                senders: 1,
+                receiver: true,
+                receiving: false,
+            }),
+        })
+    }
+
+    fn lock(&self) {
+        unsafe { self.lock.lock() }
+    }
+
+    fn unlock(&self) {
+        unsafe { self.lock.unlock() }
+    }
+
+    // The state may only be accessed with the lock held.  Callers get it again after each `wait`,
+    // since other threads may have changed it in the meantime.
+    fn state(&self) -> &mut State<T> {
+        unsafe { &mut *self.state.get() }
+    }
+
+    fn wait(&self) {
+        self.changed.wait(&self.lock)
+    }
+
+    fn notify(&self) {
+        self.changed.notify_all()
+    }
+}
+
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let chan = Channel::new(None);
+    (Sender { chan: chan.clone() }, Receiver { chan })
+}
+
+pub fn sync_channel<T>(bound: usize) -> (Sender<T>, Receiver<T>) {
+    let chan = Channel::new(Some(bound));
+    (Sender { chan: chan.clone() }, Receiver { chan })
+}
+
+pub struct Sender<T> {
+    chan: Arc<Channel<T>>,
+}
+
+impl<T> Sender<T> {
+    pub fn send(&self, msg: T) -> Result<(), SendError<T>> {
+        let chan = &*self.chan;
+        chan.lock();
+        loop {
+            let st = chan.state();
+            if !st.receiver {
+                chan.unlock();
+                return Err(SendError(msg));
+            }
+            if st.has_room() {
+                break;
+            }
+            chan.wait();
+        }
+        let st = chan.state();
+        st.queue.push_back(msg);
+        st.sent += 1;
+        let ticket = st.sent;
+        let rendezvous = st.bound == Some(0);
+        chan.notify();
+        while rendezvous {
+            let st = chan.state();
+            if st.received >= ticket {
+                break;
+            }
+            if !st.receiver {
+                // The message was never received, and it is the only one in the queue.
+                let msg = st.queue.pop_front().unwrap();
+                chan.unlock();
+                return Err(SendError(msg));
+            }
+            chan.wait();
+        }
+        chan.unlock();
+        Ok(())
+    }
+
+    pub fn try_send(&self, msg: T) -> Result<(), TrySendError<T>> {
+        let chan = &*self.chan;
+        chan.lock();
+        let st = chan.state();
+        let result = if !st.receiver {
+            Err(TrySendError::Disconnected(msg))
+        } else if !st.has_room() || (st.bound == Some(0) && !st.receiving) {
+            Err(TrySendError::Full(msg))
+        } else {
+            st.queue.push_back(msg);
+            st.sent += 1;
+            chan.notify();
+            Ok(())
+        };
+        chan.unlock();
+        result
+    }
+
+    // There is no time under Crux.  This gives the receiver the chance to make room once, and
+    // then times out like `try_send`.
+    pub fn send_timeout(&self, msg: T, _timeout: Duration) -> Result<(), TrySendError<T>> {
+        match self.try_send(msg) {
+            Err(TrySendError::Full(msg)) => {
+                let chan = &*self.chan;
+                chan.lock();
+                chan.unlock();
+                self.try_send(msg)
+            }
+            result => result,
+        }
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Sender<T> {
+        let chan = &*self.chan;
+        chan.lock();
+        chan.state().senders += 1;
+        chan.unlock();
+        Sender { chan: self.chan.clone() }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let chan = &*self.chan;
+        chan.lock();
+        chan.state().senders -= 1;
+        chan.notify();
+        chan.unlock();
+    }
+}
+
+pub struct Receiver<T> {
+    chan: Arc<Channel<T>>,
+}
+
+impl<T> Receiver<T> {
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        let chan = &*self.chan;
+        chan.lock();
+        let st = chan.state();
+        let result = match st.queue.pop_front() {
+            Some(msg) => {
+                st.received += 1;
+                chan.notify();
+                Ok(msg)
+            }
+            None if st.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        };
+        chan.unlock();
+        result
+    }
+
+    pub fn recv(&self) -> Result<T, RecvError> {
+        let chan = &*self.chan;
+        chan.lock();
+        loop {
+            let st = chan.state();
+            if let Some(msg) = st.queue.pop_front() {
+                st.received += 1;
+                chan.notify();
+                chan.unlock();
+                return Ok(msg);
+            }
+            if st.senders == 0 {
+                chan.unlock();
+                return Err(RecvError);
+            }
+            st.receiving = true;
+            chan.notify();
+            chan.wait();
+            chan.state().receiving = false;
+        }
+    }
+
+    // As with `Condvar::wait_timeout`, this gives the senders one chance to send a message, and
+    // then times out.
+    pub fn recv_timeout(&self, _timeout: Duration) -> Result<T, RecvTimeoutError> {
+        match self.try_recv() {
+            Ok(msg) => Ok(msg),
+            Err(TryRecvError::Disconnected) => Err(RecvTimeoutError::Disconnected),
+            Err(TryRecvError::Empty) => {
+                let chan = &*self.chan;
+                chan.lock();
+                chan.unlock();
+                self.try_recv().map_err(|e| match e {
+                    TryRecvError::Empty => RecvTimeoutError::Timeout,
+                    TryRecvError::Disconnected => RecvTimeoutError::Disconnected,
+                })
+            }
+        }
+    }
+
+    pub fn recv_deadline(&self, deadline: Instant) -> Result<T, RecvTimeoutError> {
+        self.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let chan = &*self.chan;
+        chan.lock();
+        chan.state().receiver = false;
+        chan.notify();
+        chan.unlock();
+    }
+}
@@ -1,7 +1,10 @@
 use crate::sys::crux::mutex::Mutex;
 use crate::time::Duration;
+use core::crucible::concurrency;
 
 pub struct Condvar {
+    // The scheduler identifies a condvar by its address, so it must not be zero-sized.
+    _addr: u8,
 }
 
 unsafe impl Send for Condvar {}
@@ -9,31 +12,43 @@ unsafe impl Sync for Condvar {}
 
 impl Condvar {
     pub const fn new() -> Condvar {
-        Condvar {}
+        Condvar { _addr: 0 }
     }
 
     pub fn init(&mut self) {
         // No-op
     }
 
+    // This wakes up every waiting thread, not just one.
     #[inline]
     pub fn notify_one(&self) {
-        // No-op
+        concurrency::cond_notify(self);
     }
 
     #[inline]
     pub fn notify_all(&self) {
-        // No-op
+        concurrency::cond_notify(self);
     }
 
     #[inline]
     pub fn wait(&self, mutex: &Mutex) {
-        panic!("deadlock: single-threaded program is waiting on a condvar");
+        unsafe {
+            // Releasing the mutex is not a preemption point, so no notification can be lost
+            // between the unlock and the wait.
+            mutex.unlock();
+            concurrency::cond_wait(self, mutex);
+            mutex.lock();
+        }
     }
 
     #[inline]
     pub fn wait_timeout(&self, mutex: &Mutex, dur: Duration) -> bool {
-        // Pretend to have timed out, as there's no other way to end a wait.
+        // Pretend to have timed out, but give other threads the chance to take the mutex
+        // first.
+        unsafe {
+            mutex.unlock();
+            mutex.lock();
+        }
         false
     }
 
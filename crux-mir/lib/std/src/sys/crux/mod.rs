@@ -1,4 +1,6 @@
+pub mod channel;
 pub mod condvar;
 pub mod mutex;
 pub mod rwlock;
+pub mod thread;
 pub mod time;
@@ -22,26 +22,27 @@ impl Mutex {
     }
     #[inline]
     pub unsafe fn lock(&self) {
-        // TODO: Currently, we never run `drop` code, so locks are never released.  Once we support
-        // drops, we can enable this assertion to check for (invalid) reentrant locking.
-        // assert!(!self.locked.get());
         concurrency::mutex_lock(self);
+        concurrency::sync_acquire(self);
+        // Without `--concurrency`, `mutex_lock` never blocks, so this is what catches (invalid)
+        // reentrant locking.  Otherwise the scheduler reports the deadlock first.
+        assert!(!self.locked.get(), "deadlock: mutex is already locked");
         self.locked.set(true);
     }
     #[inline]
     pub unsafe fn unlock(&self) {
-        // TODO: Currently, we never run `drop` code, so locks are never released.  Once we support
-        // drops, we can enable this assertion to check for invalid usage.
+        concurrency::sync_release(self);
         concurrency::mutex_unlock(self);
-        // assert!(self.locked.get());
+        assert!(self.locked.get(), "unlocking a mutex that is not locked");
         self.locked.set(false);
     }
     #[inline]
     pub unsafe fn try_lock(&self) -> bool {
+        concurrency::sched_yield(true, self);
         if self.locked.get() {
             false
         } else {
-            self.locked.set(true);
+            self.lock();
             true
         }
     }
@@ -1,4 +1,5 @@
 use crate::cell::Cell;
+use core::crucible::concurrency;
 
 pub struct RwLock {
     num_readers: Cell<usize>,
@@ -17,14 +18,16 @@ impl RwLock {
     }
     #[inline]
     pub unsafe fn read(&self) {
-        // TODO: Currently, we never run `drop` code, so locks are never released.  Once we support
-        // drops, we can enable this assertion to check for invalid usage.
-        //assert!(!self.write_locked.get());
+        concurrency::rwlock_read(self);
+        concurrency::sync_acquire(self);
+        // As in `Mutex::lock`, this only fails without `--concurrency`.
+        assert!(!self.write_locked.get(), "deadlock: rwlock is already write-locked");
         self.num_readers.set(self.num_readers.get() + 1);
     }
 
     #[inline]
     pub unsafe fn try_read(&self) -> bool {
+        concurrency::sched_yield(true, self);
         if self.write_locked.get() {
             false
         } else {
@@ -35,15 +38,16 @@ impl RwLock {
 
     #[inline]
     pub unsafe fn write(&self) {
-        // TODO: Currently, we never run `drop` code, so locks are never released.  Once we support
-        // drops, we can enable this assertion to check for invalid usage.
-        //assert!(!self.write_locked.get());
-        //assert!(self.num_readers.get() == 0);
+        concurrency::rwlock_write(self);
+        concurrency::sync_acquire(self);
+        assert!(!self.write_locked.get(), "deadlock: rwlock is already write-locked");
+        assert!(self.num_readers.get() == 0, "deadlock: rwlock is already read-locked");
         self.write_locked.set(true);
     }
 
     #[inline]
     pub unsafe fn try_write(&self) -> bool {
+        concurrency::sched_yield(true, self);
         if self.write_locked.get() || self.num_readers.get() > 0 {
             false
         } else {
@@ -54,11 +58,16 @@ impl RwLock {
 
     #[inline]
     pub unsafe fn read_unlock(&self) {
+        concurrency::sync_release(self);
+        concurrency::rwlock_read_unlock(self);
+        assert!(self.num_readers.get() > 0);
         self.num_readers.set(self.num_readers.get() - 1);
     }
 
     #[inline]
     pub unsafe fn write_unlock(&self) {
+        concurrency::sync_release(self);
+        concurrency::rwlock_write_unlock(self);
         assert!(self.write_locked.get());
         self.write_locked.set(false);
     }
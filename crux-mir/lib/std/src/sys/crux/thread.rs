@@ -0,0 +1,71 @@
+//! The threads behind `std::thread`.  Each one is spawned through `crucible::concurrency`, so with
+//! `--concurrency`, the scheduler explores its interleavings with the other threads.
+use crate::ffi::CStr;
+use crate::io;
+use crate::time::Duration;
+use core::crucible::concurrency;
+
+pub use crate::sys::thread::available_parallelism;
+
+pub struct Thread {
+    id: u32,
+}
+
+unsafe impl Send for Thread {}
+unsafe impl Sync for Thread {}
+
+// `concurrency::spawn` requires a `Send` closure, but `std::thread` has already checked that
+// everything the thread's main function captures can be sent.
+struct Main(Box<dyn FnOnce()>);
+
+unsafe impl Send for Main {}
+
+impl Main {
+    // Calling a method makes the closure below capture all of `Main`, rather than just the
+    // (non-`Send`) box inside it.
+    fn call(self) {
+        (self.0)()
+    }
+}
+
+impl Thread {
+    pub unsafe fn new(_stack: usize, p: Box<dyn FnOnce()>) -> io::Result<Thread> {
+        let main = Main(p);
+        // The scheduler only supports joining threads that return a `u32`.  The real result is
+        // passed back through the `Packet` in `std::thread`.
+        let id = concurrency::spawn(move || {
+            main.call();
+            0u32
+        });
+        Ok(Thread { id })
+    }
+
+    pub fn yield_now() {
+        // No-op
+    }
+
+    pub fn set_name(_name: &CStr) {
+        // No-op
+    }
+
+    pub fn sleep(_dur: Duration) {
+        // No-op
+    }
+
+    pub fn join(self) {
+        join_id(self.id)
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn into_id(self) -> u32 {
+        self.id
+    }
+}
+
+/// Block until the thread `id` has terminated.  Joining a thread more than once is allowed.
+pub fn join_id(id: u32) {
+    concurrency::join::<u32>(id as u64);
+}
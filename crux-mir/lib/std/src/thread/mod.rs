@@ -164,14 +164,12 @@ use crate::marker::PhantomData;
 use crate::mem::{self, forget};
 use crate::num::NonZeroU64;
 use crate::num::NonZeroUsize;
-use crate::panic;
 use crate::panicking;
 use crate::pin::Pin;
 use crate::ptr::addr_of_mut;
 use crate::str;
 use crate::sync::Arc;
-use crate::sys::thread as imp;
-use crate::sys_common::thread;
+use crate::sys::crux::thread as imp;
 use crate::sys_common::thread_info;
 use crate::sys_common::thread_parking::Parker;
 use crate::sys_common::{AsInner, IntoInner};
@@ -499,7 +497,8 @@ impl Builder {
     {
         let Builder { name, stack_size } = self;
 
-        let stack_size = stack_size.unwrap_or_else(thread::min_stack);
+        // `min_stack` reads the environment, and Crux threads have no stack of their own anyway.
+        let stack_size = stack_size.unwrap_or(0);
 
         let my_thread = Thread::new(name.map(|name| {
             CString::new(name).expect("thread name may not contain interior null bytes")
@@ -513,9 +512,6 @@ impl Builder {
         });
         let their_packet = my_packet.clone();
 
-        let output_capture = crate::io::set_output_capture(None);
-        crate::io::set_output_capture(output_capture.clone());
-
         // Pass `f` in `MaybeUninit` because actually that closure might *run longer than the lifetime of `F`*.
         // See <https://github.com/rust-lang/rust/issues/101983> for more details.
         // To prevent leaks we use a wrapper that drops its contents.
@@ -546,17 +542,12 @@ impl Builder {
                 imp::Thread::set_name(name);
             }
 
-            crate::io::set_output_capture(output_capture);
-
             // SAFETY: we constructed `f` initialized.
             let f = f.into_inner();
-            // SAFETY: the stack guard passed is the one for the current thread.
-            // This means the current thread's stack and the new thread's stack
-            // are properly set and protected from each other.
-            thread_info::set(unsafe { imp::guard::current() }, their_thread);
-            let try_result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
-                crate::sys_common::backtrace::__rust_begin_short_backtrace(f)
-            }));
+            // Thread locals are shared by every thread under Crux, so this skips setting
+            // `thread_info` and the output capture, which would clobber the spawning thread's.
+            // Crux can't catch panics either: a panic in the thread fails the test right away.
+            let try_result = Ok(f());
             // SAFETY: `their_packet` as been built just above and moved by the
             // closure (it is an Arc<...>) and `my_packet` will be stored in the
             // same `JoinInner` as this closure meaning the mutation will be
@@ -574,28 +565,33 @@ impl Builder {
             scope_data.increment_num_running_threads();
         }
 
+        // SAFETY:
+        //
+        // `imp::Thread::new` takes a closure with a `'static` lifetime, since it's passed
+        // through FFI or otherwise used with low-level threading primitives that have no
+        // notion of or way to enforce lifetimes.
+        //
+        // As mentioned in the `Safety` section of this function's documentation, the caller of
+        // this function needs to guarantee that the passed-in lifetime is sufficiently long
+        // for the lifetime of the thread.
+        //
+        // Similarly, the `sys` implementation must guarantee that no references to the closure
+        // exist after the thread has terminated, which is signaled by `Thread::join`
+        // returning.
+        let native = unsafe {
+            imp::Thread::new(
+                stack_size,
+                mem::transmute::<Box<dyn FnOnce() + 'a>, Box<dyn FnOnce() + 'static>>(Box::new(
+                    main,
+                )),
+            )?
+        };
+        if let Some(scope_data) = &my_packet.scope {
+            scope_data.add_thread(native.id());
+        }
+
         Ok(JoinInner {
-            // SAFETY:
-            //
-            // `imp::Thread::new` takes a closure with a `'static` lifetime, since it's passed
-            // through FFI or otherwise used with low-level threading primitives that have no
-            // notion of or way to enforce lifetimes.
-            //
-            // As mentioned in the `Safety` section of this function's documentation, the caller of
-            // this function needs to guarantee that the passed-in lifetime is sufficiently long
-            // for the lifetime of the thread.
-            //
-            // Similarly, the `sys` implementation must guarantee that no references to the closure
-            // exist after the thread has terminated, which is signaled by `Thread::join`
-            // returning.
-            native: unsafe {
-                imp::Thread::new(
-                    stack_size,
-                    mem::transmute::<Box<dyn FnOnce() + 'a>, Box<dyn FnOnce() + 'static>>(
-                        Box::new(main),
-                    ),
-                )?
-            },
+            native,
             thread: my_thread,
             packet: my_packet,
         })
@@ -1429,11 +1425,8 @@ impl<'scope, T> Drop for Packet<'scope, T> {
         // (And even if we tried to handle it somehow, we'd also need to handle
         // the case where the panic payload we get out of it also panics on
         // drop, and so on. See issue #86027.)
-        if let Err(_) = panic::catch_unwind(panic::AssertUnwindSafe(|| {
-            *self.result.get_mut() = None;
-        })) {
-            rtabort!("thread result panicked on drop");
-        }
+        // Crux can't catch panics, so a panic here fails the test instead.
+        *self.result.get_mut() = None;
         // Book-keeping so the scope knows when it's done.
         if let Some(scope) = &self.scope {
             // Now that there will be no more user code running on this thread
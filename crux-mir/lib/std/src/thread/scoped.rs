@@ -1,10 +1,10 @@
-use super::{current, park, Builder, JoinInner, Result, Thread};
+use super::{current, Builder, JoinInner, Result, Thread};
 use crate::fmt;
 use crate::io;
 use crate::marker::PhantomData;
-use crate::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
 use crate::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use crate::sync::Arc;
+use crate::sync::{Arc, Mutex};
+use crate::sys::crux::thread as imp;
 
 /// A scope to spawn scoped threads in.
 ///
@@ -39,6 +39,8 @@ pub(super) struct ScopeData {
     num_running_threads: AtomicUsize,
     a_thread_panicked: AtomicBool,
     main_thread: Thread,
+    // The IDs of the threads spawned in the scope, which `scope` joins before returning.
+    threads: Mutex<Vec<u32>>,
 }
 
 impl ScopeData {
@@ -55,8 +57,21 @@ impl ScopeData {
         if panic {
             self.a_thread_panicked.store(true, Ordering::Relaxed);
         }
-        if self.num_running_threads.fetch_sub(1, Ordering::Release) == 1 {
-            self.main_thread.unpark();
+        // The scope joins its threads instead of parking until this reaches 0.
+        self.num_running_threads.fetch_sub(1, Ordering::Release);
+    }
+    pub(super) fn add_thread(&self, id: u32) {
+        self.threads.lock().unwrap().push(id);
+    }
+    fn join_all(&self) {
+        // The threads being joined may spawn more threads in the scope, so this keeps going until
+        // none are left.  The lock must not be held while joining.
+        loop {
+            let id = self.threads.lock().unwrap().pop();
+            match id {
+                Some(id) => imp::join_id(id),
+                None => break,
+            }
         }
     }
 }
@@ -137,27 +152,22 @@ where
             num_running_threads: AtomicUsize::new(0),
             main_thread: current(),
             a_thread_panicked: AtomicBool::new(false),
+            threads: Mutex::new(Vec::new()),
         }),
         env: PhantomData,
         scope: PhantomData,
     };
 
-    // Run `f`, but catch panics so we can make sure to wait for all the threads to join.
-    let result = catch_unwind(AssertUnwindSafe(|| f(&scope)));
+    // Crux can't catch panics, so a panic in `f` fails the test without waiting for the threads.
+    let result = f(&scope);
 
     // Wait until all the threads are finished.
-    while scope.data.num_running_threads.load(Ordering::Acquire) != 0 {
-        park();
-    }
+    scope.data.join_all();
 
-    // Throw any panic from `f`, or the return value of `f` if no thread panicked.
-    match result {
-        Err(e) => resume_unwind(e),
-        Ok(_) if scope.data.a_thread_panicked.load(Ordering::Relaxed) => {
-            panic!("a scoped thread panicked")
-        }
-        Ok(result) => result,
+    if scope.data.a_thread_panicked.load(Ordering::Relaxed) {
+        panic!("a scoped thread panicked")
     }
+    result
 }
 
 impl<'scope, 'env> Scope<'scope, 'env> {
@@ -0,0 +1,66 @@
+//! Comparing the branch coverage of two sets of reports, such as the runs before and after a
+//! change.  Branch arms are matched by function, branch span, and the value that leads to the
+//! arm, so the comparison is only meaningful when the spans are stable between the two runs.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+
+use crate::formats::{percent, totals, FileCoverage};
+
+
+/// A branch arm: function ID, branch span, and the value that leads to the arm.
+pub type ArmKey<'a> = (&'a str, &'a str, &'a str);
+
+pub fn arms(files: &BTreeMap<String, FileCoverage>) -> BTreeMap<ArmKey<'_>, bool> {
+    let mut arms = BTreeMap::new();
+    for fc in files.values() {
+        for b in &fc.branches {
+            for (value, taken) in &b.arms {
+                arms.insert((&b.fn_id as &str, &b.span as &str, value as &str), *taken);
+            }
+        }
+    }
+    arms
+}
+
+fn write_arms(out: &mut impl Write, title: &str, arms: &BTreeSet<ArmKey<'_>>) -> io::Result<()> {
+    writeln!(out, "{} ({}):", title, arms.len())?;
+    for (fn_id, span, value) in arms {
+        writeln!(out, "  {}: {}: arm {}", span, fn_id, value)?;
+    }
+    writeln!(out)
+}
+
+/// Print the branch arms that `old` covered but `new` doesn't, the ones `new` covers but `old`
+/// didn't, and the overall branch coverage of each.  An arm that's missing from one side (for
+/// example, because no test reached its branch) counts as not covered there.
+pub fn write_diff(
+    out: &mut impl Write,
+    old: &BTreeMap<String, FileCoverage>,
+    new: &BTreeMap<String, FileCoverage>,
+) -> io::Result<()> {
+    let old_arms = arms(old);
+    let new_arms = arms(new);
+    let taken = |arms: &BTreeMap<ArmKey<'_>, bool>, k| arms.get(k).copied().unwrap_or(false);
+
+    let newly_uncovered = old_arms.iter()
+        .filter(|&(k, &t)| t && !taken(&new_arms, k))
+        .map(|(&k, _)| k)
+        .collect::<BTreeSet<_>>();
+    let newly_covered = new_arms.iter()
+        .filter(|&(k, &t)| t && !taken(&old_arms, k))
+        .map(|(&k, _)| k)
+        .collect::<BTreeSet<_>>();
+
+    write_arms(out, "newly uncovered branches", &newly_uncovered)?;
+    write_arms(out, "newly covered branches", &newly_covered)?;
+
+    let ot = totals(old);
+    let nt = totals(new);
+    writeln!(
+        out,
+        "branch coverage: {:.1}% ({}/{}), previously {:.1}% ({}/{})",
+        percent(nt.branches_hit, nt.branches_found), nt.branches_hit, nt.branches_found,
+        percent(ot.branches_hit, ot.branches_found), ot.branches_hit, ot.branches_found,
+    )
+}
@@ -0,0 +1,358 @@
+//! Machine-readable coverage output: LCOV, Cobertura XML, and JSON.
+//!
+//! Each format is written from a per-file summary of the merged `Coverage`.  crux-mir doesn't
+//! count how many times a branch is executed, only whether it was, so every hit count in the
+//! output is either 0 or 1.
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde_json::json;
+
+use crate::{parse_span, span_in_filters, Coverage, Filter, SpanInfo};
+
+
+/// Coverage of a single source file.
+#[derive(Clone, Debug, Default)]
+pub struct FileCoverage {
+    /// Each line where a block or branch starts, with whether it was reached.
+    pub lines: BTreeMap<usize, bool>,
+    pub branches: Vec<BranchLine>,
+    pub functions: Vec<FunctionLine>,
+}
+
+/// A function, at the line where its entry block starts.
+#[derive(Clone, Debug)]
+pub struct FunctionLine {
+    pub fn_id: String,
+    pub line: usize,
+    pub called: bool,
+}
+
+/// The outcomes of one branch, at a particular line of a file.
+#[derive(Clone, Debug)]
+pub struct BranchLine {
+    pub fn_id: String,
+    pub span: String,
+    pub line: usize,
+    pub col: usize,
+    /// Each exit of the branch, labeled with the value that leads to it, and whether it was
+    /// taken.
+    pub arms: Vec<(String, bool)>,
+}
+
+impl BranchLine {
+    fn reached(&self) -> bool {
+        self.arms.iter().any(|&(_, taken)| taken)
+    }
+}
+
+/// Numbers of lines, branch exits, and functions found and hit.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Totals {
+    pub lines_found: usize,
+    pub lines_hit: usize,
+    pub branches_found: usize,
+    pub branches_hit: usize,
+    pub functions_found: usize,
+    pub functions_hit: usize,
+}
+
+impl Totals {
+    pub fn add(&mut self, other: Totals) {
+        self.lines_found += other.lines_found;
+        self.lines_hit += other.lines_hit;
+        self.branches_found += other.branches_found;
+        self.branches_hit += other.branches_hit;
+        self.functions_found += other.functions_found;
+        self.functions_hit += other.functions_hit;
+    }
+}
+
+impl FileCoverage {
+    pub fn totals(&self) -> Totals {
+        let arms = self.branches.iter().flat_map(|b| &b.arms);
+        Totals {
+            lines_found: self.lines.len(),
+            lines_hit: self.lines.values().filter(|&&hit| hit).count(),
+            branches_found: arms.clone().count(),
+            branches_hit: arms.filter(|&&(_, taken)| taken).count(),
+            functions_found: self.functions.len(),
+            functions_hit: self.functions.iter().filter(|f| f.called).count(),
+        }
+    }
+}
+
+pub fn totals(files: &BTreeMap<String, FileCoverage>) -> Totals {
+    let mut t = Totals::default();
+    for fc in files.values() {
+        t.add(fc.totals());
+    }
+    t
+}
+
+/// Group the blocks, branches, and functions of `cov` by the file they appear in.  Each is placed
+/// at the start of its own span, not its macro callsite.  As with warnings, if `filters` is set,
+/// only those whose span or callsite falls within one of the filter regions are included;
+/// otherwise, unreached code is only included for files that some test reached.
+pub fn collect_files(
+    cov: &Coverage,
+    filters: Option<&[Filter]>,
+) -> BTreeMap<String, FileCoverage> {
+    let reached_files = cov.reached_files();
+    // Parse `span`, and check whether it should be included in the output.
+    let locate = |span: &str, reached: bool| -> Option<SpanInfo> {
+        let (sp, callsite) = parse_span(span)?;
+        let ok = match filters {
+            Some(filters) => span_in_filters(filters, &sp, callsite.as_ref()),
+            None => reached || reached_files.contains(&sp.filename),
+        };
+        if ok { Some(sp) } else { None }
+    };
+
+    let mut files = BTreeMap::<String, FileCoverage>::new();
+
+    // Blocks often have no source position, so failing to parse their spans isn't worth a
+    // warning.
+    for (key, visited) in cov.iter_blocks_sorted() {
+        if let Some(sp) = locate(key.span, false) {
+            *files.entry(sp.filename).or_default().lines.entry(sp.line1).or_default() |= visited;
+        }
+    }
+
+    for (fn_id, fcov) in cov.iter_functions_sorted() {
+        if let Some(sp) = fcov.span.and_then(|span| locate(span, false)) {
+            files.entry(sp.filename).or_default().functions.push(FunctionLine {
+                fn_id: fn_id.to_owned(),
+                line: sp.line1,
+                called: fcov.called,
+            });
+        }
+    }
+
+    for (key, bcov) in cov.iter_sorted() {
+        if parse_span(key.span).is_none() {
+            eprintln!("invalid span {:?}", key.span);
+            continue;
+        }
+        let sp = match locate(key.span, bcov.reached()) {
+            Some(x) => x,
+            None => continue,
+        };
+
+        let branch = BranchLine {
+            fn_id: key.fn_id.to_owned(),
+            span: key.span.to_owned(),
+            line: sp.line1,
+            col: sp.col1,
+            arms: bcov.arms(),
+        };
+        let fc = files.entry(sp.filename).or_default();
+        *fc.lines.entry(branch.line).or_default() |= branch.reached();
+        fc.branches.push(branch);
+    }
+    files
+}
+
+pub fn percent(hit: usize, found: usize) -> f64 {
+    if found == 0 {
+        100.0
+    } else {
+        100.0 * hit as f64 / found as f64
+    }
+}
+
+fn rate(hit: usize, found: usize) -> String {
+    format!("{:.4}", percent(hit, found) / 100.0)
+}
+
+
+/// Write an LCOV tracefile, as read by `genhtml` and most coverage services.
+pub fn write_lcov(out: &mut impl Write, files: &BTreeMap<String, FileCoverage>) -> io::Result<()> {
+    writeln!(out, "TN:")?;
+    for (filename, fc) in files {
+        writeln!(out, "SF:{}", filename)?;
+
+        for f in &fc.functions {
+            writeln!(out, "FN:{},{}", f.line, f.fn_id)?;
+        }
+        for f in &fc.functions {
+            writeln!(out, "FNDA:{},{}", f.called as u8, f.fn_id)?;
+        }
+
+        // LCOV identifies a branch by its line and a block number within that line.
+        let mut blocks_on_line = BTreeMap::<usize, usize>::new();
+        for b in &fc.branches {
+            let block = blocks_on_line.entry(b.line).or_default();
+            for (i, &(_, taken)) in b.arms.iter().enumerate() {
+                let taken = if !b.reached() { "-" } else if taken { "1" } else { "0" };
+                writeln!(out, "BRDA:{},{},{},{}", b.line, block, i, taken)?;
+            }
+            *block += 1;
+        }
+
+        for (&line, &hit) in &fc.lines {
+            writeln!(out, "DA:{},{}", line, hit as u8)?;
+        }
+
+        let t = fc.totals();
+        writeln!(out, "FNF:{}", t.functions_found)?;
+        writeln!(out, "FNH:{}", t.functions_hit)?;
+        writeln!(out, "BRF:{}", t.branches_found)?;
+        writeln!(out, "BRH:{}", t.branches_hit)?;
+        writeln!(out, "LF:{}", t.lines_found)?;
+        writeln!(out, "LH:{}", t.lines_hit)?;
+        writeln!(out, "end_of_record")?;
+    }
+    Ok(())
+}
+
+
+pub fn xml_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Write a Cobertura XML report.  Each directory becomes a package, and each file a class.
+pub fn write_cobertura(
+    out: &mut impl Write,
+    files: &BTreeMap<String, FileCoverage>,
+) -> io::Result<()> {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis());
+    let t = totals(files);
+    writeln!(out, r#"<?xml version="1.0" ?>"#)?;
+    writeln!(out, r#"<!DOCTYPE coverage SYSTEM "http://cobertura.sourceforge.net/xml/coverage-04.dtd">"#)?;
+    writeln!(
+        out,
+        r#"<coverage line-rate="{}" branch-rate="{}" lines-covered="{}" lines-valid="{}" branches-covered="{}" branches-valid="{}" complexity="0" version="{}" timestamp="{}">"#,
+        rate(t.lines_hit, t.lines_found), rate(t.branches_hit, t.branches_found),
+        t.lines_hit, t.lines_found, t.branches_hit, t.branches_found,
+        env!("CARGO_PKG_VERSION"), timestamp,
+    )?;
+    writeln!(out, "  <sources>")?;
+    writeln!(out, "    <source>.</source>")?;
+    writeln!(out, "  </sources>")?;
+    writeln!(out, "  <packages>")?;
+
+    let mut packages = BTreeMap::<&str, Vec<(&String, &FileCoverage)>>::new();
+    for (filename, fc) in files {
+        let dir = filename.rfind('/').map_or("", |i| &filename[..i]);
+        packages.entry(dir).or_default().push((filename, fc));
+    }
+
+    for (dir, pkg_files) in packages {
+        let mut pt = Totals::default();
+        for (_, fc) in &pkg_files {
+            pt.add(fc.totals());
+        }
+        writeln!(
+            out,
+            r#"    <package name="{}" line-rate="{}" branch-rate="{}" complexity="0">"#,
+            xml_escape(dir), rate(pt.lines_hit, pt.lines_found),
+            rate(pt.branches_hit, pt.branches_found),
+        )?;
+        writeln!(out, "      <classes>")?;
+        for (filename, fc) in pkg_files {
+            let ft = fc.totals();
+            writeln!(
+                out,
+                r#"        <class name="{}" filename="{}" line-rate="{}" branch-rate="{}" complexity="0">"#,
+                xml_escape(filename), xml_escape(filename),
+                rate(ft.lines_hit, ft.lines_found), rate(ft.branches_hit, ft.branches_found),
+            )?;
+            writeln!(out, "          <methods>")?;
+            for f in &fc.functions {
+                let hit = f.called as u8;
+                writeln!(
+                    out,
+                    r#"            <method name="{}" signature="" line-rate="{}" branch-rate="1" complexity="0">"#,
+                    xml_escape(&f.fn_id), hit,
+                )?;
+                writeln!(out, r#"              <lines><line number="{}" hits="{}"/></lines>"#,
+                    f.line, hit)?;
+                writeln!(out, "            </method>")?;
+            }
+            writeln!(out, "          </methods>")?;
+            writeln!(out, "          <lines>")?;
+            for (&line, &hit) in &fc.lines {
+                let arms = fc.branches.iter().filter(|b| b.line == line).flat_map(|b| &b.arms);
+                let found = arms.clone().count();
+                let taken = arms.filter(|&&(_, taken)| taken).count();
+                if found == 0 {
+                    writeln!(out, r#"            <line number="{}" hits="{}" branch="false"/>"#,
+                        line, hit as u8)?;
+                } else {
+                    writeln!(
+                        out,
+                        r#"            <line number="{}" hits="{}" branch="true" condition-coverage="{}% ({}/{})"/>"#,
+                        line, hit as u8, percent(taken, found).round(), taken, found,
+                    )?;
+                }
+            }
+            writeln!(out, "          </lines>")?;
+            writeln!(out, "        </class>")?;
+        }
+        writeln!(out, "      </classes>")?;
+        writeln!(out, "    </package>")?;
+    }
+
+    writeln!(out, "  </packages>")?;
+    writeln!(out, "</coverage>")?;
+    Ok(())
+}
+
+
+fn totals_json(t: Totals) -> serde_json::Value {
+    json!({
+        "lines_found": t.lines_found,
+        "lines_hit": t.lines_hit,
+        "branches_found": t.branches_found,
+        "branches_hit": t.branches_hit,
+        "functions_found": t.functions_found,
+        "functions_hit": t.functions_hit,
+    })
+}
+
+/// Write the per-file coverage as JSON, for tools that want more detail than LCOV provides.
+pub fn write_json(out: &mut impl Write, files: &BTreeMap<String, FileCoverage>) -> io::Result<()> {
+    let files_json = files.iter().map(|(filename, fc)| json!({
+        "filename": filename,
+        "lines": fc.lines.iter().map(|(&line, &hit)| json!({
+            "line": line,
+            "hit": hit,
+        })).collect::<Vec<_>>(),
+        "branches": fc.branches.iter().map(|b| json!({
+            "function": b.fn_id,
+            "span": b.span,
+            "line": b.line,
+            "column": b.col,
+            "arms": b.arms.iter().map(|(value, taken)| json!({
+                "value": value,
+                "taken": taken,
+            })).collect::<Vec<_>>(),
+        })).collect::<Vec<_>>(),
+        "functions": fc.functions.iter().map(|f| json!({
+            "function": f.fn_id,
+            "line": f.line,
+            "called": f.called,
+        })).collect::<Vec<_>>(),
+        "totals": totals_json(fc.totals()),
+    })).collect::<Vec<_>>();
+
+    let j = json!({
+        "files": files_json,
+        "totals": totals_json(totals(files)),
+    });
+    serde_json::to_writer_pretty(&mut *out, &j)?;
+    writeln!(out)
+}
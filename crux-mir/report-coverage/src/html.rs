@@ -0,0 +1,201 @@
+//! A static HTML coverage report: an index of source files and functions, and a page for each
+//! source file that shows its code with visited and unvisited blocks highlighted and the outcome
+//! of each branch arm marked.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::formats::{percent, xml_escape, BranchLine, FileCoverage, Totals};
+
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 1em 2em; }
+table { border-collapse: collapse; }
+th, td { padding: 0.1em 0.6em; text-align: left; }
+.summary td.num, .summary th.num { text-align: right; }
+.source { font-family: monospace; white-space: pre; }
+.source td { padding: 0 0.6em; vertical-align: top; }
+.source td.lineno { text-align: right; color: #888; }
+.source td.lineno a { color: inherit; text-decoration: none; }
+tr.covered td.code { background: #dfd; }
+tr.partial td.code { background: #ffd; }
+tr.uncovered td.code { background: #fdd; }
+.arm { display: inline-block; margin-right: 0.4em; padding: 0 0.3em; border-radius: 0.2em; }
+.arm.taken { background: #bdb; }
+.arm.missed { background: #e99; font-weight: bold; }
+td.missed { color: #c00; font-weight: bold; }
+";
+
+
+/// The name of the page for `filename`.  Each `/` becomes `_`, and any other character that isn't
+/// alphanumeric or `.` becomes its code point in hex between `-`s, so that no two files share a
+/// page.
+fn page_name(filename: &str) -> String {
+    let mut s = String::new();
+    for c in filename.chars() {
+        if c.is_ascii_alphanumeric() || c == '.' {
+            s.push(c);
+        } else if c == '/' {
+            s.push('_');
+        } else {
+            s.push_str(&format!("-{:x}-", c as u32));
+        }
+    }
+    s.push_str(".html");
+    s
+}
+
+fn called_cell(t: &Totals) -> &'static str {
+    match (t.functions_found, t.functions_hit) {
+        (0, _) => "<td>-</td>",
+        (_, 0) => "<td class=\"missed\">no</td>",
+        _ => "<td>yes</td>",
+    }
+}
+
+fn percent_cell(hit: usize, found: usize) -> String {
+    if found == 0 {
+        "<td class=\"num\">-</td>".to_owned()
+    } else {
+        format!("<td class=\"num\">{:.1}% ({}/{})</td>", percent(hit, found), hit, found)
+    }
+}
+
+fn write_header(out: &mut impl Write, title: &str) -> io::Result<()> {
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html><head><meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>{}</title>", xml_escape(title))?;
+    writeln!(out, "<style>{}</style>", STYLE)?;
+    writeln!(out, "</head><body>")?;
+    writeln!(out, "<h1>{}</h1>", xml_escape(title))
+}
+
+fn write_footer(out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "</body></html>")
+}
+
+/// Per-function totals for one file, keyed by function ID, along with the line where the
+/// function starts (or failing that, its first branch in the file).  `functions_found` is 1 if the
+/// function's entry is in this file.
+fn function_totals(fc: &FileCoverage) -> BTreeMap<&str, (usize, Totals)> {
+    let mut fns = BTreeMap::<&str, (usize, Totals)>::new();
+    for f in &fc.functions {
+        let t = Totals { functions_found: 1, functions_hit: f.called as usize, ..Totals::default() };
+        fns.insert(&f.fn_id, (f.line, t));
+    }
+    for b in &fc.branches {
+        let (line, t) = fns.entry(&b.fn_id).or_insert((b.line, Totals::default()));
+        *line = (*line).min(b.line);
+        t.branches_found += b.arms.len();
+        t.branches_hit += b.arms.iter().filter(|&&(_, taken)| taken).count();
+    }
+    fns
+}
+
+
+/// Write the report into `dir`: `index.html`, and one page per source file.
+pub fn write_html(dir: &Path, files: &BTreeMap<String, FileCoverage>) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+
+    let mut out = io::BufWriter::new(fs::File::create(dir.join("index.html"))?);
+    write_header(&mut out, "Coverage report")?;
+
+    writeln!(out, "<h2>Files</h2>")?;
+    writeln!(out, "<table class=\"summary\">")?;
+    writeln!(out, "<tr><th>File</th><th class=\"num\">Lines</th><th class=\"num\">Branches</th>\
+        <th class=\"num\">Functions</th></tr>")?;
+    for (filename, fc) in files {
+        let t = fc.totals();
+        writeln!(out, "<tr><td><a href=\"{}\">{}</a></td>{}{}{}</tr>",
+            xml_escape(&page_name(filename)), xml_escape(filename),
+            percent_cell(t.lines_hit, t.lines_found),
+            percent_cell(t.branches_hit, t.branches_found),
+            percent_cell(t.functions_hit, t.functions_found))?;
+    }
+    writeln!(out, "</table>")?;
+
+    writeln!(out, "<h2>Functions</h2>")?;
+    writeln!(out, "<table class=\"summary\">")?;
+    writeln!(out, "<tr><th>Function</th><th>File</th><th>Called</th><th class=\"num\">Branches</th></tr>")?;
+    let mut all_fns = Vec::new();
+    for (filename, fc) in files {
+        for (fn_id, (line, t)) in function_totals(fc) {
+            all_fns.push((fn_id, filename, line, t));
+        }
+    }
+    all_fns.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+    for (fn_id, filename, line, t) in all_fns {
+        writeln!(out, "<tr><td><a href=\"{}#L{}\">{}</a></td><td>{}</td>{}{}</tr>",
+            xml_escape(&page_name(filename)), line, xml_escape(fn_id), xml_escape(filename),
+            called_cell(&t), percent_cell(t.branches_hit, t.branches_found))?;
+    }
+    writeln!(out, "</table>")?;
+    write_footer(&mut out)?;
+    out.flush()?;
+
+    for (filename, fc) in files {
+        let mut out = io::BufWriter::new(fs::File::create(dir.join(page_name(filename)))?);
+        write_file_page(&mut out, filename, fc)?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+fn write_file_page(out: &mut impl Write, filename: &str, fc: &FileCoverage) -> io::Result<()> {
+    write_header(out, filename)?;
+    writeln!(out, "<p><a href=\"index.html\">Back to index</a></p>")?;
+
+    writeln!(out, "<table class=\"summary\">")?;
+    writeln!(out, "<tr><th>Function</th><th>Called</th><th class=\"num\">Branches</th></tr>")?;
+    for (fn_id, (line, t)) in function_totals(fc) {
+        writeln!(out, "<tr><td><a href=\"#L{}\">{}</a></td>{}{}</tr>", line, xml_escape(fn_id),
+            called_cell(&t), percent_cell(t.branches_hit, t.branches_found))?;
+    }
+    writeln!(out, "</table>")?;
+
+    let source = match fs::read_to_string(filename) {
+        Ok(x) => x,
+        Err(e) => {
+            writeln!(out, "<p>Couldn't read {}: {}</p>", xml_escape(filename), xml_escape(&e.to_string()))?;
+            return write_footer(out);
+        },
+    };
+
+    let mut branches_on_line = BTreeMap::<usize, Vec<&BranchLine>>::new();
+    for b in &fc.branches {
+        branches_on_line.entry(b.line).or_default().push(b);
+    }
+
+    writeln!(out, "<table class=\"source\">")?;
+    for (i, text) in source.lines().enumerate() {
+        let line = i + 1;
+        let branches = branches_on_line.get(&line).map_or(&[] as &[_], |v| &v[..]);
+        let class = match fc.lines.get(&line) {
+            None => "",
+            Some(false) => "uncovered",
+            Some(true) if branches.iter().all(|b| b.arms.iter().all(|&(_, taken)| taken)) =>
+                "covered",
+            Some(true) => "partial",
+        };
+
+        write!(out, "<tr class=\"{}\" id=\"L{}\"><td class=\"lineno\"><a href=\"#L{}\">{}</a></td>",
+            class, line, line, line)?;
+        write!(out, "<td class=\"code\">{}</td><td>", xml_escape(text))?;
+        for b in branches {
+            for (value, taken) in &b.arms {
+                let (cls, title) = if *taken {
+                    ("taken", "taken")
+                } else {
+                    ("missed", "never taken")
+                };
+                write!(out, "<span class=\"arm {}\" title=\"{}: {}\">{}</span>", cls,
+                    xml_escape(&b.span), title, xml_escape(value))?;
+            }
+        }
+        writeln!(out, "</td></tr>")?;
+    }
+    writeln!(out, "</table>")?;
+    write_footer(out)
+}
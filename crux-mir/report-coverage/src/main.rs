@@ -1,16 +1,22 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fmt::Display;
 use std::fs;
+use std::io;
 #[allow(deprecated)]
 use std::hash::{Hash, Hasher, SipHasher};
-use std::path::Path;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::rc::Rc;
 use clap::{App, Arg, ArgMatches};
 use codespan_reporting::diagnostic::{Diagnostic, Label};
 use codespan_reporting::files::{SimpleFiles, Files};
 use serde_json::Value;
-use termcolor;
 
+mod diff;
+mod formats;
+mod html;
+mod per_test;
 
 fn parse_args() -> ArgMatches<'static> {
     App::new("crux-report-coverage")
@@ -18,9 +24,27 @@ fn parse_args() -> ArgMatches<'static> {
         .arg(Arg::with_name("input")
              .takes_value(true)
              .value_name("REPORT_DATA.JS")
-             .help("coverage data file produced by crux-mir")
+             .help("coverage data file produced by crux-mir, or a directory to search for them")
              .required(true)
              .multiple(true))
+        .arg(Arg::with_name("baseline")
+             .long("baseline")
+             .takes_value(true)
+             .value_name("REPORT_DATA.JS")
+             .help("compare against the coverage data in these files or directories, printing \
+                    the branches that are newly covered or uncovered")
+             .multiple(true)
+             .number_of_values(1))
+        .arg(Arg::with_name("per-test")
+             .long("per-test")
+             .conflicts_with("baseline")
+             .help("print which branch arms each test covers, and a smallest set of tests that \
+                    covers all of them"))
+        .arg(Arg::with_name("min-branch-coverage")
+             .long("min-branch-coverage")
+             .takes_value(true)
+             .value_name("PERCENT")
+             .help("exit with an error if less than this percentage of branch arms are covered"))
         .arg(Arg::with_name("filter")
              .short("f")
              .long("filter")
@@ -36,6 +60,26 @@ fn parse_args() -> ArgMatches<'static> {
         .arg(Arg::with_name("no-color")
              .long("no-color")
              .help("don't colorize output"))
+        .arg(Arg::with_name("unreached")
+             .long("unreached")
+             .help("also warn about branches that no test reached and functions that no test \
+                    called"))
+        .arg(Arg::with_name("format")
+             .long("format")
+             .takes_value(true)
+             .value_name("FORMAT")
+             .possible_values(&["text", "lcov", "cobertura", "json", "html"])
+             .default_value("text")
+             .help("output format: warnings for uncovered branches (`text`), line and branch \
+                    coverage for each file (`lcov`, `cobertura`, `json`), or an annotated copy \
+                    of the source (`html`, written to `--output-dir`)"))
+        .arg(Arg::with_name("output-dir")
+             .short("o")
+             .long("output-dir")
+             .takes_value(true)
+             .value_name("DIR")
+             .required_if("format", "html")
+             .help("directory to write the HTML report into"))
         .get_matches()
 }
 
@@ -79,26 +123,26 @@ struct BranchReport {
 
 fn parse_report_into(json: Value, r: &mut Report) -> Result<(), String> {
     let sections = json.as_array()
-        .ok_or_else(|| format!("expected array at top level"))?;
+        .ok_or_else(|| "expected array at top level".to_owned())?;
     for sec in sections {
         let sec = sec.as_object()
-            .ok_or_else(|| format!("expected section to be an object"))?;
-        if !sec.get("type").map_or(false, |j| j == "callgraph") {
+            .ok_or_else(|| "expected section to be an object".to_owned())?;
+        if !sec.get("type").is_some_and(|j| j == "callgraph") {
             continue;
         }
 
         let events = sec.get("events")
-            .ok_or_else(|| format!("callgraph section has no `events` field"))?;
+            .ok_or_else(|| "callgraph section has no `events` field".to_owned())?;
         let events = events.as_array()
-            .ok_or_else(|| format!("expected callgraph `events` field to be an array"))?;
+            .ok_or_else(|| "expected callgraph `events` field to be an array".to_owned())?;
         for evt in events {
             let evt = evt.as_object()
-                .ok_or_else(|| format!("expected event to be an object"))?;
-            if evt.get("type").map_or(false, |j| j == "BLOCK") {
+                .ok_or_else(|| "expected event to be an object".to_owned())?;
+            if evt.get("type").is_some_and(|j| j == "BLOCK") {
                 let fn_id = event_function(evt)?;
-                r.fns.entry(fn_id).or_insert_with(FnReport::default)
+                r.fns.entry(fn_id).or_default()
                     .visited_blocks.extend(event_blocks(evt)?);
-            } else if evt.get("type").map_or(false, |j| j == "BRANCH") {
+            } else if evt.get("type").is_some_and(|j| j == "BRANCH") {
                 let fn_id = event_function(evt)?;
                 let span = event_callsite(evt)?;
                 if let Some((branch_id, index)) = parse_callsite(&span) {
@@ -108,7 +152,7 @@ fn parse_report_into(json: Value, r: &mut Report) -> Result<(), String> {
                     }
                     let mut it = blocks.into_iter();
                     let dests = [it.next().unwrap(), it.next().unwrap()];
-                    r.fns.entry(fn_id).or_insert_with(FnReport::default)
+                    r.fns.entry(fn_id).or_default()
                         .branches.push(BranchReport { branch_id, index, dests });
                 }
             }
@@ -123,7 +167,7 @@ fn event_callsite(evt: &serde_json::Map<String, Value>) -> Result<String, String
         None => return Ok(String::new()),
     };
     Ok(callsite.as_str()
-       .ok_or_else(|| format!("expected event `callsite` field to be a string"))?
+       .ok_or_else(|| "expected event `callsite` field to be a string".to_owned())?
        .to_owned())
 }
 
@@ -142,9 +186,9 @@ fn parse_callsite(s: &str) -> Option<(u32, usize)> {
 
 fn event_function(evt: &serde_json::Map<String, Value>) -> Result<FnId, String> {
     Ok(evt.get("function")
-       .ok_or_else(|| format!("event has no `function` field"))?
+       .ok_or_else(|| "event has no `function` field".to_owned())?
        .as_str()
-       .ok_or_else(|| format!("expected event `function` field to be a string"))?
+       .ok_or_else(|| "expected event `function` field to be a string".to_owned())?
        .to_owned())
 }
 
@@ -154,10 +198,10 @@ fn event_blocks(evt: &serde_json::Map<String, Value>) -> Result<Vec<BlockId>, St
         None => return Ok(Vec::new()),
     };
     let blocks = blocks.as_array()
-        .ok_or_else(|| format!("expected event `blocks` field to be an array"))?;
+        .ok_or_else(|| "expected event `blocks` field to be an array".to_owned())?;
     let names = blocks.iter().map(|blk| -> Result<_, String> {
         let blk = blk.as_str()
-            .ok_or_else(|| format!("expected `blocks` entry to be a string"))?;
+            .ok_or_else(|| "expected `blocks` entry to be a string".to_owned())?;
         Ok(BlockId(blk.to_owned()))
     }).collect::<Result<Vec<_>, _>>()?;
     Ok(names)
@@ -173,6 +217,9 @@ struct Trans {
 struct FnTrans {
     branches: Vec<BranchTrans>,
     unreachable: HashSet<RegBlockId>,
+    /// The span where each block starts.  Blocks that end in an error have no span.
+    block_spans: HashMap<BlockId, String>,
+    entry_block: Option<BlockId>,
 }
 
 #[derive(Clone, Debug)]
@@ -191,19 +238,25 @@ impl BranchTrans {
         }
     }
 
-    fn is_drop_flag(&self) -> bool {
+    /// The source span of the branch.  Drop flag branches have none.
+    fn span(&self) -> &str {
         match *self {
-            BranchTrans::DropFlag => true,
-            _ => false,
+            BranchTrans::Bool(_, ref span) => span,
+            BranchTrans::Int(_, _, ref span) => span,
+            BranchTrans::DropFlag => "",
         }
     }
+
+    fn is_drop_flag(&self) -> bool {
+        matches!(*self, BranchTrans::DropFlag)
+    }
 }
 
 fn parse_trans(json: Value) -> Result<Trans, String> {
     let mut t = Trans::default();
 
     let fns = json.as_object()
-        .ok_or_else(|| format!("expected object at top level"))?;
+        .ok_or_else(|| "expected object at top level".to_owned())?;
     for (fn_name, fn_json) in fns {
         let ft = t.fns.entry(fn_name.to_owned()).or_insert_with(FnTrans::default);
 
@@ -228,6 +281,23 @@ fn parse_trans(json: Value) -> Result<Trans, String> {
             let block_id = RegBlockId(block_id.to_owned());
             ft.unreachable.insert(block_id);
         }
+
+        // Translations from older versions of crux-mir have no block spans.
+        if let Some(spans_json) = fn_json.get("_ftiBlockSpans") {
+            let spans_json = spans_json.as_object()
+                .ok_or_else(|| format!("expected {:?} _ftiBlockSpans to be an object", fn_name))?;
+            for (block_id, span) in spans_json {
+                let span = span.as_str()
+                    .ok_or_else(|| format!("expected {:?} block span to be a string", fn_name))?;
+                ft.block_spans.insert(BlockId(block_id.to_owned()), span.to_owned());
+            }
+        }
+
+        if let Some(entry_json) = fn_json.get("_ftiEntryBlock").filter(|j| !j.is_null()) {
+            let entry = entry_json.as_str()
+                .ok_or_else(|| format!("expected {:?} _ftiEntryBlock to be a string", fn_name))?;
+            ft.entry_block = Some(BlockId(entry.to_owned()));
+        }
     }
 
     Ok(t)
@@ -235,14 +305,14 @@ fn parse_trans(json: Value) -> Result<Trans, String> {
 
 fn parse_branch(json: &Value) -> Result<BranchTrans, String> {
     let obj = json.as_object()
-        .ok_or_else(|| format!("expected branch value to be an object"))?;
+        .ok_or_else(|| "expected branch value to be an object".to_owned())?;
     let tag = obj.get("tag")
-        .ok_or_else(|| format!("branch object has no `tag`"))?
+        .ok_or_else(|| "branch object has no `tag`".to_owned())?
         .as_str()
-        .ok_or_else(|| format!("expected `tag` to be a string"))?;
+        .ok_or_else(|| "expected `tag` to be a string".to_owned())?;
     let args = match obj.get("contents") {
         Some(x) => x.as_array()
-            .ok_or_else(|| format!("expected `contents` to be an array"))? as &[Value],
+            .ok_or_else(|| "expected `contents` to be an array".to_owned())? as &[Value],
         None => &[],
     };
 
@@ -300,7 +370,7 @@ fn parse_branch(json: &Value) -> Result<BranchTrans, String> {
         },
 
         "DropFlagBranch" => {
-            if args.len() != 0 {
+            if !args.is_empty() {
                 die!("expected 3 args for {}, but got {}", tag, args.len());
             }
             Ok(BranchTrans::DropFlag)
@@ -313,10 +383,11 @@ fn parse_branch(json: &Value) -> Result<BranchTrans, String> {
 }
 
 
+/// A branch or block, identified by its function and its span.
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
 struct CoverageKey<'a> {
     fn_id: &'a str,
-    branch_span: &'a str,
+    span: &'a str,
 }
 
 struct Coverage<'a> {
@@ -325,6 +396,23 @@ struct Coverage<'a> {
     /// source function; otherwise, every exit must be covered in every monomorphization.
     merge_functions: bool,
     branches: HashMap<CoverageKey<'a>, BranchCoverage>,
+    /// Whether any block starting at each span was visited.
+    blocks: HashMap<CoverageKey<'a>, bool>,
+    functions: HashMap<&'a str, FunctionCoverage<'a>>,
+    /// Instances seen so far, keyed by their function (without the instance disambiguator) and
+    /// the spans of their branches.  Only used when `merge_functions` is unset.
+    instances: HashMap<(&'a str, Vec<&'a str>), Vec<&'a str>>,
+    /// Instances that correspond to a differently-named instance from an earlier translation,
+    /// mapped to the name of that instance.
+    aliases: HashMap<&'a str, &'a str>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct FunctionCoverage<'a> {
+    /// The span of the function's entry block, if known.
+    span: Option<&'a str>,
+    /// Whether any test called the function.
+    called: bool,
 }
 
 impl<'a> Coverage<'a> {
@@ -332,40 +420,112 @@ impl<'a> Coverage<'a> {
         Coverage {
             merge_functions,
             branches: HashMap::new(),
+            blocks: HashMap::new(),
+            functions: HashMap::new(),
+            instances: HashMap::new(),
+            aliases: HashMap::new(),
+        }
+    }
+
+    fn fn_key(&self, fn_id: &'a FnId) -> &'a str {
+        if self.merge_functions {
+            strip_instance(fn_id)
+        } else {
+            self.aliases.get(fn_id as &str).copied().unwrap_or(fn_id)
+        }
+    }
+
+    /// Match the instances in `trans` with those from earlier translations.  Instance
+    /// disambiguators can change when a library is rebuilt, so an instance that's new in `trans`
+    /// is treated as the same as an earlier one if that one is missing from `trans`, and both are
+    /// instances of the same function with the same branch spans.  If there are several such
+    /// earlier instances, none of them is used.
+    pub fn match_instances(&mut self, trans: &'a Trans) {
+        let mut new_instances = Vec::new();
+        for (fn_id, ft) in &trans.fns {
+            let spans = ft.branches.iter().map(|bt| bt.span()).collect::<Vec<_>>();
+            let sig = (strip_instance(fn_id), spans);
+            let earlier = self.instances.get(&sig).map_or(&[] as &[_], |v| &v[..]);
+            if !earlier.contains(&(fn_id as &str)) {
+                let candidates = earlier.iter()
+                    .filter(|&&id| !trans.fns.contains_key(id))
+                    .collect::<Vec<_>>();
+                if let [&old_id] = candidates[..] {
+                    let old_id = self.aliases.get(old_id).copied().unwrap_or(old_id);
+                    self.aliases.insert(fn_id, old_id);
+                }
+            }
+            new_instances.push((sig, fn_id as &str));
+        }
+        for (sig, fn_id) in new_instances {
+            let ids = self.instances.entry(sig).or_default();
+            if !ids.contains(&fn_id) {
+                ids.push(fn_id);
+            }
         }
     }
 
     fn key(&self, fn_id: &'a FnId, span: &'a str) -> CoverageKey<'a> {
         CoverageKey {
-            fn_id: if self.merge_functions {
-                strip_instance(fn_id)
-            } else {
-                fn_id as &str
-            },
-            branch_span: span,
+            fn_id: self.fn_key(fn_id),
+            span,
         }
     }
 
     pub fn branch(&mut self, fn_id: &'a FnId, span: &'a str) -> &mut BranchCoverage {
         let key = self.key(fn_id, span);
-        self.branches.entry(key).or_insert_with(BranchCoverage::default)
+        self.branches.entry(key).or_default()
+    }
+
+    pub fn block(&mut self, fn_id: &'a FnId, span: &'a str) -> &mut bool {
+        let key = self.key(fn_id, span);
+        self.blocks.entry(key).or_default()
+    }
+
+    pub fn function(&mut self, fn_id: &'a FnId) -> &mut FunctionCoverage<'a> {
+        let key = self.fn_key(fn_id);
+        self.functions.entry(key).or_default()
+    }
+
+    pub fn iter_blocks_sorted(&self) -> impl Iterator<Item = (CoverageKey<'a>, bool)> + '_ {
+        let mut keys = self.blocks.keys().collect::<Vec<_>>();
+        keys.sort();
+        keys.into_iter().map(move |k| (*k, self.blocks[k]))
+    }
+
+    pub fn iter_functions_sorted(&self) -> impl Iterator<Item = (&'a str, &FunctionCoverage<'a>)> + '_ {
+        let mut keys = self.functions.keys().collect::<Vec<_>>();
+        keys.sort();
+        keys.into_iter().map(move |&k| (k, &self.functions[k]))
+    }
+
+    /// The source files containing at least one visited block.  Unless there are filters, block
+    /// and function coverage is only reported for these files, so that the untested parts of
+    /// libraries, which are translated along with the crate under test, don't drown out
+    /// everything else.
+    pub fn reached_files(&self) -> HashSet<String> {
+        self.blocks.iter()
+            .filter(|&(_, &visited)| visited)
+            .filter_map(|(k, _)| parse_span(k.span))
+            .map(|(sp, _)| sp.filename)
+            .collect()
     }
 
-    pub fn iter_sorted<'b>(&'b self) -> impl Iterator<Item = (&'a str, &'b BranchCoverage)> + 'b {
+    pub fn iter_sorted<'b>(&'b self) -> impl Iterator<Item = (CoverageKey<'a>, &'b BranchCoverage)> + 'b {
         let mut keys = self.branches.keys().collect::<Vec<_>>();
         keys.sort();
-        keys.into_iter().map(move |k| (k.branch_span, self.branches.get(k).unwrap()))
+        keys.into_iter().map(move |k| (*k, self.branches.get(k).unwrap()))
     }
 }
 
 /// If `s` ends with something that looks like a monomorphized instance disambiguator
 /// (`::inst0123456789abcdef[0]`), then remove that suffix.  If no such suffix is present, `s` is
 /// returned unchanged.
-fn strip_instance<'a>(s: &'a str) -> &'a str {
+fn strip_instance(s: &str) -> &str {
     try_strip_instance(s).unwrap_or(s)
 }
 
-fn try_strip_instance<'a>(s: &'a str) -> Option<&'a str> {
+fn try_strip_instance(s: &str) -> Option<&str> {
     if !s.ends_with("[0]") {
         return None;
     }
@@ -403,10 +563,52 @@ struct BranchCoverage {
     pub is_boolean: bool,
 }
 
-fn process<'a>(cov: &mut Coverage<'a>, fn_id: &'a FnId, report: &'a FnReport, trans: &'a FnTrans) {
+impl BranchCoverage {
+    /// Whether any test reached this branch at all.
+    pub fn reached(&self) -> bool {
+        !self.seen.is_empty() || self.default_seen
+    }
+
+    /// Whether to treat this as a boolean branch, which requires that all of the branches merged
+    /// into it were boolean.
+    pub fn is_bool(&self) -> bool {
+        self.is_boolean &&
+            self.possible.iter().all(|&x| x == 0 || x == 1) &&
+            !self.default_possible
+    }
+
+    /// The possible exits of the branch, each labeled with the value that leads to it, and
+    /// whether that exit was taken.  The default exit, if any, comes last, labeled `otherwise`.
+    pub fn arms(&self) -> Vec<(String, bool)> {
+        if self.is_bool() {
+            return [(1, "true"), (0, "false")].iter()
+                .filter(|&&(val, _)| self.possible.contains(&val))
+                .map(|&(val, name)| (name.to_owned(), self.seen.contains(&val)))
+                .collect();
+        }
+
+        let mut possible = self.possible.iter().cloned().collect::<Vec<_>>();
+        possible.sort();
+        let mut arms = possible.into_iter()
+            .map(|val| (val.to_string(), self.seen.contains(&val)))
+            .collect::<Vec<_>>();
+        if self.default_possible {
+            arms.push(("otherwise".to_owned(), self.default_seen));
+        }
+        arms
+    }
+}
+
+/// Record the outcomes of every branch in a function.  `report` is `None` if no test called the
+/// function, in which case none of its branches were reached.
+fn process<'a>(
+    cov: &mut Coverage<'a>,
+    fn_id: &'a FnId,
+    report: Option<&'a FnReport>,
+    trans: &'a FnTrans,
+) {
     // Maps (branch ID, dest index) to the Core `BlockId` of the destination.
     let mut dest_map: HashMap<(u32, usize), BlockId> = HashMap::new();
-    let mut visited_branches = HashSet::new();
 
     fn insert_dest(
         fn_id: &FnId,
@@ -426,7 +628,7 @@ fn process<'a>(cov: &mut Coverage<'a>, fn_id: &'a FnId, report: &'a FnReport, tr
         }
     }
 
-    for branch in &report.branches {
+    for branch in report.iter().flat_map(|r| &r.branches) {
         let BranchReport { branch_id, index, ref dests } = *branch;
 
         let bt = match trans.branches.get(branch_id as usize) {
@@ -449,8 +651,6 @@ fn process<'a>(cov: &mut Coverage<'a>, fn_id: &'a FnId, report: &'a FnReport, tr
             continue;
         }
 
-        visited_branches.insert(branch_id);
-
         insert_dest(fn_id, &mut dest_map, branch_id, index, &dests[0]);
 
         if index + 2 == bt.dests().len() {
@@ -461,17 +661,15 @@ fn process<'a>(cov: &mut Coverage<'a>, fn_id: &'a FnId, report: &'a FnReport, tr
     }
 
 
-    let mut branch_ids = visited_branches.into_iter().collect::<Vec<_>>();
-    branch_ids.sort();
-    for branch_id in branch_ids {
-        let bt = &trans.branches[branch_id as usize];
+    for (branch_id, bt) in trans.branches.iter().enumerate() {
+        let branch_id = branch_id as u32;
 
         let dest_visited = |index| {
             let block_id = match dest_map.get(&(branch_id, index)) {
                 Some(x) => x,
                 None => return false,
             };
-            report.visited_blocks.contains(block_id)
+            report.is_some_and(|r| r.visited_blocks.contains(block_id))
         };
 
         let dest_unreachable = |index| {
@@ -532,6 +730,27 @@ fn process<'a>(cov: &mut Coverage<'a>, fn_id: &'a FnId, report: &'a FnReport, tr
     }
 }
 
+/// Record which blocks of a function were visited, and whether it was called at all.  `report`
+/// is `None` if no test called the function.
+fn process_blocks<'a>(
+    cov: &mut Coverage<'a>,
+    fn_id: &'a FnId,
+    report: Option<&'a FnReport>,
+    trans: &'a FnTrans,
+) {
+    let visited = |block_id| report.is_some_and(|r| r.visited_blocks.contains(block_id));
+    for (block_id, span) in &trans.block_spans {
+        *cov.block(fn_id, span) |= visited(block_id);
+    }
+
+    let span = trans.entry_block.as_ref().and_then(|b| trans.block_spans.get(b));
+    let fcov = cov.function(fn_id);
+    fcov.called |= report.is_some_and(|r| !r.visited_blocks.is_empty());
+    if fcov.span.is_none() {
+        fcov.span = span.map(|s| s as &str);
+    }
+}
+
 
 struct SpanInfo {
     filename: String,
@@ -628,16 +847,8 @@ impl Reporter {
             },
         };
 
-        // If any `--filter` option was passed, then either the span or the callsite must fall
-        // within one of the filter regions.
         if let Some(ref filters) = self.filters {
-            let span_ok = filters.iter().any(|f| f.contains_span(&sp));
-            let callsite_ok = match callsite {
-                Some(ref callsite) => filters.iter().any(|f| f.contains_span(callsite)),
-                None => false,
-            };
-
-            if !span_ok && !callsite_ok {
+            if !span_in_filters(filters, &sp, callsite.as_ref()) {
                 return;
             }
         }
@@ -676,10 +887,7 @@ impl Reporter {
             return id;
         }
 
-        let content = match fs::read_to_string(name) {
-            Ok(x) => x,
-            Err(_) => String::new(),
-        };
+        let content = fs::read_to_string(name).unwrap_or_default();
         let id = self.files.add(name.to_owned(), content);
         self.file_map.insert(name.to_owned(), id);
         id
@@ -720,7 +928,7 @@ impl Filter {
                 None => return Err("expected `[LINE]-[LINE]` after `:`".to_owned()),
             };
             fn parse(s: &str) -> Result<Option<usize>, String> {
-                if s.len() == 0 {
+                if s.is_empty() {
                     Ok(None)
                 } else {
                     s.parse::<usize>().map(Some).map_err(|e| e.to_string())
@@ -740,18 +948,45 @@ impl Filter {
         // at the start of a line as covering that line.  But hopefully nobody is quite that picky
         // about the placement of their filters...
         self.filename == sp.filename &&
-            self.start_line.map_or(true, |line| sp.line2 >= line) &&
-            self.end_line.map_or(true, |line| sp.line1 <= line)
+            self.start_line.is_none_or(|line| sp.line2 >= line) &&
+            self.end_line.is_none_or(|line| sp.line1 <= line)
     }
 }
 
+/// If any `--filter` option was passed, then either the span or the callsite must fall within one
+/// of the filter regions.
+fn span_in_filters(filters: &[Filter], sp: &SpanInfo, callsite: Option<&SpanInfo>) -> bool {
+    filters.iter().any(|f| f.contains_span(sp)) ||
+        callsite.is_some_and(|callsite| filters.iter().any(|f| f.contains_span(callsite)))
+}
+
+
+/// Warn about every branch arm that no test took.  With `unreached`, also warn about branches that
+/// no test reached and functions that no test called.  Without it, branches in functions that no
+/// test called are skipped, and a branch that was never reached gets a warning for each value it
+/// never had.
+fn report_all(reporter: &mut Reporter, cov: &Coverage, unreached: bool) {
+    // Without filters, only complain about unreached code in files that some test reached.
+    let reached_files = cov.reached_files();
+    let have_filters = reporter.filters.is_some();
+    let in_reached_file = |span: &str| {
+        have_filters || parse_span(span)
+            .is_some_and(|(sp, _)| reached_files.contains(&sp.filename))
+    };
+
+    for (key, bcov) in cov.iter_sorted() {
+        let span = key.span;
+        if !unreached && !cov.functions.get(key.fn_id).is_some_and(|f| f.called) {
+            continue;
+        }
+        if unreached && !bcov.reached() {
+            if in_reached_file(span) {
+                reporter.warn(span, "branch never reached by any symbolic test");
+            }
+            continue;
+        }
 
-fn report_all(reporter: &mut Reporter, cov: &Coverage) {
-    for (span, bcov) in cov.iter_sorted() {
-        let is_boolean = bcov.is_boolean &&
-            bcov.possible.iter().all(|&x| x == 0 || x == 1) &&
-            !bcov.default_possible;
-        if is_boolean {
+        if bcov.is_bool() {
             if !bcov.seen.contains(&0) {
                 reporter.warn(span, "branch condition never has value false");
             }
@@ -776,6 +1011,19 @@ fn report_all(reporter: &mut Reporter, cov: &Coverage) {
             );
         }
     }
+
+    if !unreached {
+        return;
+    }
+    for (_, fcov) in cov.iter_functions_sorted() {
+        let span = match fcov.span {
+            Some(x) => x,
+            None => continue,
+        };
+        if !fcov.called && in_reached_file(span) {
+            reporter.warn(span, "function never called by any symbolic test");
+        }
+    }
 }
 
 
@@ -803,14 +1051,121 @@ fn main() {
             filters.push(f);
         }
     }
-    let filters = if filters.len() > 0 { Some(filters) } else { None };
+    let filters = if !filters.is_empty() { Some(filters) } else { None };
+
+    let min_branch_coverage = m.value_of("min-branch-coverage").map(|s| match s.parse::<f64>() {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("bad branch coverage percentage {:?}: {}", s, e);
+            eprintln!("{}", m.usage());
+            process::exit(1);
+        },
+    });
+
+    // `--format` has a default value, so `conflicts_with` wouldn't catch this.
+    if m.is_present("per-test") && m.occurrences_of("format") > 0 {
+        eprintln!("--per-test can't be combined with --format");
+        eprintln!("{}", m.usage());
+        process::exit(1);
+    }
+
+    let default_ft = FnTrans::default();
+    let merge_monos = !m.is_present("no-merge-monos");
+    let paths = find_reports(m.values_of_os("input").unwrap());
+    let per_test = m.is_present("per-test");
+    let groups = load_reports(&paths, per_test);
+    let coverage = compute_coverage(&groups, &default_ft, merge_monos);
+    let files = formats::collect_files(&coverage, filters.as_deref());
+
+    if per_test {
+        let names = test_names(&paths);
+        let test_files = groups.iter()
+            .map(|g| {
+                let cov = compute_coverage(std::slice::from_ref(g), &default_ft, merge_monos);
+                formats::collect_files(&cov, filters.as_deref())
+            })
+            .collect::<Vec<_>>();
+        let tests = names.iter().map(|s| s as &str).zip(&test_files).collect::<Vec<_>>();
+        per_test::write_per_test(&mut io::stdout().lock(), &tests, &files).unwrap();
+    } else if let Some(baseline) = m.values_of_os("baseline") {
+        let old_groups = load_reports(&find_reports(baseline), false);
+        let old_coverage = compute_coverage(&old_groups, &default_ft, merge_monos);
+        let old_files = formats::collect_files(&old_coverage, filters.as_deref());
+        diff::write_diff(&mut io::stdout().lock(), &old_files, &files).unwrap();
+    } else {
+        write_output(&m, &coverage, &files, filters);
+    }
 
-    let mut report = Report::default();
-    let mut trans = None;
-    let mut trans_hash = None;
+    if let Some(min) = min_branch_coverage {
+        let t = formats::totals(&files);
+        let pct = formats::percent(t.branches_hit, t.branches_found);
+        if pct < min {
+            eprintln!("branch coverage {:.1}% ({}/{}) is below the minimum of {}%",
+                pct, t.branches_hit, t.branches_found, min);
+            process::exit(1);
+        }
+    }
+}
+
+/// Expand the input paths into a list of reports.  A directory stands for all of the
+/// `report_data.js` files under it, such as the per-test reports in a crux-mir output directory.
+fn find_reports<'a>(paths: impl Iterator<Item = &'a OsStr>) -> Vec<PathBuf> {
+    fn walk(dir: &Path, out: &mut Vec<PathBuf>) {
+        let mut entries = fs::read_dir(dir).unwrap()
+            .map(|e| e.unwrap().path())
+            .collect::<Vec<_>>();
+        entries.sort();
+        for path in entries {
+            if path.is_dir() {
+                walk(&path, out);
+            } else if path.file_name().is_some_and(|n| n == "report_data.js") {
+                out.push(path);
+            }
+        }
+    }
 
-    for report_path_str in m.values_of_os("input").unwrap() {
-        let report_path = Path::new(report_path_str);
+    let mut out = Vec::new();
+    for path in paths {
+        let path = Path::new(path);
+        if path.is_dir() {
+            walk(path, &mut out);
+        } else {
+            out.push(path.to_owned());
+        }
+    }
+    out
+}
+
+/// The name of the test that produced each report.  crux-mir writes each test's report to
+/// `<output dir>/<test name>/report_data.js`, so this is the name of the report's directory, or
+/// the whole directory path if the same test name appears in several output directories.
+fn test_names(paths: &[PathBuf]) -> Vec<String> {
+    let dirs = paths.iter()
+        .map(|p| p.parent().unwrap_or_else(|| Path::new("")))
+        .collect::<Vec<_>>();
+    let short = dirs.iter()
+        .map(|d| d.file_name().map_or_else(|| d.display().to_string(),
+                                           |n| n.to_string_lossy().into_owned()))
+        .collect::<Vec<_>>();
+    let mut counts = HashMap::<&str, usize>::new();
+    for name in &short {
+        *counts.entry(name).or_default() += 1;
+    }
+    short.iter().zip(&dirs)
+        .map(|(name, dir)| if counts[name as &str] > 1 { dir.display().to_string() } else { name.clone() })
+        .collect()
+}
+
+/// Load the reports at `paths`, along with the translation metadata next to each one.  Branch and
+/// block IDs in a report refer to the translation it was produced with, so reports are grouped by
+/// translation, and the reports in each group are merged.  Reports from different crates, or from
+/// before and after a rebuild, end up in different groups.  With `per_report`, each report gets
+/// a group of its own, in the order of `paths`.
+fn load_reports(paths: &[PathBuf], per_report: bool) -> Vec<(Report, Rc<Trans>)> {
+    let mut groups = Vec::<(Report, Rc<Trans>)>::new();
+    let mut group_by_hash = HashMap::<u64, usize>::new();
+
+    for report_path in paths {
         let trans_path = report_path.with_file_name("translation.json");
 
         let report_bytes = fs::read(report_path).unwrap();
@@ -819,38 +1174,79 @@ fn main() {
         let report_json: Value = serde_json::from_slice(&report_bytes[idx0..idx1]).unwrap();
         drop(report_bytes);
 
-        parse_report_into(report_json, &mut report).unwrap();
-
         let trans_bytes = fs::read(trans_path).unwrap();
-        let cur_trans_hash = hash(&trans_bytes);
-        if let Some(old_trans_hash) = trans_hash {
-            assert!(
-                cur_trans_hash == old_trans_hash,
-                "translation hashes for {:?} and {:?} do not match",
-                report_path_str, m.value_of_os("input").unwrap(),
-            );
-        } else {
-            let trans_json: Value = serde_json::from_slice(&trans_bytes).unwrap();
-            drop(trans_bytes);
-            let cur_trans = parse_trans(trans_json).unwrap();
-            trans = Some(cur_trans);
-            trans_hash = Some(cur_trans_hash);
-        }
+        let trans_hash = hash(&trans_bytes);
+        let idx = match group_by_hash.get(&trans_hash) {
+            Some(&idx) if !per_report => idx,
+            Some(&idx) => {
+                let trans = groups[idx].1.clone();
+                groups.push((Report::default(), trans));
+                groups.len() - 1
+            },
+            None => {
+                let trans_json: Value = serde_json::from_slice(&trans_bytes).unwrap();
+                drop(trans_bytes);
+                groups.push((Report::default(), Rc::new(parse_trans(trans_json).unwrap())));
+                group_by_hash.insert(trans_hash, groups.len() - 1);
+                groups.len() - 1
+            },
+        };
+
+        parse_report_into(report_json, &mut groups[idx].0).unwrap();
     }
 
-    let trans = match trans {
-        Some(x) => x,
-        None => {
-            panic!("must provide at least one report file");
-        },
-    };
+    if groups.is_empty() {
+        panic!("must provide at least one report file");
+    }
+    groups
+}
 
-    let default_ft = FnTrans::default();
-    let merge_monos = !m.is_present("no-merge-monos");
+/// Compute the coverage of each group of reports, and merge the results by function and span.
+/// Functions that are missing from a group's translation use `default_ft`.
+fn compute_coverage<'a>(
+    groups: &'a [(Report, Rc<Trans>)],
+    default_ft: &'a FnTrans,
+    merge_monos: bool,
+) -> Coverage<'a> {
     let mut coverage = Coverage::new(merge_monos);
-    for (fn_id, fr) in report.fns.iter() {
-        let ft = trans.fns.get(fn_id).unwrap_or(&default_ft);
-        process(&mut coverage, fn_id, fr, ft);
+    for (report, trans) in groups {
+        if !merge_monos {
+            coverage.match_instances(trans);
+        }
+        let fn_ids = report.fns.keys().chain(trans.fns.keys()).collect::<BTreeSet<_>>();
+        for fn_id in fn_ids {
+            let fr = report.fns.get(fn_id);
+            let ft = trans.fns.get(fn_id).unwrap_or(default_ft);
+            process(&mut coverage, fn_id, fr, ft);
+            process_blocks(&mut coverage, fn_id, fr, ft);
+        }
+    }
+    coverage
+}
+
+/// Print the coverage in the format chosen by `--format`.
+fn write_output(
+    m: &ArgMatches,
+    coverage: &Coverage,
+    files: &BTreeMap<String, formats::FileCoverage>,
+    filters: Option<Vec<Filter>>,
+) {
+    let format = m.value_of("format").unwrap();
+    if format == "html" {
+        let dir = Path::new(m.value_of_os("output-dir").unwrap());
+        html::write_html(dir, files).unwrap();
+        return;
+    }
+    if format != "text" {
+        let stdout = io::stdout();
+        let mut out = io::BufWriter::new(stdout.lock());
+        match format {
+            "lcov" => formats::write_lcov(&mut out, files),
+            "cobertura" => formats::write_cobertura(&mut out, files),
+            "json" => formats::write_json(&mut out, files),
+            _ => unreachable!(),
+        }.unwrap();
+        return;
     }
 
     let color_choice = if m.is_present("no-color") {
@@ -859,5 +1255,5 @@ fn main() {
         termcolor::ColorChoice::Auto
     };
     let mut reporter = Reporter::new(filters, color_choice);
-    report_all(&mut reporter, &coverage);
+    report_all(&mut reporter, coverage, m.is_present("unreached"));
 }
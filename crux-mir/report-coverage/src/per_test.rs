@@ -0,0 +1,89 @@
+//! Attributing branch coverage to individual tests: which test covers which branch arms, and a
+//! small set of tests that together cover every arm that the full set of tests covers.  The tests
+//! outside that set are candidates for removal, at least as far as branch coverage is concerned.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+
+use crate::diff::{arms, ArmKey};
+use crate::formats::FileCoverage;
+
+
+/// Choose tests greedily, each time taking the test that covers the most arms not covered by the
+/// tests chosen so far (the earliest such test, in case of a tie), until no test adds anything.
+/// Returns the index of each chosen test and the number of arms it added.  This isn't always the
+/// smallest possible set, but it's usually close, and finding the smallest one is NP-hard.
+fn greedy_cover(covered: &[BTreeSet<ArmKey<'_>>]) -> Vec<(usize, usize)> {
+    let mut done = BTreeSet::new();
+    let mut chosen = Vec::new();
+    loop {
+        let best = covered.iter().enumerate()
+            .map(|(i, arms)| (i, arms.difference(&done).count()))
+            .fold(None, |best: Option<(usize, usize)>, (i, n)| match best {
+                Some((_, best_n)) if best_n >= n => best,
+                _ => Some((i, n)),
+            });
+        match best {
+            Some((i, n)) if n > 0 => {
+                done.extend(covered[i].iter().copied());
+                chosen.push((i, n));
+            },
+            _ => return chosen,
+        }
+    }
+}
+
+/// Print a matrix of the branch arms in `all` (the merged coverage of every test) against the
+/// tests in `tests`, marking the arms each test covers, followed by a greedily chosen set of tests
+/// that covers the same arms as all of them together.
+pub fn write_per_test(
+    out: &mut impl Write,
+    tests: &[(&str, &BTreeMap<String, FileCoverage>)],
+    all: &BTreeMap<String, FileCoverage>,
+) -> io::Result<()> {
+    let covered = tests.iter()
+        .map(|(_, files)| {
+            arms(files).into_iter().filter(|&(_, taken)| taken).map(|(k, _)| k)
+                .collect::<BTreeSet<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    writeln!(out, "tests ({}):", tests.len())?;
+    for (i, (name, _)) in tests.iter().enumerate() {
+        writeln!(out, "  {:>3}  {}", i + 1, name)?;
+    }
+    writeln!(out)?;
+
+    let width = tests.len().to_string().len();
+    writeln!(out, "branch arms covered by each test:")?;
+    write!(out, " ")?;
+    for i in 0..tests.len() {
+        write!(out, " {:>w$}", i + 1, w = width)?;
+    }
+    writeln!(out)?;
+    for (k, _) in arms(all) {
+        let (fn_id, span, value) = k;
+        write!(out, " ")?;
+        for c in &covered {
+            write!(out, " {:>w$}", if c.contains(&k) { "x" } else { "." }, w = width)?;
+        }
+        writeln!(out, "  {}: {}: arm {}", span, fn_id, value)?;
+    }
+    writeln!(out)?;
+
+    let chosen = greedy_cover(&covered);
+    let total = chosen.iter().map(|&(_, n)| n).sum::<usize>();
+    writeln!(out, "tests that cover the same branch arms ({} of {}):", chosen.len(), tests.len())?;
+    for &(i, n) in &chosen {
+        writeln!(out, "  {} (adds {} of {} arms)", tests[i].0, n, total)?;
+    }
+    writeln!(out)?;
+
+    let chosen = chosen.iter().map(|&(i, _)| i).collect::<BTreeSet<_>>();
+    let redundant = (0..tests.len()).filter(|i| !chosen.contains(i)).collect::<Vec<_>>();
+    writeln!(out, "tests that add no branch coverage to the ones above ({}):", redundant.len())?;
+    for i in redundant {
+        writeln!(out, "  {}", tests[i].0)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,299 @@
+//! Run `crux-report-coverage` on the reports in `tests/fixtures`.  These cover
+//! `tests/fixtures/demo.rs`: the test in `run/t1` takes the `true` arm of the branch in `f`, the
+//! one in `run/t2` takes the `false` arm, `run/t3` repeats `run/t1`, and none of them calls `g`.
+//! `rebuilt/t2` is the same test as `run/t2`, but from a rebuild that renumbered the blocks of `f`
+//! and changed the instance disambiguator of `g`.  `names/t1` visits blocks in `src/a_b.rs` and
+//! `src/a/b.rs`, which don't exist.
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+use serde_json::{json, Value};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_crux-report-coverage"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// Run with `args`, check that it succeeded, and return its output.
+fn run_ok(args: &[&str]) -> String {
+    let out = run(args);
+    assert!(out.status.success(), "failed with {}:\n{}",
+        out.status, String::from_utf8_lossy(&out.stderr));
+    String::from_utf8(out.stdout).unwrap()
+}
+
+
+#[test]
+fn text() {
+    let out = run_ok(&["--no-color", "tests/fixtures/run/t1"]);
+    assert_eq!(out, "\
+warning: branch condition never has value false
+  ┌─ tests/fixtures/demo.rs:2:8
+  │
+2 │     if x {
+  │        ^
+
+");
+
+    let out = run_ok(&["--no-color", "--unreached", "tests/fixtures/run/t1"]);
+    assert_eq!(out, "\
+warning: branch condition never has value false
+  ┌─ tests/fixtures/demo.rs:2:8
+  │
+2 │     if x {
+  │        ^
+
+warning: function never called by any symbolic test
+   ┌─ tests/fixtures/demo.rs:10:5
+   │
+10 │     3
+   │     ^
+
+");
+}
+
+#[test]
+fn lcov() {
+    let out = run_ok(&["--format", "lcov", "tests/fixtures/run/t1"]);
+    assert_eq!(out, "\
+TN:
+SF:tests/fixtures/demo.rs
+FN:2,demo/abc::f[0]
+FN:10,demo/abc::g
+FNDA:1,demo/abc::f[0]
+FNDA:0,demo/abc::g
+BRDA:2,0,0,1
+BRDA:2,0,1,0
+DA:2,1
+DA:3,1
+DA:5,0
+DA:10,0
+FNF:2
+FNH:1
+BRF:2
+BRH:1
+LF:4
+LH:2
+end_of_record
+");
+}
+
+#[test]
+fn cobertura() {
+    let out = run_ok(&["--format", "cobertura", "tests/fixtures/run/t1"]);
+    // The `coverage` element has a timestamp, so only check the lines after it.
+    let lines = out.lines().collect::<Vec<_>>();
+    assert!(lines[2].starts_with(r#"<coverage line-rate="0.5000" branch-rate="0.5000" lines-covered="2" lines-valid="4" branches-covered="1" branches-valid="2" "#),
+        "{}", lines[2]);
+    assert_eq!(lines[3..].join("\n"), r#"  <sources>
+    <source>.</source>
+  </sources>
+  <packages>
+    <package name="tests/fixtures" line-rate="0.5000" branch-rate="0.5000" complexity="0">
+      <classes>
+        <class name="tests/fixtures/demo.rs" filename="tests/fixtures/demo.rs" line-rate="0.5000" branch-rate="0.5000" complexity="0">
+          <methods>
+            <method name="demo/abc::f[0]" signature="" line-rate="1" branch-rate="1" complexity="0">
+              <lines><line number="2" hits="1"/></lines>
+            </method>
+            <method name="demo/abc::g" signature="" line-rate="0" branch-rate="1" complexity="0">
+              <lines><line number="10" hits="0"/></lines>
+            </method>
+          </methods>
+          <lines>
+            <line number="2" hits="1" branch="true" condition-coverage="50% (1/2)"/>
+            <line number="3" hits="1" branch="false"/>
+            <line number="5" hits="0" branch="false"/>
+            <line number="10" hits="0" branch="false"/>
+          </lines>
+        </class>
+      </classes>
+    </package>
+  </packages>
+</coverage>"#);
+}
+
+#[test]
+fn json() {
+    let out = run_ok(&["--format", "json", "tests/fixtures/run/t1"]);
+    let j: Value = serde_json::from_str(&out).unwrap();
+    let totals = json!({
+        "lines_found": 4,
+        "lines_hit": 2,
+        "branches_found": 2,
+        "branches_hit": 1,
+        "functions_found": 2,
+        "functions_hit": 1,
+    });
+    assert_eq!(j, json!({
+        "files": [{
+            "filename": "tests/fixtures/demo.rs",
+            "lines": [
+                {"line": 2, "hit": true},
+                {"line": 3, "hit": true},
+                {"line": 5, "hit": false},
+                {"line": 10, "hit": false},
+            ],
+            "branches": [{
+                "function": "demo/abc::f[0]",
+                "span": "tests/fixtures/demo.rs:2:8: 2:9",
+                "line": 2,
+                "column": 8,
+                "arms": [
+                    {"value": "true", "taken": true},
+                    {"value": "false", "taken": false},
+                ],
+            }],
+            "functions": [
+                {"function": "demo/abc::f[0]", "line": 2, "called": true},
+                {"function": "demo/abc::g", "line": 10, "called": false},
+            ],
+            "totals": totals,
+        }],
+        "totals": totals,
+    }));
+}
+
+#[test]
+fn merge_tests() {
+    // The reports of both tests are merged, so both arms are covered.
+    let out = run_ok(&["--format", "lcov", "tests/fixtures/run/t1", "tests/fixtures/run/t2"]);
+    assert!(out.contains("BRDA:2,0,0,1\nBRDA:2,0,1,1\n"), "{}", out);
+    assert!(out.contains("BRH:2\n"), "{}", out);
+    assert!(out.contains("LF:4\nLH:3\n"), "{}", out);
+}
+
+#[test]
+fn html() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("html");
+    let _ = fs::remove_dir_all(&dir);
+    run_ok(&["--format", "html", "-o", dir.to_str().unwrap(), "tests/fixtures/run/t1"]);
+
+    let index = fs::read_to_string(dir.join("index.html")).unwrap();
+    for row in &[
+        "<tr><td><a href=\"tests_fixtures_demo.rs.html\">tests/fixtures/demo.rs</a></td>\
+            <td class=\"num\">50.0% (2/4)</td><td class=\"num\">50.0% (1/2)</td>\
+            <td class=\"num\">50.0% (1/2)</td></tr>",
+        "<tr><td><a href=\"tests_fixtures_demo.rs.html#L2\">demo/abc::f[0]</a></td>\
+            <td>tests/fixtures/demo.rs</td><td>yes</td><td class=\"num\">50.0% (1/2)</td></tr>",
+        "<tr><td><a href=\"tests_fixtures_demo.rs.html#L10\">demo/abc::g</a></td>\
+            <td>tests/fixtures/demo.rs</td><td class=\"missed\">no</td><td class=\"num\">-</td></tr>",
+    ] {
+        assert!(index.contains(row), "missing {}:\n{}", row, index);
+    }
+
+    let page = fs::read_to_string(dir.join("tests_fixtures_demo.rs.html")).unwrap();
+    for row in &[
+        "<tr class=\"\" id=\"L1\"><td class=\"lineno\"><a href=\"#L1\">1</a></td>\
+            <td class=\"code\">pub fn f(x: bool) -&gt; u32 {</td><td></td></tr>",
+        "<tr class=\"partial\" id=\"L2\"><td class=\"lineno\"><a href=\"#L2\">2</a></td>\
+            <td class=\"code\">    if x {</td><td>\
+            <span class=\"arm taken\" title=\"tests/fixtures/demo.rs:2:8: 2:9: taken\">true</span>\
+            <span class=\"arm missed\" title=\"tests/fixtures/demo.rs:2:8: 2:9: never taken\">false</span>\
+            </td></tr>",
+        "<tr class=\"covered\" id=\"L3\"><td class=\"lineno\"><a href=\"#L3\">3</a></td>\
+            <td class=\"code\">        1</td><td></td></tr>",
+        "<tr class=\"uncovered\" id=\"L5\"><td class=\"lineno\"><a href=\"#L5\">5</a></td>\
+            <td class=\"code\">        2</td><td></td></tr>",
+    ] {
+        assert!(page.contains(row), "missing {}:\n{}", row, page);
+    }
+}
+
+#[test]
+fn html_page_names() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("html_page_names");
+    let _ = fs::remove_dir_all(&dir);
+    run_ok(&["--format", "html", "-o", dir.to_str().unwrap(), "tests/fixtures/names/t1"]);
+
+    // Each file gets its own page, even though the names differ only in `_` versus `/`.
+    let a_b = fs::read_to_string(dir.join("src_a-5f-b.rs.html")).unwrap();
+    assert!(a_b.contains("<h1>src/a_b.rs</h1>"), "{}", a_b);
+    let a_slash_b = fs::read_to_string(dir.join("src_a_b.rs.html")).unwrap();
+    assert!(a_slash_b.contains("<h1>src/a/b.rs</h1>"), "{}", a_slash_b);
+}
+
+#[test]
+fn diff() {
+    let out = run_ok(&["--baseline", "tests/fixtures/run/t1", "tests/fixtures/run/t2"]);
+    assert_eq!(out, "\
+newly uncovered branches (1):
+  tests/fixtures/demo.rs:2:8: 2:9: demo/abc::f[0]: arm true
+
+newly covered branches (1):
+  tests/fixtures/demo.rs:2:8: 2:9: demo/abc::f[0]: arm false
+
+branch coverage: 50.0% (1/2), previously 50.0% (1/2)
+");
+}
+
+#[test]
+fn min_branch_coverage() {
+    run_ok(&["--min-branch-coverage", "50", "--format", "lcov", "tests/fixtures/run/t1"]);
+
+    let out = run(&["--min-branch-coverage", "60", "--format", "lcov", "tests/fixtures/run/t1"]);
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(String::from_utf8(out.stderr).unwrap(),
+        "branch coverage 50.0% (1/2) is below the minimum of 60%\n");
+
+    // The threshold applies to the new reports when comparing against a baseline.
+    let out = run(&["--baseline", "tests/fixtures/run/t1", "--min-branch-coverage", "60",
+        "tests/fixtures/run/t2"]);
+    assert_eq!(out.status.code(), Some(1));
+    run_ok(&["--baseline", "tests/fixtures/run/t1", "--min-branch-coverage", "100",
+        "tests/fixtures/run/t1", "tests/fixtures/run/t2"]);
+}
+
+#[test]
+fn merge_rebuilt() {
+    // Each report is read with its own translation, so the renumbered blocks still count.
+    let out = run_ok(&["--format", "lcov", "tests/fixtures/run/t1", "tests/fixtures/rebuilt/t2"]);
+    assert!(out.contains("BRDA:2,0,0,1\nBRDA:2,0,1,1\n"), "{}", out);
+    assert!(out.contains("DA:5,1\n"), "{}", out);
+
+    // Without merging monomorphizations, the renamed instance of `g` is matched with the old one
+    // by its branch spans.
+    let out = run_ok(&["--no-merge-monos", "--format", "lcov",
+        "tests/fixtures/run/t1", "tests/fixtures/rebuilt/t2"]);
+    assert!(out.contains("\
+FN:2,demo/abc::f[0]
+FN:10,demo/abc::g::_inst0123456789abcdef[0]
+FNDA:1,demo/abc::f[0]
+FNDA:0,demo/abc::g::_inst0123456789abcdef[0]
+BRDA:2,0,0,1
+BRDA:2,0,1,1
+"), "{}", out);
+    assert!(out.contains("FNF:2\n"), "{}", out);
+}
+
+#[test]
+fn per_test() {
+    let out = run_ok(&["--per-test", "tests/fixtures/run"]);
+    assert_eq!(out, "\
+tests (3):
+    1  t1
+    2  t2
+    3  t3
+
+branch arms covered by each test:
+  1 2 3
+  . x .  tests/fixtures/demo.rs:2:8: 2:9: demo/abc::f[0]: arm false
+  x . x  tests/fixtures/demo.rs:2:8: 2:9: demo/abc::f[0]: arm true
+
+tests that cover the same branch arms (2 of 3):
+  t1 (adds 1 of 2 arms)
+  t2 (adds 1 of 2 arms)
+
+tests that add no branch coverage to the ones above (1):
+  t3
+");
+
+    let out = run(&["--per-test", "--format", "lcov", "tests/fixtures/run"]);
+    assert_eq!(out.status.code(), Some(1));
+    let err = String::from_utf8(out.stderr).unwrap();
+    assert!(err.starts_with("--per-test can't be combined with --format\n"), "{}", err);
+}
@@ -0,0 +1,11 @@
+pub fn f(x: bool) -> u32 {
+    if x {
+        1
+    } else {
+        2
+    }
+}
+
+pub fn g<T>() -> u32 {
+    3
+}
@@ -0,0 +1,27 @@
+data.receiveData([
+ {
+  "type": "metadata",
+  "form": "",
+  "name": "t1",
+  "source": "",
+  "time": "",
+  "version": "1"
+ },
+ {
+  "type": "callgraph",
+  "events": [
+   {
+    "function": "names/abc::h[0]",
+    "type": "BLOCK",
+    "blocks": [
+     "bb0",
+     "bb1"
+    ]
+   }
+  ]
+ },
+ {
+  "type": "solver-calls",
+  "events": []
+ }
+]);
@@ -0,0 +1,42 @@
+data.receiveData([
+ {
+  "type": "metadata",
+  "form": "",
+  "name": "t1",
+  "source": "",
+  "time": "",
+  "version": "1"
+ },
+ {
+  "type": "callgraph",
+  "events": [
+   {
+    "function": "demo/abc::f[0]",
+    "type": "BLOCK",
+    "blocks": [
+     "bb0"
+    ]
+   },
+   {
+    "function": "demo/abc::f[0]",
+    "type": "BRANCH",
+    "callsite": "tests/fixtures/demo.rs:2:8: 2:9 #0,0",
+    "blocks": [
+     "bb1",
+     "bb2"
+    ]
+   },
+   {
+    "function": "demo/abc::f[0]",
+    "type": "BLOCK",
+    "blocks": [
+     "bb1"
+    ]
+   }
+  ]
+ },
+ {
+  "type": "solver-calls",
+  "events": []
+ }
+]);
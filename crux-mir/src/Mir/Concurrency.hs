@@ -32,13 +32,14 @@ import           Mir.Intrinsics ( MIR
                                 , MirReference(..)
                                 , MirReferencePath(..) )
 import           Mir.FancyMuxTree (viewFancyMuxTree)
-import           Mir.DefId (textId, getTraitName, DefId)
+import           Mir.DefId (textId, getTraitName, didCrate, DefId)
 import Data.Parameterized.Context (indexVal)
 
 mirExplorePrimitives ::
   (C.IsSymInterface sym, W4.IsExprBuilder sym) => ExplorePrimitives p sym MIR
 mirExplorePrimitives =
   [ Match mirLock
+  , Match mirCond
   , Match mirAtomic
   , Match mirJoin
   , Match mirSpawn
@@ -47,7 +48,7 @@ mirExplorePrimitives =
 
 mirJoin :: C.IsSymInterface sym => ExplorePrimitiveMatcher p sym MIR
 mirJoin _ nm args cf _
-  | matchGeneric "core::crucible::concurrency::join" nm
+  | matchGeneric "core::crucible::concurrency::join_internal" nm
   = do bv <- retrieveTypedArg args cf (C.BVRepr (W4.knownNat @64)) 0
        case W4.asBV bv of
          Just (BV.BV i) -> pure $! ThreadJoin (fromIntegral i)
@@ -66,22 +67,44 @@ mirAtomic _ nm ctx cf _
 
 mirLock :: C.IsSymInterface sym => ExplorePrimitiveMatcher p sym MIR
 mirLock _ nm ctx cf _
-  | matchGeneric "core::crucible::concurrency::mutex_lock" nm
+  | Just mkSpec <- lookupGeneric nm lockPrimitives
   , Ctx.Empty Ctx.:> MirReferenceRepr t <- ctx
   = do arg <- retrieveTypedArg ctx cf (MirReferenceRepr t) 0
        let refs = mirRefName arg
        case refs of
          [ref] ->
-           pure $! ThreadYield (Acquire ref) refs False
-         _ -> error "TODO: Muxed mutex lock"
-  | matchGeneric "core::crucible::concurrency::mutex_unlock" nm
-  , Ctx.Empty Ctx.:> MirReferenceRepr t <- ctx
-  = do arg <- retrieveTypedArg ctx cf (MirReferenceRepr t) 0
-       let refs = mirRefName arg
-       case refs of
-         [ref] ->
-           pure $! ThreadYield (Release ref) refs False
-         _ -> error "TODO: Muxed mutex unlock"
+           pure $! ThreadYield (mkSpec ref) refs False
+         _ -> error $ "TODO: Muxed lock in " ++ show nm
+  | otherwise = Nothing
+  where
+    -- Write locks on an RwLock are released just like mutexes: the scheduler
+    -- only needs to distinguish readers from writers when acquiring the lock.
+    lockPrimitives =
+      [ ("core::crucible::concurrency::mutex_lock", Acquire)
+      , ("core::crucible::concurrency::mutex_unlock", Release)
+      , ("core::crucible::concurrency::rwlock_read", AcquireRead)
+      , ("core::crucible::concurrency::rwlock_read_unlock", ReleaseRead)
+      , ("core::crucible::concurrency::rwlock_write", AcquireWrite)
+      , ("core::crucible::concurrency::rwlock_write_unlock", Release)
+      ]
+
+mirCond :: C.IsSymInterface sym => ExplorePrimitiveMatcher p sym MIR
+mirCond _ nm ctx cf _
+  | matchGeneric "core::crucible::concurrency::cond_wait" nm
+  , Ctx.Empty Ctx.:> MirReferenceRepr c Ctx.:> MirReferenceRepr m <- ctx
+  = do cv <- retrieveTypedArg ctx cf (MirReferenceRepr c) 0
+       mv <- retrieveTypedArg ctx cf (MirReferenceRepr m) 1
+       case (mirRefName cv, mirRefName mv) of
+         ([cvRef], [mRef]) ->
+           pure $! ThreadCondWait cvRef mRef
+         _ -> error "TODO: Muxed condvar wait"
+  | matchGeneric "core::crucible::concurrency::cond_notify" nm
+  , Ctx.Empty Ctx.:> MirReferenceRepr c <- ctx
+  = do cv <- retrieveTypedArg ctx cf (MirReferenceRepr c) 0
+       case mirRefName cv of
+         [cvRef] ->
+           pure $! ThreadCondSignal cvRef
+         _ -> error "TODO: Muxed condvar notify"
   | otherwise = Nothing
 
 mutexName :: BV.BV 32 -> Text.Text
@@ -124,7 +147,7 @@ mirRefName t =
        let refs = fst <$> viewFancyMuxTree tree
            extr r =
              case r of
-               MirReference root p ->
+               MirReference root p _ ->
                  Text.pack (show root ++ mirPathName p)
                -- If std::sys::crux::Mutex becomes zero sized, then we might
                -- actually hit this case, as the compiler will be free to choose
@@ -140,6 +163,7 @@ mirPathName p =
   case p of
     Empty_RefPath -> ""
     Any_RefPath _ p -> mirPathName p
+    Union_RefPath _ p -> mirPathName p
     Field_RefPath _ p idx -> mirPathName p ++ "." ++ show (indexVal idx)
     Variant_RefPath _ _ p idx -> mirPathName p ++ "." ++ show (indexVal idx)
     Index_RefPath _ p idx -> mirPathName p
@@ -147,6 +171,17 @@ mirPathName p =
     VectorAsMirVector_RefPath _ p -> mirPathName p
     ArrayAsMirVector_RefPath _ p -> mirPathName p
 
+-- | Whether the function belongs to one of the libraries bundled with crux-mir.
+-- Deadlock reports skip over these frames to point at the user's code.
+isLibraryFunction :: W4.FunctionName -> Bool
+isLibraryFunction nm =
+  textId (W4.functionName nm) ^. didCrate `elem` ["core", "alloc", "std", "crucible"]
+
+-- | Find the entry of a polymorphic function matching the given instance name
+lookupGeneric :: W4.FunctionName -> [(DefId, a)] -> Maybe a
+lookupGeneric instNm prims =
+  lookup (getTraitName (textId (W4.functionName instNm))) prims
+
 -- | Match a the name of a polymorphic method with a possible instance by
 -- dropping the last segment
 matchGeneric :: DefId -> W4.FunctionName -> Bool
@@ -0,0 +1,222 @@
+{-# LANGUAGE GADTs #-}
+{-# LANGUAGE OverloadedStrings #-}
+
+-- | Machine-readable counterexamples.  For each goal that the solver
+-- disproves, we record the concrete value of every symbolic variable (in the
+-- order the variables were created) along with the failed assertion.  These
+-- can be written out as JSON, or as Rust regression tests that replay the
+-- counterexample without Crux.
+module Mir.Counterexample
+  ( Counterexample(..)
+  , ModelVar(..)
+  , ModelValue(..)
+  , counterexamples
+  , writeCounterexamples
+  , regressionTests
+  , testDirName
+  ) where
+
+import           Control.Lens ((^.))
+import           Data.Aeson ((.=))
+import qualified Data.Aeson as Aeson
+import qualified Data.BitVector.Sized as BV
+import qualified Data.Char as Char
+import qualified Data.List as List
+import           Data.Text (Text)
+import qualified Data.Text as Text
+import           Numeric (showHex)
+import           Numeric.Natural (Natural)
+import           System.Directory (createDirectoryIfMissing)
+import           System.FilePath ((</>))
+
+import qualified LibBF as BF
+
+import           Data.Parameterized.NatRepr (natValue)
+
+import           What4.BaseTypes
+import           What4.Expr (GroundValue, GroundValueWrapper(..))
+import           What4.ProgramLoc (ProgramLoc(..))
+
+import           Lang.Crucible.Backend (CrucibleEvent(..))
+import           Lang.Crucible.Simulator.SimError
+
+import           Crux.Types (ProvedGoals(..))
+
+import           Mir.DefId (DefId, didCrate, didPath)
+
+-- | A disproved goal, together with the model that disproves it.
+data Counterexample = Counterexample
+  { cexError :: SimError
+  , cexVars :: [ModelVar]
+  }
+
+-- | The value the solver chose for one symbolic variable.
+data ModelVar = ModelVar
+  { mvName :: Text
+    -- ^ The `desc` string passed to `Symbolic::symbolic`.
+  , mvLoc :: ProgramLoc
+  , mvValue :: ModelValue
+  }
+
+data ModelValue
+  = MVBits Natural Integer
+    -- ^ A bitvector of the given width, as an unsigned integer.
+  | MVFloat Natural Integer
+    -- ^ An IEEE-754 float of the given width, as the unsigned integer with
+    -- the same bit pattern (i.e., the result of `f32::to_bits`).
+  | MVBool Bool
+  | MVInteger Integer
+  | MVReal Rational
+
+-- | Collect the counterexamples for all the disproved goals in a test
+-- result.  Goals that failed without producing a model are skipped.
+counterexamples :: ProvedGoals -> [Counterexample]
+counterexamples (Branch g1 g2) = counterexamples g1 ++ counterexamples g2
+counterexamples ProvedGoal{} = []
+counterexamples (NotProvedGoal _ _ _ _ Nothing _) = []
+counterexamples (NotProvedGoal _ err _ _ (Just (_, evs)) _) =
+  [Counterexample err [v | ev <- evs, Just v <- [eventVar ev]]]
+
+eventVar :: CrucibleEvent GroundValueWrapper -> Maybe ModelVar
+eventVar (CreateVariableEvent loc nm tpr (GVW v)) =
+  ModelVar (Text.pack nm) loc <$> groundValue tpr v
+eventVar LocationReachedEvent{} = Nothing
+
+groundValue :: BaseTypeRepr tp -> GroundValue tp -> Maybe ModelValue
+groundValue tpr v = case tpr of
+  BaseBVRepr w -> Just $ MVBits (natValue w) (BV.asUnsigned v)
+  BaseFloatRepr (FloatingPointPrecisionRepr eb sb) ->
+    let opts = BF.expBits (fromIntegral (natValue eb))
+            <> BF.precBits (fromIntegral (natValue sb))
+            <> BF.allowSubnormal
+            <> BF.rnd BF.NearEven
+    in Just $ MVFloat (natValue eb + natValue sb) (BF.bfToBits opts v)
+  BaseBoolRepr -> Just $ MVBool v
+  BaseIntegerRepr -> Just $ MVInteger v
+  BaseRealRepr -> Just $ MVReal v
+  _ -> Nothing
+
+-- | Write one JSON file per counterexample into @dir@, under a subdirectory
+-- named after the test (see 'testDirName').
+writeCounterexamples :: FilePath -> DefId -> [Counterexample] -> IO ()
+writeCounterexamples _ _ [] = return ()
+writeCounterexamples dir fnName cexs = do
+  let testDir = dir </> testDirName fnName
+  createDirectoryIfMissing True testDir
+  mapM_ (\(i, cex) -> Aeson.encodeFile
+          (testDir </> "counterexample-" ++ show i ++ ".json")
+          (counterexampleJSON (show fnName) cex))
+    (zip [0 :: Int ..] cexs)
+
+-- | A directory name for a test's output files.  @show fnName@ contains
+-- @/@, @::@ and brackets, so instead we join the crate name and path
+-- segments with @-@, leaving out the crate disambiguator (which changes
+-- whenever the crate does).  Segments other than the first of their name get
+-- their index appended.
+testDirName :: DefId -> FilePath
+testDirName fnName = List.intercalate "-" $ map (map safe) $
+  Text.unpack (fnName ^. didCrate) :
+    [ Text.unpack name ++ (if idx == 0 then "" else "_" ++ show idx)
+    | (name, idx) <- fnName ^. didPath ]
+  where
+    safe c
+      | Char.isAscii c && (Char.isAlphaNum c || c == '_') = c
+      | otherwise = '_'
+
+counterexampleJSON :: String -> Counterexample -> Aeson.Value
+counterexampleJSON testName (Counterexample err vars) = Aeson.object
+  [ "test" .= testName
+  , "goal" .= goalJSON err
+  , "variables" .= map varJSON vars
+  ]
+
+goalJSON :: SimError -> Aeson.Value
+goalJSON (SimError loc reason) = Aeson.object
+  [ "location" .= assertLoc
+  , "function" .= show (plFunction loc)
+  , "message" .= msg
+  , "details" .= simErrorDetailsMsg reason
+  ]
+  where
+    -- `crucible_assert!` reports the location of the assertion in the
+    -- message itself, since `loc` points into the `crucible` library.
+    (assertLoc, msg) =
+      case List.stripPrefix "MIR assertion at " (simErrorReasonMsg reason) of
+        Just rest
+          | (l, '\n' : m) <- break (== '\n') rest ->
+            (dropSuffix ":" l, dropWhile (== '\t') m)
+        _ -> (show (plSourceLoc loc), simErrorReasonMsg reason)
+    dropSuffix s x = maybe x reverse $ List.stripPrefix (reverse s) (reverse x)
+
+varJSON :: ModelVar -> Aeson.Value
+varJSON (ModelVar name loc val) = Aeson.object $
+  [ "name" .= name
+  , "location" .= show (plSourceLoc loc)
+  ] ++ case val of
+    -- Values are rendered as strings, since they may not fit in a JSON
+    -- number without losing precision.
+    MVBits w x -> ["kind" .= ("bv" :: Text), "width" .= w, "value" .= show x]
+    MVFloat w x -> ["kind" .= ("float" :: Text), "width" .= w, "value" .= show x]
+    MVBool b -> ["kind" .= ("bool" :: Text), "value" .= (if b then "true" else "false" :: Text)]
+    MVInteger x -> ["kind" .= ("int" :: Text), "value" .= show x]
+    MVReal x -> ["kind" .= ("real" :: Text), "value" .= show x]
+
+
+-- | Render a Rust module containing a @#[test]@ for each counterexample.
+-- Each test replays the test function with its symbolic variables set to the
+-- values from the model, using the replay shim in @lib/crucible_replay@.
+regressionTests :: [(DefId, [Counterexample])] -> String
+regressionTests tests = unlines $ header ++ concatMap testFns tests
+  where
+    header =
+      [ "// Regression tests generated by crux-mir from counterexamples.  To run them,"
+      , "// include this file as a module in the crate under test, and build that crate"
+      , "// against `crux-mir/lib/crucible_replay` in place of `crucible`."
+      ]
+    testFns (fnName, cexs) = concat $ zipWith (regressionTest fnName) [0 ..] cexs
+
+regressionTest :: DefId -> Int -> Counterexample -> [String]
+regressionTest fnName i (Counterexample _ vars) =
+  case mapM rustIdent (fnName ^. didPath) of
+    Nothing ->
+      ["", "// Skipped " ++ show fnName ++ ": not a plain function path"]
+    Just segs ->
+      [ ""
+      , "#[test]"
+      , "fn " ++ List.intercalate "_" segs ++ "_cex_" ++ show i ++ "() {"
+      , "    crucible::replay::run(&["
+      ] ++
+      [ "        (" ++ rustStr (Text.unpack (mvName v)) ++ ", " ++ show x ++ "),"
+      | v <- vars, Just x <- [replayValue v] ] ++
+      [ "    ], || { let _ = crate::" ++ List.intercalate "::" segs ++ "(); });"
+      , "}"
+      ]
+  where
+    rustIdent (name, _)
+      | not (Text.null name), Text.all (\c -> Char.isAlphaNum c || c == '_') name =
+        Just (Text.unpack name)
+      | otherwise = Nothing
+
+-- | The replay table stores every value as the @u128@ with the same bit
+-- pattern.  Variables that can't be stored that way were not created by
+-- @Symbolic::symbolic@, which only makes bitvectors of at most 128 bits,
+-- floats and booleans, so they are left out of the table.  The replay shim
+-- looks values up by name, so any other variables in the table are ignored.
+replayValue :: ModelVar -> Maybe Integer
+replayValue v = case mvValue v of
+  MVBits w x
+    | w <= 128 -> Just x
+    | otherwise -> Nothing
+  MVFloat _ x -> Just x
+  MVBool b -> Just (if b then 1 else 0)
+  MVInteger _ -> Nothing
+  MVReal _ -> Nothing
+
+rustStr :: String -> String
+rustStr str = "\"" ++ concatMap esc str ++ "\""
+  where
+    esc '"' = "\\\""
+    esc '\\' = "\\\\"
+    esc c
+      | Char.isAscii c && Char.isPrint c = [c]
+      | otherwise = "\\u{" ++ showHex (Char.ord c) "}"
@@ -38,6 +38,7 @@ import qualified Data.Aeson as Aeson
 import qualified Data.BitVector.Sized as BV
 import qualified Data.Char       as Char
 import           Data.Functor.Const (Const(..))
+import           Control.Applicative ((<|>))
 import           Control.Monad
 import           Control.Monad.IO.Class
 import qualified Data.List       as List
@@ -45,10 +46,11 @@ import           Data.Text (Text)
 import qualified Data.Text       as Text
 import           Data.Type.Equality ((:~:)(..),TestEquality(..))
 import qualified Data.Map.Strict as Map
-import           Data.Maybe (fromMaybe)
+import           Data.IORef (newIORef, readIORef, writeIORef)
+import           Data.Maybe (fromMaybe, listToMaybe)
 import qualified Data.Sequence   as Seq
 import qualified Data.Vector     as Vector
-import           Control.Lens ((^.), (^?), (^..), ix, each)
+import           Control.Lens ((^.), (^?), (^..), (&), (.~), ix, each)
 import           GHC.Generics (Generic)
 
 import System.Console.ANSI
@@ -57,6 +59,7 @@ import qualified SimpleGetOpt as GetOpt
 import           System.Directory (createDirectoryIfMissing)
 import           System.Exit (exitSuccess, exitWith, ExitCode(..))
 import           System.FilePath ((</>))
+import           Text.Read (readMaybe)
 
 import           Prettyprinter (pretty)
 
@@ -77,6 +80,7 @@ import qualified Lang.Crucible.Backend                 as C
 -- what4
 import qualified What4.Expr.Builder                    as W4
 import qualified What4.Interface                       as W4
+import qualified What4.InterpretedFloatingPoint        as W4
 import qualified What4.Config                          as W4
 import qualified What4.Partial                         as W4
 import qualified What4.ProgramLoc                      as W4
@@ -96,6 +100,11 @@ import Crux.Log
 -- concurrency
 import Crucibles.DPOR
 import Crucibles.Explore
+import Crucibles.Execution (Schedule, parseSchedule, ppSchedule)
+import Crucibles.ExploreTypes
+    ( ExploreResult(..), libraryFunction, threadIDVar, maxExecutions
+    , replaySchedule, reportExploration, statComplete, statBounded
+    , statSchedulingPoints, statPreemptions )
 import Cruces.ExploreCrux
 
 -- crux-mir
@@ -105,8 +114,11 @@ import           Mir.PP ()
 import           Mir.Overrides
 import           Mir.Intrinsics (MIR, mirExtImpl, mirIntrinsicTypes,
                     pattern RustEnumRepr, SomeRustEnumRepr(..),
-                    pattern MirVectorRepr, MirVector(..))
+                    pattern MirVectorRepr, MirVector(..), emptyBorrowState,
+                    emptyHeapAllocs, checkLeaksSim, emptyWeakMemory,
+                    emptyRaceDetector)
 import           Mir.Generator
+import           Mir.Loops (UnwindBound(..), unwindBounds)
 import           Mir.Generate (generateMIR)
 import qualified Mir.Log as Log
 import           Mir.ParseTranslate (translateMIR)
@@ -114,6 +126,8 @@ import           Mir.Trans (transStatics)
 import qualified Mir.TransCustom as TransCustom
 import           Mir.TransTy
 import           Mir.Concurrency
+import           Mir.Counterexample
+    (counterexamples, writeCounterexamples, regressionTests, testDirName)
 import           Paths_crux_mir (version)
 
 defaultOutputConfig :: IO (Maybe Crux.OutputOptions -> OutputConfig MirLogging)
@@ -211,6 +225,10 @@ runTestsWithExtraOverrides bindExtra (cruxOpts, mirOpts) = do
     --let ?assertFalseOnError = assertFalse mirOpts
     let ?assertFalseOnError = True
     let ?printCrucible      = printCrucible mirOpts
+    let ?checkAliasing      = checkAliasing mirOpts
+    let ?checkLeaks         = checkLeaks mirOpts
+    let ?weakMemory         = weakMemory mirOpts
+    let ?detectRaces        = detectRaces mirOpts
     let ?defaultRlibsDir    = defaultRlibsDir mirOpts
     let ?customOps          = TransCustom.customOps
 
@@ -276,7 +294,17 @@ runTestsWithExtraOverrides bindExtra (cruxOpts, mirOpts) = do
     let filterTests defIds = case nameFilter of
             Just x -> filter (\d -> x `Text.isInfixOf` idText d) defIds
             Nothing -> defIds
-    let testNames = List.sort $ filterTests $ col ^. roots
+    -- A replayed schedule only makes sense for the test it was recorded for.
+    replay <- forM (replayScheduleFile mirOpts) $ \path -> do
+        contents <- readFile path
+        sched <- either (\e -> fail $ path ++ ": " ++ e) return $ parseSchedule contents
+        return (scheduleTestName contents, sched)
+    let filterReplay defIds = case replay of
+            Just (Just name, _) -> filter (\d -> show d == name) defIds
+            _ -> defIds
+    let testNames = List.sort $ filterReplay $ filterTests $ col ^. roots
+    forM_ replay $ \(name, _) -> when (null testNames) $
+        fail $ "couldn't find the test for the replayed schedule: " ++ fromMaybe "<unknown>" name
 
     -- The output for each test looks like:
     --      test foo::bar1: ok
@@ -288,7 +316,7 @@ runTestsWithExtraOverrides bindExtra (cruxOpts, mirOpts) = do
     -- that calls `simTest`.  Counterexamples are printed separately, and only
     -- for tests that failed.
 
-    let ?bound = 0
+    let ?bound = preemptionBound mirOpts
     let simTestBody :: forall sym bak p t st fs.
             ( C.IsSymBackend sym bak
             , sym ~ W4.ExprBuilder t st fs
@@ -299,13 +327,40 @@ runTestsWithExtraOverrides bindExtra (cruxOpts, mirOpts) = do
             Fun p sym MIR Ctx.EmptyCtx C.UnitType
         simTestBody bak symOnline fnName =
           do linkOverrides symOnline
+             forM_ (mir ^. rmCS . borrowStateVar) $ \gv ->
+                 C.writeGlobal gv emptyBorrowState
              _ <- C.callCFG staticInitCfg C.emptyRegMap
+             -- Start tracking allocations after the statics are initialized,
+             -- since those live for the rest of the program.
+             forM_ (mir ^. rmCS . heapAllocsVar) $ \gv ->
+                 C.writeGlobal gv emptyHeapAllocs
 
              -- Label the current path for later use
              let sym = C.backendGetSym bak
              liftIO $ C.addAssumption bak $
                  C.BranchCondition entry (Just $ testStartLoc fnName) (W4.truePred sym)
 
+             -- The test starts on the main thread, whose ID is 0.  The
+             -- scheduler updates the thread ID each time it switches threads.
+             forM_ (mir ^. rmCS . weakMemoryVars) $ \(gv, tidVar) -> do
+                 C.writeGlobal gv (emptyWeakMemory sym)
+                 tid <- liftIO $ W4.natLit sym 0
+                 C.writeGlobal tidVar tid
+             forM_ (mir ^. rmCS . raceDetectorVars) $ \(gv, tidVar) -> do
+                 C.writeGlobal gv emptyRaceDetector
+                 tid <- liftIO $ W4.natLit sym 0
+                 C.writeGlobal tidVar tid
+
+             -- Loops without an unwinding bound of their own use the bound
+             -- from the test's `#[crucible::unwind]`, if it has one.
+             forM_ (mir ^. rmCS . unwindBoundVars) $ \(limitVar, assumeVar) -> do
+                 let bound = case List.find (\fn -> fn ^. fname == fnName) (col ^. functions) of
+                         Just fn | Right (Just b, _) <- unwindBounds (fn ^. fbody) -> Just b
+                         _ -> Nothing
+                 limit <- liftIO $ W4.intLit sym (maybe (-1) unwindLimit bound)
+                 C.writeGlobal limitVar limit
+                 C.writeGlobal assumeVar (W4.backendPred sym (maybe False unwindAssume bound))
+
              -- Find and run the target function
              C.AnyCFG cfg <- case Map.lookup (idText fnName) cfgMap of
                  Just x -> return x
@@ -329,6 +384,16 @@ runTestsWithExtraOverrides bindExtra (cruxOpts, mirOpts) = do
                  str <- showRegEntry @sym col resTy res
                  liftIO $ output $ "returned " ++ str ++ ", "
 
+             -- Anything that is still allocated and isn't owned by the
+             -- returned value has leaked.  Leaks are reported at the
+             -- innermost call from the test's own crate.
+             let testCratePrefix =
+                     fnName ^. didCrate <> "/" <> fnName ^. didCrateDisambig <> "::"
+             let isTestCrateLoc loc = testCratePrefix `Text.isPrefixOf`
+                     W4.functionName (W4.plFunction loc)
+             forM_ (mir ^. rmCS . heapAllocsVar) $ \gv ->
+                 checkLeaksSim isTestCrateLoc gv res
+
     let printTest :: DefId -> Fun p sym ext args C.UnitType
         printTest fnName =
           when (not $ printResultOnly mirOpts) $
@@ -341,16 +406,23 @@ runTestsWithExtraOverrides bindExtra (cruxOpts, mirOpts) = do
             , Log.SupportsCruxLogMessage msgs
             , Log.SupportsMirLogMessage msgs
             ) =>
+            (ExploreResult -> IO ()) ->
             bak ->
             Maybe (Crux.SomeOnlineSolver sym bak) ->
             DefId ->
             SomeTestOvr sym Ctx.EmptyCtx C.UnitType
-        simTest bak symOnline fnName
+        simTest report bak symOnline fnName
           | concurrency mirOpts = SomeTestOvr
             { testOvr = do printTest fnName
                            exploreOvr bak symOnline cruxOpts $ simTestBody bak symOnline fnName
             , testFeatures = [scheduleFeature mirExplorePrimitives []]
             , testPersonality = emptyExploration @DPOR
+                & threadIDVar .~ (fmap snd (mir ^. rmCS . weakMemoryVars)
+                                    <|> fmap snd (mir ^. rmCS . raceDetectorVars))
+                & libraryFunction .~ isLibraryFunction
+                & maxExecutions .~ executionLimit mirOpts
+                & replaySchedule .~ fmap snd replay
+                & reportExploration .~ report
             }
           | otherwise = SomeTestOvr
             { testOvr = do printTest fnName
@@ -359,13 +431,13 @@ runTestsWithExtraOverrides bindExtra (cruxOpts, mirOpts) = do
             , testPersonality = Crux.CruxPersonality
             }
 
-    let simCallbacks fnName =
+    let simCallbacks report fnName =
           Crux.SimulatorCallbacks $
             return $
               Crux.SimulatorHooks
                 { Crux.setupHook =
                     \bak symOnline ->
-                      case simTest bak symOnline fnName of
+                      case simTest report bak symOnline fnName of
                         SomeTestOvr testFn features personality -> do
                           let outH = view outputHandle ?outputConfig
                           let sym = C.backendGetSym bak
@@ -390,7 +462,7 @@ runTestsWithExtraOverrides bindExtra (cruxOpts, mirOpts) = do
             proved = sum (fmap (provedGoals . fst) gls)
             disproved = sum (fmap (disprovedGoals . fst) gls)
 
-    results <- forM testNames $ \fnName -> do
+    outcomes <- forM testNames $ \fnName -> do
         let cruxOpts' = cruxOpts {
                 Crux.outDir = if Crux.outDir cruxOpts == "" then ""
                     else Crux.outDir cruxOpts </> show fnName
@@ -407,12 +479,26 @@ runTestsWithExtraOverrides bindExtra (cruxOpts, mirOpts) = do
             -- same `outDir`.
             Aeson.encodeFile path (mir ^. rmTransInfo)
 
-        res <- Crux.runSimulator cruxOpts' $ simCallbacks fnName
+        exploreRef <- newIORef Nothing
+        res <- Crux.runSimulator cruxOpts' $ simCallbacks (writeIORef exploreRef . Just) fnName
+        explored <- readIORef exploreRef
         when (not $ printResultOnly mirOpts) $ do
             clearFromCursorToLineEnd
             outputResult res
             outputLn ""
-        return res
+            forM_ explored $ \r -> do
+                when (concurrencyStats mirOpts) $ mapM_ outputLn (ppExploreStats r)
+                when (exploreStoppedEarly r) $ outputLn $
+                    "  stopped after " ++ show (exploreExecutions r)
+                        ++ " executions (--max-executions): not every schedule was explored"
+
+        -- Save the schedule of the failing execution, so that it can be
+        -- replayed with `--replay-schedule`.
+        schedFile <- case (scheduleDir mirOpts, explored >>= exploreFailingSchedule) of
+            (Just dir, Just sched) -> Just <$> writeSchedule dir fnName sched
+            _ -> return Nothing
+        return (res, schedFile)
+    let results = map fst outcomes
 
     -- Print counterexamples
     let isResultOK (CruxSimulationResult comp gls) =
@@ -434,11 +520,24 @@ runTestsWithExtraOverrides bindExtra (cruxOpts, mirOpts) = do
     when anyFailed $ do
         outputLn ""
         outputLn "failures:"
-        forM_ (zip testNames results) $ \(fnName, res) -> do
+        forM_ (zip testNames outcomes) $ \(fnName, (res, schedFile)) -> do
             when (not $ isResultOK res) $ do
                 outputLn ""
                 outputLn $ "---- " ++ show fnName ++ " counterexamples ----"
                 mapM_ (printCounterexamples . snd) $ cruxSimResultGoals res
+                forM_ schedFile $ \path ->
+                    outputLn $ "schedule of the failing execution written to " ++ path
+                        ++ " (replay it with --replay-schedule " ++ path ++ ")"
+
+    forM_ (counterexampleDir mirOpts) $ \dir ->
+        forM_ (zip testNames results) $ \(fnName, res) ->
+            writeCounterexamples dir fnName $
+                concatMap (counterexamples . snd) $ cruxSimResultGoals res
+
+    forM_ (regressionTestFile mirOpts) $ \path ->
+        writeFile path $ regressionTests
+            [ (fnName, concatMap (counterexamples . snd) $ cruxSimResultGoals res)
+            | (fnName, res) <- zip testNames results ]
 
     -- Print final tally of proved/disproved goals (except if
     -- --print-result-only is set)
@@ -462,10 +561,45 @@ runTestsWithExtraOverrides bindExtra (cruxOpts, mirOpts) = do
 
 
 
+-- | Describe an exploration, for `--concurrency-stats`.
+ppExploreStats :: ExploreResult -> [String]
+ppExploreStats r =
+    [ "  " ++ show n ++ " executions: " ++ show complete ++ " complete, "
+        ++ show bounded ++ " over the preemption bound, "
+        ++ show (n - complete - bounded) ++ " abandoned as infeasible"
+    , "  " ++ show (st ^. statSchedulingPoints) ++ " scheduling points, "
+        ++ show (st ^. statPreemptions) ++ " preemptions"
+    , "  " ++ List.intercalate ", " [ show v ++ " " ++ k | (k, v) <- exploreAlgStats r ]
+    ]
+  where
+    n = exploreExecutions r
+    st = exploreStats r
+    complete = st ^. statComplete
+    bounded = st ^. statBounded
+
+-- | Write the schedule of a test's failing execution to
+-- `dir/<test>/schedule.txt` (see 'testDirName'), and return the path.
+writeSchedule :: FilePath -> DefId -> Schedule -> IO FilePath
+writeSchedule dir fnName sched = do
+    let testDir = dir </> testDirName fnName
+    createDirectoryIfMissing True testDir
+    let path = testDir </> "schedule.txt"
+    writeFile path $ "# test " ++ show fnName ++ "\n" ++ ppSchedule sched
+    return path
+
+-- | The test named in the header of a schedule written by 'writeSchedule'.
+scheduleTestName :: String -> Maybe String
+scheduleTestName contents =
+    listToMaybe [ name | l <- lines contents, Just name <- [List.stripPrefix "# test " l] ]
+
 data MIROptions = MIROptions
     { onlyPP       :: Bool
     , printCrucible :: Bool
     , showModel    :: Bool
+    -- | Write a JSON description of each counterexample to this directory.
+    , counterexampleDir :: Maybe FilePath
+    -- | Write a Rust `#[test]` replaying each counterexample to this file.
+    , regressionTestFile :: Maybe FilePath
     , assertFalse  :: Bool
     -- | Print only the result of evaluation, with no additional text.  On
     -- concrete programs, this should normally produce the exact same output as
@@ -474,6 +608,29 @@ data MIROptions = MIROptions
     -- | Generate test overrides that recognize concurrency primitives
     -- and attempt to explore all interleaving executions
     , concurrency :: Bool
+    -- | Check for aliasing violations in unsafe code.  See the "Aliasing
+    -- checks" section of `Mir.Intrinsics`.
+    , checkAliasing :: Bool
+    -- | Check that each test frees all of its heap allocations.
+    , checkLeaks :: Bool
+    -- | Model atomics with the weak memory model described in the "Weak
+    -- memory" section of `Mir.Intrinsics`.  This implies `concurrency`.
+    , weakMemory :: Bool
+    -- | Report data races between non-atomic accesses, using the detector
+    -- described in the "Data race detection" section of `Mir.Intrinsics`.
+    -- This implies `concurrency`.
+    , detectRaces :: Bool
+    -- | Only explore executions with at most this many preemptions.
+    , preemptionBound :: Maybe Int
+    -- | Stop exploring a test's schedules after this many executions.
+    , executionLimit :: Maybe Int
+    -- | Print statistics about each test's exploration.
+    , concurrencyStats :: Bool
+    -- | Write the schedule of each failing concurrent execution under this
+    -- directory.
+    , scheduleDir :: Maybe FilePath
+    -- | Follow the schedule in this file instead of exploring.
+    , replayScheduleFile :: Maybe FilePath
     , testFilter   :: Maybe Text
     , cargoTestFile :: Maybe FilePath
     , defaultRlibsDir :: FilePath
@@ -488,8 +645,19 @@ defaultMirOptions = MIROptions
     { onlyPP = False
     , printCrucible = False
     , showModel = False
+    , counterexampleDir = Nothing
+    , regressionTestFile = Nothing
     , assertFalse = False
     , concurrency = False
+    , checkAliasing = False
+    , checkLeaks = False
+    , weakMemory = False
+    , detectRaces = False
+    , preemptionBound = Nothing
+    , executionLimit = Nothing
+    , concurrencyStats = False
+    , scheduleDir = Nothing
+    , replayScheduleFile = Nothing
     , printResultOnly = False
     , testFilter = Nothing
     , cargoTestFile = Nothing
@@ -517,6 +685,14 @@ mirConfig = Crux.Config
             "show model on counter-example"
             (GetOpt.NoArg (\opts -> Right opts { showModel = True }))
 
+        , GetOpt.Option []    ["counterexamples"]
+            "write each counterexample as JSON to a file in this directory"
+            (GetOpt.ReqArg "dir" (\v opts -> Right opts { counterexampleDir = Just v }))
+
+        , GetOpt.Option []    ["regression-tests"]
+            "write a Rust test replaying each counterexample to this file (see lib/crucible_replay)"
+            (GetOpt.ReqArg "file" (\v opts -> Right opts { regressionTestFile = Just v }))
+
         , GetOpt.Option [] ["assert-false-on-error"]
             "when translation fails, assert false in output and keep going"
             (GetOpt.NoArg (\opts -> Right opts { assertFalse = True }))
@@ -525,6 +701,46 @@ mirConfig = Crux.Config
             "run with support for concurrency primitives"
             (GetOpt.NoArg (\opts -> Right opts { concurrency = True }))
 
+        , GetOpt.Option [] ["check-aliasing"]
+            "report aliasing violations, such as writing through a pointer derived from a shared reference (based on Tree Borrows)"
+            (GetOpt.NoArg (\opts -> Right opts { checkAliasing = True }))
+
+        , GetOpt.Option [] ["check-leaks"]
+            "report heap allocations that are not freed by the end of each test"
+            (GetOpt.NoArg (\opts -> Right opts { checkLeaks = True }))
+
+        , GetOpt.Option [] ["weak-memory"]
+            "model relaxed and release/acquire atomics instead of treating all atomics as sequentially consistent (implies --concurrency)"
+            (GetOpt.NoArg (\opts -> Right opts { weakMemory = True, concurrency = True }))
+
+        , GetOpt.Option [] ["detect-races"]
+            "report data races between non-atomic accesses to shared memory (implies --concurrency)"
+            (GetOpt.NoArg (\opts -> Right opts { detectRaces = True, concurrency = True }))
+
+        , GetOpt.Option [] ["preemption-bound"]
+            "explore only the executions with at most this many preemptions (implies --concurrency)"
+            (GetOpt.ReqArg "num" (\v opts -> case readMaybe v of
+                Just n | n >= 0 -> Right opts { preemptionBound = Just n, concurrency = True }
+                _ -> Left "--preemption-bound requires a non-negative integer"))
+
+        , GetOpt.Option [] ["max-executions"]
+            "stop exploring each test after this many executions (implies --concurrency)"
+            (GetOpt.ReqArg "num" (\v opts -> case readMaybe v of
+                Just n | n > 0 -> Right opts { executionLimit = Just n, concurrency = True }
+                _ -> Left "--max-executions requires a positive integer"))
+
+        , GetOpt.Option [] ["concurrency-stats"]
+            "print how many executions and scheduling choices were explored for each test (implies --concurrency)"
+            (GetOpt.NoArg (\opts -> Right opts { concurrencyStats = True, concurrency = True }))
+
+        , GetOpt.Option [] ["schedule-dir"]
+            "write the schedule of each failing concurrent execution under this directory"
+            (GetOpt.ReqArg "dir" (\v opts -> Right opts { scheduleDir = Just v }))
+
+        , GetOpt.Option [] ["replay-schedule"]
+            "run the test a schedule was written for, following that schedule (implies --concurrency)"
+            (GetOpt.ReqArg "file" (\v opts -> Right opts { replayScheduleFile = Just v, concurrency = True }))
+
         , GetOpt.Option []  ["test-filter"]
             "run only tests whose names contain this string"
             (GetOpt.ReqArg "string" (\v opts -> Right opts { testFilter = Just $ Text.pack v }))
@@ -581,9 +797,20 @@ showRegEntry col mty (C.RegEntry tp rv) =
     (TyUint _sz, C.BVRepr _w) -> return $ case W4.asBV rv of
                      Just i  -> show (BV.asUnsigned i)
                      Nothing -> "Symbolic BV"
-    (TyFloat _,  C.RealValRepr) -> return $ case W4.asRational rv of
-                     Just f -> show f
-                     Nothing -> "Symbolic real"
+    (TyFloat _, C.FloatRepr (_ :: C.FloatInfoRepr fi)) -> do
+        sym <- C.getSymInterface
+        liftIO $ do
+          isNaN' <- W4.iFloatIsNaN @sym @fi sym rv
+          isInf <- W4.iFloatIsInf @sym @fi sym rv
+          isNeg <- W4.iFloatIsNeg @sym @fi sym rv
+          r <- W4.iFloatToReal @sym @fi sym rv
+          return $ case (W4.asConstantPred isNaN', W4.asConstantPred isInf,
+                         W4.asConstantPred isNeg, W4.asRational r) of
+            (Just True, _, _, _) -> "NaN"
+            (_, Just True, Just True, _) -> "-inf"
+            (_, Just True, Just False, _) -> "inf"
+            (Just False, Just False, _, Just q) -> show (fromRational q :: Double)
+            _ -> "Symbolic float"
 
     (TyTuple [], C.UnitRepr) -> return "()"
 
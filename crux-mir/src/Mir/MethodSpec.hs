@@ -0,0 +1,611 @@
+{-# Language DataKinds #-}
+{-# Language FlexibleContexts #-}
+{-# Language FlexibleInstances #-}
+{-# Language GADTs #-}
+{-# Language MultiParamTypeClasses #-}
+{-# Language OverloadedStrings #-}
+{-# Language PatternSynonyms #-}
+{-# Language RankNTypes #-}
+{-# Language ScopedTypeVariables #-}
+{-# Language TypeApplications #-}
+{-# Language TypeFamilies #-}
+{-# Language TypeOperators #-}
+{-# Language UndecidableInstances #-}
+
+-- | Compositional reasoning with @MethodSpec@s.  A spec for a function is
+-- built by a spec function in the test crate (see @crucible::method_spec@),
+-- which creates symbolic arguments, assumes the function's preconditions,
+-- and asserts its postconditions about a symbolic result.  Once enabled, the
+-- spec overrides the function for the rest of the test: each call checks the
+-- preconditions against the actual arguments, and then assumes the
+-- postconditions about a fresh result instead of running the function body.
+--
+-- The spec is only as good as the separate test that checks the function
+-- against it.  What this module does check is that the spec can describe
+-- everything the call may do: arguments and results may not contain
+-- references or raw pointers (other than a reference passed directly as an
+-- argument), references that the spec writes through may not alias other
+-- reference arguments, and mutable statics are clobbered after each call,
+-- since specs can't mention them.
+module Mir.MethodSpec
+  ( builderNew
+  , clobberGlobals
+  ) where
+
+import Control.Lens ((^.), (^?), ix)
+import Control.Monad
+import Control.Monad.IO.Class
+import Control.Monad.State (StateT, execStateT, modify)
+
+import Data.Foldable (toList)
+import Data.IORef
+import qualified Data.List as List
+import qualified Data.Map as Map
+import Data.Maybe (isJust, isNothing)
+import Data.Sequence (Seq)
+import qualified Data.Sequence as Seq
+import Data.Set (Set)
+import qualified Data.Set as Set
+import qualified Data.Text as Text
+import qualified Data.Vector as V
+
+import Data.Parameterized.Context (pattern Empty, pattern (:>))
+import qualified Data.Parameterized.Context as Ctx
+import qualified Data.Parameterized.Map as MapF
+import Data.Parameterized.Map (MapF)
+import Data.Parameterized.Nonce (freshNonce, globalNonceGenerator, indexValue)
+import Data.Parameterized.Some
+import Data.Parameterized.TraversableFC (toListFC)
+
+import What4.Expr.Builder (ExprBuilder, Expr(BoundVarExpr), ExprBoundVar, bvarName, bvarType, evalBoundVars)
+import What4.Interface
+import What4.InterpretedFloatingPoint (iFloatBaseTypeRepr)
+import What4.LabeledPred (LabeledPred(..))
+import What4.Partial (PartExpr, pattern PE, pattern Unassigned)
+
+import Lang.Crucible.Backend
+    ( CrucibleAssumption(..), FrameIdentifier, ProofGoal(..), IsSymBackend
+    , IsSymInterface, addAssumption, addAssumptions, addProofObligation
+    , assert, assumptionPred, assumptionsPred
+    , backendGetSym, collectAssumptions, flattenAssumptions, goalsToList
+    , popAssumptionFrameAndObligations, pushAssumptionFrame )
+import Lang.Crucible.CFG.Common (globalType)
+import Lang.Crucible.FunctionHandle
+import Lang.Crucible.Simulator.ExecutionTree
+import Lang.Crucible.Simulator.GlobalState
+import Lang.Crucible.Simulator.OverrideSim
+import Lang.Crucible.Simulator.RegMap
+import Lang.Crucible.Simulator.SimError
+import Lang.Crucible.Types
+
+import Mir.DefId
+import Mir.Generator (CollectionState, collection, handleMap, staticMap, MirHandle(..), StaticVar(..))
+import Mir.Intrinsics
+import qualified Mir.Mir as M
+
+
+-- | A value in the spec, together with its type.
+data SpecValue sym = forall tp. SpecValue (TypeRepr tp) (RegValue sym tp)
+
+-- | An argument of the subject function.
+data SpecArg sym
+  = ValueArg (SpecValue sym)
+  -- | A reference argument.  The spec describes the value it points to: its
+  -- type, the spec's own reference, the value before the call, and the value
+  -- after the call if the spec changes it.
+  | forall tp. RefArg (TypeRepr tp) (MirReferenceMux sym tp) (RegValue sym tp) (Maybe (RegValue sym tp))
+
+-- | The state of a `MethodSpecBuilder`.  The spec function calls `add_arg`,
+-- then `gather_assumes`, then `set_return` and `gather_asserts`, and finally
+-- `finish`.
+data SpecBuilder sym = SpecBuilder
+  { sbSubject :: DefId
+  , sbCS :: CollectionState
+  , sbFrame :: FrameIdentifier
+    -- ^ The assumption frame pushed by `builder_new`, which separates the
+    -- spec function's assertions, the postconditions, from earlier ones.
+  , sbArgs :: Seq (SpecArg sym)
+  , sbPre :: Maybe [Pred sym]
+  , sbReturn :: Maybe (SpecValue sym)
+  , sbPost :: Maybe [Pred sym]
+  }
+
+data Spec sym = Spec
+  { specSubject :: DefId
+  , specCS :: CollectionState
+  , specArgs :: [SpecArg sym]
+  , specPre :: [Pred sym]
+  , specReturn :: Maybe (SpecValue sym)
+  , specPost :: [Pred sym]
+  }
+
+instance (IsSymInterface sym, sym ~ ExprBuilder t st fs) =>
+    MethodSpecBuilderImpl sym (SpecBuilder sym) where
+  msbAddArg = builderAddArg
+  msbSetReturn = builderSetReturn
+  msbGatherAssumes = builderGatherAssumes
+  msbGatherAsserts = builderGatherAsserts
+  msbFinish = builderFinish
+
+instance (IsSymInterface sym, sym ~ ExprBuilder t st fs) =>
+    MethodSpecImpl sym (Spec sym) where
+  msPrettyPrint = specPrettyPrint
+  msEnable = specEnable
+
+
+-- | Start building a spec for the function @defId@.
+builderNew ::
+  (IsSymInterface sym, sym ~ ExprBuilder t st fs) =>
+  CollectionState ->
+  DefId ->
+  OverrideSim (p sym) sym MIR rtp args ret (MethodSpecBuilder sym)
+builderNew cs defId = ovrWithBackend $ \bak -> do
+  frame <- liftIO $ pushAssumptionFrame bak
+  return $ MethodSpecBuilder SpecBuilder
+    { sbSubject = defId
+    , sbCS = cs
+    , sbFrame = frame
+    , sbArgs = mempty
+    , sbPre = Nothing
+    , sbReturn = Nothing
+    , sbPost = Nothing
+    }
+
+builderAddArg ::
+  (IsSymInterface sym, sym ~ ExprBuilder t st fs) =>
+  TypeRepr tp ->
+  MirReferenceMux sym tp ->
+  SpecBuilder sym ->
+  OverrideSim (p sym) sym MIR rtp args ret (SpecBuilder sym)
+builderAddArg tpr ref sb = do
+  when (isJust $ sbPre sb) $
+    fail "MethodSpecBuilder: add_arg was called after gather_assumes"
+  sym <- getSymInterface
+  let desc = "argument " ++ show (Seq.length $ sbArgs sb) ++ " of " ++ show (sbSubject sb)
+  v <- readMirRefSim tpr ref
+  arg <- case tpr of
+    MirReferenceRepr tpr' -> do
+      pre <- readMirRefSim tpr' v
+      liftIO $ checkNoRefs sym desc tpr' pre
+      return $ RefArg tpr' v pre Nothing
+    _ -> do
+      liftIO $ checkNoRefs sym desc tpr v
+      return $ ValueArg $ SpecValue tpr v
+  return sb { sbArgs = sbArgs sb Seq.|> arg }
+
+-- | The preconditions are the assumptions in scope that mention one of the
+-- arguments' variables, including those made before `builder_new`.
+builderGatherAssumes ::
+  (IsSymInterface sym, sym ~ ExprBuilder t st fs) =>
+  SpecBuilder sym ->
+  OverrideSim (p sym) sym MIR rtp args ret (SpecBuilder sym)
+builderGatherAssumes sb = ovrWithBackend $ \bak -> do
+  when (isJust $ sbPre sb) $
+    fail "MethodSpecBuilder: gather_assumes was called twice"
+  let sym = backendGetSym bak
+  vars <- liftIO $ Set.unions <$> mapM (argVars sym) (toList $ sbArgs sb)
+  asmps <- liftIO $ flattenAssumptions sym =<< collectAssumptions bak
+  let relevant p = not $ Set.disjoint vars $ exprUninterpConstants sym p
+  return sb { sbPre = Just [p | a <- asmps, let p = assumptionPred a, relevant p] }
+
+builderSetReturn ::
+  (IsSymInterface sym, sym ~ ExprBuilder t st fs) =>
+  TypeRepr tp ->
+  MirReferenceMux sym tp ->
+  SpecBuilder sym ->
+  OverrideSim (p sym) sym MIR rtp args ret (SpecBuilder sym)
+builderSetReturn tpr ref sb = do
+  when (isJust $ sbPost sb) $
+    fail "MethodSpecBuilder: set_return was called after gather_asserts"
+  sym <- getSymInterface
+  v <- readMirRefSim tpr ref
+  liftIO $ checkNoRefs sym ("the return value of " ++ show (sbSubject sb)) tpr v
+  return sb { sbReturn = Just $ SpecValue tpr v }
+
+-- | The postconditions are the assertions made since `builder_new`.  They
+-- remain proof obligations of the test that runs the spec function, so that
+-- the spec is only used once it has been checked.  This also records the
+-- values that reference arguments point to after the call.
+builderGatherAsserts ::
+  (IsSymInterface sym, sym ~ ExprBuilder t st fs) =>
+  SpecBuilder sym ->
+  OverrideSim (p sym) sym MIR rtp args ret (SpecBuilder sym)
+builderGatherAsserts sb = ovrWithBackend $ \bak -> do
+  when (isNothing $ sbPre sb) $
+    fail "MethodSpecBuilder: gather_asserts was called before gather_assumes"
+  when (isJust $ sbPost sb) $
+    fail "MethodSpecBuilder: gather_asserts was called twice"
+  let sym = backendGetSym bak
+  args' <- forM (zip [0 :: Int ..] $ toList $ sbArgs sb) $ \(i, arg) -> case arg of
+    ValueArg _ -> return arg
+    RefArg tpr ref pre _ -> do
+      post <- readMirRefSim tpr ref
+      liftIO $ checkNoRefs sym ("argument " ++ show i ++ " of " ++ show (sbSubject sb)) tpr post
+      same <- liftIO $ sameValue sym tpr pre post
+      return $ RefArg tpr ref pre (if same then Nothing else Just post)
+  (frameAsmps, obligations) <- liftIO $ popAssumptionFrameAndObligations bak (sbFrame sb)
+  let goals = maybe [] goalsToList obligations
+  posts <- liftIO $ forM goals $ \(ProofGoal asmps (LabeledPred p _)) -> do
+    asmpsPred <- assumptionsPred sym asmps
+    impliesPred sym asmpsPred p
+  liftIO $ do
+    addAssumptions bak frameAsmps
+    forM_ (zip goals posts) $ \(ProofGoal _ (LabeledPred _ msg), post) ->
+      addProofObligation bak (LabeledPred post msg)
+  return sb { sbArgs = Seq.fromList args', sbPost = Just posts }
+
+builderFinish ::
+  (IsSymInterface sym, sym ~ ExprBuilder t st fs) =>
+  SpecBuilder sym ->
+  OverrideSim (p sym) sym MIR rtp args ret (MethodSpec sym)
+builderFinish sb = do
+  (pre, post) <- case (sbPre sb, sbPost sb) of
+    (Just pre, Just post) -> return (pre, post)
+    _ -> fail "MethodSpecBuilder: finish was called before gather_asserts"
+  nonce <- liftIO $ freshNonce globalNonceGenerator
+  return MethodSpec
+    { msData = Spec
+        { specSubject = sbSubject sb
+        , specCS = sbCS sb
+        , specArgs = toList $ sbArgs sb
+        , specPre = pre
+        , specReturn = sbReturn sb
+        , specPost = post
+        }
+    , msNonce = indexValue nonce
+    }
+
+
+-- | Override the subject function with the spec for the rest of the test.
+-- A later spec for the same function replaces this one.
+specEnable ::
+  (IsSymInterface sym, sym ~ ExprBuilder t st fs) =>
+  Spec sym ->
+  OverrideSim (p sym) sym MIR rtp args ret ()
+specEnable spec = do
+  MirHandle _ _ fh <- case specCS spec ^? handleMap . ix (specSubject spec) of
+    Just mh -> return mh
+    Nothing -> fail $ "MethodSpec: couldn't find the function " ++ show (specSubject spec)
+  let argTys = toListFC Some $ handleArgTypes fh
+  when (length argTys /= length (specArgs spec)) $
+    fail $ "MethodSpec for " ++ show (specSubject spec) ++ ": the function takes " ++
+      show (length argTys) ++ " arguments, but the spec has " ++ show (length (specArgs spec))
+  forM_ (zip3 [0 :: Int ..] (specArgs spec) argTys) $ \(i, arg, Some tpr) ->
+    unless (argMatchesType arg tpr) $
+      fail $ "MethodSpec for " ++ show (specSubject spec) ++ ": argument " ++ show i ++
+        " has type " ++ show tpr ++ ", which doesn't match the spec"
+  let retTy = handleReturnType fh
+  unless (returnMatchesType (specReturn spec) retTy) $
+    fail $ "MethodSpec for " ++ show (specSubject spec) ++ ": the function returns " ++
+      show retTy ++ ", which doesn't match the spec"
+  bindFnHandle fh $ UseOverride $ mkOverride' (handleName fh) retTy $ runSpec spec retTy
+  where
+    argMatchesType :: SpecArg sym -> TypeRepr tp -> Bool
+    argMatchesType (ValueArg (SpecValue tpr' _)) tpr = isJust $ testEquality tpr' tpr
+    argMatchesType (RefArg tpr' _ _ _) (MirReferenceRepr tpr) = isJust $ testEquality tpr' tpr
+    argMatchesType RefArg{} _ = False
+
+    returnMatchesType :: Maybe (SpecValue sym) -> TypeRepr tp -> Bool
+    returnMatchesType (Just (SpecValue tpr' _)) tpr = isJust $ testEquality tpr' tpr
+    returnMatchesType Nothing UnitRepr = True
+    returnMatchesType Nothing _ = False
+
+-- | A reference argument as seen by one call of the override.
+data CallRef sym = forall tp. CallRef Int (TypeRepr tp) (MirReferenceMux sym tp) (Maybe (RegValue sym tp))
+
+-- | Run one call of the subject function under the spec.  The spec's
+-- variables that appear by themselves in the arguments are bound to the
+-- actual arguments; every other part of the arguments must be equal to the
+-- spec's, which is asserted along with the preconditions.  The remaining
+-- variables, such as the result's, are instantiated with fresh variables.
+runSpec ::
+  forall sym t st fs p rtp args ret.
+  (IsSymInterface sym, sym ~ ExprBuilder t st fs) =>
+  Spec sym ->
+  TypeRepr ret ->
+  OverrideSim (p sym) sym MIR rtp args ret (RegValue sym ret)
+runSpec spec retTy = ovrWithBackend $ \bak -> do
+  let sym = backendGetSym bak
+  let name = show $ specSubject spec
+  RegMap actuals <- getOverrideArgs
+
+  substRef <- liftIO $ newIORef MapF.empty
+  eqsRef <- liftIO $ newIORef []
+  let matchLeaf :: forall bt. BaseTypeRepr bt -> Pred sym -> Expr t bt -> Expr t bt -> IO ()
+      matchLeaf _ g x y = do
+        s <- readIORef substRef
+        case x of
+          BoundVarExpr v | not (MapF.member v s) -> writeIORef substRef $ MapF.insert v y s
+          _ -> modifyIORef eqsRef $ (:) $ \s' -> do
+            x' <- substExpr sym s' x
+            impliesPred sym g =<< isEq sym x' y
+  let matchValue :: String -> TypeRepr tp -> RegValue sym tp -> RegValue sym tp -> IO ()
+      matchValue desc tpr x y = do
+        ok <- zipLeaves sym desc matchLeaf (truePred sym) tpr x y
+        unless ok $ fail $ "MethodSpec for " ++ name ++ ": " ++ desc ++
+          " doesn't have the same shape as in the spec"
+
+  refs <- fmap concat $ forM (zip3 [0 :: Int ..] (specArgs spec) (toListFC Some actuals)) $
+    \(i, arg, Some (RegEntry tpr v)) -> do
+      let desc = "argument " ++ show i
+      case arg of
+        ValueArg (SpecValue tpr' x)
+          | Just Refl <- testEquality tpr' tpr -> do
+            liftIO $ matchValue desc tpr x v
+            return []
+        RefArg tpr' _ pre post
+          | MirReferenceRepr tpr'' <- tpr
+          , Just Refl <- testEquality tpr' tpr'' -> do
+            actual <- readMirRefSim tpr' v
+            liftIO $ matchValue desc tpr' pre actual
+            return [CallRef i tpr' v post]
+        _ -> fail $ "MethodSpec for " ++ name ++ ": unexpected type for " ++ desc
+
+  -- Check the preconditions.
+  preVars <- liftIO $ Set.unions <$> sequence
+    (map (argVars sym) (specArgs spec) ++ map (return . exprUninterpConstants sym) (specPre spec))
+  subst <- liftIO $ freshenVars sym preVars =<< readIORef substRef
+  eqs <- liftIO $ readIORef eqsRef
+  liftIO $ forM_ (reverse eqs) $ \eq -> do
+    p <- eq subst
+    assert bak p $ AssertFailureSimError
+      ("MethodSpec precondition for " ++ name ++ " does not hold")
+      "an argument doesn't match the spec"
+  liftIO $ forM_ (specPre spec) $ \pre -> do
+    p <- substExpr sym subst pre
+    assert bak p $ AssertFailureSimError
+      ("MethodSpec precondition for " ++ name ++ " does not hold")
+      (show $ printSymExpr pre)
+
+  -- The spec writes through each reference it changes, which only describes
+  -- the call if no other reference argument points into the same memory.
+  -- The references may overlap without having the same type, as when one
+  -- points to a field of the other's target.
+  let modified (CallRef _ _ _ post) = isJust post
+  forM_ (pairs refs) $ \(a@(CallRef i _ r _), b@(CallRef j _ r' _)) ->
+    when (modified a || modified b) $ liftIO $ do
+      p <- notPred sym =<< mirRef_overlapsIO bak r r'
+      assert bak p $ AssertFailureSimError
+        ("MethodSpec for " ++ name ++ ": arguments " ++ show i ++ " and " ++ show j ++
+          " refer to overlapping memory")
+        "the spec changes the value behind one of them, so it can't describe this call"
+
+  -- Assume the postconditions about fresh instances of the remaining
+  -- variables.
+  postVars <- liftIO $ Set.unions <$> sequence
+    ([valueVars sym tpr v | Just (SpecValue tpr v) <- [specReturn spec]] ++
+     [valueVars sym tpr v | RefArg tpr _ _ (Just v) <- specArgs spec] ++
+     map (return . exprUninterpConstants sym) (specPost spec))
+  subst' <- liftIO $ freshenVars sym postVars subst
+  loc <- liftIO $ getCurrentProgramLoc sym
+  liftIO $ forM_ (specPost spec) $ \post -> do
+    p <- substExpr sym subst' post
+    addAssumption bak $ GenericAssumption loc ("MethodSpec postcondition for " ++ name) p
+
+  forM_ refs $ \(CallRef i tpr r post) -> forM_ post $ \v -> do
+    v' <- liftIO $ substValue sym subst' ("argument " ++ show i) tpr v
+    writeMirRefSim tpr r v'
+
+  -- The function may also have changed any mutable static.
+  clobberGlobals (specCS spec)
+
+  case specReturn spec of
+    Just (SpecValue tpr v) | Just Refl <- testEquality tpr retTy ->
+      liftIO $ substValue sym subst' "the return value" tpr v
+    Nothing | UnitRepr <- retTy -> return ()
+    _ -> fail $ "MethodSpec for " ++ name ++ ": unexpected return type " ++ show retTy
+  where
+    pairs xs = [(x, y) | (i, x) <- zip [0 :: Int ..] xs, (j, y) <- zip [0 ..] xs, i < j]
+
+specPrettyPrint ::
+  (IsSymInterface sym, sym ~ ExprBuilder t st fs) =>
+  Spec sym ->
+  OverrideSim (p sym) sym MIR rtp args ret (RegValue sym (MirSlice (BVType 8)))
+specPrettyPrint spec = do
+  sym <- getSymInterface
+  argLines <- liftIO $ forM (zip [0 :: Int ..] (specArgs spec)) $ \(i, arg) -> case arg of
+    ValueArg (SpecValue tpr v) -> do
+      v' <- ppValue sym tpr v
+      return $ "arg " ++ show i ++ ": " ++ v'
+    RefArg tpr _ pre post -> do
+      pre' <- ppValue sym tpr pre
+      post' <- mapM (ppValue sym tpr) post
+      return $ "arg " ++ show i ++ ": &" ++ pre' ++ maybe "" (" -> " ++) post'
+  retLines <- liftIO $ forM (toList $ specReturn spec) $ \(SpecValue tpr v) ->
+    ("return: " ++) <$> ppValue sym tpr v
+  strSlice $ Text.pack $ List.intercalate "\n" $
+    ["subject: " ++ show (specSubject spec)] ++
+    argLines ++
+    ["pre: " ++ show (printSymExpr p) | p <- specPre spec] ++
+    retLines ++
+    ["post: " ++ show (printSymExpr p) | p <- specPost spec]
+
+
+-- | Replace every mutable static with arbitrary values of the same shape.
+clobberGlobals ::
+  IsSymInterface sym =>
+  CollectionState ->
+  OverrideSim (p sym) sym MIR rtp args ret ()
+clobberGlobals cs = do
+  sym <- getSymInterface
+  forM_ (Map.elems $ cs ^. collection . M.statics) $ \static ->
+    when (static ^. M.sMutable) $
+      forM_ (Map.lookup (static ^. M.sName) (cs ^. staticMap)) $ \(StaticVar gv) -> do
+        let desc = "static " ++ show (static ^. M.sName)
+        v <- readGlobal gv
+        v' <- liftIO $ traverseLeaves sym desc
+          (\btpr _ -> freshConstant sym (safeSymbol $ show $ static ^. M.sName) btpr)
+          (globalType gv) v
+        writeGlobal gv v'
+
+
+-- | Apply a function to each base-type value inside a value.  This fails on
+-- references and raw pointers, since the values they point to are not part
+-- of the value itself, and on types that specs don't support.
+traverseLeaves ::
+  forall sym m tp.
+  (IsSymInterface sym, MonadFail m) =>
+  sym ->
+  String ->
+  (forall bt. BaseTypeRepr bt -> SymExpr sym bt -> m (SymExpr sym bt)) ->
+  TypeRepr tp ->
+  RegValue sym tp ->
+  m (RegValue sym tp)
+traverseLeaves sym desc f = go
+  where
+    go :: forall tp'. TypeRepr tp' -> RegValue sym tp' -> m (RegValue sym tp')
+    go tpr v | AsBaseType btpr <- asBaseType tpr = f btpr v
+    go (FloatRepr fi) v = f (iFloatBaseTypeRepr sym fi) v
+    go UnitRepr () = pure ()
+    go AnyRepr (AnyValue tpr v) = AnyValue tpr <$> go tpr v
+    go (MaybeRepr tpr) pe = goPartExpr tpr pe
+    go (VectorRepr tpr) vec = traverse (go tpr) vec
+    go (StructRepr ctx) v = Ctx.zipWithM (\tpr (RV x) -> RV <$> go tpr x) ctx v
+    go (VariantRepr ctx) v = Ctx.zipWithM (\tpr (VB pe) -> VB <$> goPartExpr tpr pe) ctx v
+    go (MirVectorRepr tpr) vec = case vec of
+      MirVector_Vector v -> MirVector_Vector <$> go (VectorRepr tpr) v
+      MirVector_PartialVector v -> MirVector_PartialVector <$> go (VectorRepr (MaybeRepr tpr)) v
+      MirVector_Array a
+        | AsBaseType btpr <- asBaseType tpr -> MirVector_Array <$> go (UsizeArrayRepr btpr) a
+        | otherwise -> error "unreachable: MirVector_Array elem type is always a base type"
+    go (MirReferenceRepr _) _ = fail $ "MethodSpec: " ++ desc ++
+      " contains a reference or raw pointer, which specs can't describe"
+    go tpr _ = fail $ "MethodSpec: " ++ desc ++ " has type " ++ show tpr ++
+      ", which specs don't support"
+
+    goPartExpr :: forall tp'. TypeRepr tp' ->
+      PartExpr (Pred sym) (RegValue sym tp') -> m (PartExpr (Pred sym) (RegValue sym tp'))
+    goPartExpr _ Unassigned = pure Unassigned
+    goPartExpr tpr (PE p v) = PE <$> f BaseBoolRepr p <*> go tpr v
+
+-- | Call a function on each pair of corresponding base-type values inside two
+-- values of the same type, along with the condition under which they are
+-- present.  Returns @False@ if the values have different shapes.
+zipLeaves ::
+  forall sym tp.
+  IsSymInterface sym =>
+  sym ->
+  String ->
+  (forall bt. BaseTypeRepr bt -> Pred sym -> SymExpr sym bt -> SymExpr sym bt -> IO ()) ->
+  Pred sym ->
+  TypeRepr tp ->
+  RegValue sym tp ->
+  RegValue sym tp ->
+  IO Bool
+zipLeaves sym desc f = go
+  where
+    go :: forall tp'. Pred sym -> TypeRepr tp' -> RegValue sym tp' -> RegValue sym tp' -> IO Bool
+    go g tpr x y | AsBaseType btpr <- asBaseType tpr = f btpr g x y >> return True
+    go g (FloatRepr fi) x y = f (iFloatBaseTypeRepr sym fi) g x y >> return True
+    go _ UnitRepr () () = return True
+    go g AnyRepr (AnyValue tx x) (AnyValue ty y) = case testEquality tx ty of
+      Just Refl -> go g tx x y
+      Nothing -> return False
+    go g (MaybeRepr tpr) x y = goPartExpr g tpr x y
+    go g (VectorRepr tpr) x y
+      | V.length x == V.length y = and <$> V.zipWithM (go g tpr) x y
+      | otherwise = return False
+    go g (StructRepr ctx) x y = Ctx.forIndex (Ctx.size ctx)
+      (\acc i -> (&&) <$> acc <*> go g (ctx Ctx.! i) (unRV $ x Ctx.! i) (unRV $ y Ctx.! i))
+      (return True)
+    go g (VariantRepr ctx) x y = Ctx.forIndex (Ctx.size ctx)
+      (\acc i -> (&&) <$> acc <*> goPartExpr g (ctx Ctx.! i) (unVB $ x Ctx.! i) (unVB $ y Ctx.! i))
+      (return True)
+    go g (MirVectorRepr tpr) x y = case (x, y) of
+      (MirVector_Vector a, MirVector_Vector b) -> go g (VectorRepr tpr) a b
+      (MirVector_PartialVector a, MirVector_PartialVector b) -> go g (VectorRepr (MaybeRepr tpr)) a b
+      (MirVector_Array a, MirVector_Array b)
+        | AsBaseType btpr <- asBaseType tpr -> go g (UsizeArrayRepr btpr) a b
+      _ -> return False
+    go _ tpr _ _ = fail $ "MethodSpec: " ++ desc ++ " has type " ++ show tpr ++
+      ", which specs don't support"
+
+    goPartExpr :: forall tp'. Pred sym -> TypeRepr tp' ->
+      PartExpr (Pred sym) (RegValue sym tp') -> PartExpr (Pred sym) (RegValue sym tp') -> IO Bool
+    goPartExpr _ _ Unassigned Unassigned = return True
+    goPartExpr g tpr (PE p x) (PE q y) = do
+      f BaseBoolRepr g p q
+      g' <- andPred sym g q
+      go g' tpr x y
+    goPartExpr g _ (PE p _) Unassigned = f BaseBoolRepr g p (falsePred sym) >> return True
+    goPartExpr g _ Unassigned (PE q _) = f BaseBoolRepr g (falsePred sym) q >> return True
+
+-- | The variables of the spec that appear in a value.
+valueVars ::
+  (IsSymInterface sym, sym ~ ExprBuilder t st fs) =>
+  sym ->
+  TypeRepr tp ->
+  RegValue sym tp ->
+  IO (Set (Some (ExprBoundVar t)))
+valueVars sym tpr v = execStateT (traverseLeaves sym "a value" addVars tpr v) Set.empty
+  where
+    addVars :: BaseTypeRepr bt -> Expr t bt -> StateT (Set (Some (ExprBoundVar t))) IO (Expr t bt)
+    addVars _ e = modify (Set.union $ exprUninterpConstants sym e) >> return e
+
+argVars ::
+  (IsSymInterface sym, sym ~ ExprBuilder t st fs) =>
+  sym ->
+  SpecArg sym ->
+  IO (Set (Some (ExprBoundVar t)))
+argVars sym (ValueArg (SpecValue tpr v)) = valueVars sym tpr v
+argVars sym (RefArg tpr _ pre _) = valueVars sym tpr pre
+
+type VarSubst t = MapF (ExprBoundVar t) (Expr t)
+
+data BoundVarAssignment t = forall ctx.
+  BoundVarAssignment (Ctx.Assignment (ExprBoundVar t) ctx) (Ctx.Assignment (Expr t) ctx)
+
+substExpr :: ExprBuilder t st fs -> VarSubst t -> Expr t bt -> IO (Expr t bt)
+substExpr sym s e =
+  case MapF.foldrWithKey add (BoundVarAssignment Ctx.empty Ctx.empty) s of
+    BoundVarAssignment vars vals -> evalBoundVars sym e vars vals
+  where
+    add :: ExprBoundVar t tp -> Expr t tp -> BoundVarAssignment t -> BoundVarAssignment t
+    add var val (BoundVarAssignment vars vals) =
+      BoundVarAssignment (Ctx.extend vars var) (Ctx.extend vals val)
+
+substValue ::
+  (IsSymInterface sym, sym ~ ExprBuilder t st fs) =>
+  sym ->
+  VarSubst t ->
+  String ->
+  TypeRepr tp ->
+  RegValue sym tp ->
+  IO (RegValue sym tp)
+substValue sym s desc = traverseLeaves sym desc (\_ e -> substExpr sym s e)
+
+-- | Extend the substitution with a fresh variable for each of @vars@ that it
+-- doesn't bind yet.
+freshenVars ::
+  ExprBuilder t st fs ->
+  Set (Some (ExprBoundVar t)) ->
+  VarSubst t ->
+  IO (VarSubst t)
+freshenVars sym vars s0 = foldM add s0 (Set.toList vars)
+  where
+    add s (Some var)
+      | MapF.member var s = return s
+      | otherwise = do
+        val <- freshConstant sym (bvarName var) (bvarType var)
+        return $ MapF.insert var val s
+
+checkNoRefs :: IsSymInterface sym => sym -> String -> TypeRepr tp -> RegValue sym tp -> IO ()
+checkNoRefs sym desc tpr v = void $ traverseLeaves sym desc (\_ e -> return e) tpr v
+
+-- | Check whether two values are syntactically the same.
+sameValue :: IsSymInterface sym => sym -> TypeRepr tp -> RegValue sym tp -> RegValue sym tp -> IO Bool
+sameValue sym tpr x y = do
+  same <- newIORef True
+  let check _ _ a b = unless (isJust $ testEquality a b) $ writeIORef same False
+  sameShape <- zipLeaves sym "a value" check (truePred sym) tpr x y
+  (sameShape &&) <$> readIORef same
+
+ppValue :: IsSymInterface sym => sym -> TypeRepr tp -> RegValue sym tp -> IO String
+ppValue sym tpr v = do
+  leaves <- execStateT (traverseLeaves sym "a value" addLeaf tpr v) []
+  return $ case reverse leaves of
+    [leaf] -> leaf
+    leaves' -> "(" ++ List.intercalate ", " leaves' ++ ")"
+  where
+    addLeaf :: BaseTypeRepr bt -> SymExpr sym bt -> StateT [String] IO (SymExpr sym bt)
+    addLeaf _ e = modify (show (printSymExpr e) :) >> return e
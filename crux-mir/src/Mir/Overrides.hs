@@ -13,7 +13,7 @@
 
 module Mir.Overrides (bindFn, getString) where
 
-import Control.Lens ((^?), (.=), use, ix, _Wrapped)
+import Control.Lens ((^.), (^?), (.=), use, ix, _Wrapped)
 import Control.Monad
 import Control.Monad.IO.Class
 import Control.Monad.State (get)
@@ -38,9 +38,11 @@ import qualified Data.Parameterized.Context as Ctx
 import Data.Parameterized.NatRepr
 
 import What4.Config( getOpt, setOpt, getOptionSetting )
+import What4.Expr.Builder (ExprBuilder)
 import What4.Expr.GroundEval (GroundValue, GroundEvalFn(..), GroundArray(..))
 import What4.FunctionName (FunctionName, functionNameFromText)
 import What4.Interface
+import What4.InterpretedFloatingPoint (iFloatBaseTypeRepr)
 import What4.Partial (PartExpr, pattern PE, pattern Unassigned)
 import What4.Protocol.Online ( checkWithAssumptionsAndModel )
 import What4.SatResult (SatResult(..))
@@ -69,8 +71,9 @@ import qualified Crux.Overrides as Crux
 
 import Mir.DefId
 import Mir.FancyMuxTree
-import Mir.Generator (CollectionState, collection, handleMap, MirHandle(..))
+import Mir.Generator (CollectionState, collection, handleMap, weakMemoryVars, MirHandle(..))
 import Mir.Intrinsics
+import Mir.MethodSpec (builderNew, clobberGlobals)
 import qualified Mir.Mir as M
 
 
@@ -97,17 +100,35 @@ makeSymbolicVar ::
     BaseTypeRepr btp ->
     TypedOverride (p sym) sym MIR (EmptyCtx ::> MirSlice (BVType 8)) (BaseToType btp)
 makeSymbolicVar btpr =
-  Crux.baseFreshOverride btpr strrepr $ \(RV strSlice) -> do
-    mstr <- getString strSlice
+  Crux.baseFreshOverride btpr strrepr $ \(RV nameArg) -> symbolicVarName nameArg
+  where
+    strrepr :: TypeRepr (MirSlice (BVType 8))
+    strrepr = knownRepr
+
+makeSymbolicFloat ::
+    IsSymInterface sym =>
+    FloatInfoRepr fi ->
+    TypedOverride (p sym) sym MIR (EmptyCtx ::> MirSlice (BVType 8)) (FloatType fi)
+makeSymbolicFloat fi =
+  TypedOverride
+  { typedOverrideHandler = \(Empty :> RV nameArg) -> do
+      name <- symbolicVarName nameArg
+      Crux.mkFreshFloat name fi
+  , typedOverrideArgs = Empty :> knownRepr
+  , typedOverrideRet = FloatRepr fi
+  }
+
+symbolicVarName :: IsSymInterface sym =>
+    RegValue sym (MirSlice (BVType 8)) ->
+    OverrideSim p sym MIR rtp args ret SolverSymbol
+symbolicVarName nameArg = do
+    mstr <- getString nameArg
     case mstr of
-      Nothing -> fail "symbolic variable name must be a concrete string"            
+      Nothing -> fail "symbolic variable name must be a concrete string"
       Just name ->
         case userSymbol (Text.unpack name) of
           Left err -> fail $ "invalid symbolic variable name " ++ show name ++ ": " ++ show err
           Right x -> return x
-  where
-    strrepr :: TypeRepr (MirSlice (BVType 8))
-    strrepr = knownRepr
 
 array_symbolic ::
   forall sym rtp btp p .
@@ -165,6 +186,7 @@ groundExpr sym tpr v = case tpr of
     BaseBVRepr w -> bvLit sym w v
     BaseComplexRepr -> mkComplexLit sym v
     BaseStringRepr _ -> stringLit sym v
+    BaseFloatRepr fpp -> floatLit sym fpp v
     -- TODO: this case is implemented, but always hits the `ArrayMapping` case,
     -- which fails.  It seems like z3 always returns a function for array
     -- instances.  Fixing this would require Crucible changes to recognize
@@ -232,7 +254,7 @@ regEval bak baseEval tpr v = go tpr v
         ptr <- subindexMirRefSim tpr' vecRef =<< liftIO (bvLit sym knownRepr (BV.zero knownRepr))
         return $ Empty :> RV ptr :> RV len'
 
-    go (FloatRepr fi) v = pure v
+    go (FloatRepr fi) v = baseEval (iFloatBaseTypeRepr sym fi) v
     go AnyRepr (AnyValue tpr v) = AnyValue tpr <$> go tpr v
     go UnitRepr () = pure ()
     go CharRepr c = pure c
@@ -252,8 +274,8 @@ regEval bak baseEval tpr v = go tpr v
     go (MirReferenceRepr tpr') (MirReferenceMux mux) = do
         ref <- goMuxTreeEntries tpr (viewFancyMuxTree mux)
         ref' <- case ref of
-            MirReference root path ->
-                MirReference <$> goMirReferenceRoot root <*> goMirReferencePath path
+            MirReference root path tag ->
+                MirReference <$> goMirReferenceRoot root <*> goMirReferencePath path <*> pure tag
             (MirReference_Integer _tpr i) ->
                 MirReference_Integer tpr' <$> go UsizeRepr i
         return $ MirReferenceMux $ toFancyMuxTree sym ref'
@@ -339,6 +361,8 @@ regEval bak baseEval tpr v = go tpr v
         pure Empty_RefPath
     goMirReferencePath (Any_RefPath tpr p) =
         Any_RefPath tpr <$> goMirReferencePath p
+    goMirReferencePath (Union_RefPath tpr p) =
+        Union_RefPath tpr <$> goMirReferencePath p
     goMirReferencePath (Field_RefPath ctx p idx) =
         Field_RefPath ctx <$> goMirReferencePath p <*> pure idx
     goMirReferencePath (Variant_RefPath discrTp ctx p idx) =
@@ -386,8 +410,8 @@ overrideRust cs name = do
 
 
 bindFn ::
-  forall p ng args ret blocks sym bak rtp a r.
-  (IsSymInterface sym) =>
+  forall p ng args ret blocks sym bak rtp a r t st fs.
+  (IsSymInterface sym, sym ~ ExprBuilder t st fs) =>
   Maybe (SomeOnlineSolver sym bak) ->
   CollectionState ->
   Text ->
@@ -423,6 +447,34 @@ bindFn symOnline cs name cfg
             Just x -> return x
             Nothing -> fail $ "dump_what4: desc string must be concrete"
         liftIO $ putStrLn $ Text.unpack str ++ " = " ++ show (printSymExpr expr)
+
+  | hasInstPrefix ["crucible", "method_spec", "raw", "builder_new"] explodedName
+  , Empty <- cfgArgTypes cfg
+  , MethodSpecBuilderRepr <- cfgReturnType cfg
+  = bindFnHandle (cfgHandle cfg) $ UseOverride $
+    mkOverride' "method_spec_builder_new" MethodSpecBuilderRepr $ do
+        let tyArgs = cs ^? collection . M.intrinsics . ix (textId name) .
+              M.intrInst . M.inSubsts . _Wrapped
+        defId <- case tyArgs of
+            Just [M.TyFnDef defId] -> return defId
+            _ -> fail $ "MethodSpecBuilder: expected a function, but got " ++ show tyArgs
+        builderNew cs defId
+
+  | hasInstPrefix ["crucible", "method_spec", "raw", "builder_add_arg"] explodedName
+  , Empty :> MethodSpecBuilderRepr :> MirReferenceRepr tpr <- cfgArgTypes cfg
+  , MethodSpecBuilderRepr <- cfgReturnType cfg
+  = bindFnHandle (cfgHandle cfg) $ UseOverride $
+    mkOverride' "method_spec_builder_add_arg" MethodSpecBuilderRepr $ do
+        RegMap (Empty :> RegEntry _ (MethodSpecBuilder msb) :> RegEntry _ ref) <- getOverrideArgs
+        MethodSpecBuilder <$> msbAddArg tpr ref msb
+
+  | hasInstPrefix ["crucible", "method_spec", "raw", "builder_set_return"] explodedName
+  , Empty :> MethodSpecBuilderRepr :> MirReferenceRepr tpr <- cfgArgTypes cfg
+  , MethodSpecBuilderRepr <- cfgReturnType cfg
+  = bindFnHandle (cfgHandle cfg) $ UseOverride $
+    mkOverride' "method_spec_builder_set_return" MethodSpecBuilderRepr $ do
+        RegMap (Empty :> RegEntry _ (MethodSpecBuilder msb) :> RegEntry _ ref) <- getOverrideArgs
+        MethodSpecBuilder <$> msbSetReturn tpr ref msb
   where
     explodedName = textIdKey name
 
@@ -436,7 +488,7 @@ bindFn symOnline cs name cfg
           pfxInit == edidInit &&
           "_inst" `Text.isPrefixOf` edidLast
 
-bindFn _symOnline _cs fn cfg =
+bindFn _symOnline cs fn cfg =
   ovrWithBackend $ \bak ->
   let s = backendGetSym bak in
   case Map.lookup (textIdKey fn) (overrides bak) of
@@ -476,6 +528,10 @@ bindFn _symOnline _cs fn cfg =
             -> (ExplodedDefId, SomeTypedOverride (p sym) sym MIR)
     symb_bv edid n = (edid, SomeTypedOverride $ makeSymbolicVar (BaseBVRepr n))
 
+    symb_float :: ExplodedDefId -> FloatInfoRepr fi
+               -> (ExplodedDefId, SomeTypedOverride (p sym) sym MIR)
+    symb_float edid fi = (edid, SomeTypedOverride $ makeSymbolicFloat fi)
+
     overrides :: IsSymBackend sym bak'
               => bak'
               -> Map ExplodedDefId (SomeTypedOverride (p sym) sym MIR)
@@ -491,7 +547,16 @@ bindFn _symOnline _cs fn cfg =
                , symb_bv ["crucible", "symbolic", "symbolic_u32"] (knownNat @32)
                , symb_bv ["crucible", "symbolic", "symbolic_u64"] (knownNat @64)
                , symb_bv ["crucible", "symbolic", "symbolic_u128"] (knownNat @128)
+               , symb_float ["crucible", "symbolic", "symbolic_f32"] SingleFloatRepr
+               , symb_float ["crucible", "symbolic", "symbolic_f64"] DoubleFloatRepr
                , symb_bv ["int512", "symbolic"] (knownNat @512)
+               , override ["crucible", "symbolic", "sub_name"] (Empty :> strrepr :> strrepr) strrepr $
+                    \(Empty :> RV descArg :> RV fieldArg) -> do
+                       desc <- maybe (fail "symbolic variable name must be a concrete string") pure
+                                 =<< getString descArg
+                       field <- maybe (fail "symbolic variable name must be a concrete string") pure
+                                  =<< getString fieldArg
+                       strSlice (desc <> "_" <> field)
                , symb_bv ["crucible", "bitvector", "make_symbolic_128"] (knownNat @128)
                , symb_bv ["crucible", "bitvector", "make_symbolic_256"] (knownNat @256)
                , symb_bv ["crucible", "bitvector", "make_symbolic_512"] (knownNat @512)
@@ -509,7 +574,21 @@ bindFn _symOnline _cs fn cfg =
                               (BV.asUnsigned <$> asBV (unRV colArg))
                        let locStr = Text.unpack file <> ":" <> show line <> ":" <> show col
                        let reason = AssertFailureSimError ("MIR assertion at " <> locStr <> ":\n\t" <> src) ""
-                       liftIO $ assert bak (unRV c) reason
+                       case cs ^. weakMemoryVars of
+                         Nothing -> liftIO $ assert bak (unRV c) reason
+                         Just (gv, _) -> do
+                           -- Split the assertion so that failures that need a
+                           -- stale atomic load are reported separately.
+                           weak <- wmWeak <$> readGlobal gv
+                           let weakReason = AssertFailureSimError
+                                 ("MIR assertion at " <> locStr <> ":\n\t" <> src <>
+                                  "\n\t(only fails under weak memory: an atomic load read a stale value)") ""
+                           liftIO $ do
+                             scOk <- orPred sym weak (unRV c)
+                             assert bak scOk reason
+                             notWeak <- notPred sym weak
+                             weakOk <- orPred sym notWeak (unRV c)
+                             assert bak weakOk weakReason
                        return ()
                , let argTys = (Empty :> BoolRepr :> strrepr :> strrepr :> u32repr :> u32repr)
                  in override ["crucible", "crucible_assume_impl"] argTys UnitRepr $
@@ -527,4 +606,22 @@ bindFn _symOnline _cs fn cfg =
                        let reason = GenericAssumption loc ("Assumption \n\t" <> src <> "\nfrom " <> locStr) (unRV c)
                        liftIO $ addAssumption bak reason
                        return ()
+
+               , override ["crucible", "method_spec", "raw", "builder_gather_assumes"]
+                   (Empty :> MethodSpecBuilderRepr) MethodSpecBuilderRepr $
+                    \(Empty :> RV (MethodSpecBuilder b)) -> MethodSpecBuilder <$> msbGatherAssumes b
+               , override ["crucible", "method_spec", "raw", "builder_gather_asserts"]
+                   (Empty :> MethodSpecBuilderRepr) MethodSpecBuilderRepr $
+                    \(Empty :> RV (MethodSpecBuilder b)) -> MethodSpecBuilder <$> msbGatherAsserts b
+               , override ["crucible", "method_spec", "raw", "builder_finish"]
+                   (Empty :> MethodSpecBuilderRepr) MethodSpecRepr $
+                    \(Empty :> RV (MethodSpecBuilder b)) -> msbFinish b
+               , override ["crucible", "method_spec", "raw", "spec_pretty_print"]
+                   (Empty :> MethodSpecRepr) strrepr $
+                    \(Empty :> RV (MethodSpec ms _)) -> msPrettyPrint ms
+               , override ["crucible", "method_spec", "raw", "spec_enable"]
+                   (Empty :> MethodSpecRepr) UnitRepr $
+                    \(Empty :> RV (MethodSpec ms _)) -> msEnable ms
+               , override ["crucible", "method_spec", "raw", "clobber_globals"] Empty UnitRepr $
+                    \_args -> clobberGlobals cs
                ]
@@ -5,15 +5,21 @@
 {-# OPTIONS_GHC -Wall #-}
 module Main (main) where
 
+import           Control.Monad (forM, forM_, when)
+import qualified Data.Aeson as Aeson
+import           Data.Aeson ((.:), (.:?))
+import qualified Data.Aeson.Types as Aeson
 import qualified Data.ByteString as BS
 import qualified Data.ByteString.UTF8 as BS8
-import           Data.Char (isSpace)
-import           Data.List (dropWhileEnd, isPrefixOf)
+import           Data.Char (isDigit, isSpace)
+import           Data.List (dropWhileEnd, isPrefixOf, sort)
 import           Data.Maybe (catMaybes)
-import           System.Directory (listDirectory, doesDirectoryExist, doesFileExist, removeFile)
+import           System.Directory
+    (listDirectory, doesDirectoryExist, doesFileExist, removeFile, removeDirectoryRecursive)
 import           System.Exit (ExitCode(..))
 import           System.FilePath
-    ((<.>), (</>), takeBaseName, takeExtension, replaceExtension, takeFileName, takeDirectory)
+    ( (<.>), (</>), takeBaseName, takeExtension, replaceExtension, takeFileName, takeDirectory
+    , makeRelative )
 import           System.IO (IOMode(..), Handle, withFile, hClose, hGetContents, hGetLine, openFile)
 import           System.IO.Temp (withSystemTempFile)
 
@@ -60,9 +66,21 @@ defaultCruxOptions = case res of
     ss = Crux.cfgFile Crux.cruxOptions
     res = Config.loadValue (Config.sectionsSpec "crux" ss) (Config.Sections () [])
 
-data RunCruxMode = RcmConcrete | RcmSymbolic | RcmCoverage
+data RunCruxMode
+  = RcmConcrete | RcmSymbolic | RcmCoverage | RcmAliasing | RcmLeaks | RcmCounterexamples
+  | RcmPreemptionBound | RcmMaxExecutions | RcmConcurrencyStats | RcmSchedule
+  | RcmReplaySchedule FilePath
   deriving (Show, Eq)
 
+isConcurrencyMode :: RunCruxMode -> Bool
+isConcurrencyMode = \case
+  RcmPreemptionBound -> True
+  RcmMaxExecutions -> True
+  RcmConcurrencyStats -> True
+  RcmSchedule -> True
+  RcmReplaySchedule _ -> True
+  _ -> False
+
 runCrux :: FilePath -> Handle -> RunCruxMode -> IO ()
 runCrux rustFile outHandle mode =
   Mir.withMirLogging $
@@ -89,7 +107,29 @@ runCrux rustFile outHandle mode =
                                             RcmCoverage -> getOutputDir rustFile
                                             _ -> "",
                                         Crux.branchCoverage = (mode == RcmCoverage) } ,
-                   Mir.defaultMirOptions { Mir.printResultOnly = (mode == RcmConcrete) })
+                   Mir.defaultMirOptions { Mir.printResultOnly = (mode == RcmConcrete),
+                                           Mir.checkAliasing = (mode == RcmAliasing),
+                                           Mir.checkLeaks = (mode == RcmLeaks),
+                                           Mir.counterexampleDir = case mode of
+                                               RcmCounterexamples -> Just (getCounterexampleDir rustFile)
+                                               _ -> Nothing,
+                                           Mir.regressionTestFile = case mode of
+                                               RcmCounterexamples -> Just (getRegressionTestFile rustFile)
+                                               _ -> Nothing,
+                                           Mir.concurrency = isConcurrencyMode mode,
+                                           Mir.preemptionBound = case mode of
+                                               RcmPreemptionBound -> Just 0
+                                               _ -> Nothing,
+                                           Mir.executionLimit = case mode of
+                                               RcmMaxExecutions -> Just 1
+                                               _ -> Nothing,
+                                           Mir.concurrencyStats = (mode == RcmConcurrencyStats),
+                                           Mir.scheduleDir = case mode of
+                                               RcmSchedule -> Just (getScheduleDir rustFile)
+                                               _ -> Nothing,
+                                           Mir.replayScheduleFile = case mode of
+                                               RcmReplaySchedule file -> Just file
+                                               _ -> Nothing })
     let ?outputConfig = Crux.mkOutputConfig (outHandle, False) (outHandle, False) Mir.mirLoggingToSayWhat $
                         Just (Crux.outputOptions (fst options))
     _exitCode <- Mir.runTests options
@@ -98,6 +138,16 @@ runCrux rustFile outHandle mode =
 getOutputDir :: FilePath -> FilePath
 getOutputDir rustFile = takeDirectory rustFile </> "out"
 
+getCounterexampleDir :: FilePath -> FilePath
+getCounterexampleDir rustFile = getOutputDir rustFile </> takeBaseName rustFile
+
+-- | Not a @.rs@ file, so that 'findByExtension' doesn't pick it up as a test.
+getRegressionTestFile :: FilePath -> FilePath
+getRegressionTestFile rustFile = getCounterexampleDir rustFile </> "regression_tests.txt"
+
+getScheduleDir :: FilePath -> FilePath
+getScheduleDir rustFile = getOutputDir rustFile </> takeBaseName rustFile
+
 cruxOracleTest :: FilePath -> String -> (String -> IO ()) -> Assertion
 cruxOracleTest dir name step = do
 
@@ -123,14 +173,14 @@ cruxOracleTest dir name step = do
   assertBool "crux doesn't match oracle" (orOut == cruxOut)
 
 
-symbTest :: FilePath -> IO TestTree
-symbTest dir =
+symbTest :: RunCruxMode -> FilePath -> IO TestTree
+symbTest mode dir =
   do rustFiles <- findByExtension [".rs"] dir
      return $
        testGroup "Output testing"
          [ doGoldenTest (takeBaseName rustFile) goodFile outFile $
            do withFile outFile WriteMode $ \h ->
-                runCrux rustFile h RcmSymbolic
+                runCrux rustFile h mode
               sanitizeGoldenOutputFile outFile
          | rustFile <- rustFiles
          -- Skip hidden files, such as editor swap files
@@ -163,6 +213,113 @@ coverageTests dir = do
         writeFile outFile out
 
 
+-- | Run each test with @--counterexamples@ and @--regression-tests@, and
+-- compare the Crux output, a summary of each counterexample file, and the
+-- generated regression tests against the golden file.
+counterexampleTests :: FilePath -> IO TestTree
+counterexampleTests dir = do
+    rustFiles <- findByExtension [".rs"] dir
+    return $ testGroup "Output testing"
+        [ doGoldenTest rustFile goodFile outFile (doTest rustFile outFile)
+        | rustFile <- rustFiles
+        -- Skip hidden files, such as editor swap files
+        , not $ "." `isPrefixOf` takeFileName rustFile
+        , let goodFile = replaceExtension rustFile ".good"
+        , let outFile = replaceExtension rustFile ".out"
+        ]
+
+  where
+    doTest rustFile outFile = do
+        let cexDir = getCounterexampleDir rustFile
+        stale <- doesDirectoryExist cexDir
+        when stale $ removeDirectoryRecursive cexDir
+        withFile outFile WriteMode $ \h -> runCrux rustFile h RcmCounterexamples
+        cexFiles <- findByExtension [".json"] cexDir
+        summaries <- forM (sort cexFiles) $ \cexFile -> do
+            summary <- Aeson.eitherDecodeFileStrict cexFile >>= \case
+                Left err -> fail $ cexFile ++ ": " ++ err
+                Right v -> either (fail . ((cexFile ++ ": ") ++)) return $
+                    Aeson.parseEither summarizeCounterexample v
+            return $ "\n==> " ++ makeRelative cexDir cexFile ++ " <==\n" ++ summary
+        regressionTests <- readFile (getRegressionTestFile rustFile)
+        appendFile outFile $ concat summaries ++
+            "\n==> " ++ makeRelative cexDir (getRegressionTestFile rustFile) ++ " <==\n" ++
+            regressionTests
+        sanitizeGoldenOutputFile outFile
+
+-- | Run each test with @--concurrency-stats@.  The counts depend on the
+-- order in which DPOR explores schedules, so only the shape of the statistics
+-- is compared against the golden file.
+concurrencyStatsTests :: FilePath -> IO TestTree
+concurrencyStatsTests dir = do
+    rustFiles <- findByExtension [".rs"] dir
+    return $ testGroup "Output testing"
+        [ doGoldenTest rustFile goodFile outFile (doTest rustFile outFile)
+        | rustFile <- rustFiles
+        -- Skip hidden files, such as editor swap files
+        , not $ "." `isPrefixOf` takeFileName rustFile
+        , let goodFile = replaceExtension rustFile ".good"
+        , let outFile = replaceExtension rustFile ".out"
+        ]
+
+  where
+    doTest rustFile outFile = do
+        withFile outFile WriteMode $ \h -> runCrux rustFile h RcmConcurrencyStats
+        sanitizeGoldenOutputFile outFile
+        out <- readFile outFile
+        length out `seq` writeFile outFile (unlines $ map hideCounts $ lines out)
+
+    -- The statistics are the only indented lines that crux-mir prints itself.
+    hideCounts l
+      | "  " `isPrefixOf` l = "  " ++ unwords [ if all isDigit w then "N" else w | w <- words l ]
+      | otherwise = l
+
+-- | Run each test with @--schedule-dir@, then replay each schedule it writes
+-- with @--replay-schedule@, and compare the output of both runs against the
+-- golden file.
+replayScheduleTests :: FilePath -> IO TestTree
+replayScheduleTests dir = do
+    rustFiles <- findByExtension [".rs"] dir
+    return $ testGroup "Output testing"
+        [ doGoldenTest rustFile goodFile outFile (doTest rustFile outFile)
+        | rustFile <- rustFiles
+        -- Skip hidden files, such as editor swap files
+        , not $ "." `isPrefixOf` takeFileName rustFile
+        , let goodFile = replaceExtension rustFile ".good"
+        , let outFile = replaceExtension rustFile ".out"
+        ]
+
+  where
+    doTest rustFile outFile = do
+        let schedDir = getScheduleDir rustFile
+        stale <- doesDirectoryExist schedDir
+        when stale $ removeDirectoryRecursive schedDir
+        withFile outFile WriteMode $ \h -> runCrux rustFile h RcmSchedule
+        schedFiles <- findByExtension [".txt"] schedDir
+        forM_ (sort schedFiles) $ \schedFile -> do
+            appendFile outFile $ "\n==> replaying " ++ makeRelative schedDir schedFile ++ " <==\n"
+            withFile outFile AppendMode $ \h -> runCrux rustFile h (RcmReplaySchedule schedFile)
+        sanitizeGoldenOutputFile outFile
+
+-- | Summarize a counterexample file written by @--counterexamples@.  The
+-- location of each variable is left out, since it points into the @crucible@
+-- library and moves whenever that does.
+summarizeCounterexample :: Aeson.Value -> Aeson.Parser String
+summarizeCounterexample = Aeson.withObject "counterexample" $ \o -> do
+    test :: String <- o .: "test"
+    goal <- o .: "goal"
+    loc :: String <- goal .: "location"
+    msg :: String <- goal .: "message"
+    vars <- o .: "variables"
+    varLines <- forM vars $ \v -> do
+        name :: String <- v .: "name"
+        kind :: String <- v .: "kind"
+        width :: Maybe Int <- v .:? "width"
+        value :: String <- v .: "value"
+        return $ "  " ++ name ++ ": " ++ kind ++ maybe "" show width ++ " = " ++ value
+    return $ unlines $ ("test: " ++ test) : ("goal: " ++ loc ++ ": " ++ msg) : varLines
+
+
 
 doGoldenTest :: FilePath -> FilePath -> FilePath -> IO () -> TestTree
 doGoldenTest rustFile goodFile outFile act = goldenTest (takeBaseName rustFile)
@@ -217,8 +374,17 @@ suite = do
   let ?printCrucible = False
   trees <- sequence
            [ testGroup "crux concrete" <$> sequence [ testDir cruxOracleTest "test/conc_eval/" ]
-           , testGroup "crux symbolic" <$> sequence [ symbTest "test/symb_eval" ]
+           , testGroup "crux symbolic" <$> sequence [ symbTest RcmSymbolic "test/symb_eval" ]
+           , testGroup "crux aliasing" <$> sequence [ symbTest RcmAliasing "test/aliasing" ]
+           , testGroup "crux leaks" <$> sequence [ symbTest RcmLeaks "test/leaks" ]
            , testGroup "crux coverage" <$> sequence [ coverageTests "test/coverage" ]
+           , testGroup "crux counterexamples" <$> sequence [ counterexampleTests "test/counterexamples" ]
+           , testGroup "crux concurrency options" <$> sequence
+               [ symbTest RcmPreemptionBound "test/concurrency_options/preemption_bound"
+               , symbTest RcmMaxExecutions "test/concurrency_options/max_executions"
+               , concurrencyStatsTests "test/concurrency_options/stats"
+               , replayScheduleTests "test/concurrency_options/replay"
+               ]
            ]
   return $ testGroup "crux-mir" trees
 
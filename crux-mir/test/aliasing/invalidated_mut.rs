@@ -0,0 +1,19 @@
+extern crate crucible;
+use crucible::*;
+
+#[crux::test]
+fn crux_test() -> i32 {
+    let mut x = 0;
+    let r1 = &mut x;
+    let p = r1 as *mut i32;
+    let r2 = unsafe { &mut *p };
+    if bool::symbolic("b") {
+        *r1 = 1;
+    }
+    *r2 = 2;
+    x
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
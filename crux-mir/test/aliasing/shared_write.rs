@@ -0,0 +1,20 @@
+extern crate crucible;
+use crucible::*;
+
+fn cast_mut(p: *const u8) -> *mut u8 {
+    p as *mut u8
+}
+
+#[crux::test]
+fn crux_test() -> u8 {
+    let x = u8::symbolic("x");
+    let p = cast_mut(&x);
+    if x > 10 {
+        unsafe { *p = 0; }
+    }
+    x
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
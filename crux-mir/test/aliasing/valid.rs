@@ -0,0 +1,38 @@
+extern crate crucible;
+use crucible::*;
+use std::cell::Cell;
+
+fn write_both(a: &mut [u8], b: &mut [u8]) {
+    a[0] = 1;
+    b[0] = 2;
+}
+
+#[crux::test]
+fn crux_test() -> u8 {
+    // Raw pointers derived from a mutable reference may be written through.
+    let mut x = 0;
+    let r = &mut x;
+    let p = r as *mut u8;
+    unsafe { *p = 1; }
+    *r += 1;
+
+    // Disjoint mutable borrows of one array.
+    let mut arr = [0; 4];
+    let (a, b) = arr.split_at_mut(2);
+    write_both(a, b);
+
+    // Mutation through a shared reference to a `Cell`.
+    let c = Cell::new(3);
+    let rc = &c;
+    rc.set(rc.get() + 1);
+
+    // A two-phase borrow: `v` is borrowed mutably before `v.len()` runs.
+    let mut v = Vec::new();
+    v.push(v.len() as u8);
+
+    x + arr[0] + arr[2] + c.get() + v[0]
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
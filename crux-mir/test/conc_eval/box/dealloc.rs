@@ -0,0 +1,22 @@
+use std::rc::Rc;
+
+// Dropping boxes, vectors and `Rc`s frees their memory; none of these should
+// be reported as a use-after-free or double free.
+#[cfg_attr(crux, crux::test)]
+fn crux_test() -> i32 {
+    let b = Box::new(1);
+    let mut v = vec![Box::new(2), Box::new(3)];
+    v.push(b);
+    let popped = v.pop().unwrap();
+    let sum: i32 = v.iter().map(|x| **x).sum::<i32>() + *popped;
+    drop(v);
+
+    let r = Rc::new(10);
+    let r2 = r.clone();
+    drop(r);
+    sum + *r2
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
@@ -0,0 +1,14 @@
+// Boxes of zero-sized values don't own an allocation, so dropping them must
+// not free anything.
+#[cfg_attr(crux, crux::test)]
+fn crux_test() -> usize {
+    let a: Box<[i32]> = Vec::new().into_boxed_slice();
+    let b: Box<str> = String::new().into_boxed_str();
+    let c: Box<[u8]> = Box::default();
+    let d: Box<[()]> = vec![(); 3].into_boxed_slice();
+    a.len() + b.len() + c.len() + d.len()
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
@@ -0,0 +1,14 @@
+fn call(f: Box<dyn FnOnce(i32) -> i32>, x: i32) -> i32 {
+    f(x)
+}
+
+#[cfg_attr(crux, crux::test)]
+fn crux_test() -> i32 {
+    let s = String::from("hello");
+    let f: Box<dyn FnOnce(i32) -> i32> = Box::new(move |x| x + s.len() as i32);
+    call(f, 1)
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
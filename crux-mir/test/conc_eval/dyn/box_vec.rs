@@ -0,0 +1,50 @@
+use std::cell::Cell;
+
+trait Shape {
+    fn area(&self) -> i32;
+}
+
+struct Square(i32);
+struct Rect(i32, i32);
+
+impl Shape for Square {
+    fn area(&self) -> i32 { self.0 * self.0 }
+}
+
+impl Shape for Rect {
+    fn area(&self) -> i32 { self.0 * self.1 }
+}
+
+struct Counted<'a>(&'a Cell<i32>);
+
+impl Shape for Counted<'_> {
+    fn area(&self) -> i32 { 0 }
+}
+
+impl Drop for Counted<'_> {
+    fn drop(&mut self) {
+        self.0.set(self.0.get() + 1);
+    }
+}
+
+fn total(shapes: &[Box<dyn Shape + '_>]) -> i32 {
+    shapes.iter().map(|s| s.area()).sum()
+}
+
+#[cfg_attr(crux, crux::test)]
+fn crux_test() -> (i32, i32) {
+    let drops = Cell::new(0);
+    let area = {
+        let mut v: Vec<Box<dyn Shape + '_>> = Vec::new();
+        v.push(Box::new(Square(3)));
+        v.push(Box::new(Counted(&drops)));
+        v.push(Box::new(Rect(2, 5)));
+        v.push(Box::new(Counted(&drops)));
+        total(&v)
+    };
+    (area, drops.get())
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
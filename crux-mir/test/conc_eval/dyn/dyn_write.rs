@@ -0,0 +1,16 @@
+use std::fmt::Write;
+
+fn emit(out: &mut dyn Write, x: i32) {
+    write!(out, "x = {}", x).unwrap();
+}
+
+#[cfg_attr(crux, crux::test)]
+fn crux_test() -> usize {
+    let mut s = String::new();
+    emit(&mut s, 42);
+    s.len()
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
@@ -0,0 +1,19 @@
+use std::mem;
+
+trait Tr {
+    fn get(&self) -> u8;
+}
+
+impl Tr for u8 {
+    fn get(&self) -> u8 { *self }
+}
+
+#[cfg_attr(crux, crux::test)]
+fn crux_test() -> (usize, usize, u8) {
+    let x: Box<dyn Tr> = Box::new(7_u8);
+    (mem::size_of_val(&*x), mem::align_of_val(&*x), x.get())
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
@@ -0,0 +1,44 @@
+use std::mem;
+
+trait Tr {
+    fn get(&self) -> u64;
+}
+
+impl Tr for u64 {
+    fn get(&self) -> u64 { *self }
+}
+
+struct Mixed {
+    a: u8,
+    b: u32,
+    c: u16,
+}
+
+impl Tr for Mixed {
+    fn get(&self) -> u64 { self.a as u64 + self.b as u64 + self.c as u64 }
+}
+
+struct Unit;
+
+impl Tr for Unit {
+    fn get(&self) -> u64 { 0 }
+}
+
+#[cfg_attr(crux, crux::test)]
+fn crux_test() -> [usize; 8] {
+    let x: Box<dyn Tr> = Box::new(7_u64);
+    let m: Box<dyn Tr> = Box::new(Mixed { a: 1, b: 2, c: 3 });
+    let u: Box<dyn Tr> = Box::new(Unit);
+    let t = (1_u8, 2_u64, 3_u16);
+    assert!(x.get() + m.get() + u.get() == 13);
+    [
+        mem::size_of_val(&*x), mem::align_of_val(&*x),
+        mem::size_of_val(&*m), mem::align_of_val(&*m),
+        mem::size_of_val(&*u), mem::align_of_val(&*u),
+        mem::size_of_val(&t), mem::align_of_val(&t),
+    ]
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
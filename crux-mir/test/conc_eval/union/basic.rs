@@ -0,0 +1,28 @@
+#![cfg_attr(not(with_main), no_std)]
+use core::mem::ManuallyDrop;
+
+union IntOrPair {
+    int: u32,
+    pair: (u16, u16),
+    boxed: ManuallyDrop<[u8; 4]>,
+}
+
+fn f(x: u32) -> u32 {
+    let mut u = IntOrPair { int: x };
+    let a = unsafe { u.int };
+    u.pair = (1, 2);
+    let b = unsafe { u.pair.0 as u32 + u.pair.1 as u32 };
+    unsafe { u.pair.1 = 10 };
+    let c = unsafe { u.pair.1 as u32 };
+    u.boxed = ManuallyDrop::new([1, 2, 3, 4]);
+    let d = unsafe { u.boxed[3] as u32 };
+    a + b + c + d
+}
+
+const ARG: u32 = 100;
+
+#[cfg(with_main)]
+pub fn main() {
+    println!("{:?}", f(ARG));
+}
+#[cfg(not(with_main))] #[cfg_attr(crux, crux::test)] fn crux_test() -> u32 { f(ARG) }
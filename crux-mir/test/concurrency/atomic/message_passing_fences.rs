@@ -0,0 +1,30 @@
+extern crate crucible;
+use crucible::*;
+use std::thread;
+use std::sync::Arc;
+use std::sync::atomic::{fence, AtomicU32, Ordering};
+
+// Run with --weak-memory.  Same as `message_passing_release_acquire`, but the
+// flag accesses are relaxed and the synchronization comes from fences.
+#[cfg(not(with_main))]
+#[crux::test]
+fn crux_test() {
+    let data = Arc::new(AtomicU32::new(0));
+    let flag = Arc::new(AtomicU32::new(0));
+
+    let (d, f) = (Arc::clone(&data), Arc::clone(&flag));
+    let t = thread::spawn(move || {
+        d.store(42, Ordering::Relaxed);
+        fence(Ordering::Release);
+        f.store(1, Ordering::Relaxed);
+    });
+
+    if flag.load(Ordering::Relaxed) == 1 {
+        fence(Ordering::Acquire);
+        crucible_assert!(data.load(Ordering::Relaxed) == 42);
+    }
+    t.join().unwrap();
+}
+
+#[cfg(with_main)]
+fn main() {}
@@ -0,0 +1,28 @@
+extern crate crucible;
+use crucible::*;
+use std::thread;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+// Run with --weak-memory.  The release store of the flag, read by the acquire
+// load, makes the data store visible to the main thread.
+#[cfg(not(with_main))]
+#[crux::test]
+fn crux_test() {
+    let data = Arc::new(AtomicU32::new(0));
+    let flag = Arc::new(AtomicU32::new(0));
+
+    let (d, f) = (Arc::clone(&data), Arc::clone(&flag));
+    let t = thread::spawn(move || {
+        d.store(42, Ordering::Relaxed);
+        f.store(1, Ordering::Release);
+    });
+
+    if flag.load(Ordering::Acquire) == 1 {
+        crucible_assert!(data.load(Ordering::Relaxed) == 42);
+    }
+    t.join().unwrap();
+}
+
+#[cfg(with_main)]
+fn main() {}
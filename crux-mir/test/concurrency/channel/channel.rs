@@ -0,0 +1,32 @@
+extern crate crucible;
+use crucible::*;
+use std::sync::mpsc;
+use std::thread;
+
+// Every message sent by either producer arrives, in whatever order the producers ran.
+#[cfg(not(with_main))]
+#[crux::test]
+fn crux_test() -> u32 {
+    let (tx, rx) = mpsc::channel();
+    let tx1 = tx.clone();
+    let t1 = thread::spawn(move || {
+        tx1.send(1).unwrap();
+        tx1.send(2).unwrap();
+    });
+    let t2 = thread::spawn(move || {
+        tx.send(10).unwrap();
+    });
+
+    let mut sum = 0;
+    for _ in 0..3 {
+        sum += rx.recv().unwrap();
+    }
+    crucible_assert!(sum == 13);
+
+    t1.join().unwrap();
+    t2.join().unwrap();
+    sum
+}
+
+#[cfg(with_main)]
+fn main() {}
@@ -0,0 +1,24 @@
+extern crate crucible;
+use crucible::*;
+use std::sync::mpsc;
+use std::thread;
+
+// `recv` drains the messages that were already sent, and then reports that the sender is gone.
+#[cfg(not(with_main))]
+#[crux::test]
+fn crux_test() {
+    let (tx, rx) = mpsc::channel();
+    let t = thread::spawn(move || {
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+    });
+
+    crucible_assert!(rx.recv() == Ok(1));
+    crucible_assert!(rx.recv() == Ok(2));
+    crucible_assert!(rx.recv().is_err());
+
+    t.join().unwrap();
+}
+
+#[cfg(with_main)]
+fn main() {}
@@ -0,0 +1,25 @@
+extern crate crucible;
+use crucible::*;
+use std::sync::mpsc;
+use std::thread;
+
+// Both threads wait to receive before sending, so neither can make progress.  This should fail
+// with a deadlock.
+#[cfg(not(with_main))]
+#[crux::test]
+fn crux_test() {
+    let (tx1, rx1) = mpsc::channel::<u32>();
+    let (tx2, rx2) = mpsc::channel::<u32>();
+    let t = thread::spawn(move || {
+        let x = rx1.recv().unwrap();
+        tx2.send(x + 1).unwrap();
+    });
+
+    let y = rx2.recv().unwrap();
+    tx1.send(y).unwrap();
+
+    t.join().unwrap();
+}
+
+#[cfg(with_main)]
+fn main() {}
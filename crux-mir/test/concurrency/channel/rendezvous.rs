@@ -0,0 +1,27 @@
+extern crate crucible;
+use crucible::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+
+// With a bound of 0, `send` can't return before the receiver has taken the message.
+#[cfg(not(with_main))]
+#[crux::test]
+fn crux_test() {
+    let (tx, rx) = mpsc::sync_channel(0);
+    let sent = Arc::new(AtomicBool::new(false));
+    let sent1 = Arc::clone(&sent);
+    let t = thread::spawn(move || {
+        tx.send(1).unwrap();
+        sent1.store(true, Ordering::SeqCst);
+    });
+
+    crucible_assert!(!sent.load(Ordering::SeqCst));
+    crucible_assert!(rx.recv().unwrap() == 1);
+
+    t.join().unwrap();
+}
+
+#[cfg(with_main)]
+fn main() {}
@@ -0,0 +1,31 @@
+extern crate crucible;
+use crucible::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+
+// The producer can only get one message ahead of the consumer, and the messages arrive in order.
+#[cfg(not(with_main))]
+#[crux::test]
+fn crux_test() {
+    let (tx, rx) = mpsc::sync_channel(1);
+    let sent = Arc::new(AtomicUsize::new(0));
+    let sent1 = Arc::clone(&sent);
+    let t = thread::spawn(move || {
+        for i in 0..3 {
+            tx.send(i).unwrap();
+            sent1.fetch_add(1, Ordering::SeqCst);
+        }
+    });
+
+    for i in 0..3 {
+        crucible_assert!(sent.load(Ordering::SeqCst) <= i + 1);
+        crucible_assert!(rx.recv().unwrap() == i);
+    }
+
+    t.join().unwrap();
+}
+
+#[cfg(with_main)]
+fn main() {}
@@ -0,0 +1,30 @@
+extern crate crucible;
+use crucible::*;
+use std::thread;
+use std::sync::{Arc,Condvar,Mutex};
+
+#[cfg(not(with_main))]
+#[crux::test]
+fn crux_test() {
+    let pair = Arc::new((Mutex::new(false), Condvar::new()));
+
+    let p = Arc::clone(&pair);
+    let t = thread::spawn(move || {
+        let (lock, cvar) = &*p;
+        *lock.lock().unwrap() = true;
+        cvar.notify_one();
+    });
+
+    let (lock, cvar) = &*pair;
+    let mut started = lock.lock().unwrap();
+    while !*started {
+        started = cvar.wait(started).unwrap();
+    }
+    crucible_assert!(*started);
+    drop(started);
+
+    t.join().unwrap();
+}
+
+#[cfg(with_main)]
+fn main() {}
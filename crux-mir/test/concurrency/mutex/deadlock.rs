@@ -0,0 +1,27 @@
+extern crate crucible;
+use crucible::*;
+use std::thread;
+use std::sync::{Arc,Mutex};
+
+// Should fail: the two threads take the locks in opposite orders.
+#[cfg(not(with_main))]
+#[crux::test]
+fn crux_test() {
+    let a = Arc::new(Mutex::new(0 as u32));
+    let b = Arc::new(Mutex::new(0 as u32));
+
+    let (a1, b1) = (Arc::clone(&a), Arc::clone(&b));
+    let t = thread::spawn(move || {
+        let _pa = a1.lock().unwrap();
+        let _pb = b1.lock().unwrap();
+    });
+
+    {
+        let _pb = b.lock().unwrap();
+        let _pa = a.lock().unwrap();
+    }
+    t.join().unwrap();
+}
+
+#[cfg(with_main)]
+fn main() {}
@@ -0,0 +1,37 @@
+extern crate crucible;
+use crucible::*;
+use std::thread;
+use std::sync::{Arc,RwLock};
+
+// Readers never observe the two halves of a write out of sync.
+#[cfg(not(with_main))]
+#[crux::test]
+fn crux_test() {
+    let data = Arc::new(RwLock::new((0 as u32, 0 as u32)));
+    let mut children = vec![];
+
+    for _ in 0..2 {
+        let d = Arc::clone(&data);
+        children.push(thread::spawn(move || {
+            let p = d.read().unwrap();
+            crucible_assert!(p.0 == p.1);
+        }));
+    }
+
+    let d = Arc::clone(&data);
+    children.push(thread::spawn(move || {
+        let mut p = d.write().unwrap();
+        p.0 += 1;
+        p.1 += 1;
+    }));
+
+    for c in children {
+        c.join().unwrap();
+    }
+
+    let p = data.read().unwrap();
+    crucible_assert!(*p == (1, 1));
+}
+
+#[cfg(with_main)]
+fn main() {}
@@ -0,0 +1,31 @@
+extern crate crucible;
+use crucible::*;
+use std::cell::UnsafeCell;
+use std::sync::Arc;
+use std::thread;
+
+struct Shared(UnsafeCell<u32>);
+unsafe impl Sync for Shared {}
+
+// Joining the thread orders its write before the main thread's accesses.
+#[cfg(not(with_main))]
+#[crux::test]
+fn crux_test() -> u32 {
+    let s = Arc::new(Shared(UnsafeCell::new(0)));
+    unsafe {
+        *s.0.get() = 1;
+    }
+    let s1 = Arc::clone(&s);
+    let t = thread::spawn(move || unsafe {
+        *s1.0.get() += 1;
+    });
+    t.join().unwrap();
+    unsafe {
+        *s.0.get() += 1;
+        crucible_assert!(*s.0.get() == 3);
+        *s.0.get()
+    }
+}
+
+#[cfg(with_main)]
+fn main() {}
@@ -0,0 +1,30 @@
+extern crate crucible;
+use crucible::*;
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+struct Shared(UnsafeCell<u32>);
+unsafe impl Sync for Shared {}
+
+// Should fail with --detect-races: relaxed accesses to the flag don't order the
+// accesses to the data.
+#[cfg(not(with_main))]
+#[crux::test]
+fn crux_test() {
+    let data = Arc::new(Shared(UnsafeCell::new(0)));
+    let flag = Arc::new(AtomicBool::new(false));
+    let (data1, flag1) = (Arc::clone(&data), Arc::clone(&flag));
+    let t = thread::spawn(move || {
+        unsafe { *data1.0.get() = 42; }
+        flag1.store(true, Ordering::Relaxed);
+    });
+    if flag.load(Ordering::Relaxed) {
+        crucible_assert!(unsafe { *data.0.get() } == 42);
+    }
+    t.join().unwrap();
+}
+
+#[cfg(with_main)]
+fn main() {}
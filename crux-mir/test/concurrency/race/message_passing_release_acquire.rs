@@ -0,0 +1,30 @@
+extern crate crucible;
+use crucible::*;
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+struct Shared(UnsafeCell<u32>);
+unsafe impl Sync for Shared {}
+
+// The release store and acquire load order the write to the data before the
+// read.
+#[cfg(not(with_main))]
+#[crux::test]
+fn crux_test() {
+    let data = Arc::new(Shared(UnsafeCell::new(0)));
+    let flag = Arc::new(AtomicBool::new(false));
+    let (data1, flag1) = (Arc::clone(&data), Arc::clone(&flag));
+    let t = thread::spawn(move || {
+        unsafe { *data1.0.get() = 42; }
+        flag1.store(true, Ordering::Release);
+    });
+    if flag.load(Ordering::Acquire) {
+        crucible_assert!(unsafe { *data.0.get() } == 42);
+    }
+    t.join().unwrap();
+}
+
+#[cfg(with_main)]
+fn main() {}
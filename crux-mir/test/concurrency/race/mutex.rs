@@ -0,0 +1,31 @@
+extern crate crucible;
+use crucible::*;
+use std::cell::UnsafeCell;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+struct Shared(UnsafeCell<u32>);
+unsafe impl Sync for Shared {}
+
+// Both threads only touch the counter while holding the lock, so there is no
+// race.
+#[cfg(not(with_main))]
+#[crux::test]
+fn crux_test() -> u32 {
+    let s = Arc::new(Shared(UnsafeCell::new(0)));
+    let m = Arc::new(Mutex::new(()));
+    let (s1, m1) = (Arc::clone(&s), Arc::clone(&m));
+    let t = thread::spawn(move || {
+        let _g = m1.lock().unwrap();
+        unsafe { *s1.0.get() += 1; }
+    });
+    {
+        let _g = m.lock().unwrap();
+        unsafe { *s.0.get() += 1; }
+    }
+    t.join().unwrap();
+    unsafe { *s.0.get() }
+}
+
+#[cfg(with_main)]
+fn main() {}
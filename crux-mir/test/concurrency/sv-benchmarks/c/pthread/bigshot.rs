@@ -13,7 +13,7 @@ fn bigshot_p() {
     let t1 = thread::spawn(move|| {
         let mut pv = v1.lock().unwrap();
         *pv = Some("");
-        v1.crucible_TEMP_unlock();
+        drop(pv);
     });
     let v2 = Arc::clone(&v);
     let t2 = thread::spawn(move|| {
@@ -21,7 +21,7 @@ fn bigshot_p() {
         if let Some(_) = *pv {
             *pv = Some("Bigshot");
         }
-        v2.crucible_TEMP_unlock();
+        drop(pv);
     });
     t1.join();
     t2.join();
@@ -30,7 +30,7 @@ fn bigshot_p() {
     if let Some(x) = *pv {
         crucible_assert!(&x[0..1] == "B"); // This fails
     }
-    v.crucible_TEMP_unlock();
+    drop(pv);
 
 }
 
@@ -43,7 +43,7 @@ fn bigshot_s() {
     let t1 = thread::spawn(move|| {
         let mut pv = v1.lock().unwrap();
         *pv = Some("");
-        v1.crucible_TEMP_unlock();
+        drop(pv);
     });
     t1.join();
     let v2 = Arc::clone(&v);
@@ -52,7 +52,7 @@ fn bigshot_s() {
         if let Some(_) = *pv {
             *pv = Some("Bigshot");
         }
-        v2.crucible_TEMP_unlock();
+        drop(pv);
     });
     t2.join();
 
@@ -60,7 +60,7 @@ fn bigshot_s() {
     if let Some(x) = *pv {
         crucible_assert!(&x[0..1] == "B"); // This succeeds
     }
-    v.crucible_TEMP_unlock();
+    drop(pv);
 
 }
 
@@ -73,7 +73,7 @@ fn bigshot_s2() {
     let t1 = thread::spawn(move|| {
         let mut pv = v1.lock().unwrap();
         *pv = Some("");
-        v1.crucible_TEMP_unlock();
+        drop(pv);
     });
     t1.join();
     let v2 = Arc::clone(&v);
@@ -82,13 +82,13 @@ fn bigshot_s2() {
         if let Some(_) = *pv {
             *pv = Some("Bigshot");
         }
-        v2.crucible_TEMP_unlock();
+        drop(pv);
     });
     t2.join();
 
     let mut pv = v.lock().unwrap();
     let x = pv.unwrap();
     crucible_assert!(&x[0..1] == "B"); // This succeeds
-    v.crucible_TEMP_unlock();
+    drop(pv);
 
 }
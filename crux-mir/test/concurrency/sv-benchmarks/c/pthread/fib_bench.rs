@@ -11,7 +11,7 @@ fn t1(m : Arc<Mutex<(i32,i32)>>) {
         let mut p = m.lock().unwrap();
         let (i, j) = *p;
         *p = (i+j, j);
-        m.crucible_TEMP_unlock();
+        drop(p);
     }
 }
 
@@ -20,7 +20,7 @@ fn t2(m : Arc<Mutex<(i32,i32)>>) {
         let mut p = m.lock().unwrap();
         let (i, j) = *p;
         *p = (i, i+j);
-        m.crucible_TEMP_unlock();
+        drop(p);
     }
 }
 
@@ -55,8 +55,7 @@ fn crux_test_fail() {
     let (i,j) = *p;
     let bound = calc_fib();
     crucible_assert!( !((i >= bound) || (j >= bound)) );
-    // Until crux-mir supports drop()
-    data.crucible_TEMP_unlock();
+    drop(p);
 }
 
 #[crux::test]
@@ -79,7 +78,6 @@ fn crux_test() {
     let p = data.lock().unwrap();
     let (i,j) = *p;
     let bound = calc_fib();
-    // Until crux-mir supports drop()
-    data.crucible_TEMP_unlock();
+    drop(p);
     crucible_assert!( !((i > bound) || (j > bound)) );
 }
@@ -8,20 +8,20 @@ use std::sync::{Arc,Mutex,};
 fn thread1(v: Arc<Mutex<Option<i32>>>) {
     let mut p = v.lock().unwrap();
     *p = Some(0);
-    v.crucible_TEMP_unlock();
+    drop(p);
 }
 
 fn thread2(v: Arc<Mutex<Option<i32>>>) {
     let mut p = v.lock().unwrap();
     let _ = p.unwrap();
     *p = Some(1);
-    v.crucible_TEMP_unlock();
+    drop(p);
 }
 fn thread3(v: Arc<Mutex<Option<i32>>>) {
     let mut p = v.lock().unwrap();
     let _ = p.unwrap();
     *p = Some(2);
-    v.crucible_TEMP_unlock();
+    drop(p);
 }
 
 fn thread0(v: Arc<Mutex<Option<i32>>>) {
@@ -56,5 +56,5 @@ fn singleton() {
     let mut p = v.lock().unwrap();
     let x = p.unwrap();
     crucible_assert!(x == 1);
-    v.crucible_TEMP_unlock();
+    drop(p);
 }
@@ -12,22 +12,22 @@ const LIMIT: i32 = (2*NUM + 6);
 fn t1(i:Arc<AtomicI32>, j:Arc<AtomicI32>, m:Arc<Mutex<()>>)
 {
     for k in 0..NUM {
-        m.lock().unwrap();
+        let p = m.lock().unwrap();
 
         i.store(1+j.load(SeqCst), SeqCst);
 
-        m.crucible_TEMP_unlock();
+        drop(p);
     }
 }
 
 fn t2(i:Arc<AtomicI32>, j:Arc<AtomicI32>, m:Arc<Mutex<()>>)
 {
     for k in 0..NUM {
-        m.lock().unwrap();
+        let p = m.lock().unwrap();
 
         j.store(1+i.load(SeqCst), SeqCst);
 
-        m.crucible_TEMP_unlock();
+        drop(p);
     }
 }
 
@@ -15,22 +15,22 @@ static j: AtomicI32 = AtomicI32::new(6);
 fn t1(m:Arc<Mutex<()>>)
 {
     for k in 0..NUM {
-        m.lock().unwrap();
+        let p = m.lock().unwrap();
 
         i.store(1+j.load(SeqCst), SeqCst);
 
-        m.crucible_TEMP_unlock();
+        drop(p);
     }
 }
 
 fn t2(m:Arc<Mutex<()>>)
 {
     for k in 0..NUM {
-        m.lock().unwrap();
+        let p = m.lock().unwrap();
 
         j.store(1+i.load(SeqCst), SeqCst);
 
-        m.crucible_TEMP_unlock();
+        drop(p);
     }
 }
 
@@ -0,0 +1,20 @@
+extern crate crucible;
+use crucible::*;
+use std::thread;
+
+// Threads spawned through a `Builder` are scheduled like those from `thread::spawn`.
+#[cfg(not(with_main))]
+#[crux::test]
+fn crux_test() -> u32 {
+    let t = thread::Builder::new()
+        .name("worker".to_string())
+        .stack_size(4096)
+        .spawn(|| 7)
+        .unwrap();
+    let x = t.join().unwrap();
+    crucible_assert!(x == 7);
+    x
+}
+
+#[cfg(with_main)]
+fn main() {}
@@ -0,0 +1,22 @@
+extern crate crucible;
+use crucible::*;
+use std::thread;
+
+// Scoped threads can borrow from the enclosing stack frame, and have all finished by the time
+// `scope` returns.
+#[cfg(not(with_main))]
+#[crux::test]
+fn crux_test() -> u32 {
+    let mut a = 1;
+    let mut b = 2;
+    thread::scope(|s| {
+        s.spawn(|| a += 10);
+        s.spawn(|| b += 20);
+    });
+    crucible_assert!(a == 11);
+    crucible_assert!(b == 22);
+    a + b
+}
+
+#[cfg(with_main)]
+fn main() {}
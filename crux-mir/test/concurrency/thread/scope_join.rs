@@ -0,0 +1,25 @@
+extern crate crucible;
+use crucible::*;
+use std::sync::Mutex;
+use std::thread;
+
+// A scoped thread may spawn more threads in the same scope, and its result can be collected with
+// `join` before the scope ends.
+#[cfg(not(with_main))]
+#[crux::test]
+fn crux_test() {
+    let total = Mutex::new(0);
+    let r = thread::scope(|s| {
+        let h = s.spawn(|| {
+            s.spawn(|| *total.lock().unwrap() += 1);
+            *total.lock().unwrap() += 2;
+            5
+        });
+        h.join().unwrap()
+    });
+    crucible_assert!(r == 5);
+    crucible_assert!(*total.lock().unwrap() == 3);
+}
+
+#[cfg(with_main)]
+fn main() {}
@@ -0,0 +1,25 @@
+extern crate crucible;
+use crucible::*;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+// An increment is only lost if the spawned thread preempts the main thread
+// between its load and store, so this passes with `--preemption-bound 0`.
+#[cfg(not(with_main))]
+#[crux::test]
+fn crux_test() {
+    let x = Arc::new(AtomicU32::new(0));
+    let x1 = Arc::clone(&x);
+    let t = thread::spawn(move || {
+        let v = x1.load(Ordering::SeqCst);
+        x1.store(v + 1, Ordering::SeqCst);
+    });
+    let v = x.load(Ordering::SeqCst);
+    x.store(v + 1, Ordering::SeqCst);
+    t.join().unwrap();
+    crucible_assert!(x.load(Ordering::SeqCst) == 2);
+}
+
+#[cfg(with_main)]
+fn main() {}
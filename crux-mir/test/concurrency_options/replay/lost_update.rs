@@ -0,0 +1,26 @@
+extern crate crucible;
+use crucible::*;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+// Should fail: if the spawned thread runs between the main thread's load and
+// store, one of the increments is lost.  Replaying the recorded schedule
+// fails the same way.
+#[cfg(not(with_main))]
+#[crux::test]
+fn crux_test() {
+    let x = Arc::new(AtomicU32::new(0));
+    let x1 = Arc::clone(&x);
+    let t = thread::spawn(move || {
+        let v = x1.load(Ordering::SeqCst);
+        x1.store(v + 1, Ordering::SeqCst);
+    });
+    let v = x.load(Ordering::SeqCst);
+    x.store(v + 1, Ordering::SeqCst);
+    t.join().unwrap();
+    crucible_assert!(x.load(Ordering::SeqCst) == 2);
+}
+
+#[cfg(with_main)]
+fn main() {}
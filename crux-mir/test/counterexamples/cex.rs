@@ -0,0 +1,15 @@
+extern crate crucible;
+use crucible::*;
+
+#[derive(Symbolic)]
+struct Point {
+    x: u8,
+    y: i32,
+}
+
+#[crux::test]
+fn crux_test() {
+    let p = Point::symbolic("p");
+    let b = bool::symbolic("b");
+    crucible_assert!(!(b && p.x == 7 && p.y == -3));
+}
@@ -0,0 +1,17 @@
+extern crate crucible;
+use crucible::*;
+
+#[crux::test]
+fn crux_test() -> i32 {
+    let x = Box::new(1);
+    let y = Box::into_raw(Box::new(2));
+    let r = *x + unsafe { *y };
+    if bool::symbolic("free") {
+        drop(unsafe { Box::from_raw(y) });
+    }
+    r
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
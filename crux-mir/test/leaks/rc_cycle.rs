@@ -0,0 +1,17 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+struct Node {
+    next: RefCell<Option<Rc<Node>>>,
+}
+
+#[crux::test]
+fn crux_test() -> usize {
+    let a = Rc::new(Node { next: RefCell::new(None) });
+    *a.next.borrow_mut() = Some(a.clone());
+    Rc::strong_count(&a)
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
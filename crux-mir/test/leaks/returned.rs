@@ -0,0 +1,12 @@
+use std::rc::Rc;
+
+// The returned reference still owns its allocation, so it isn't a leak.
+#[crux::test]
+fn crux_test() -> &'static i32 {
+    let rc = Rc::new(Box::new(5));
+    Box::leak(Box::new(**rc))
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
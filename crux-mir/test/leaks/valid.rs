@@ -0,0 +1,27 @@
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+struct Parent {
+    child: RefCell<Option<Rc<Child>>>,
+}
+
+struct Child {
+    parent: Weak<Parent>,
+}
+
+#[crux::test]
+fn crux_test() -> i32 {
+    let mut v = Vec::new();
+    for i in 0..4 {
+        v.push(Box::new(i));
+    }
+    let p = Rc::new(Parent { child: RefCell::new(None) });
+    let c = Rc::new(Child { parent: Rc::downgrade(&p) });
+    *p.child.borrow_mut() = Some(c);
+    let alive = p.child.borrow().as_ref().unwrap().parent.upgrade().is_some();
+    v.iter().map(|b| **b).sum::<i32>() + alive as i32
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
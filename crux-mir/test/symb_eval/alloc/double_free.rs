@@ -0,0 +1,16 @@
+extern crate crucible;
+use crucible::*;
+use crucible::alloc::{allocate, deallocate};
+
+#[crux::test]
+fn crux_test() {
+    unsafe {
+        let ptr = allocate::<i32>(1);
+        deallocate(ptr);
+        deallocate(ptr);
+    }
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
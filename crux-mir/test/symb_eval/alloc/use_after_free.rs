@@ -0,0 +1,17 @@
+extern crate crucible;
+use crucible::*;
+use crucible::alloc::{allocate, deallocate};
+
+#[crux::test]
+fn crux_test() -> i32 {
+    unsafe {
+        let ptr = allocate::<i32>(10);
+        *ptr = 1;
+        deallocate(ptr);
+        *ptr
+    }
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
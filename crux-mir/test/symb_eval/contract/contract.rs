@@ -0,0 +1,26 @@
+extern crate crucible;
+use crucible::*;
+
+#[crucible::contract]
+#[crux::requires(x < 100)]
+#[crux::ensures(result == x + 1)]
+fn f(x: u8) -> u8 {
+    x + 1
+}
+
+#[crux::test]
+fn crux_test() {
+    crucible::use_contract!(f);
+
+    let a = u8::symbolic("a");
+    crucible_assume!(a < 50);
+    let b = f(a);
+    crucible_assert!(b > a);
+    let c = f(b);
+    crucible_assert!(c > a);
+}
+
+pub fn main() {
+    println!("{:?}", f_contract());
+    println!("{:?}", crux_test());
+}
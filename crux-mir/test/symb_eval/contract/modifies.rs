@@ -0,0 +1,27 @@
+extern crate crucible;
+use crucible::*;
+
+#[crucible::contract]
+#[crux::requires(*x < 1000 && *y < 1000)]
+#[crux::ensures(*x == old(*x) + *y)]
+#[crux::modifies(x)]
+fn add_to(x: &mut u32, y: &u32) {
+    *x += *y;
+}
+
+#[crux::test]
+fn crux_test() {
+    crucible::use_contract!(add_to);
+
+    let mut a = u32::symbolic("a");
+    crucible_assume!(a < 10);
+    let a0 = a;
+    add_to(&mut a, &5);
+    add_to(&mut a, &5);
+    crucible_assert!(a == a0 + 10);
+}
+
+pub fn main() {
+    println!("{:?}", add_to_contract());
+    println!("{:?}", crux_test());
+}
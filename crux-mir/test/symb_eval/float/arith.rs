@@ -0,0 +1,30 @@
+extern crate crucible;
+use crucible::*;
+
+#[crux::test]
+fn crux_test() {
+    // Concrete arithmetic rounds like IEEE-754 doubles.
+    let a: f64 = 0.1;
+    let b: f64 = 0.2;
+    crucible_assert!(a + b != 0.3);
+    crucible_assert!(a + b == 0.30000000000000004);
+    crucible_assert!(1.0f64 / 0.0 == f64::INFINITY);
+    crucible_assert!((-1.0f64 / 0.0).is_infinite());
+    crucible_assert!((0.0f64 / 0.0).is_nan());
+
+    let x = f32::symbolic("x");
+    if !x.is_nan() {
+        crucible_assert!(x + 0.0 == x);
+        crucible_assert!(x * 1.0 == x);
+        crucible_assert!(-(-x) == x);
+        if x.is_finite() {
+            crucible_assert!(x - x == 0.0);
+        } else {
+            crucible_assert!((x - x).is_nan());
+        }
+    }
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
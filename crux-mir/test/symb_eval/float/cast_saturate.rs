@@ -0,0 +1,19 @@
+extern crate crucible;
+use crucible::*;
+
+#[crux::test]
+fn crux_test() {
+    let x = f32::symbolic("x");
+    let y = x as u8;
+    if x.is_nan() {
+        crucible_assert!(y == 0);
+    } else if x >= 255.0 {
+        crucible_assert!(y == 255);
+    } else if x <= 0.0 {
+        crucible_assert!(y == 0);
+    }
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
@@ -0,0 +1,17 @@
+extern crate crucible;
+use crucible::*;
+
+#[crux::test]
+fn crux_test() {
+    let x = f64::symbolic("x");
+    if x.is_nan() {
+        crucible_assert!(x != x);
+        crucible_assert!(!(x < 1.0) && !(x >= 1.0));
+    } else {
+        crucible_assert!(x == x);
+    }
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
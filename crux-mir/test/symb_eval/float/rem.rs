@@ -0,0 +1,25 @@
+extern crate crucible;
+use crucible::*;
+
+#[crux::test]
+fn crux_test() {
+    // `%` truncates the quotient, so the result has the sign of the dividend.
+    crucible_assert!(5.0f64 % 3.0 == 2.0);
+    crucible_assert!(-5.0f64 % 3.0 == -2.0);
+    crucible_assert!(5.0f64 % -3.0 == 2.0);
+    crucible_assert!(7.5f64 % 2.0 == 1.5);
+    crucible_assert!(1.0f64 % f64::INFINITY == 1.0);
+    crucible_assert!((1.0f64 % 0.0).is_nan());
+
+    let x = f32::symbolic("x");
+    let y = f32::symbolic("y");
+    if x.is_finite() && y.is_finite() && y != 0.0 {
+        let r = x % y;
+        crucible_assert!(r.abs() < y.abs());
+        crucible_assert!(r == 0.0 || (r < 0.0) == (x < 0.0));
+    }
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
@@ -0,0 +1,30 @@
+extern crate crucible;
+use crucible::*;
+use std::cell::Cell;
+
+fn bump(c: &Cell<u32>) {
+    c.set(c.get() + 1);
+}
+
+// Should fail: `bump` modifies `*c` through a shared reference.
+fn count(c: &Cell<u32>, n: u32) {
+    let mut i = 0;
+    while i < n {
+        loop_invariant!(i < n && c.get() == i);
+        bump(c);
+        i += 1;
+    }
+}
+
+#[crux::test]
+fn crux_test() {
+    let n = u32::symbolic("n");
+    crucible_assume!(n <= 1000);
+    let c = Cell::new(0);
+    count(&c, n);
+    crucible_assert!(c.get() == n);
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
@@ -0,0 +1,30 @@
+extern crate crucible;
+use crucible::*;
+
+// Adds 3 to `*total` for each of `n` iterations, one at a time.
+fn count(total: &mut u32, n: u32) {
+    let mut i = 0;
+    while i < n {
+        loop_invariant!(i < n && *total == 3 * i);
+        let mut j = 0;
+        while j < 3 {
+            loop_invariant!(j < 3 && *total == 3 * i + j);
+            *total += 1;
+            j += 1;
+        }
+        i += 1;
+    }
+}
+
+#[crux::test]
+fn crux_test() {
+    let n = u32::symbolic("n");
+    crucible_assume!(n <= 1000);
+    let mut total = 0;
+    count(&mut total, n);
+    crucible_assert!(total == 3 * n);
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
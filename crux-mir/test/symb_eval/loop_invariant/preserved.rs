@@ -0,0 +1,21 @@
+extern crate crucible;
+use crucible::*;
+
+// Should fail: the invariant holds on entry, but each iteration adds 3 to
+// `sum` rather than 2, so it isn't preserved.
+#[crux::test]
+fn crux_test() {
+    let n = u32::symbolic("n");
+    crucible_assume!(n <= 1000);
+    let mut i = 0;
+    let mut sum = 0;
+    while i < n {
+        loop_invariant!(i < n && sum == 2 * i);
+        sum += 3;
+        i += 1;
+    }
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
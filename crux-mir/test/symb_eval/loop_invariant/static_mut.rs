@@ -0,0 +1,30 @@
+extern crate crucible;
+use crucible::*;
+
+static mut COUNT: u32 = 0;
+
+fn bump() {
+    unsafe { COUNT += 1; }
+}
+
+// Should fail: `bump` modifies `COUNT`.
+fn count(n: u32) {
+    let mut i = 0;
+    while i < n {
+        loop_invariant!(i < n);
+        bump();
+        i += 1;
+    }
+}
+
+#[crux::test]
+fn crux_test() {
+    let n = u32::symbolic("n");
+    crucible_assume!(n <= 1000);
+    count(n);
+    crucible_assert!(unsafe { COUNT } == n);
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
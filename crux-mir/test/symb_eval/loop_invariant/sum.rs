@@ -0,0 +1,20 @@
+extern crate crucible;
+use crucible::*;
+
+#[crux::test]
+fn crux_test() {
+    let n = u32::symbolic("n");
+    crucible_assume!(n <= 1000);
+    let mut i = 0;
+    let mut sum = 0;
+    while i < n {
+        loop_invariant!(i < n && sum == 2 * i);
+        sum += 2;
+        i += 1;
+    }
+    crucible_assert!(sum == 2 * n);
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
@@ -0,0 +1,25 @@
+extern crate crucible;
+use crucible::*;
+
+// Should fail: the loop modifies `*state`, and an `Option` can't be replaced
+// with a fresh value.
+fn step(state: &mut Option<u32>, n: u32) {
+    let mut i = 0;
+    while i < n {
+        loop_invariant!(i < n);
+        *state = Some(i);
+        i += 1;
+    }
+}
+
+#[crux::test]
+fn crux_test() {
+    let n = u32::symbolic("n");
+    crucible_assume!(n <= 1000);
+    let mut state = None;
+    step(&mut state, n);
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
@@ -0,0 +1,35 @@
+extern crate crucible;
+use crucible::*;
+
+#[derive(Clone, Copy)]
+struct P {
+    p: *mut u32,
+}
+
+fn bump(p: P) {
+    unsafe { *p.p += 1; }
+}
+
+// Should fail: `bump` modifies `*total` through the pointer inside `p`.
+fn count(total: &mut u32, n: u32) {
+    let p = P { p: total };
+    let mut i = 0;
+    while i < n {
+        loop_invariant!(i < n);
+        bump(p);
+        i += 1;
+    }
+}
+
+#[crux::test]
+fn crux_test() {
+    let n = u32::symbolic("n");
+    crucible_assume!(n <= 1000);
+    let mut total = 0;
+    count(&mut total, n);
+    crucible_assert!(total == n);
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
@@ -0,0 +1,46 @@
+extern crate crucible;
+use crucible::*;
+use crucible::method_spec::{MethodSpec, MethodSpecBuilder};
+
+fn bump(p: &mut (u32, u32), y: &u32) {
+    p.0 = *y + 1;
+}
+
+fn bump_spec() -> MethodSpec {
+    let mut p = (u32::symbolic("a"), u32::symbolic("b"));
+    let y = u32::symbolic("y");
+    crucible_assume!(y < 1000);
+    let b0 = p.1;
+
+    let mut msb = MethodSpecBuilder::new(bump);
+    msb.add_arg(&&mut p);
+    msb.add_arg(&&y);
+    msb.gather_assumes();
+
+    bump(&mut p, &y);
+    crucible_assert!(p.0 == y + 1);
+    crucible_assert!(p.1 == b0);
+
+    msb.set_return(&());
+    msb.gather_asserts();
+    msb.finish()
+}
+
+#[crux::test]
+fn crux_test() {
+    bump_spec().enable();
+
+    // Should fail: `y` points into `*p`, so the spec, which changes `*p`,
+    // doesn't describe this call, even though `*p` and `*y` have different
+    // types.
+    let mut p = (u32::symbolic("a"), 0);
+    crucible_assume!(p.0 < 10);
+    let pp = &mut p as *mut (u32, u32);
+    unsafe {
+        bump(&mut *pp, &(*pp).0);
+    }
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
@@ -0,0 +1,40 @@
+extern crate crucible;
+use crucible::*;
+use crucible::method_spec::{MethodSpec, MethodSpecBuilder};
+
+fn incr(x: &mut u32) {
+    *x += 1;
+}
+
+fn incr_spec() -> MethodSpec {
+    let mut x = u32::symbolic("x");
+    crucible_assume!(x < 1000);
+    let x0 = x;
+
+    let mut msb = MethodSpecBuilder::new(incr);
+    msb.add_arg(&&mut x);
+    msb.gather_assumes();
+
+    incr(&mut x);
+    crucible_assert!(x == x0 + 1);
+
+    msb.set_return(&());
+    msb.gather_asserts();
+    msb.finish()
+}
+
+#[crux::test]
+fn crux_test() {
+    incr_spec().enable();
+
+    let mut y = u32::symbolic("y");
+    crucible_assume!(y < 10);
+    let y0 = y;
+    incr(&mut y);
+    incr(&mut y);
+    crucible_assert!(y == y0 + 2);
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
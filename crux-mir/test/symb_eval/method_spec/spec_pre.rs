@@ -0,0 +1,36 @@
+extern crate crucible;
+use crucible::*;
+use crucible::method_spec::{MethodSpec, MethodSpecBuilder};
+
+fn f(x: u8) -> u8 {
+    x + 1
+}
+
+fn f_spec() -> MethodSpec {
+    let x = u8::symbolic("x");
+    crucible_assume!(x < 100);
+
+    let mut msb = MethodSpecBuilder::new(f);
+    msb.add_arg(&x);
+    msb.gather_assumes();
+
+    let result = f(x);
+    crucible_assert!(result > x);
+
+    msb.set_return(&result);
+    msb.gather_asserts();
+    msb.finish()
+}
+
+#[crux::test]
+fn crux_test() -> u8 {
+    f_spec().enable();
+
+    // Nothing rules out `a >= 100`, where the spec doesn't apply.
+    let a = u8::symbolic("a");
+    f(a)
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
@@ -0,0 +1,39 @@
+extern crate crucible;
+use crucible::*;
+use crucible::method_spec::{MethodSpec, MethodSpecBuilder};
+
+fn f(x: u8) -> u8 {
+    x + 1
+}
+
+fn f_spec() -> MethodSpec {
+    let x = u8::symbolic("x");
+    crucible_assume!(x < 100);
+
+    let mut msb = MethodSpecBuilder::new(f);
+    msb.add_arg(&x);
+    msb.gather_assumes();
+
+    let result = f(x);
+    crucible_assert!(result > x);
+
+    msb.set_return(&result);
+    msb.gather_asserts();
+    msb.finish()
+}
+
+#[crux::test]
+fn crux_test() {
+    f_spec().enable();
+
+    let a = u8::symbolic("a");
+    crucible_assume!(a < 50);
+    let b = f(a);
+    crucible_assert!(b > a);
+    let c = f(b);
+    crucible_assert!(c > a);
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
@@ -0,0 +1,54 @@
+extern crate crucible;
+use crucible::*;
+
+#[crux::test]
+fn crux_test() {
+    let o = Option::<u8>::symbolic("o");
+    if let Some(x) = o {
+        crucible_assert!(x as u32 <= 255);
+    }
+
+    let r = Result::<u8, bool>::symbolic("r");
+    crucible_assert!(r.is_ok() != r.is_err());
+
+    let c = char::symbolic("c");
+    crucible_assert!((c as u32) < 0xd800 || (c as u32) >= 0xe000);
+
+    let arr = <[u16; 40]>::symbolic("arr");
+    crucible_assert!(arr.len() == 40);
+
+    let v = Vec::<u8>::symbolic_bounded("v", 3);
+    crucible_assert!(v.len() <= 3);
+
+    let s = String::symbolic_bounded("s", 2);
+    crucible_assert!(s.len() <= 2);
+    crucible_assert!(s.is_ascii());
+}
+
+// Should fail: each assertion rules out a value that the symbolic one can
+// take, showing that both variants of `Option` and `Result`, the largest
+// lengths, and characters outside the BMP are all reachable.
+#[crux::test]
+fn reachable() {
+    let o = Option::<u8>::symbolic("o");
+    crucible_assert!(o.is_some());
+    crucible_assert!(o.is_none());
+
+    let r = Result::<u8, bool>::symbolic("r");
+    crucible_assert!(r.is_ok());
+    crucible_assert!(r.is_err());
+
+    let c = char::symbolic("c");
+    crucible_assert!((c as u32) <= 0xffff);
+
+    let v = Vec::<u8>::symbolic_bounded("v", 3);
+    crucible_assert!(v.len() < 3);
+
+    let s = String::symbolic_bounded("s", 2);
+    crucible_assert!(s.len() < 2);
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+    println!("{:?}", reachable());
+}
@@ -0,0 +1,34 @@
+extern crate crucible;
+use crucible::*;
+
+#[derive(Symbolic)]
+#[symbolic(where = |p: &Point| p.x <= p.y)]
+struct Point {
+    x: u8,
+    #[symbolic(where = |&y| y < 100)]
+    y: u8,
+}
+
+#[derive(Symbolic)]
+enum Shape {
+    Empty,
+    Circle(Point, u8),
+    Rect { corner: Point, w: u8, h: u8 },
+}
+
+#[crux::test]
+fn crux_test() {
+    let p = Point::symbolic("p");
+    crucible_assert!(p.x <= p.y && p.y < 100);
+
+    let s = Shape::symbolic("s");
+    match s {
+        Shape::Empty => {},
+        Shape::Circle(c, _) => crucible_assert!(c.x < 100),
+        Shape::Rect { corner, .. } => crucible_assert!(corner.x <= corner.y),
+    }
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
@@ -0,0 +1,17 @@
+extern crate crucible;
+use crucible::*;
+
+union IntOrFloat {
+    i: u32,
+    f: f32,
+}
+
+#[crux::test]
+fn crux_test() -> u32 {
+    let u = IntOrFloat { f: 1.0 };
+    unsafe { u.i }
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
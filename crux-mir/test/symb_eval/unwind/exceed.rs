@@ -0,0 +1,19 @@
+extern crate crucible;
+use crucible::*;
+
+// Should fail: `n` can be up to 8, so the loop can go around more times than
+// its bound of 4 allows.
+#[crux::test]
+fn crux_test() {
+    let n = u32::symbolic("n");
+    crucible_assume!(n <= 8);
+    let mut i = 0;
+    while i < n {
+        loop_unwind!(4);
+        i += 1;
+    }
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
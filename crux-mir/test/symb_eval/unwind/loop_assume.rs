@@ -0,0 +1,17 @@
+extern crate crucible;
+use crucible::*;
+
+#[crux::test]
+fn crux_test() {
+    let n = u32::symbolic("n");
+    let mut i = 0;
+    while i < n {
+        loop_unwind!(4, assume);
+        i += 1;
+    }
+    crucible_assert!(i <= 4);
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
@@ -0,0 +1,22 @@
+extern crate crucible;
+use crucible::*;
+
+fn count_to(n: u32) -> u32 {
+    let mut i = 0;
+    while i < n {
+        i += 1;
+    }
+    i
+}
+
+#[crux::test]
+#[crucible::unwind(8)]
+fn crux_test() {
+    let n = u32::symbolic("n");
+    crucible_assume!(n <= 8);
+    crucible_assert!(count_to(n) == n);
+}
+
+pub fn main() {
+    println!("{:?}", crux_test());
+}
@@ -47,9 +47,14 @@ translate lib/libc/src/lib.rs  --crate-name libc --cfg 'feature="align"' --cfg '
 echo 'Building compiler_builtins...'
 translate lib/compiler_builtins/src/lib.rs  --crate-name compiler_builtins --cfg 'feature="compiler-builtins"' --cfg 'feature="core"' --cfg 'feature="default"' --cfg 'feature="rustc-dep-of-std"' --cfg 'feature="unstable"' --cfg 'feature="mem-unaligned"`' --extern "core=${RLIBS}/libcore.rlib"
 
-# extra lib (added manually)
+# extra libs (added manually)
+# `crucible_derive` is a proc macro, so it's compiled natively for the host
+# rather than translated.
+echo "Building crucible_derive..."
+rustc lib/crucible_derive/lib.rs --edition=2021 --crate-name crucible_derive --crate-type proc-macro --out-dir "${RLIBS}"
+
 echo "Building crucible..."
-translate lib/crucible/lib.rs --crate-name crucible --edition=2021 --extern "compiler_builtins=${RLIBS}/libcompiler_builtins.rlib" --extern "core=${RLIBS}/libcore.rlib"
+translate lib/crucible/lib.rs --crate-name crucible --edition=2021 --extern "compiler_builtins=${RLIBS}/libcompiler_builtins.rlib" --extern "core=${RLIBS}/libcore.rlib" --extern crucible_derive
 
 echo 'Building alloc...'
 translate lib/alloc/src/lib.rs --edition=2021 --crate-name alloc --extern "compiler_builtins=${RLIBS}/libcompiler_builtins.rlib" --extern "core=${RLIBS}/libcore.rlib" --extern "crucible=${RLIBS}/libcrucible.rlib"